@@ -1,6 +1,6 @@
 use colored::*;
 use gemini_ai_rust::{
-    models::{Content, Part, Request, Response},
+    models::{Part, Request, Response},
     GenerativeModel,
 };
 use std::error::Error;
@@ -29,7 +29,7 @@ fn display_response(response: &Response) {
                 println!("\n{}", "💭 Generated Text:".cyan().bold());
                 for part in &content.parts {
                     match part {
-                        Part::Text { text } => println!("{}", text.white()),
+                        Part::Text { text, .. } => println!("{}", text.white()),
                         _ => println!("{}", "Unsupported content type".red()),
                     }
                 }
@@ -88,14 +88,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         println!("{} {}", "🔍 Prompt:".blue().bold(), prompt);
 
         // Create the request
-        let request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
-            .build();
+        let request = Request::from_prompt(prompt);
 
         // Generate content
         match model.generate_response(request).await {
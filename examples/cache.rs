@@ -1,7 +1,8 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, Part, Request},
+    cache::CacheManager,
+    models::{Content, Request},
     GenerativeModel,
 };
 use indicatif::{ProgressBar, ProgressStyle};
@@ -99,14 +100,7 @@ async fn demonstrate_caching(model: &GenerativeModel) -> Result<(), Box<dyn Erro
             println!("{}", cached_response.white());
         } else {
             pb.set_message("Generating response...");
-            let request = Request::builder()
-                .contents(vec![Content {
-                    role: None,
-                    parts: vec![Part::Text {
-                        text: query_text.into(),
-                    }],
-                }])
-                .build();
+            let request = Request::from_prompt(query_text);
 
             match model.generate_response(request).await {
                 Ok(response) => {
@@ -147,6 +141,49 @@ async fn demonstrate_caching(model: &GenerativeModel) -> Result<(), Box<dyn Erro
     Ok(())
 }
 
+/// Demonstrate context caching: a long document is cached once on the server and
+/// referenced by later requests, which then only carry the new question
+async fn demonstrate_context_caching() -> Result<(), Box<dyn Error>> {
+    println!("\n{}", "━".repeat(100).bright_black());
+    println!("{}", "🗄️  Context Caching".blue().bold());
+
+    // Context caching requires a model version and a minimum amount of content
+    let model_name = "gemini-1.5-flash-001";
+    let document = "The quick brown fox jumps over the lazy dog. ".repeat(4000);
+
+    let cache_manager = CacheManager::new(std::env::var("GOOGLE_API_KEY")?);
+    let cache = cache_manager
+        .create_cache_from_text(model_name, document, "300s")
+        .await?;
+    println!(
+        "{} {}",
+        "✓ Cache created:".green(),
+        cache.name.bright_green()
+    );
+
+    let model = GenerativeModel::from_env(model_name)?;
+    let request = Request::builder()
+        .contents(vec![Content::user("Which animal jumps in the document?")])
+        .cached_content(cache.name.clone())
+        .build();
+
+    let result = model.generate_response(request).await;
+    cache_manager.delete_cache(&cache.name).await?;
+    let response = result?;
+
+    println!("{}", response.text().white());
+    if let Some(usage) = &response.usage_metadata {
+        println!(
+            "\n{} {} prompt tokens, {} served from the cache",
+            "📊 Usage:".bright_white().bold(),
+            usage.prompt_token_count,
+            usage.cached_content_token_count.unwrap_or(0)
+        );
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     println!("{}", "📦 Gemini Response Cache Demo".bright_green().bold());
@@ -168,6 +205,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Run caching demonstrations
     demonstrate_caching(&model).await?;
+    demonstrate_context_caching().await?;
 
     println!(
         "\n{}",
@@ -32,10 +32,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
         // Create a request with code execution enabled
         let request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: task.into() }],
-            }])
+            .contents(vec![Content::user(task)])
             .tools(vec![Tool::CODE_EXECUTION])
             .build();
 
@@ -55,57 +52,49 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             if let Some(content) = candidate.content {
                                 for part in content.parts {
                                     match part {
-                                        Part::Text { text } => {
+                                        Part::Text { text, .. } => {
                                             print!("{}", text.bright_white());
                                             stdout.flush()?;
                                         }
-                                        Part::ExecutableCode { executable_code } => {
-                                            if !seen_code {
-                                                println!(
-                                                    "\n{}",
-                                                    "📝 Generated Code:".yellow().bold()
-                                                );
-                                                println!(
-                                                    "\n{} {}",
-                                                    "Language:".bright_yellow(),
-                                                    executable_code.language
-                                                );
-                                                println!(
-                                                    "{}\n{}",
-                                                    "Code:".bright_yellow(),
-                                                    executable_code.code.cyan()
-                                                );
-                                                seen_code = true;
-                                            }
+                                        Part::ExecutableCode { executable_code } if !seen_code => {
+                                            println!("\n{}", "📝 Generated Code:".yellow().bold());
+                                            println!(
+                                                "\n{} {}",
+                                                "Language:".bright_yellow(),
+                                                executable_code.language
+                                            );
+                                            println!(
+                                                "{}\n{}",
+                                                "Code:".bright_yellow(),
+                                                executable_code.code.cyan()
+                                            );
+                                            seen_code = true;
                                         }
                                         Part::CodeExecutionResult {
                                             code_execution_result,
-                                        } => {
-                                            if !seen_result {
-                                                println!(
-                                                    "\n{}",
-                                                    "🚀 Execution Results:".magenta().bold()
-                                                );
-                                                let outcome_str =
-                                                    format!("{:?}", code_execution_result.outcome);
-                                                let colored_outcome = match code_execution_result
-                                                    .outcome
-                                                {
+                                        } if !seen_result => {
+                                            println!(
+                                                "\n{}",
+                                                "🚀 Execution Results:".magenta().bold()
+                                            );
+                                            let outcome_str =
+                                                format!("{:?}", code_execution_result.outcome);
+                                            let colored_outcome =
+                                                match code_execution_result.outcome {
                                                     CodeExecutionOutcome::Ok => outcome_str.green(),
                                                     _ => outcome_str.red(),
                                                 };
-                                                println!(
-                                                    "\n{} {}",
-                                                    "Outcome:".bright_magenta(),
-                                                    colored_outcome
-                                                );
-                                                println!(
-                                                    "{}\n{}",
-                                                    "Output:".bright_magenta(),
-                                                    code_execution_result.output.white()
-                                                );
-                                                seen_result = true;
-                                            }
+                                            println!(
+                                                "\n{} {}",
+                                                "Outcome:".bright_magenta(),
+                                                colored_outcome
+                                            );
+                                            println!(
+                                                "{}\n{}",
+                                                "Output:".bright_magenta(),
+                                                code_execution_result.output.white()
+                                            );
+                                            seen_result = true;
                                         }
                                         _ => {} // Ignore other part types
                                     }
@@ -0,0 +1,54 @@
+use colored::*;
+use dotenv::dotenv;
+use gemini_ai_rust::{
+    models::{Content, Request, Tools},
+    GenerativeModel,
+};
+use std::error::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    println!(
+        "{}",
+        "🤖 Gemini Code Execution Summary".bright_green().bold()
+    );
+    println!("{}", "================================".bright_green());
+
+    // Load environment variables from .env file
+    dotenv().ok();
+
+    // Create a client from environment variables
+    let model = GenerativeModel::from_env("gemini-2.0-flash")?;
+    println!("{}", "✓ Gemini model initialized".green());
+
+    let task = "Compute the 20th Fibonacci number, then divide it by zero to show what happens.";
+    println!("\n{} {}", "🔍 Task:".blue().bold(), task);
+
+    // Request the code execution tool
+    let request = Request::builder()
+        .contents(vec![Content::user(task)])
+        .tools(Tools::new().code_execution())
+        .build();
+
+    let response = model.generate_response(request).await?;
+
+    // Walk through each piece of code the model ran, together with its result
+    for (index, execution) in response.code_execution_summary().into_iter().enumerate() {
+        println!(
+            "\n{} {} ({})",
+            "📝 Step".yellow().bold(),
+            index + 1,
+            execution.code.language
+        );
+        println!("{}", execution.code.code.cyan());
+
+        match execution.result.map(|result| result.into_result()) {
+            Some(Ok(output)) => println!("{}\n{}", "✓ Output:".green(), output.white()),
+            Some(Err(error)) => println!("{} {}", "❌".red(), error.to_string().red()),
+            None => println!("{}", "No result returned".bright_black()),
+        }
+    }
+
+    println!("\n{}\n{}", "💡 Answer:".magenta().bold(), response.text());
+    Ok(())
+}
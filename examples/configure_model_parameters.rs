@@ -1,7 +1,7 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, GenerationConfig, HarmCategory, Part, Request, Role, SafetyThreshold},
+    models::{Content, GenerationConfig, HarmCategory, Request, SafetyThreshold},
     GenerativeModel,
 };
 use std::error::Error;
@@ -23,12 +23,7 @@ async fn demonstrate_temperature(model: &GenerativeModel) -> Result<(), Box<dyn
         println!("{} {}", "🎯 Temperature:".yellow().bold(), temp);
 
         let request = Request::builder()
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .generation_config(
                 GenerationConfig::builder()
                     .temperature(temp)
@@ -69,12 +64,7 @@ async fn demonstrate_safety_settings(model: &GenerativeModel) -> Result<(), Box<
         println!("{} {}", "🔒 Safety Level:".yellow().bold(), name);
 
         let request = Request::builder()
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .safety_settings([(HarmCategory::HarmCategoryHarassment, threshold).into()])
             .build();
 
@@ -116,12 +106,7 @@ async fn demonstrate_sampling(model: &GenerativeModel) -> Result<(), Box<dyn Err
         );
 
         let request = Request::builder()
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .generation_config(
                 GenerationConfig::builder()
                     .temperature(0.9)
@@ -1,8 +1,8 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, GenerationConfig, Part, Request, ResponseSchema, Role, SchemaType},
-    GenerativeModel,
+    models::{Content, GenerationConfig, Request},
+    schema, GenerativeModel,
 };
 use std::error::Error;
 
@@ -16,57 +16,17 @@ async fn demonstrate_recipe_schema(model: &GenerativeModel) -> Result<(), Box<dy
     );
 
     let request = Request::builder()
-        .contents(vec![Content {
-            role: Some(Role::User),
-            parts: vec![Part::Text {
-                text: "List 3 popular cookie recipes with detailed descriptions".into(),
-            }],
-        }])
+        .contents(vec![Content::user(
+            "List 3 popular cookie recipes with detailed descriptions",
+        )])
         .generation_config(
             GenerationConfig::builder()
                 .response_mime_type("application/json")
-                .response_schema(
-                    ResponseSchema::builder()
-                        .r#type(SchemaType::Array)
-                        .items(
-                            ResponseSchema::builder()
-                                .r#type(SchemaType::Object)
-                                .properties([
-                                    (
-                                        "recipe_name".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .description("Name of the cookie recipe")
-                                            .build(),
-                                    ),
-                                    (
-                                        "description".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .description("Brief description of the cookie")
-                                            .build(),
-                                    ),
-                                    (
-                                        "difficulty".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .enum_values(vec![
-                                                "Easy".into(),
-                                                "Medium".into(),
-                                                "Hard".into(),
-                                            ])
-                                            .build(),
-                                    ),
-                                ])
-                                .required(vec![
-                                    "recipe_name".into(),
-                                    "description".into(),
-                                    "difficulty".into(),
-                                ])
-                                .build(),
-                        )
-                        .build(),
-                )
+                .response_schema(schema!([{
+                    "recipe_name": string("Name of the cookie recipe"),
+                    "description": string("Brief description of the cookie"),
+                    "difficulty": enum_["Easy", "Medium", "Hard"],
+                }]))
                 .build(),
         )
         .build();
@@ -93,65 +53,18 @@ async fn demonstrate_book_schema(model: &GenerativeModel) -> Result<(), Box<dyn
     );
 
     let request = Request::builder()
-        .contents(vec![Content {
-            role: Some(Role::User),
-            parts: vec![Part::Text {
-                text: "Recommend 3 science fiction books for beginners".into(),
-            }],
-        }])
+        .contents(vec![Content::user(
+            "Recommend 3 science fiction books for beginners",
+        )])
         .generation_config(
             GenerationConfig::builder()
                 .response_mime_type("application/json")
-                .response_schema(
-                    ResponseSchema::builder()
-                        .r#type(SchemaType::Array)
-                        .items(
-                            ResponseSchema::builder()
-                                .r#type(SchemaType::Object)
-                                .properties([
-                                    (
-                                        "title".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .description("Book title")
-                                            .build(),
-                                    ),
-                                    (
-                                        "author".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .description("Book author")
-                                            .build(),
-                                    ),
-                                    (
-                                        "year_published".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::Integer)
-                                            .description("Year of publication")
-                                            .build(),
-                                    ),
-                                    (
-                                        "themes".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::Array)
-                                            .items(
-                                                ResponseSchema::builder()
-                                                    .r#type(SchemaType::String)
-                                                    .build(),
-                                            )
-                                            .build(),
-                                    ),
-                                ])
-                                .required(vec![
-                                    "title".into(),
-                                    "author".into(),
-                                    "year_published".into(),
-                                    "themes".into(),
-                                ])
-                                .build(),
-                        )
-                        .build(),
-                )
+                .response_schema(schema!([{
+                    "title": string("Book title"),
+                    "author": string("Book author"),
+                    "year_published": integer("Year of publication"),
+                    "themes": [string],
+                }]))
                 .build(),
         )
         .build();
@@ -176,70 +89,20 @@ async fn demonstrate_weather_schema(model: &GenerativeModel) -> Result<(), Box<d
     );
 
     let request = Request::builder()
-        .contents(vec![Content {
-            role: Some(Role::User),
-            parts: vec![Part::Text {
-                text: "Generate a 3-day weather forecast for New York".into(),
-            }],
-        }])
+        .contents(vec![Content::user(
+            "Generate a 3-day weather forecast for New York",
+        )])
         .generation_config(
             GenerationConfig::builder()
                 .response_mime_type("application/json")
-                .response_schema(
-                    ResponseSchema::builder()
-                        .r#type(SchemaType::Array)
-                        .items(
-                            ResponseSchema::builder()
-                                .r#type(SchemaType::Object)
-                                .properties([
-                                    (
-                                        "day".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::Integer)
-                                            .build(),
-                                    ),
-                                    (
-                                        "condition".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::String)
-                                            .enum_values(vec![
-                                                "Sunny".into(),
-                                                "Cloudy".into(),
-                                                "Rainy".into(),
-                                                "Snowy".into(),
-                                            ])
-                                            .build(),
-                                    ),
-                                    (
-                                        "temperature".to_string(),
-                                        ResponseSchema::builder()
-                                            .r#type(SchemaType::Object)
-                                            .properties([
-                                                (
-                                                    "high".to_string(),
-                                                    ResponseSchema::builder()
-                                                        .r#type(SchemaType::Integer)
-                                                        .build(),
-                                                ),
-                                                (
-                                                    "low".to_string(),
-                                                    ResponseSchema::builder()
-                                                        .r#type(SchemaType::Integer)
-                                                        .build(),
-                                                ),
-                                            ])
-                                            .build(),
-                                    ),
-                                ])
-                                .required(vec![
-                                    "day".into(),
-                                    "condition".into(),
-                                    "temperature".into(),
-                                ])
-                                .build(),
-                        )
-                        .build(),
-                )
+                .response_schema(schema!([{
+                    "day": integer,
+                    "condition": enum_["Sunny", "Cloudy", "Rainy", "Snowy"],
+                    "temperature": {
+                        "high": optional(integer),
+                        "low": optional(integer),
+                    },
+                }]))
                 .build(),
         )
         .build();
@@ -1,9 +1,6 @@
 use colored::*;
 use dotenv::dotenv;
-use gemini_ai_rust::{
-    models::{Content, Part, Request},
-    GenerativeModel,
-};
+use gemini_ai_rust::{models::Request, GenerativeModel};
 use std::error::Error;
 
 /// Demonstrate token counting for different types of content
@@ -69,12 +66,7 @@ async fn demonstrate_token_counting(model: &GenerativeModel) -> Result<(), Box<d
         );
 
         // Count tokens
-        let request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: text.into() }],
-            }])
-            .build();
+        let request = Request::from_prompt(text);
 
         match model.count_tokens(request).await {
             Ok(token_count) => {
@@ -182,7 +182,7 @@ impl DocSearchEngine {
         doc_type_filter: Option<DocType>,
         language_filter: Option<&str>,
         limit: usize,
-    ) -> Result<Vec<SearchResult>, EmbeddingError> {
+    ) -> Result<Vec<SearchResult<'_>>, EmbeddingError> {
         // Embed the search query
         let request = EmbedContentRequest::new(query, Some(TaskType::RetrievalQuery), None);
         let response = self.model.embed_content(&self.model_name, request).await?;
@@ -195,9 +195,9 @@ impl DocSearchEngine {
             .filter(|doc| {
                 doc_type_filter
                     .as_ref()
-                    .map_or(true, |t| t == &doc.metadata.doc_type)
+                    .is_none_or(|t| t == &doc.metadata.doc_type)
                     && language_filter
-                        .map_or(true, |l| l.eq_ignore_ascii_case(&doc.metadata.language))
+                        .is_none_or(|l| l.eq_ignore_ascii_case(&doc.metadata.language))
             })
             .filter_map(|doc| {
                 doc.embedding.as_ref().map(|emb| SearchResult {
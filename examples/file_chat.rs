@@ -1,4 +1,3 @@
-use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use colored::*;
 use dialoguer::Input;
 use figment::{
@@ -8,8 +7,8 @@ use figment::{
 use gemini_ai_rust::{
     client::GenerativeModel,
     error::GoogleGenerativeAIError,
-    file::{FileError, FileInfo as GeminiFileInfo, FileState, GoogleAIFileManager},
-    models::{Content, InlineData, ModelParams, Part, Request},
+    file::{FileError, FileInfo as GeminiFileInfo, FileState, GoogleAIFileManager, UploadPhase},
+    models::{Content, ModelParams, Part, Request},
 };
 use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
@@ -221,7 +220,7 @@ impl FileChatManager {
         let max_cache_size = MAX_CACHE_SIZE_MB * 1024 * 1024;
         let mut cache_files: Vec<_> = fs::read_dir(&self.cache_dir)?
             .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "cache"))
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "cache"))
             .collect();
 
         if cache_files.is_empty() {
@@ -289,16 +288,15 @@ impl FileChatManager {
                 println!("{}", "🔍 Verifying cached file...".bright_yellow().bold());
                 let pb = PrettyPrinter::print_thinking();
 
-                // List files and check if our cached file exists
-                match self.file_manager.list_files().await {
-                    Ok(files) => {
-                        if files.iter().any(|f| f.name == *google_file_name) {
-                            self.current_file_info = Some(google_file_name.clone());
-                            pb.finish_and_clear();
-                            println!("{}", "📦 Using cached file reference".bright_green().bold());
-                            return Ok(());
-                        }
+                // Check if our cached file still exists
+                match self.file_manager.get_file(google_file_name).await {
+                    Ok(_) => {
+                        self.current_file_info = Some(google_file_name.clone());
+                        pb.finish_and_clear();
+                        println!("{}", "📦 Using cached file reference".bright_green().bold());
+                        return Ok(());
                     }
+                    Err(FileError::NotFound(_)) => pb.finish_and_clear(),
                     Err(_e) => {
                         pb.finish_and_clear();
                         println!("{}", "⚠️  Could not verify cached file".yellow().bold());
@@ -316,12 +314,27 @@ impl FileChatManager {
         })?;
 
         println!("{}", "📤 Uploading to Google AI...".bright_yellow().bold());
-        let pb = PrettyPrinter::print_thinking();
+        let pb = ProgressBar::new(0);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{msg} [{bar:30.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                .unwrap()
+                .progress_chars("=> "),
+        );
 
         // Upload the file
         let file_info = self
             .file_manager
-            .upload_file(path, file_name.to_string())
+            .upload_file_with_progress(path, file_name.to_string(), |progress| {
+                pb.set_length(progress.total_bytes);
+                pb.set_position(progress.bytes_sent);
+                pb.set_message(match progress.phase {
+                    UploadPhase::Initiating => "Starting upload".to_string(),
+                    UploadPhase::Uploading { chunk } => format!("Uploaded chunk {}", chunk),
+                    UploadPhase::Finalizing => "Upload complete".to_string(),
+                    UploadPhase::Processing => "Processing file".to_string(),
+                });
+            })
             .await?;
 
         // Wait for processing if needed
@@ -365,25 +378,26 @@ impl FileChatManager {
         let mime_type = self.mime_type.as_ref().unwrap();
         self.chat_session.add_message("user", user_input);
 
-        let mut parts = vec![Part::Text {
-            text: format!(
-                "Context: I'm looking at a file of type {}. Here's my question: {}",
-                mime_type, user_input
-            ),
-        }];
+        let mut parts = vec![Part::text(format!(
+            "Context: I'm looking at a file of type {}. Here's my question: {}",
+            mime_type, user_input
+        ))];
 
         // Add file content based on whether we have a Google AI file or local file
         if let Some(file_name) = &self.current_file_info {
             // Get the full file information to use the URI
             let pb = PrettyPrinter::print_thinking();
-            match self.file_manager.list_files().await {
-                Ok(files) => {
-                    if let Some(file_info) = files.iter().find(|f| f.name == *file_name) {
-                        parts.push(Part::file_data(mime_type.clone(), file_info.uri.clone()));
-                    } else {
+            match self.file_manager.get_file(file_name).await {
+                Ok(file_info) => match Part::from_file_info(&file_info) {
+                    Ok(part) => parts.push(part),
+                    Err(e) => {
                         pb.finish_and_clear();
-                        return Err(ChatError::FileProcessing("File not found".into()));
+                        return Err(ChatError::FileProcessing(e.to_string()));
                     }
+                },
+                Err(FileError::NotFound(_)) => {
+                    pb.finish_and_clear();
+                    return Err(ChatError::FileProcessing("File not found".into()));
                 }
                 Err(_e) => {
                     pb.finish_and_clear();
@@ -395,15 +409,13 @@ impl FileChatManager {
             // Fallback to direct content
             if mime_type.starts_with("text/") {
                 if let Ok(text) = String::from_utf8(self.file_content.as_ref().unwrap().clone()) {
-                    parts.push(Part::Text { text });
+                    parts.push(Part::text(text));
                 }
             } else if mime_type.starts_with("image/") {
-                parts.push(Part::InlineData {
-                    inline_data: InlineData {
-                        mime_type: mime_type.clone(),
-                        data: base64_engine.encode(self.file_content.as_ref().unwrap()),
-                    },
-                });
+                parts.push(Part::inline_data(
+                    mime_type.clone(),
+                    self.file_content.as_ref().unwrap(),
+                ));
             }
         }
 
@@ -447,61 +459,27 @@ impl FileChatManager {
         );
         let pb = PrettyPrinter::print_thinking();
 
-        match self.file_manager.list_files().await {
-            Ok(files) => {
-                if files.is_empty() {
-                    pb.finish_and_clear();
-                    println!("{}", "No files to delete".bright_yellow());
-                    return Ok(());
-                }
-
-                pb.set_message("Deleting files...");
-                let total = files.len();
-                let mut deleted = 0;
-
-                for file in files {
-                    match self.file_manager.delete_file(&file.name).await {
-                        Ok(_) => {
-                            deleted += 1;
-                            pb.set_message(match deleted {
-                                1 => "Deleted 1 file...",
-                                n => {
-                                    if n == total {
-                                        "All files deleted!"
-                                    } else {
-                                        "Deleting files..."
-                                    }
-                                }
-                            });
-                        }
-                        Err(e) => {
-                            println!("{} Failed to delete {}: {}", "⚠️".yellow(), file.name, e);
-                        }
-                    }
-                }
+        pb.set_message("Deleting files...");
+        let summary = self.file_manager.delete_all_files(8).await;
+        pb.finish_and_clear();
+        let summary = summary.map_err(ChatError::FileManagement)?;
 
-                pb.finish_and_clear();
-                println!(
-                    "{} Deleted {} files",
-                    "✨".bright_green(),
-                    deleted.to_string().bright_green()
-                );
-
-                // Clear current file info since files are deleted
-                self.current_file_info = None;
-                Ok(())
-            }
-            Err(e) => {
-                pb.finish_and_clear();
-                // Check if it's a decoding error (usually means empty response)
-                if e.to_string().contains("error decoding response body") {
-                    println!("{}", "No files to delete".bright_yellow());
-                    Ok(())
-                } else {
-                    Err(ChatError::FileManagement(e))
-                }
-            }
+        for (name, e) in &summary.failures {
+            println!("{} Failed to delete {}: {}", "⚠️".yellow(), name, e);
         }
+        if summary.deleted == 0 && summary.failures.is_empty() {
+            println!("{}", "No files to delete".bright_yellow());
+            return Ok(());
+        }
+        println!(
+            "{} Deleted {} files",
+            "✨".bright_green(),
+            summary.deleted.to_string().bright_green()
+        );
+
+        // Clear current file info since files are deleted
+        self.current_file_info = None;
+        Ok(())
     }
 
     pub fn get_file_info(&self) -> Option<FileInfo> {
@@ -523,7 +501,7 @@ impl FileChatManager {
         println!("{}", "📋 Fetching uploaded files...".bright_yellow().bold());
         let pb = PrettyPrinter::print_thinking();
 
-        match self.file_manager.list_files().await {
+        match self.file_manager.list_all_files().await {
             Ok(files) => {
                 pb.finish_and_clear();
                 PrettyPrinter::print_uploaded_files(&files);
@@ -531,13 +509,7 @@ impl FileChatManager {
             }
             Err(e) => {
                 pb.finish_and_clear();
-                // Check if it's a decoding error (usually means empty response)
-                if e.to_string().contains("error decoding response body") {
-                    println!("{}", "No files found".bright_yellow());
-                    Ok(())
-                } else {
-                    Err(ChatError::FileManagement(e))
-                }
+                Err(ChatError::FileManagement(e))
             }
         }
     }
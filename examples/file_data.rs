@@ -62,17 +62,10 @@ async fn demonstrate_file_operations(model: &GenerativeModel) -> Result<(), Box<
         .bright_yellow()
     );
 
-    let text_request = Request::builder()
-        .contents(vec![Content {
-            role: None,
-            parts: vec![Part::Text {
-                text: format!(
-                    "Analyze this text content and provide insights: {}",
-                    text_content
-                ),
-            }],
-        }])
-        .build();
+    let text_request = Request::from_prompt(format!(
+        "Analyze this text content and provide insights: {}",
+        text_content
+    ));
 
     pb.set_message("Analyzing text content...");
     match model.generate_response(text_request).await {
@@ -123,20 +116,15 @@ async fn demonstrate_file_operations(model: &GenerativeModel) -> Result<(), Box<
     );
 
     let image_request = Request::builder()
-        .contents(vec![Content {
-            role: None,
-            parts: vec![
-                Part::Text {
-                    text: "Describe this image in detail:".into(),
-                },
-                Part::InlineData {
-                    inline_data: InlineData {
-                        mime_type,
-                        data: base64_engine.encode(image_data),
-                    },
+        .contents(vec![Content::user_parts(vec![
+            Part::text("Describe this image in detail:"),
+            Part::InlineData {
+                inline_data: InlineData {
+                    mime_type,
+                    data: base64_engine.encode(image_data),
                 },
-            ],
-        }])
+            },
+        ])])
         .build();
 
     pb.set_message("Analyzing image content...");
@@ -157,15 +157,10 @@ async fn demonstrate_file_management(
     println!("{}", "═".repeat(50).bright_blue());
 
     let request = Request::builder()
-        .contents(vec![Content {
-            role: None,
-            parts: vec![
-                Part::Text {
-                    text: "Analyze this file:".into(),
-                },
-                Part::file_data(text_file_info.mime_type, text_file_info.uri),
-            ],
-        }])
+        .contents(vec![Content::user_parts(vec![
+            Part::text("Analyze this file:"),
+            Part::file_data(text_file_info.mime_type, text_file_info.uri),
+        ])])
         .build();
 
     pb.set_message("Analyzing file content...");
@@ -226,7 +221,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Initialize managers
     let model = GenerativeModel::from_env("gemini-1.5-flash")?;
-    let file_manager = GoogleAIFileManager::from_env();
+    let file_manager = GoogleAIFileManager::try_from_env()?;
     println!("{}", "✓ Gemini managers initialized".green());
 
     // Run file management demonstrations
@@ -2,9 +2,8 @@ use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
     models::{
-        Content,   FunctionDeclaration,
-        FunctionDeclarationSchema, FunctionResponse, Part, Request, Role, Schema, SchemaType,
-         Tool, Response,
+        Content, FunctionDeclaration, FunctionResponse, Part, Request, Response, Role, Schema,
+        SchemaType, Tool, Tools,
     },
     GenerativeModel,
 };
@@ -93,12 +92,6 @@ struct CalendarParams {
     duration_minutes: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SearchResultParams {
-    query: String,
-    max_results: i32,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct BookmarkParams {
     title: String,
@@ -110,14 +103,17 @@ struct BookmarkParams {
 async fn demonstrate_weather_function(model: &GenerativeModel) -> Result<(), Box<dyn Error>> {
     println!("\n{}", "🌤️  Weather Function Demo".bright_blue().bold());
     println!("{}", "=====================".bright_blue());
-    println!("{}", "Testing weather data retrieval function".bright_black());
+    println!(
+        "{}",
+        "Testing weather data retrieval function".bright_black()
+    );
 
     // Define the weather function
     let get_weather = FunctionDeclaration::builder()
         .name("get_weather")
         .description("Get the weather data for a location based on search results")
         .parameters(
-            FunctionDeclarationSchema::builder()
+            Schema::builder()
                 .r#type(SchemaType::Object)
                 .properties([
                     (
@@ -163,14 +159,12 @@ async fn demonstrate_weather_function(model: &GenerativeModel) -> Result<(), Box
         // First, search for real weather data
         let search_query = format!("current weather temperature {}", query);
         let search_request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: search_query }],
-            }])
+            .contents(vec![Content::user(search_query)])
             .tools(vec![Tool::GOOGLE_SEARCH])
             .build();
 
-        let search_response = generate_response_with_retry(model, search_request, "weather search").await?;
+        let search_response =
+            generate_response_with_retry(model, search_request, "weather search").await?;
         display_grounding_metadata(&search_response);
 
         // Add a small delay between requests to help with rate limiting
@@ -179,33 +173,37 @@ async fn demonstrate_weather_function(model: &GenerativeModel) -> Result<(), Box
         // Now use the weather function with the real data
         let weather_request = Request::builder()
             .contents(vec![
-                Content {
-                    role: Some(Role::User),
-                    parts: vec![Part::Text { text: query.into() }],
-                },
-                Content {
-                    role: Some(Role::Model),
-                    parts: vec![Part::Text { 
-                        text: "Based on the search results above, I'll provide you with the current weather information.".to_string() 
-                    }],
-                },
+                Content::user(query),
+                Content::model("Based on the search results above, I'll provide you with the current weather information."),
             ])
             .tools(vec![vec![get_weather.clone()].into()])
             .build();
 
-        let weather_response = generate_response_with_retry(model, weather_request, "weather function").await?;
+        let weather_response =
+            generate_response_with_retry(model, weather_request, "weather function").await?;
         let function_calls = weather_response.function_calls();
 
         if function_calls.is_empty() {
-            println!("{} {}", "🤖 Response:".green().bold(), weather_response.text().white());
+            println!(
+                "{} {}",
+                "🤖 Response:".green().bold(),
+                weather_response.text().white()
+            );
             continue;
         }
 
-        for call in function_calls {
-            println!("{} {} with {}", "📞 Function Call:".yellow().bold(), call.name, call.args);
-            
-            let params: WeatherParams = serde_json::from_value(call.args.clone())?;
-            
+        // Answer every call of this turn, then send all responses in one follow-up
+        let mut function_responses = Vec::new();
+        for call in &function_calls {
+            println!(
+                "{} {} with {}",
+                "📞 Function Call:".yellow().bold(),
+                call.name,
+                call.args
+            );
+
+            let params: WeatherParams = call.args_as()?;
+
             // Create a response using the real data from search
             let weather_response = FunctionResponse {
                 name: call.name.clone(),
@@ -233,31 +231,33 @@ async fn demonstrate_weather_function(model: &GenerativeModel) -> Result<(), Box
                 }),
             };
 
-            // Add a small delay before the follow-up request
-            sleep(Duration::from_millis(500)).await;
+            function_responses.push(weather_response);
+        }
 
-            let follow_up = Request::builder()
-                .contents(vec![
-                    Content {
-                        role: Some(Role::User),
-                        parts: vec![Part::Text {
-                            text: query.to_string(),
-                        }],
-                    },
-                    Content {
-                        role: Some(Role::Model),
-                        parts: vec![Part::FunctionCall { function_call: call }],
-                    },
-                    Content {
-                        role: Some(Role::Function),
-                        parts: vec![Part::FunctionResponse { function_response: weather_response }],
-                    },
-                ])
-                .build();
+        // Add a small delay before the follow-up request
+        sleep(Duration::from_millis(500)).await;
 
-            let final_response = generate_response_with_retry(model, follow_up, "weather summary").await?;
-            println!("{} {}", "🤖 Response:".green().bold(), final_response.text().white());
-        }
+        let follow_up = Request::builder()
+            .contents(vec![
+                Content::user(query.to_string()),
+                Content {
+                    role: Some(Role::Model),
+                    parts: function_calls
+                        .into_iter()
+                        .map(Part::function_call)
+                        .collect(),
+                },
+                Content::function_responses(function_responses),
+            ])
+            .build();
+
+        let final_response =
+            generate_response_with_retry(model, follow_up, "weather summary").await?;
+        println!(
+            "{} {}",
+            "🤖 Response:".green().bold(),
+            final_response.text().white()
+        );
 
         // Add a delay between different weather queries
         sleep(Duration::from_millis(1000)).await;
@@ -280,7 +280,7 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
     //         .name("add_event")
     //         .description("Add a new event to the calendar")
     //         .parameters(
-    //             FunctionDeclarationSchema::builder()
+    //             Schema::builder()
     //                 .r#type(SchemaType::Object)
     //                 .properties([
     //                     (
@@ -317,7 +317,7 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
     //         .name("view_events")
     //         .description("View events for a specific date")
     //         .parameters(
-    //             FunctionDeclarationSchema::builder()
+    //             Schema::builder()
     //                 .r#type(SchemaType::Object)
     //                 .properties([(
     //                     "date".to_string(),
@@ -340,14 +340,12 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
             .with_parameters(&[
                 "date, string, The date in YYYY-MM-DD format",
                 "event, string, The event description",
-                "duration_minutes, integer, Duration of the event in minutes"
-            ]),
+                "duration_minutes, integer, Duration of the event in minutes",
+            ])?,
         FunctionDeclaration::new()
             .with_name("view_events")
             .with_description("View events for a specific date")
-            .with_parameters(&[
-                "date, string, The date in YYYY-MM-DD format"
-            ])
+            .with_parameters(&["date, string, The date in YYYY-MM-DD format"])?,
     ];
 
     // Test calendar queries
@@ -362,12 +360,7 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
         println!("{} {}", "👤 User:".blue().bold(), query);
 
         let request = Request::builder()
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text {
-                    text: query.to_string(),
-                }],
-            }])
+            .contents(vec![Content::user(query.to_string())])
             .tools(vec![calendar_functions.clone().into()])
             .build();
 
@@ -383,7 +376,9 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
             continue;
         }
 
-        for call in function_calls {
+        // Answer every call of this turn, then send all responses in one follow-up
+        let mut function_responses = Vec::new();
+        for call in &function_calls {
             println!(
                 "{} {} with {}",
                 "📞 Function Call:".yellow().bold(),
@@ -394,7 +389,7 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
             // Simulate function execution
             let function_response = match call.name.as_str() {
                 "add_event" => {
-                    let params: CalendarParams = serde_json::from_value(call.args.clone())?;
+                    let params: CalendarParams = call.args_as()?;
                     FunctionResponse {
                         name: call.name.clone(),
                         response: json!({
@@ -440,34 +435,30 @@ async fn demonstrate_calendar_function(model: &GenerativeModel) -> Result<(), Bo
                 },
             };
 
-            let follow_up = Request::builder()
-                .contents(vec![
-                    Content {
-                        role: Some(Role::User),
-                        parts: vec![Part::Text {
-                            text: query.to_string(),
-                        }],
-                    },
-                    Content {
-                        role: Some(Role::Model),
-                        parts: vec![Part::FunctionCall {
-                            function_call: call,
-                        }],
-                    },
-                    Content {
-                        role: Some(Role::Function),
-                        parts: vec![Part::FunctionResponse { function_response }],
-                    },
-                ])
-                .build();
-
-            let final_response = generate_response_with_retry(model, follow_up, "calendar summary").await?;
-            println!(
-                "{} {}",
-                "🤖 Response:".green().bold(),
-                final_response.text().white()
-            );
+            function_responses.push(function_response);
         }
+
+        let follow_up = Request::builder()
+            .contents(vec![
+                Content::user(query.to_string()),
+                Content {
+                    role: Some(Role::Model),
+                    parts: function_calls
+                        .into_iter()
+                        .map(Part::function_call)
+                        .collect(),
+                },
+                Content::function_responses(function_responses),
+            ])
+            .build();
+
+        let final_response =
+            generate_response_with_retry(model, follow_up, "calendar summary").await?;
+        println!(
+            "{} {}",
+            "🤖 Response:".green().bold(),
+            final_response.text().white()
+        );
     }
 
     Ok(())
@@ -483,7 +474,7 @@ async fn demonstrate_search_function(model: &GenerativeModel) -> Result<(), Box<
     //     .name("bookmark_page")
     //     .description("Save a webpage as a bookmark")
     //     .parameters(
-    //         FunctionDeclarationSchema::builder()
+    //         Schema::builder()
     //             .r#type(SchemaType::Object)
     //             .properties([
     //                 (
@@ -520,8 +511,8 @@ async fn demonstrate_search_function(model: &GenerativeModel) -> Result<(), Box<
         .with_parameters(&[
             "title, string, Title of the webpage",
             "url, string, URL of the webpage",
-            "category, string, Category for organizing bookmarks"
-        ]);
+            "category, string, Category for organizing bookmarks",
+        ])?;
 
     // Test search and bookmark queries
     let queries = [
@@ -534,47 +525,43 @@ async fn demonstrate_search_function(model: &GenerativeModel) -> Result<(), Box<
         println!("\n{}", "━".repeat(50).bright_black());
         println!("{} {}", "👤 User:".blue().bold(), query);
 
-        // First, perform the search
-        let search_request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: query.into() }],
-            }])
-            .tools(vec![Tool::GOOGLE_SEARCH])
-            .build();
-
-        let search_response = generate_response_with_retry(model, search_request, "search").await?;
-        display_grounding_metadata(&search_response);
-
-        // Now, ask the model to bookmark the most relevant result
+        // Search and bookmark in one request, offering both tools
         let bookmark_request = Request::builder()
-            .contents(vec![
-                Content {
-                    role: Some(Role::User),
-                    parts: vec![Part::Text { text: query.into() }],
-                },
-                Content {
-                    role: Some(Role::Model),
-                    parts: vec![Part::Text { 
-                        text: "Based on the search results above, I'll help you bookmark the most relevant page.".to_string() 
-                    }],
-                },
-            ])
-            .tools(vec![vec![bookmark_function.clone()].into()])
+            .contents(vec![Content::user(query)])
+            .tools(
+                Tools::new()
+                    .google_search()
+                    .functions(vec![bookmark_function.clone()]),
+            )
             .build();
 
-        let bookmark_response = generate_response_with_retry(model, bookmark_request, "bookmark").await?;
+        let bookmark_response =
+            generate_response_with_retry(model, bookmark_request, "bookmark").await?;
+        if bookmark_response.tool_usage().grounding {
+            display_grounding_metadata(&bookmark_response);
+        }
         let function_calls = bookmark_response.function_calls();
 
         if function_calls.is_empty() {
-            println!("{} {}", "🤖 Response:".green().bold(), bookmark_response.text().white());
+            println!(
+                "{} {}",
+                "🤖 Response:".green().bold(),
+                bookmark_response.text().white()
+            );
             continue;
         }
 
-        for call in function_calls {
-            println!("{} {} with {}", "📞 Function Call:".yellow().bold(), call.name, call.args);
+        // Answer every call of this turn, then send all responses in one follow-up
+        let mut function_responses = Vec::new();
+        for call in &function_calls {
+            println!(
+                "{} {} with {}",
+                "📞 Function Call:".yellow().bold(),
+                call.name,
+                call.args
+            );
 
-            let params: BookmarkParams = serde_json::from_value(call.args.clone())?;
+            let params: BookmarkParams = call.args_as()?;
             let function_response = FunctionResponse {
                 name: call.name.clone(),
                 response: json!({
@@ -590,28 +577,30 @@ async fn demonstrate_search_function(model: &GenerativeModel) -> Result<(), Box<
                 }),
             };
 
-            let follow_up = Request::builder()
-                .contents(vec![
-                    Content {
-                        role: Some(Role::User),
-                        parts: vec![Part::Text {
-                            text: query.to_string(),
-                        }],
-                    },
-                    Content {
-                        role: Some(Role::Model),
-                        parts: vec![Part::FunctionCall { function_call: call }],
-                    },
-                    Content {
-                        role: Some(Role::Function),
-                        parts: vec![Part::FunctionResponse { function_response }],
-                    },
-                ])
-                .build();
-
-            let final_response = generate_response_with_retry(model, follow_up, "bookmark summary").await?;
-            println!("{} {}", "🤖 Response:".green().bold(), final_response.text().white());
+            function_responses.push(function_response);
         }
+
+        let follow_up = Request::builder()
+            .contents(vec![
+                Content::user(query.to_string()),
+                Content {
+                    role: Some(Role::Model),
+                    parts: function_calls
+                        .into_iter()
+                        .map(Part::function_call)
+                        .collect(),
+                },
+                Content::function_responses(function_responses),
+            ])
+            .build();
+
+        let final_response =
+            generate_response_with_retry(model, follow_up, "bookmark summary").await?;
+        println!(
+            "{} {}",
+            "🤖 Response:".green().bold(),
+            final_response.text().white()
+        );
     }
 
     Ok(())
@@ -71,7 +71,7 @@ fn display_grounding_metadata(response: &gemini_ai_rust::models::Response) {
                 println!("\n{}", "💡 AI Response:".magenta().bold());
                 for part in &content.parts {
                     match part {
-                        Part::Text { text } => {
+                        Part::Text { text, .. } => {
                             println!("{}", text.bright_white());
                         }
                         _ => println!("{}", "Unsupported content type".red()),
@@ -106,10 +106,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         println!("{} {}", "🔍 Query:".blue().bold(), query);
 
         let request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: query.into() }],
-            }])
+            .contents(vec![Content::user(query)])
             .tools(vec![Tool::GOOGLE_SEARCH])
             .build();
 
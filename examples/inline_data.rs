@@ -1,7 +1,7 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, GenerationConfig, Part, Request, ResponseSchema, Role, SchemaType},
+    models::{Content, GenerationConfig, Part, Request, ResponseSchema, SchemaType},
     GenerativeModel,
 };
 use serde::{Deserialize, Serialize};
@@ -301,15 +301,10 @@ async fn demonstrate_inventory_extraction(model: &GenerativeModel) -> Result<(),
 
     // Create request with image and schema
     let request = Request::builder()
-        .contents(vec![Content {
-            role: Some(Role::User),
-            parts: vec![
-                Part::text(
-                    "Extract the inventory data from this image and convert it to JSON format",
-                ),
-                Part::image_from_path(IMAGE_PATH)?,
-            ],
-        }])
+        .contents(vec![Content::user_parts(vec![
+            Part::text("Extract the inventory data from this image and convert it to JSON format"),
+            Part::image_from_path(IMAGE_PATH)?,
+        ])])
         .generation_config(
             GenerationConfig::builder()
                 .response_mime_type("application/json")
@@ -0,0 +1,64 @@
+use colored::*;
+use dotenv::dotenv;
+use gemini_ai_rust::{
+    models::{Content, GenerationConfig, Request},
+    GenerativeModel,
+};
+use std::error::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    println!("{}", "🤖 Gemini Logprobs Demo".bright_green().bold());
+    println!("{}", "=======================".bright_green());
+
+    // Load environment variables from .env file
+    dotenv().ok();
+
+    // Create a client from environment variables
+    let model = GenerativeModel::from_env("gemini-1.5-flash")?;
+    println!("{}", "✓ Gemini model initialized".green());
+
+    let prompt = "Is Rust a memory-safe language? Answer in one short sentence.";
+    println!("\n{} {}", "🔍 Prompt:".blue().bold(), prompt);
+
+    // Ask for the log probability of each chosen token and the top 3 alternatives
+    let request = Request::builder()
+        .contents(vec![Content::user(prompt)])
+        .generation_config(
+            GenerationConfig::builder()
+                .response_logprobs(true)
+                .logprobs(3)
+                .build(),
+        )
+        .build();
+
+    let response = model.generate_response(request).await?;
+    println!("\n{}\n{}", "💡 Answer:".magenta().bold(), response.text());
+
+    let Some(candidate) = response.candidates.as_ref().and_then(|c| c.first()) else {
+        return Ok(());
+    };
+    if let Some(average) = candidate.avg_logprobs {
+        println!("\n{} {:.4}", "Average log probability:".yellow(), average);
+    }
+
+    if let Some(logprobs) = &candidate.logprobs_result {
+        println!("\n{}", "📊 Top tokens per decoding step:".cyan().bold());
+        for (step, (chosen, top)) in logprobs.steps().enumerate() {
+            let alternatives = top
+                .iter()
+                .map(|candidate| format!("{:?} {:.3}", candidate.token, candidate.log_probability))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!(
+                "{:>3}. {} {:.3}  {}",
+                step + 1,
+                format!("{:?}", chosen.token).bright_white().bold(),
+                chosen.log_probability,
+                alternatives.bright_black()
+            );
+        }
+    }
+
+    Ok(())
+}
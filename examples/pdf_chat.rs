@@ -9,7 +9,7 @@ use futures::StreamExt;
 use gemini_ai_rust::{
     client::GenerativeModel,
     error::GoogleGenerativeAIError,
-    models::{Content, EmbedContentRequest, ModelParams, Part, Request, TaskType},
+    models::{Content, EmbedContentRequest, ModelParams, Request, TaskType},
 };
 use indicatif::{ProgressBar, ProgressStyle};
 use pdf_extract::extract_text_by_pages;
@@ -52,7 +52,10 @@ impl Config {
 
         // Create default config file if it doesn't exist
         if !config_path.exists() {
-            std::fs::write(&config_path, r#"{ "google_api_key": "", "generative_model": "gemini-1.5-flash", "embedding_model": "embedding-001" }"#)?;
+            std::fs::write(
+                &config_path,
+                r#"{ "google_api_key": "", "generative_model": "gemini-1.5-flash", "embedding_model": "embedding-001" }"#,
+            )?;
         }
 
         // Load config from multiple sources, with following precedence:
@@ -248,11 +251,11 @@ pub async fn get_files(
         if path.is_file()
             && path
                 .extension()
-                .map_or(false, |ext| ext.eq_ignore_ascii_case(extension))
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
         {
             let metadata = entry.metadata().await?;
             let size = metadata.len();
-            if min_size.map_or(true, |min| size >= min) {
+            if min_size.is_none_or(|min| size >= min) {
                 index += 1;
                 matching_files.push(EntityFile {
                     index,
@@ -625,10 +628,7 @@ impl DocumentChatManager {
             Respond using Markdown."
                     .into(),
             ))
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: prompt }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .build();
 
         let mut stream = self.model.stream_generate_response(request).await?;
@@ -724,10 +724,7 @@ impl DocumentChatManager {
             Respond using Markdown."
                     .into(),
             ))
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text { text: prompt }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .build();
 
         let response = self.model.generate_response(request).await?;
@@ -946,7 +943,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let model = GenerativeModel::new(
         config.google_api_key.clone(),
-        ModelParams::builder().model(&config.generative_model).build(),
+        ModelParams::builder()
+            .model(&config.generative_model)
+            .build(),
     );
     let mut doc_manager = DocumentChatManager::new(model.clone(), &config.embedding_model, 200);
     PrettyPrinter::print_success("Document manager initialized");
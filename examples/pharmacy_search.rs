@@ -193,7 +193,7 @@ impl PharmacySearchEngine {
         category_filter: Option<&str>,
         prescription_only: Option<bool>,
         limit: usize,
-    ) -> Result<Vec<SearchResult>, PharmacyError> {
+    ) -> Result<Vec<SearchResult<'_>>, PharmacyError> {
         let request = EmbedContentRequest::new(query, Some(TaskType::RetrievalQuery), None);
         let response = self.model.embed_content(&self.model_name, request).await?;
         let query_embedding = response.embedding.values;
@@ -204,10 +204,10 @@ impl PharmacySearchEngine {
             .filter(|doc| {
                 doc_type_filter
                     .as_ref()
-                    .map_or(true, |t| t == &doc.metadata.doc_type)
+                    .is_none_or(|t| t == &doc.metadata.doc_type)
                     && category_filter
-                        .map_or(true, |c| c.eq_ignore_ascii_case(&doc.metadata.category))
-                    && prescription_only.map_or(true, |p| p == doc.metadata.requires_prescription)
+                        .is_none_or(|c| c.eq_ignore_ascii_case(&doc.metadata.category))
+                    && prescription_only.is_none_or(|p| p == doc.metadata.requires_prescription)
             })
             .filter_map(|doc| {
                 doc.embedding.as_ref().map(|emb| SearchResult {
@@ -1,7 +1,7 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, HarmCategory, Part, Request, SafetySetting, SafetyThreshold},
+    models::{Content, HarmCategory, Request, SafetySetting, SafetyThreshold},
     GenerativeModel,
 };
 use std::error::Error;
@@ -132,12 +132,7 @@ async fn demonstrate_safety_configs(model: &GenerativeModel) -> Result<(), Box<d
             println!("\n{} {}", "🔍 Prompt:".yellow().bold(), prompt);
 
             let request = Request::builder()
-                .contents(vec![Content {
-                    role: None,
-                    parts: vec![Part::Text {
-                        text: (*prompt).into(),
-                    }],
-                }])
+                .contents(vec![Content::user(*prompt)])
                 .safety_settings(safety_settings.clone())
                 .build();
 
@@ -1,10 +1,7 @@
 use colored::*;
 use dotenv::dotenv;
 use futures::StreamExt;
-use gemini_ai_rust::{
-    models::{Content, Part, Request},
-    GenerativeModel,
-};
+use gemini_ai_rust::{models::Request, GenerativeModel};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::{error::Error, time::Duration};
 
@@ -47,14 +44,7 @@ async fn demonstrate_streaming(model: &GenerativeModel) -> Result<(), Box<dyn Er
         pb.set_message("Generating response...");
         pb.enable_steady_tick(Duration::from_millis(100));
 
-        let request = Request::builder()
-            .contents(vec![Content {
-                role: None,
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
-            .build();
+        let request = Request::from_prompt(prompt);
 
         let mut stream = model.stream_generate_response(request).await?;
         pb.finish_and_clear();
@@ -1,7 +1,7 @@
 use colored::*;
 use dotenv::dotenv;
 use gemini_ai_rust::{
-    models::{Content, Part, Request, Role},
+    models::{Content, Request},
     GenerativeModel,
 };
 use std::error::Error;
@@ -51,12 +51,7 @@ async fn demonstrate_personas(model: &GenerativeModel) -> Result<(), Box<dyn Err
 
         let request = Request::builder()
             .system_instruction(Some(system_instruction.into()))
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text {
-                    text: prompt.into(),
-                }],
-            }])
+            .contents(vec![Content::user(prompt)])
             .build();
 
         match model.generate_response(request).await {
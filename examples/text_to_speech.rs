@@ -0,0 +1,50 @@
+use colored::*;
+use dotenv::dotenv;
+use gemini_ai_rust::{
+    models::{Content, GenerationConfig, Modality, Request, SpeechConfig},
+    GenerativeModel,
+};
+use std::error::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    println!("{}", "🤖 Gemini Text-to-Speech Demo".bright_green().bold());
+    println!("{}", "=============================".bright_green());
+
+    // Load environment variables from .env file
+    dotenv().ok();
+
+    // Create a client for a text-to-speech model from environment variables
+    let model = GenerativeModel::from_env("gemini-2.5-flash-preview-tts")?;
+    println!("{}", "✓ Gemini model initialized".green());
+
+    let text = "Say cheerfully: Have a wonderful day!";
+    println!("\n{} {}", "🗣 Text:".blue().bold(), text);
+
+    // Ask for a spoken reply in the prebuilt voice "Kore"
+    let request = Request::builder()
+        .contents(vec![Content::user(text)])
+        .generation_config(
+            GenerationConfig::builder()
+                .response_modalities(vec![Modality::Audio])
+                .speech_config(SpeechConfig::voice("Kore"))
+                .build(),
+        )
+        .build();
+
+    let response = model.generate_response(request).await?;
+    if let Some((mime_type, data)) = response.audio() {
+        println!(
+            "{} {} ({} bytes)",
+            "🔊 Audio:".magenta().bold(),
+            mime_type,
+            data.len()
+        );
+    }
+
+    // The raw PCM reply is wrapped in a WAV header when saved
+    response.save_audio("speech.wav").await?;
+    println!("{}", "✓ Saved to speech.wav".green());
+
+    Ok(())
+}
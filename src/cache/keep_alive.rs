@@ -0,0 +1,224 @@
+//! Keeping a cached content alive while it is in use
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::{CacheError, CacheManager};
+
+/// The shortest time between two refreshes of a [`CacheKeepAlive`]
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps a cached content alive by extending its TTL in the background
+///
+/// A tokio task sets the TTL of the cache again at a fixed interval, by default
+/// every half TTL and at most once per [`MIN_REFRESH_INTERVAL`]. Refreshing stops
+/// when the handle is dropped or [`release`](Self::release)d, after which the cache
+/// expires at the end of its last TTL. Failed refreshes do not stop the task; the
+/// outcome of the latest refresh is published through [`status`](Self::status).
+#[derive(Debug)]
+pub struct CacheKeepAlive {
+    /// The resource name of the cached content
+    name: String,
+    /// The task refreshing the TTL
+    task: JoinHandle<()>,
+    /// The outcome of the latest refresh
+    status: watch::Receiver<Option<Arc<CacheError>>>,
+}
+
+impl CacheKeepAlive {
+    /// Starts refreshing the TTL of a cached content every `ttl / 2`
+    ///
+    /// Must be called within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager`: The manager used for the refreshes
+    /// * `name`: The resource name of the cached content
+    /// * `ttl`: The TTL set on every refresh
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CacheError::InvalidTtl`] if `ttl` is not longer than
+    /// [`MIN_REFRESH_INTERVAL`].
+    pub fn start(
+        manager: CacheManager,
+        name: impl Into<String>,
+        ttl: Duration,
+    ) -> Result<Self, CacheError> {
+        Self::start_with_interval(manager, name, ttl, ttl / 2)
+    }
+
+    /// Starts refreshing the TTL of a cached content at the specified interval
+    ///
+    /// Must be called within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager`: The manager used for the refreshes
+    /// * `name`: The resource name of the cached content
+    /// * `ttl`: The TTL set on every refresh
+    /// * `interval`: The time between two refreshes, which should be well below `ttl`;
+    ///   raised to [`MIN_REFRESH_INTERVAL`] if shorter
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CacheError::InvalidTtl`] if `ttl` is not longer than the interval,
+    /// since the cache would then expire between two refreshes.
+    pub fn start_with_interval(
+        manager: CacheManager,
+        name: impl Into<String>,
+        ttl: Duration,
+        interval: Duration,
+    ) -> Result<Self, CacheError> {
+        let interval = refresh_interval(ttl, interval)?;
+        Ok(Self::spawn(manager, name.into(), ttl, interval))
+    }
+
+    /// Spawns the task refreshing the TTL every `interval`
+    fn spawn(manager: CacheManager, name: String, ttl: Duration, interval: Duration) -> Self {
+        let (sender, status) = watch::channel(None);
+        let task = tokio::spawn({
+            let name = name.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let outcome = manager.update_cache_ttl(&name, ttl).await;
+                    sender.send_replace(outcome.err().map(Arc::new));
+                }
+            }
+        });
+        Self { name, task, status }
+    }
+
+    /// Returns the resource name of the cached content
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a receiver of the outcome of the latest refresh
+    ///
+    /// The value is `None` until a refresh fails and after every successful refresh,
+    /// and the error of the latest refresh otherwise.
+    pub fn status(&self) -> watch::Receiver<Option<Arc<CacheError>>> {
+        self.status.clone()
+    }
+
+    /// Stops refreshing the TTL and lets the cache expire
+    pub fn release(self) {
+        // Dropping the handle aborts the task
+    }
+}
+
+/// Returns the interval between two refreshes, at least [`MIN_REFRESH_INTERVAL`],
+/// rejecting a `ttl` the refreshes cannot keep up with
+fn refresh_interval(ttl: Duration, interval: Duration) -> Result<Duration, CacheError> {
+    let interval = interval.max(MIN_REFRESH_INTERVAL);
+    if ttl <= interval {
+        return Err(CacheError::InvalidTtl(format!(
+            "{:?} is not longer than the refresh interval of {:?}",
+            ttl, interval
+        )));
+    }
+    Ok(interval)
+}
+
+impl Drop for CacheKeepAlive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{MockResponse, MockServer};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_refreshes_until_released() {
+        let patches = Arc::new(AtomicUsize::new(0));
+        let server = MockServer::start({
+            let patches = patches.clone();
+            move |_| {
+                // The second refresh fails, the ones after it succeed again.
+                if patches.fetch_add(1, Ordering::SeqCst) == 1 {
+                    MockResponse::json(
+                        500,
+                        json!({ "error": { "code": 500, "message": "boom", "status": "INTERNAL" } }),
+                    )
+                } else {
+                    MockResponse::json(200, json!({ "name": "cachedContents/abc" }))
+                }
+            }
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        // Bypasses the minimum interval to keep the test fast.
+        let keep_alive = CacheKeepAlive::spawn(
+            manager,
+            "cachedContents/abc".to_string(),
+            Duration::from_secs(60),
+            Duration::from_millis(20),
+        );
+        let mut status = keep_alive.status();
+        status.wait_for(|error| error.is_some()).await.unwrap();
+        status.wait_for(|error| error.is_none()).await.unwrap();
+        keep_alive.release();
+        // Let a request that was in flight when the task was aborted settle.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let requests = server.requests();
+        assert!(requests.len() >= 3);
+        assert!(requests.iter().all(|request| request.method == "PATCH"
+            && request.path() == "/cachedContents/abc"
+            && request.json()["ttl"] == "60s"));
+
+        let sent = requests.len();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.requests().len(), sent);
+    }
+
+    #[test]
+    fn test_refresh_interval_is_clamped() {
+        let interval = refresh_interval(Duration::from_secs(60), Duration::from_secs(30));
+        assert_eq!(interval.unwrap(), Duration::from_secs(30));
+
+        let interval = refresh_interval(Duration::from_secs(60), Duration::ZERO);
+        assert_eq!(interval.unwrap(), MIN_REFRESH_INTERVAL);
+
+        let interval = refresh_interval(Duration::from_secs(3), Duration::from_millis(1));
+        assert_eq!(interval.unwrap(), MIN_REFRESH_INTERVAL);
+
+        let interval = refresh_interval(Duration::from_secs(10), Duration::from_secs(10));
+        assert!(matches!(interval, Err(CacheError::InvalidTtl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_zero_and_tiny_ttl_are_rejected_before_refreshing() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, json!({ "name": "cachedContents/abc" })))
+                .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        for ttl in [
+            Duration::ZERO,
+            Duration::from_millis(10),
+            MIN_REFRESH_INTERVAL,
+        ] {
+            let error =
+                CacheKeepAlive::start(manager.clone(), "cachedContents/abc", ttl).unwrap_err();
+            assert!(matches!(error, CacheError::InvalidTtl(_)), "{ttl:?}");
+        }
+        assert!(
+            CacheKeepAlive::start(manager, "cachedContents/abc", Duration::from_secs(60)).is_ok()
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(server.requests().is_empty());
+    }
+}
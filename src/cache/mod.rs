@@ -1,9 +1,22 @@
 //! Module for managing cached content in the Gemini AI system
 
-use crate::models::{Content, Part, Role};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::error::ApiError;
+use crate::file::{FileError, FileInfo, FileState, GoogleAIFileManager};
+use crate::key_pool::{KeyPool, PooledKey};
+use crate::models::{
+    format_duration, parse_duration, Content, Part, Role, SystemInstruction, Tool, ToolConfig,
+};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::Duration;
+use typed_builder::TypedBuilder;
+
+mod keep_alive;
+
+pub use keep_alive::{CacheKeepAlive, MIN_REFRESH_INTERVAL};
 
 /// The base URL for the cache API
 const CACHE_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
@@ -20,22 +33,68 @@ pub enum CacheError {
     /// Invalid MIME type for the file
     #[error("Invalid MIME type: {0}")]
     MimeTypeError(String),
+    /// The referenced file cannot be used yet, e.g. because it is still processing
+    #[error("File {name} is not active: {reason}")]
+    FileNotActive {
+        /// The resource name of the file
+        name: String,
+        /// Why the file cannot be used
+        reason: String,
+    },
+    /// A request to the file API failed
+    #[error("File API request failed: {0}")]
+    FileError(#[from] FileError),
+    /// The time-to-live is zero, negative or malformed
+    #[error("Invalid TTL: {0}")]
+    InvalidTtl(String),
+    /// The cached content does not exist, e.g. because it expired
+    #[error("Cached content not found: {0}")]
+    NotFound(String),
+    /// The API answered with an error status
+    #[error("{0}")]
+    Api(ApiError),
     /// Generic cache operation error
     #[error("Cache operation failed: {0}")]
     OperationError(String),
 }
 
+impl CacheError {
+    /// Returns the structured API error, if this error was returned by the API
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::Api(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the API rejected the cache because its contents have fewer
+    /// tokens than the model's minimum for cached content
+    pub fn is_too_small(&self) -> bool {
+        self.api_error().is_some_and(|error| {
+            error.code.as_deref() == Some("INVALID_ARGUMENT")
+                && (error.message.contains("min_total_token_count")
+                    || error.message.contains("too small"))
+        })
+    }
+}
+
 /// Information about a cached content
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CacheInfo {
     /// The resource name of the cached content
     pub name: String,
+    /// Optional user-chosen name of the cached content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
     /// The cached content
+    #[serde(default)]
     pub contents: Vec<Content>,
     /// Optional system instruction for the cached content
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system_instruction: Option<Content>,
     /// Time-to-live duration in seconds
+    #[serde(default)]
     pub ttl: String,
     /// Creation time of the cached content
     pub create_time: Option<String>,
@@ -43,119 +102,486 @@ pub struct CacheInfo {
     pub update_time: Option<String>,
     /// Expiration time of the cached content
     pub expire_time: Option<String>,
+    /// Token usage of the cached content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<CacheUsageMetadata>,
+}
+
+/// Token usage of a cached content
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheUsageMetadata {
+    /// Total number of tokens the cached content occupies
+    #[serde(default)]
+    pub total_token_count: i32,
+}
+
+impl CacheInfo {
+    /// Returns the time-to-live as a duration, if the API reported a valid one
+    pub fn ttl_duration(&self) -> Option<Duration> {
+        parse_duration(&self.ttl)
+    }
+}
+
+/// The time-to-live of a cached content
+///
+/// Created from a [`Duration`] or from a string in the API's format, e.g. `"300s"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheTtl(String);
+
+impl CacheTtl {
+    /// Returns the TTL in the wire format, rejecting zero, negative and malformed values
+    fn into_wire(self) -> Result<String, CacheError> {
+        match parse_duration(&self.0) {
+            Some(duration) if !duration.is_zero() => Ok(self.0),
+            _ => Err(CacheError::InvalidTtl(format!(
+                "{:?} is not a positive duration such as \"300s\"",
+                self.0
+            ))),
+        }
+    }
+}
+
+impl From<Duration> for CacheTtl {
+    fn from(duration: Duration) -> Self {
+        Self(format_duration(duration))
+    }
+}
+
+impl From<String> for CacheTtl {
+    fn from(ttl: String) -> Self {
+        Self(ttl)
+    }
+}
+
+impl From<&str> for CacheTtl {
+    fn from(ttl: &str) -> Self {
+        Self(ttl.to_string())
+    }
+}
+
+/// One page of a cache listing
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheListPage {
+    /// The cached contents of this page
+    #[serde(default)]
+    pub cached_contents: Vec<CacheInfo>,
+    /// The token of the next page, if there is one
+    pub next_page_token: Option<String>,
 }
 
 /// Request to create cached content
-#[derive(Debug, Serialize)]
+///
+/// Tool declarations and the tool configuration are cached along with the contents,
+/// so requests using the cache do not need to send them again.
+#[derive(Debug, Clone, Serialize, TypedBuilder)]
+#[serde(rename_all = "camelCase")]
+#[builder(doc)]
 pub struct CreateCacheRequest {
-    /// The model to use for the cached content
+    /// The model to use for the cached content, e.g. `gemini-1.5-flash-001` or
+    /// `models/gemini-1.5-flash-001`
+    #[builder(setter(into))]
     pub model: String,
     /// The content to cache
+    #[builder(setter(into))]
     pub contents: Vec<Content>,
     /// Optional system instruction for the cached content
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub system_instruction: Option<Content>,
-    /// Time-to-live duration in seconds
+    #[builder(default, setter(strip_option, into))]
+    pub system_instruction: Option<SystemInstruction>,
+    /// Optional tools to cache with the content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub tools: Option<Vec<Tool>>,
+    /// Optional configuration of the cached tools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub tool_config: Option<ToolConfig>,
+    /// Time-to-live, e.g. `"300s"`
+    #[builder(setter(transform = |ttl: impl Into<CacheTtl>| ttl.into().0))]
     pub ttl: String,
 }
 
+/// How many times the file state is polled before giving up
+const FILE_WAIT_RETRIES: u32 = 60;
+
+/// The delay between two polls of the file state, in milliseconds
+const FILE_WAIT_DELAY_MS: u64 = 1000;
+
+/// Returns the model name in the `models/...` resource form expected by the API
+fn model_resource_name(model: String) -> String {
+    if model.starts_with("models/") || model.starts_with("tunedModels/") {
+        model
+    } else {
+        format!("models/{}", model)
+    }
+}
+
+/// The new lifetime of a cached content, sent as exactly one of `ttl` and `expireTime`
+enum CacheExpiration {
+    /// Time-to-live duration, e.g. `"300s"`
+    Ttl(String),
+    /// Absolute expiration time
+    ExpireTime(DateTime<Utc>),
+}
+
+impl CacheExpiration {
+    /// Returns the field mask and the body of the update request
+    fn patch(&self) -> (&'static str, serde_json::Value) {
+        match self {
+            Self::Ttl(ttl) => ("ttl", serde_json::json!({ "ttl": ttl })),
+            Self::ExpireTime(expire_time) => (
+                "expireTime",
+                serde_json::json!({
+                    "expireTime": expire_time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+                }),
+            ),
+        }
+    }
+}
+
 /// Manager for cache operations
+#[derive(Debug, Clone)]
 pub struct CacheManager {
     /// The HTTP client used for cache operations
     client: reqwest::Client,
-    /// The API key used for authentication
-    api_key: String,
+    /// The API keys used for authentication
+    keys: KeyPool,
+    /// The base URL of the cache API
+    base_url: String,
 }
 
 impl CacheManager {
     /// Creates a new instance of the cache manager
     pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_key_pool(KeyPool::from(api_key.into()))
+    }
+
+    /// Creates a new instance of the cache manager that rotates between the keys of a pool
+    pub fn with_key_pool(keys: KeyPool) -> Self {
+        Self::with_key_pool_and_client(keys, reqwest::Client::new())
+    }
+
+    /// Creates a new instance of the cache manager that sends its requests through an existing HTTP client
+    pub fn with_client(api_key: impl Into<String>, client: reqwest::Client) -> Self {
+        Self::with_key_pool_and_client(KeyPool::from(api_key.into()), client)
+    }
+
+    /// Creates a new instance of the cache manager from a pool of API keys and an existing HTTP client
+    pub fn with_key_pool_and_client(keys: KeyPool, client: reqwest::Client) -> Self {
         Self {
-            client: reqwest::Client::new(),
-            api_key: api_key.into(),
+            client,
+            keys,
+            base_url: CACHE_API_URL.to_string(),
         }
     }
 
-    /// Creates a new cached content from a file
+    /// Overrides the base URL of the cache API
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Checks the status of a response, marking the key as rate limited on 429
+    async fn check_response(
+        &self,
+        response: reqwest::Response,
+        key: &PooledKey,
+    ) -> Result<reqwest::Response, CacheError> {
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            self.keys.report_rate_limited(key);
+        }
+        if status == reqwest::StatusCode::NOT_FOUND {
+            let error = ApiError::from_response(response).await;
+            return Err(CacheError::NotFound(error.message));
+        }
+        if !status.is_success() {
+            return Err(CacheError::Api(ApiError::from_response(response).await));
+        }
+        Ok(response)
+    }
+
+    /// Creates a new cached content
     ///
     /// # Arguments
     ///
-    /// * `model`: The model to use for the cached content
-    /// * `file_path`: The path to the file to cache
+    /// * `model`: The model to use for the cached content, e.g. `gemini-1.5-flash-001`
+    ///   or `models/gemini-1.5-flash-001`
+    /// * `contents`: The content to cache
     /// * `system_instruction`: Optional system instruction for the cached content
-    /// * `ttl`: Time-to-live duration in seconds
-    pub async fn create_cache_from_file(
+    /// * `ttl`: Time-to-live, e.g. `Duration::from_secs(300)` or `"300s"`
+    pub async fn create_cache(
         &self,
         model: impl Into<String>,
-        file_path: impl AsRef<Path>,
-        system_instruction: Option<Content>,
-        ttl: impl Into<String>,
+        contents: Vec<Content>,
+        system_instruction: Option<SystemInstruction>,
+        ttl: impl Into<CacheTtl>,
     ) -> Result<CacheInfo, CacheError> {
-        let file_path = file_path.as_ref();
-
-        // Create cache request
         let request = CreateCacheRequest {
             model: model.into(),
-            contents: vec![Content {
-                parts: vec![Part::image_from_path(file_path)?],
-                role: Some(Role::User),
-            }],
+            contents,
             system_instruction,
-            ttl: ttl.into(),
+            tools: None,
+            tool_config: None,
+            ttl: ttl.into().0,
         };
+        self.create_cache_from_request(request).await
+    }
+
+    /// Creates a new cached content from a full request, e.g. to cache tools as well
+    ///
+    /// # Arguments
+    ///
+    /// * `request`: The request, built with [`CreateCacheRequest::builder`]
+    pub async fn create_cache_from_request(
+        &self,
+        mut request: CreateCacheRequest,
+    ) -> Result<CacheInfo, CacheError> {
+        request.model = model_resource_name(request.model);
+        request.ttl = CacheTtl(request.ttl).into_wire()?;
 
         // Send request
-        let url = format!("{}/cachedContents", CACHE_API_URL);
+        let url = format!("{}/cachedContents", self.base_url);
+        let key = self.keys.acquire();
         let response = self
             .client
             .post(&url)
-            .query(&[("key", &self.api_key)])
+            .query(&[("key", key.as_str())])
             .json(&request)
             .send()
             .await?;
-
-        // Check if response is an error
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(CacheError::OperationError(format!(
-                "Request failed with status {}: {}",
-                status, error_text
-            )));
-        }
+        let response = self.check_response(response, &key).await?;
 
         // Parse response
         let cache_info = response.json().await?;
         Ok(cache_info)
     }
 
-    /// Lists all cached contents
+    /// Creates a new cached content from text, e.g. a long document or transcript
+    ///
+    /// # Arguments
+    ///
+    /// * `model`: The model to use for the cached content
+    /// * `text`: The text to cache
+    /// * `ttl`: Time-to-live, e.g. `Duration::from_secs(300)` or `"300s"`
+    pub async fn create_cache_from_text(
+        &self,
+        model: impl Into<String>,
+        text: impl Into<String>,
+        ttl: impl Into<CacheTtl>,
+    ) -> Result<CacheInfo, CacheError> {
+        let contents = vec![Content {
+            parts: vec![Part::text(text)],
+            role: Some(Role::User),
+        }];
+        self.create_cache(model, contents, None, ttl).await
+    }
+
+    /// Creates a new cached content from an image file
+    ///
+    /// # Arguments
+    ///
+    /// * `model`: The model to use for the cached content
+    /// * `file_path`: The path to the file to cache
+    /// * `system_instruction`: Optional system instruction for the cached content
+    /// * `ttl`: Time-to-live, e.g. `Duration::from_secs(300)` or `"300s"`
+    pub async fn create_cache_from_file(
+        &self,
+        model: impl Into<String>,
+        file_path: impl AsRef<Path>,
+        system_instruction: Option<SystemInstruction>,
+        ttl: impl Into<CacheTtl>,
+    ) -> Result<CacheInfo, CacheError> {
+        let contents = vec![Content {
+            parts: vec![Part::image_from_path(file_path.as_ref())?],
+            role: Some(Role::User),
+        }];
+        self.create_cache(model, contents, system_instruction, ttl)
+            .await
+    }
+
+    /// Creates a new cached content referencing a file uploaded with the file API
+    ///
+    /// This is the recommended way to cache large PDFs and videos.
+    ///
+    /// # Arguments
+    ///
+    /// * `model`: The model to use for the cached content
+    /// * `file`: The uploaded file, which must be active
+    /// * `system_instruction`: Optional system instruction for the cached content
+    /// * `ttl`: Time-to-live, e.g. `Duration::from_secs(300)` or `"300s"`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CacheError::FileNotActive`] without sending a request if the file is
+    /// not active yet.
+    pub async fn create_cache_from_file_info(
+        &self,
+        model: impl Into<String>,
+        file: &FileInfo,
+        system_instruction: Option<SystemInstruction>,
+        ttl: impl Into<CacheTtl>,
+    ) -> Result<CacheInfo, CacheError> {
+        if !matches!(file.state, FileState::Active) {
+            return Err(CacheError::FileNotActive {
+                name: file.name.clone(),
+                reason: format!("the file state is {}", file.state),
+            });
+        }
+
+        let contents = vec![Content {
+            parts: vec![Part::file_data(file.mime_type.clone(), file.uri.clone())],
+            role: Some(Role::User),
+        }];
+        self.create_cache(model, contents, system_instruction, ttl)
+            .await
+    }
+
+    /// Waits until an uploaded file is active and then creates a cached content
+    /// referencing it
+    ///
+    /// # Arguments
+    ///
+    /// * `file_manager`: The file manager used to poll the state of the file
+    /// * `model`: The model to use for the cached content
+    /// * `file`: The uploaded file
+    /// * `system_instruction`: Optional system instruction for the cached content
+    /// * `ttl`: Time-to-live, e.g. `Duration::from_secs(300)` or `"300s"`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CacheError::FileNotActive`] if processing the file failed or did not
+    /// finish in time, and [`CacheError::FileError`] if polling the file failed.
+    pub async fn create_cache_from_file_info_when_active(
+        &self,
+        file_manager: &GoogleAIFileManager,
+        model: impl Into<String>,
+        file: &FileInfo,
+        system_instruction: Option<SystemInstruction>,
+        ttl: impl Into<CacheTtl>,
+    ) -> Result<CacheInfo, CacheError> {
+        if matches!(file.state, FileState::Active) {
+            return self
+                .create_cache_from_file_info(model, file, system_instruction, ttl)
+                .await;
+        }
+
+        let file = file_manager
+            .wait_for_file_processing(&file.name, FILE_WAIT_RETRIES, FILE_WAIT_DELAY_MS)
+            .await
+            .map_err(|e| match e {
+                FileError::ProcessingError(reason) => CacheError::FileNotActive {
+                    name: file.name.clone(),
+                    reason,
+                },
+                FileError::Timeout { last_state, .. } => CacheError::FileNotActive {
+                    name: file.name.clone(),
+                    reason: format!(
+                        "processing did not finish, the file state is {}",
+                        last_state
+                    ),
+                },
+                e => CacheError::FileError(e),
+            })?;
+        self.create_cache_from_file_info(model, &file, system_instruction, ttl)
+            .await
+    }
+
+    /// Lists all cached contents, following every page of the listing
     pub async fn list_caches(&self) -> Result<Vec<CacheInfo>, CacheError> {
-        let url = format!("{}/cachedContents", CACHE_API_URL);
-        let response = self
-            .client
-            .get(&url)
-            .query(&[("key", &self.api_key)])
-            .send()
-            .await?;
+        let mut caches = Vec::new();
+        let mut page_token = None;
+        loop {
+            let page = self.list_caches_page(page_token.as_deref()).await?;
+            caches.extend(page.cached_contents);
+            match page.next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(caches),
+            }
+        }
+    }
 
-        // Check if response is an error
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(CacheError::OperationError(format!(
-                "Request failed with status {}: {}",
-                status, error_text
-            )));
+    /// Lists one page of cached contents
+    ///
+    /// # Arguments
+    ///
+    /// * `page_token`: The `next_page_token` of the previous page, or `None` for the
+    ///   first page
+    pub async fn list_caches_page(
+        &self,
+        page_token: Option<&str>,
+    ) -> Result<CacheListPage, CacheError> {
+        let url = format!("{}/cachedContents", self.base_url);
+        let key = self.keys.acquire();
+        let mut request = self.client.get(&url).query(&[("key", key.as_str())]);
+        if let Some(page_token) = page_token {
+            request = request.query(&[("pageToken", page_token)]);
         }
+        let response = request.send().await?;
+        let response = self.check_response(response, &key).await?;
+
+        let page = response.json().await?;
+        Ok(page)
+    }
+
+    /// Gets the first cached content with the specified display name
+    ///
+    /// # Arguments
+    ///
+    /// * `display_name`: The display name to look for
+    pub async fn get_cache_by_display_name(
+        &self,
+        display_name: &str,
+    ) -> Result<Option<CacheInfo>, CacheError> {
+        let caches = self.list_caches().await?;
+        Ok(caches
+            .into_iter()
+            .find(|cache| cache.display_name.as_deref() == Some(display_name)))
+    }
+
+    /// Deletes all cached contents with the specified display name
+    ///
+    /// Returns the number of cached contents deleted. Caches that disappear before
+    /// they are deleted, e.g. because they expired, are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `display_name`: The display name of the caches to delete
+    pub async fn delete_caches_by_display_name(
+        &self,
+        display_name: &str,
+    ) -> Result<usize, CacheError> {
+        self.delete_caches_matching(|cache| cache.display_name.as_deref() == Some(display_name))
+            .await
+    }
+
+    /// Deletes all cached contents
+    ///
+    /// Returns the number of cached contents deleted. Caches that disappear before
+    /// they are deleted, e.g. because they expired, are skipped.
+    pub async fn delete_all_caches(&self) -> Result<usize, CacheError> {
+        self.delete_caches_matching(|_| true).await
+    }
+
+    /// Deletes every listed cache accepted by the filter, tolerating missing caches
+    async fn delete_caches_matching(
+        &self,
+        filter: impl Fn(&CacheInfo) -> bool,
+    ) -> Result<usize, CacheError> {
+        let caches = self.list_caches().await?;
+        let mut deleted_count = 0;
 
-        #[derive(Deserialize)]
-        struct ListResponse {
-            cached_contents: Vec<CacheInfo>,
+        for cache in caches.iter().filter(|cache| filter(cache)) {
+            match self.delete_cache(&cache.name).await {
+                Ok(()) => deleted_count += 1,
+                Err(CacheError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
         }
 
-        let list = response.json::<ListResponse>().await?;
-        Ok(list.cached_contents)
+        Ok(deleted_count)
     }
 
     /// Gets information about a specific cached content
@@ -164,57 +590,69 @@ impl CacheManager {
     ///
     /// * `name`: The resource name of the cached content
     pub async fn get_cache(&self, name: &str) -> Result<CacheInfo, CacheError> {
-        let url = format!("{}/{}", CACHE_API_URL, name);
+        let url = format!("{}/{}", self.base_url, name);
+        let key = self.keys.acquire();
         let response = self
             .client
             .get(&url)
-            .query(&[("key", &self.api_key)])
+            .query(&[("key", key.as_str())])
             .send()
             .await?;
-
-        // Check if response is an error
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(CacheError::OperationError(format!(
-                "Request failed with status {}: {}",
-                status, error_text
-            )));
-        }
+        let response = self.check_response(response, &key).await?;
 
         let cache_info = response.json().await?;
         Ok(cache_info)
     }
 
-    /// Updates the TTL of a cached content
+    /// Updates the TTL of a cached content and returns the refreshed information
     ///
     /// # Arguments
     ///
     /// * `name`: The resource name of the cached content
-    /// * `ttl`: Time-to-live duration in seconds
+    /// * `ttl`: Time-to-live counted from now, e.g. `Duration::from_secs(300)` or `"300s"`
     pub async fn update_cache_ttl(
         &self,
         name: &str,
-        ttl: impl Into<String>,
+        ttl: impl Into<CacheTtl>,
+    ) -> Result<CacheInfo, CacheError> {
+        let ttl = ttl.into().into_wire()?;
+        self.update_cache_expiration(name, CacheExpiration::Ttl(ttl))
+            .await
+    }
+
+    /// Sets the absolute expiration time of a cached content and returns the
+    /// refreshed information
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The resource name of the cached content
+    /// * `expire_time`: When the cached content expires
+    pub async fn update_cache_expire_time(
+        &self,
+        name: &str,
+        expire_time: DateTime<Utc>,
     ) -> Result<CacheInfo, CacheError> {
-        let url = format!("{}/{}", CACHE_API_URL, name);
+        self.update_cache_expiration(name, CacheExpiration::ExpireTime(expire_time))
+            .await
+    }
+
+    /// Patches the lifetime of a cached content
+    async fn update_cache_expiration(
+        &self,
+        name: &str,
+        expiration: CacheExpiration,
+    ) -> Result<CacheInfo, CacheError> {
+        let url = format!("{}/{}", self.base_url, name);
+        let (update_mask, body) = expiration.patch();
+        let key = self.keys.acquire();
         let response = self
             .client
             .patch(&url)
-            .query(&[("key", &self.api_key)])
-            .json(&serde_json::json!({ "ttl": ttl.into() }))
+            .query(&[("key", key.as_str()), ("updateMask", update_mask)])
+            .json(&body)
             .send()
             .await?;
-
-        // Check if response is an error
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(CacheError::OperationError(format!(
-                "Request failed with status {}: {}",
-                status, error_text
-            )));
-        }
+        let response = self.check_response(response, &key).await?;
 
         let cache_info = response.json().await?;
         Ok(cache_info)
@@ -226,24 +664,558 @@ impl CacheManager {
     ///
     /// * `name`: The resource name of the cached content
     pub async fn delete_cache(&self, name: &str) -> Result<(), CacheError> {
-        let url = format!("{}/{}", CACHE_API_URL, name);
+        let url = format!("{}/{}", self.base_url, name);
+        let key = self.keys.acquire();
         let response = self
             .client
             .delete(&url)
-            .query(&[("key", &self.api_key)])
+            .query(&[("key", key.as_str())])
             .send()
             .await?;
+        self.check_response(response, &key).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FunctionCallingConfig, FunctionCallingMode, FunctionDeclaration};
+    use crate::test_utils::{MockResponse, MockServer};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_requests_rotate_through_key_pool() {
+        let server = MockServer::start(|_| MockResponse::json(200, json!({}))).await;
+        let pool = KeyPool::new(["key-a", "key-b"]).unwrap();
+        let manager = CacheManager::with_key_pool(pool).with_base_url(server.url());
+
+        manager.delete_cache("cachedContents/one").await.unwrap();
+        manager.delete_cache("cachedContents/two").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "DELETE");
+        assert_eq!(requests[0].path(), "/cachedContents/one");
+        assert_eq!(requests[0].query("key").as_deref(), Some("key-a"));
+        assert_eq!(requests[1].query("key").as_deref(), Some("key-b"));
+    }
+
+    #[test]
+    fn test_create_cache_request_serialization() {
+        let request = CreateCacheRequest {
+            model: "models/gemini-1.5-flash-001".into(),
+            contents: vec![
+                Content {
+                    role: Some(Role::User),
+                    parts: vec![
+                        Part::text("Transcript:"),
+                        Part::file_data("text/plain", "https://example.com/files/t"),
+                    ],
+                },
+                Content {
+                    role: Some(Role::Model),
+                    parts: vec![Part::text("Noted.")],
+                },
+            ],
+            system_instruction: Some(
+                Content {
+                    role: None,
+                    parts: vec![Part::text("You are an expert.")],
+                }
+                .into(),
+            ),
+            tools: None,
+            tool_config: None,
+            ttl: "600s".into(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "models/gemini-1.5-flash-001",
+                "contents": [
+                    {
+                        "role": "user",
+                        "parts": [
+                            { "text": "Transcript:" },
+                            {
+                                "file_data": {
+                                    "mime_type": "text/plain",
+                                    "file_uri": "https://example.com/files/t"
+                                }
+                            }
+                        ]
+                    },
+                    { "role": "model", "parts": [{ "text": "Noted." }] }
+                ],
+                "systemInstruction": { "parts": [{ "text": "You are an expert." }] },
+                "ttl": "600s"
+            })
+        );
+    }
+
+    #[test]
+    fn test_create_cache_request_with_tools() {
+        let request = CreateCacheRequest::builder()
+            .model("gemini-1.5-flash-001")
+            .contents(vec![])
+            .system_instruction("You are a travel agent.")
+            .tools(vec![Tool::function_declarations(vec![
+                FunctionDeclaration::new().with_name("book_flight"),
+            ])])
+            .tool_config(
+                ToolConfig::builder()
+                    .function_calling_config(
+                        FunctionCallingConfig::builder()
+                            .mode(FunctionCallingMode::Any)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .ttl(Duration::from_secs(90))
+            .build();
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["ttl"], "90s");
+        assert_eq!(
+            value["systemInstruction"]["parts"][0]["text"],
+            "You are a travel agent."
+        );
+        assert_eq!(
+            value["tools"][0]["function_declarations"][0]["name"],
+            "book_flight"
+        );
+        assert_eq!(
+            value["toolConfig"],
+            json!({ "functionCallingConfig": { "mode": "ANY" } })
+        );
+
+        let minimal = CreateCacheRequest::builder()
+            .model("gemini-1.5-flash-001")
+            .contents(vec![])
+            .ttl("60s")
+            .build();
+        assert_eq!(
+            serde_json::to_value(&minimal).unwrap(),
+            json!({ "model": "gemini-1.5-flash-001", "contents": [], "ttl": "60s" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_cache_from_request_validates_and_normalizes() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, json!({ "name": "cachedContents/abc" })))
+                .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        let request = CreateCacheRequest::builder()
+            .model("gemini-1.5-flash-001")
+            .contents(vec![])
+            .ttl("0s")
+            .build();
+        assert!(matches!(
+            manager.create_cache_from_request(request.clone()).await,
+            Err(CacheError::InvalidTtl(_))
+        ));
+
+        let request = CreateCacheRequest {
+            ttl: "60s".into(),
+            ..request
+        };
+        manager.create_cache_from_request(request).await.unwrap();
+        let sent = server.requests()[0].json();
+        assert_eq!(sent["model"], "models/gemini-1.5-flash-001");
+        assert!(sent.get("tools").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_cache_from_text_normalizes_model() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                200,
+                json!({ "name": "cachedContents/abc", "contents": [], "ttl": "300s" }),
+            )
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        let info = manager
+            .create_cache_from_text("gemini-1.5-flash-001", "A long document", "300s")
+            .await
+            .unwrap();
+        manager
+            .create_cache("models/gemini-1.5-pro-001", Vec::new(), None, "300s")
+            .await
+            .unwrap();
+
+        assert_eq!(info.name, "cachedContents/abc");
+        let requests = server.requests();
+        assert_eq!(requests[0].path(), "/cachedContents");
+        let body = requests[0].json();
+        assert_eq!(body["model"], "models/gemini-1.5-flash-001");
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "A long document");
+        assert_eq!(requests[1].json()["model"], "models/gemini-1.5-pro-001");
+    }
+
+    fn file_json(state: &str) -> serde_json::Value {
+        json!({
+            "name": "files/video1",
+            "mimeType": "video/mp4",
+            "sizeBytes": "1048576",
+            "createTime": "2024-01-01T00:00:00Z",
+            "updateTime": "2024-01-01T00:00:00Z",
+            "uri": "https://example.com/v1beta/files/video1",
+            "state": state
+        })
+    }
+
+    #[tokio::test]
+    async fn test_create_cache_from_file_info_waits_until_active() {
+        let polls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = MockServer::start({
+            let polls = polls.clone();
+            move |request| {
+                if request.path().starts_with("/v1beta/files/") {
+                    let poll = polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let state = if poll == 0 { "PROCESSING" } else { "ACTIVE" };
+                    MockResponse::json(200, file_json(state))
+                } else {
+                    MockResponse::json(
+                        200,
+                        json!({ "name": "cachedContents/c1", "contents": [], "ttl": "600s" }),
+                    )
+                }
+            }
+        })
+        .await;
+        let files = GoogleAIFileManager::new("key").with_base_url(server.url());
+        let manager = CacheManager::new("key").with_base_url(server.url());
+        let file: FileInfo = serde_json::from_value(file_json("PROCESSING")).unwrap();
+
+        let error = manager
+            .create_cache_from_file_info("gemini-1.5-flash-001", &file, None, "600s")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CacheError::FileNotActive { .. }));
+        assert!(server.requests().is_empty());
+
+        let info = manager
+            .create_cache_from_file_info_when_active(
+                &files,
+                "gemini-1.5-flash-001",
+                &file,
+                None,
+                "600s",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(info.name, "cachedContents/c1");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].path(), "/v1beta/files/video1");
+        let body = requests[2].json();
+        assert_eq!(requests[2].path(), "/cachedContents");
+        assert_eq!(
+            body["contents"][0]["parts"][0]["file_data"]["mime_type"],
+            "video/mp4"
+        );
+        assert_eq!(
+            body["contents"][0]["parts"][0]["file_data"]["file_uri"],
+            "https://example.com/v1beta/files/video1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_file_is_reported_as_not_active() {
+        let server = MockServer::start(|_| MockResponse::json(200, file_json("FAILED"))).await;
+        let files = GoogleAIFileManager::new("key").with_base_url(server.url());
+        let manager = CacheManager::new("key").with_base_url(server.url());
+        let file: FileInfo = serde_json::from_value(file_json("PROCESSING")).unwrap();
+
+        let error = manager
+            .create_cache_from_file_info_when_active(
+                &files,
+                "gemini-1.5-flash-001",
+                &file,
+                None,
+                "600s",
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CacheError::FileNotActive { name, .. } if name == "files/video1"));
+    }
 
-        // Check if response is an error
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(CacheError::OperationError(format!(
-                "Request failed with status {}: {}",
-                status, error_text
-            )));
+    #[tokio::test]
+    async fn test_update_cache_expiration_patches_one_field() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                200,
+                json!({
+                    "name": "cachedContents/c1",
+                    "model": "models/gemini-1.5-flash-001",
+                    "expireTime": "2030-01-02T03:04:05Z"
+                }),
+            )
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+        let expire_time = "2030-01-02T03:04:05Z".parse::<DateTime<Utc>>().unwrap();
+
+        let info = manager
+            .update_cache_expire_time("cachedContents/c1", expire_time)
+            .await
+            .unwrap();
+        manager
+            .update_cache_ttl("cachedContents/c1", "600s")
+            .await
+            .unwrap();
+
+        assert_eq!(info.expire_time.as_deref(), Some("2030-01-02T03:04:05Z"));
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PATCH");
+        assert_eq!(requests[0].path(), "/cachedContents/c1");
+        assert_eq!(
+            requests[0].query("updateMask").as_deref(),
+            Some("expireTime")
+        );
+        assert_eq!(
+            requests[0].json(),
+            json!({ "expireTime": "2030-01-02T03:04:05Z" })
+        );
+        assert_eq!(requests[1].query("updateMask").as_deref(), Some("ttl"));
+        assert_eq!(requests[1].json(), json!({ "ttl": "600s" }));
+    }
+
+    #[tokio::test]
+    async fn test_duration_ttl() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                200,
+                json!({ "name": "cachedContents/c1", "ttl": "259200s" }),
+            )
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        let info = manager
+            .create_cache_from_text(
+                "gemini-1.5-flash-001",
+                "text",
+                Duration::from_secs(3 * 24 * 60 * 60),
+            )
+            .await
+            .unwrap();
+        manager
+            .update_cache_ttl("cachedContents/c1", Duration::from_secs(45))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            info.ttl_duration(),
+            Some(Duration::from_secs(3 * 24 * 60 * 60))
+        );
+        let requests = server.requests();
+        assert_eq!(requests[0].json()["ttl"], "259200s");
+        assert_eq!(requests[1].json()["ttl"], "45s");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_ttl_is_rejected() {
+        let server = MockServer::start(|_| MockResponse::json(200, json!({}))).await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        for ttl in [
+            CacheTtl::from(Duration::ZERO),
+            CacheTtl::from("-30s"),
+            CacheTtl::from("300"),
+        ] {
+            let error = manager
+                .update_cache_ttl("cachedContents/c1", ttl)
+                .await
+                .unwrap_err();
+            assert!(matches!(error, CacheError::InvalidTtl(_)));
         }
+        assert!(server.requests().is_empty());
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn test_api_errors_are_structured() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "POST" => MockResponse::json(
+                400,
+                json!({
+                    "error": {
+                        "code": 400,
+                        "message": "Cached content is too small. total_token_count=12, min_total_token_count=32768",
+                        "status": "INVALID_ARGUMENT"
+                    }
+                }),
+            ),
+            "GET" => MockResponse::json(
+                404,
+                json!({
+                    "error": {
+                        "code": 404,
+                        "message": "CachedContent not found (or permission denied)",
+                        "status": "NOT_FOUND"
+                    }
+                }),
+            ),
+            _ => MockResponse::json(
+                429,
+                json!({
+                    "error": {
+                        "code": 429,
+                        "message": "Quota exceeded",
+                        "status": "RESOURCE_EXHAUSTED"
+                    }
+                }),
+            ),
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        let error = manager
+            .create_cache_from_text("gemini-1.5-flash-001", "short", "300s")
+            .await
+            .unwrap_err();
+        assert!(error.is_too_small());
+        assert_eq!(error.api_error().unwrap().status, 400);
+
+        let error = manager.get_cache("cachedContents/gone").await.unwrap_err();
+        assert!(
+            matches!(error, CacheError::NotFound(message) if message.starts_with("CachedContent not found"))
+        );
+
+        let error = manager.delete_cache("cachedContents/c1").await.unwrap_err();
+        assert!(!error.is_too_small());
+        let api_error = error.api_error().unwrap();
+        assert_eq!(api_error.code.as_deref(), Some("RESOURCE_EXHAUSTED"));
+        assert!(api_error.is_retryable());
+    }
+
+    fn cache_json() -> serde_json::Value {
+        json!({
+            "name": "cachedContents/4f8kbr0xm0vj",
+            "model": "models/gemini-1.5-flash-001",
+            "createTime": "2024-11-05T10:00:00.123456Z",
+            "updateTime": "2024-11-05T10:00:00.123456Z",
+            "expireTime": "2024-11-05T10:05:00.123456Z",
+            "displayName": "sherlock-jr",
+            "usageMetadata": { "totalTokenCount": 696190 }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cache_info_usage_metadata_and_display_name() {
+        let server = MockServer::start(|request| {
+            if request.path() == "/cachedContents" {
+                MockResponse::json(
+                    200,
+                    json!({ "cachedContents": [cache_json(), cache_json()] }),
+                )
+            } else {
+                MockResponse::json(200, cache_json())
+            }
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        let info = manager
+            .get_cache("cachedContents/4f8kbr0xm0vj")
+            .await
+            .unwrap();
+        assert_eq!(info.display_name.as_deref(), Some("sherlock-jr"));
+        assert_eq!(info.usage_metadata.unwrap().total_token_count, 696190);
+        assert_eq!(
+            info.expire_time.as_deref(),
+            Some("2024-11-05T10:05:00.123456Z")
+        );
+        assert!(info.contents.is_empty());
+
+        let caches = manager.list_caches().await.unwrap();
+        assert_eq!(caches.len(), 2);
+        assert_eq!(caches[1].display_name.as_deref(), Some("sherlock-jr"));
+    }
+
+    #[tokio::test]
+    async fn test_list_without_caches() {
+        let server = MockServer::start(|_| MockResponse::json(200, json!({}))).await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        assert!(manager.list_caches().await.unwrap().is_empty());
+    }
+
+    fn named_cache(name: &str, display_name: &str) -> serde_json::Value {
+        json!({ "name": name, "displayName": display_name })
+    }
+
+    #[tokio::test]
+    async fn test_delete_caches_by_display_name_across_pages() {
+        let server = MockServer::start(|request| match request.method.as_str() {
+            "GET" if request.query("pageToken").is_none() => MockResponse::json(
+                200,
+                json!({
+                    "cachedContents": [
+                        named_cache("cachedContents/a", "docs"),
+                        named_cache("cachedContents/b", "other")
+                    ],
+                    "nextPageToken": "page-2"
+                }),
+            ),
+            "GET" => MockResponse::json(
+                200,
+                json!({
+                    "cachedContents": [
+                        named_cache("cachedContents/c", "docs"),
+                        named_cache("cachedContents/d", "docs")
+                    ]
+                }),
+            ),
+            _ if request.path() == "/cachedContents/c" => MockResponse::json(
+                404,
+                json!({ "error": { "code": 404, "message": "gone", "status": "NOT_FOUND" } }),
+            ),
+            _ => MockResponse::json(200, json!({})),
+        })
+        .await;
+        let manager = CacheManager::new("key").with_base_url(server.url());
+
+        assert_eq!(manager.list_caches().await.unwrap().len(), 4);
+        let found = manager.get_cache_by_display_name("other").await.unwrap();
+        assert_eq!(found.unwrap().name, "cachedContents/b");
+        assert!(manager
+            .get_cache_by_display_name("missing")
+            .await
+            .unwrap()
+            .is_none());
+
+        // "c" was deleted concurrently and is skipped.
+        assert_eq!(
+            manager.delete_caches_by_display_name("docs").await.unwrap(),
+            2
+        );
+        let deleted: Vec<_> = server
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "DELETE")
+            .map(|request| request.path().to_string())
+            .collect();
+        assert_eq!(
+            deleted,
+            [
+                "/cachedContents/a",
+                "/cachedContents/c",
+                "/cachedContents/d"
+            ]
+        );
+        assert_eq!(
+            server.requests()[1].query("pageToken").as_deref(),
+            Some("page-2")
+        );
+
+        assert_eq!(manager.delete_all_caches().await.unwrap(), 3);
     }
 }
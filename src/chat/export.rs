@@ -0,0 +1,115 @@
+//! Export of the chat history as JSON or Markdown.
+
+use serde::Serialize;
+
+use crate::models::{Content, Part, Role, SystemInstruction};
+
+/// The format of an exported chat, see [`ChatSession::export`](super::ChatSession::export).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A JSON document with the full history, which can be loaded again.
+    Json,
+    /// A readable transcript with one section per message.
+    Markdown,
+}
+
+/// The data of an exported chat.
+#[derive(Debug, Serialize)]
+pub(crate) struct ChatExport<'a> {
+    pub model: &'a str,
+    pub total_token_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<&'a SystemInstruction>,
+    pub history: &'a [Content],
+}
+
+impl ChatExport<'_> {
+    /// Renders the export in the given format.
+    pub(crate) fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(self).expect("chat history is serializable")
+            }
+            ExportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Chat export\n\n");
+        markdown.push_str(&format!("- Model: {}\n", self.model));
+        markdown.push_str(&format!("- Messages: {}\n", self.history.len()));
+        markdown.push_str(&format!("- Total tokens: {}\n", self.total_token_count));
+
+        if let Some(SystemInstruction::Content(content)) = self.system_instruction {
+            markdown.push_str("\n## System\n");
+            push_parts(&mut markdown, &content.parts);
+        }
+
+        for content in self.history {
+            let role = match content.role {
+                Some(Role::User) | None => "User",
+                Some(Role::Model) => "Model",
+                Some(Role::Function) => "Function",
+                Some(Role::System) => "System",
+            };
+            markdown.push_str(&format!("\n## {}\n", role));
+            push_parts(&mut markdown, &content.parts);
+        }
+        markdown
+    }
+}
+
+fn push_parts(markdown: &mut String, parts: &[Part]) {
+    for part in parts {
+        markdown.push('\n');
+        match part {
+            Part::Text { text, .. } => {
+                markdown.push_str(text.trim_end());
+                markdown.push('\n');
+            }
+            Part::InlineData { inline_data } => {
+                markdown.push_str(&format!("*[{} attachment]*\n", inline_data.mime_type));
+            }
+            Part::FileData { file_data, .. } => {
+                markdown.push_str(&format!(
+                    "*[{} file]({})*\n",
+                    file_data.mime_type, file_data.file_uri
+                ));
+            }
+            Part::FunctionCall { function_call } => push_details(
+                markdown,
+                &format!("Function call: {}", function_call.name),
+                &function_call.args,
+            ),
+            Part::FunctionResponse { function_response } => push_details(
+                markdown,
+                &format!("Function response: {}", function_response.name),
+                &function_response.response,
+            ),
+            Part::ExecutableCode { executable_code } => {
+                markdown.push_str(&format!(
+                    "```{}\n{}\n```\n",
+                    executable_code.language.to_lowercase(),
+                    executable_code.code.trim_end()
+                ));
+            }
+            Part::CodeExecutionResult {
+                code_execution_result,
+            } => {
+                markdown.push_str(&format!(
+                    "```text\n{}\n```\n",
+                    code_execution_result.output.trim_end()
+                ));
+            }
+        }
+    }
+}
+
+/// Renders a JSON value as a collapsible block.
+fn push_details(markdown: &mut String, summary: &str, value: &serde_json::Value) {
+    let json = serde_json::to_string_pretty(value).unwrap_or_default();
+    markdown.push_str(&format!(
+        "<details>\n<summary>{}</summary>\n\n```json\n{}\n```\n\n</details>\n",
+        summary, json
+    ));
+}
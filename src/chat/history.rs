@@ -0,0 +1,312 @@
+//! Grouping of the chat history into turns.
+
+use std::ops::Range;
+
+use crate::models::{Content, Part, Role};
+
+/// Returns `true` if the content is a user message that starts a new turn.
+///
+/// Function responses are sent back with the user (or function) role, but they
+/// continue the turn of the function call they answer.
+pub(crate) fn starts_turn(content: &Content) -> bool {
+    matches!(content.role, Some(Role::User))
+        && !content
+            .parts
+            .iter()
+            .all(|part| matches!(part, Part::FunctionResponse { .. }))
+}
+
+/// Returns the indices at which the turns of the history start.
+pub(crate) fn turn_starts(history: &[Content]) -> Vec<usize> {
+    history
+        .iter()
+        .enumerate()
+        .filter(|(_, content)| starts_turn(content))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Returns the index at which the last turn of the history starts.
+///
+/// A turn consists of the user message and everything that follows it, such as
+/// function calls and responses and the model's reply. History that does not start
+/// with a user message is treated as a single turn starting at index 0.
+pub(crate) fn last_turn_start(history: &[Content]) -> Option<usize> {
+    if history.is_empty() {
+        return None;
+    }
+    Some(history.iter().rposition(starts_turn).unwrap_or(0))
+}
+
+/// Returns the index of the first content that is kept when all but the most
+/// recent `keep_recent_turns` turns are compacted.
+///
+/// The index is always the start of a turn, so a function call is never separated
+/// from its response. Returns `None` if there is nothing to compact.
+pub(crate) fn compaction_split(history: &[Content], keep_recent_turns: usize) -> Option<usize> {
+    let starts = turn_starts(history);
+    let kept = starts.len().checked_sub(keep_recent_turns)?;
+    starts.get(kept).copied().filter(|&split| split > 0)
+}
+
+/// Groups the history into logical turns and returns their index ranges.
+///
+/// Each group runs from a user message up to the next one, e.g. user, model with a
+/// function call, function response and the model's final reply. Contents before
+/// the first user message, such as a summary, form a group of their own.
+pub(crate) fn turn_groups(history: &[Content]) -> Vec<Range<usize>> {
+    let mut starts = turn_starts(history);
+    if starts.first() != Some(&0) && !history.is_empty() {
+        starts.insert(0, 0);
+    }
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&history.len()]))
+        .map(|(&start, &end)| start..end)
+        .collect()
+}
+
+/// Returns the index of the first content that is kept when the history is trimmed
+/// to at most `max_contents` contents.
+///
+/// Only whole groups of [`turn_groups`] are dropped from the front, so a function
+/// call is never separated from its response. If even the most recent group has
+/// more contents than allowed, everything is dropped.
+pub(crate) fn trim_split(history: &[Content], max_contents: usize) -> usize {
+    turn_groups(history)
+        .into_iter()
+        .map(|group| group.start)
+        .find(|&start| history.len() - start <= max_contents)
+        .unwrap_or(history.len())
+}
+
+/// Renders contents as a plain-text transcript, e.g. to ask the model for a summary
+/// without sending function calls for tools the request does not declare.
+pub(crate) fn transcript(history: &[Content]) -> String {
+    let mut transcript = String::new();
+    for content in history {
+        let speaker = match content.role {
+            Some(Role::Model) => "Model",
+            Some(Role::Function) => "Function",
+            Some(Role::System) => "System",
+            Some(Role::User) | None => "User",
+        };
+        for part in &content.parts {
+            let text = match part {
+                Part::Text { text, .. } => text.clone(),
+                Part::FunctionCall { function_call } => format!(
+                    "[calls function {} with {}]",
+                    function_call.name, function_call.args
+                ),
+                Part::FunctionResponse { function_response } => format!(
+                    "[function {} returned {}]",
+                    function_response.name, function_response.response
+                ),
+                Part::InlineData { inline_data } => {
+                    format!("[{} attachment]", inline_data.mime_type)
+                }
+                Part::FileData { file_data, .. } => {
+                    format!("[{} file {}]", file_data.mime_type, file_data.file_uri)
+                }
+                Part::ExecutableCode { executable_code } => {
+                    format!("[runs code]\n{}", executable_code.code)
+                }
+                Part::CodeExecutionResult {
+                    code_execution_result,
+                } => format!("[code output]\n{}", code_execution_result.output),
+            };
+            transcript.push_str(&format!("{}: {}\n", speaker, text));
+        }
+    }
+    transcript
+}
+
+/// Checks that the roles of a history alternate between user and model turns.
+///
+/// Every content needs a user, model or function role, two user messages may not
+/// follow each other without a reply in between, and the history may not end with a
+/// user message that is still waiting for its reply.
+pub(crate) fn validate(history: &[Content]) -> Result<(), String> {
+    let mut awaiting_reply = false;
+    for (index, content) in history.iter().enumerate() {
+        match content.role {
+            None => return Err(format!("Content {} has no role", index)),
+            Some(Role::System) => {
+                return Err(format!(
+                    "Content {} has the system role; use a system instruction instead",
+                    index
+                ))
+            }
+            _ => {}
+        }
+
+        if starts_turn(content) {
+            if awaiting_reply {
+                return Err(format!(
+                    "Content {} is a user message following a user message without a reply",
+                    index
+                ));
+            }
+            awaiting_reply = true;
+        } else if matches!(content.role, Some(Role::Model)) {
+            awaiting_reply = false;
+        }
+    }
+
+    if awaiting_reply {
+        return Err("The history ends with a user message without a reply".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{FunctionCall, FunctionResponse};
+
+    fn user(text: &str) -> Content {
+        Content {
+            role: Some(Role::User),
+            parts: vec![Part::text(text)],
+        }
+    }
+
+    fn model(text: &str) -> Content {
+        Content {
+            role: Some(Role::Model),
+            parts: vec![Part::text(text)],
+        }
+    }
+
+    fn call() -> Content {
+        Content {
+            role: Some(Role::Model),
+            parts: vec![Part::function_call(FunctionCall {
+                name: "lookup".into(),
+                args: json!({}),
+            })],
+        }
+    }
+
+    fn response(role: Role) -> Content {
+        Content {
+            role: Some(role),
+            parts: vec![Part::function_response(FunctionResponse {
+                name: "lookup".into(),
+                response: json!({}),
+            })],
+        }
+    }
+
+    #[test]
+    fn test_last_turn_start() {
+        assert_eq!(last_turn_start(&[]), None);
+        assert_eq!(last_turn_start(&[user("a"), model("b")]), Some(0));
+        assert_eq!(
+            last_turn_start(&[user("a"), model("b"), user("c"), model("d")]),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_function_responses_do_not_start_a_turn() {
+        let history = [
+            user("a"),
+            model("b"),
+            user("c"),
+            call(),
+            response(Role::User),
+            call(),
+            response(Role::Function),
+            model("d"),
+        ];
+        assert_eq!(last_turn_start(&history), Some(2));
+        assert_eq!(last_turn_start(&[call(), response(Role::User)]), Some(0));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[]).is_ok());
+        assert!(validate(&[user("a"), call(), response(Role::User), model("b")]).is_ok());
+        assert!(validate(&[user("a")]).is_err());
+        assert!(validate(&[user("a"), user("b"), model("c")]).is_err());
+
+        let mut system = user("a");
+        system.role = Some(Role::System);
+        assert!(validate(&[system, model("b")]).is_err());
+    }
+
+    #[test]
+    fn test_compaction_split_keeps_function_calls_with_their_turn() {
+        let history = [
+            user("a"),
+            model("b"),
+            user("c"),
+            call(),
+            response(Role::User),
+            model("d"),
+            user("e"),
+            model("f"),
+        ];
+        assert_eq!(compaction_split(&history, 1), Some(6));
+        assert_eq!(compaction_split(&history, 2), Some(2));
+        assert_eq!(compaction_split(&history, 3), None);
+        assert_eq!(compaction_split(&history, 4), None);
+    }
+
+    fn tool_use_history() -> Vec<Content> {
+        vec![
+            model("summary"),
+            user("a"),
+            call(),
+            response(Role::Function),
+            call(),
+            response(Role::User),
+            model("b"),
+            user("c"),
+            model("d"),
+            user("e"),
+            call(),
+            response(Role::Function),
+            model("f"),
+        ]
+    }
+
+    #[test]
+    fn test_turn_groups() {
+        assert!(turn_groups(&[]).is_empty());
+        assert_eq!(turn_groups(&tool_use_history()), [0..1, 1..7, 7..9, 9..13]);
+        assert_eq!(turn_groups(&[user("a"), model("b")]), vec![0..2]);
+    }
+
+    #[test]
+    fn test_trim_split_drops_whole_turns() {
+        let history = tool_use_history();
+        assert_eq!(trim_split(&history, 13), 0);
+        assert_eq!(trim_split(&history, 12), 1);
+        // Keeping 9 contents would start at a function response; the whole tool-use
+        // turn is dropped instead.
+        assert_eq!(trim_split(&history, 9), 7);
+        assert_eq!(trim_split(&history, 6), 7);
+        assert_eq!(trim_split(&history, 4), 9);
+        assert_eq!(trim_split(&history, 3), 13);
+        assert_eq!(trim_split(&history, 0), 13);
+
+        for max in 0..=history.len() {
+            let kept = &history[trim_split(&history, max)..];
+            assert!(kept.len() <= max);
+            assert!(kept.first().is_none_or(starts_turn) || kept.len() == history.len());
+        }
+    }
+
+    #[test]
+    fn test_transcript() {
+        let transcript = transcript(&[user("a"), call(), response(Role::Function), model("b")]);
+        assert_eq!(
+            transcript,
+            "User: a\nModel: [calls function lookup with {}]\nFunction: [function lookup returned {}]\nModel: b\n"
+        );
+    }
+}
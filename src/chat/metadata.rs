@@ -0,0 +1,58 @@
+//! Metadata recorded for the entries of a chat history.
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::UsageMetadata;
+
+/// When and how an entry of the chat history was produced.
+///
+/// User messages only carry their creation time. Model replies additionally carry
+/// the token usage, the latency and the model version of the turn, as far as the API
+/// reported them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnMetadata {
+    /// When the entry was added to the history.
+    pub created_at: SystemTime,
+    /// The number of tokens in the prompt of the turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_token_count: Option<i32>,
+    /// The number of tokens in the reply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidates_token_count: Option<i32>,
+    /// The time from sending the request until the reply was complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Duration>,
+    /// The model version that produced the reply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+}
+
+impl TurnMetadata {
+    /// Creates the metadata of a user message added now.
+    pub(crate) fn user() -> Self {
+        Self {
+            created_at: SystemTime::now(),
+            prompt_token_count: None,
+            candidates_token_count: None,
+            latency: None,
+            model_version: None,
+        }
+    }
+
+    /// Creates the metadata of a model reply completed now.
+    pub(crate) fn reply(
+        usage_metadata: Option<&UsageMetadata>,
+        latency: Duration,
+        model_version: Option<String>,
+    ) -> Self {
+        Self {
+            created_at: SystemTime::now(),
+            prompt_token_count: usage_metadata.map(|usage| usage.prompt_token_count),
+            candidates_token_count: usage_metadata.and_then(|usage| usage.candidates_token_count),
+            latency: Some(latency),
+            model_version,
+        }
+    }
+}
@@ -1,20 +1,137 @@
 //! Chat session management for the Gemini AI API.
 
+use std::{collections::HashMap, path::Path, sync::Arc, time::Instant};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+mod export;
+mod history;
+mod metadata;
+mod observer;
+mod store;
+mod stream;
+
+pub use export::ExportFormat;
+pub use metadata::TurnMetadata;
+pub use observer::{ChatObserver, LoggingObserver};
+pub use store::{ChatStore, FileChatStore, InMemoryChatStore, PersistentChatSession};
+pub use stream::ChatStream;
+
 use crate::{
+    client::{classification_config, parse_label},
     error::GoogleGenerativeAIError,
-    models::{Content, Part, Request, ResponseStream, Role, SystemInstruction},
+    file::{FileInfo, FileState},
+    models::{
+        Content, FinishReason, FunctionResponse, GenerationConfig, Part, Request, Response,
+        ResponseSchema, ResponseStream, Role, SafetySetting, SystemInstruction, Tool, ToolConfig,
+        UsageMetadata,
+    },
+    retry::RetryPolicy,
     GenerativeModel,
 };
 
+/// The persistable state of a [`ChatSession`].
+///
+/// Created by [`ChatSession::to_saved`] and turned back into a session with
+/// [`ChatSession::from_saved`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedChat {
+    /// The chat history.
+    pub history: Vec<Content>,
+    /// The system instruction of the chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<SystemInstruction>,
+    /// The metadata of each history entry, see [`TurnMetadata`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metadata: Vec<Option<TurnMetadata>>,
+}
+
+/// Settings that replace the session's defaults for a single turn.
+///
+/// Every field that is set takes precedence over the corresponding value configured
+/// on the [`ChatSession`].
+#[derive(Debug, Clone, Default, TypedBuilder)]
+#[builder(doc)]
+pub struct TurnOverrides {
+    /// Configuration for text generation
+    #[builder(default, setter(strip_option, into))]
+    pub generation_config: Option<GenerationConfig>,
+
+    /// Tools the model may use
+    #[builder(default, setter(strip_option, into))]
+    pub tools: Option<Vec<Tool>>,
+
+    /// Configuration for function calling
+    #[builder(default, setter(strip_option, into))]
+    pub tool_config: Option<ToolConfig>,
+
+    /// Safety settings for content filtering
+    #[builder(default, setter(strip_option, into))]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+
+    /// Labels for billing attribution
+    #[builder(default, setter(strip_option, into))]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+/// The default prompt used to summarize old turns, followed by their transcript.
+const DEFAULT_SUMMARIZATION_PROMPT: &str = "Summarize the following conversation between a user and an AI model. Keep every fact, decision and open question that later messages may refer to. Reply with the summary only.";
+
+/// The label that starts the content replacing summarized turns.
+pub const SUMMARY_LABEL: &str = "Summary of the earlier conversation:";
+
+/// When and how old turns are compacted into a summary.
+#[derive(Debug, Clone)]
+struct Summarization {
+    threshold_tokens: i32,
+    keep_recent_turns: usize,
+}
+
+/// A turn whose reply has several candidates, waiting for one to be chosen.
+#[derive(Debug, Clone)]
+struct PendingCandidates {
+    user_message: Content,
+    user_metadata: TurnMetadata,
+    candidates: Vec<Content>,
+    reply_metadata: TurnMetadata,
+}
+
 /// A chat session with the Gemini AI model.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ChatSession {
     /// The model client
     model: GenerativeModel,
     /// Chat history
     history: Vec<Content>,
+    /// Metadata of the history entries, aligned with `history`
+    metadata: Vec<Option<TurnMetadata>>,
     /// System instruction for the chat
     system_instruction: Option<SystemInstruction>,
+    /// Generation config applied to every turn
+    generation_config: Option<GenerationConfig>,
+    /// Tools available on every turn
+    tools: Option<Vec<Tool>>,
+    /// Function calling configuration applied to every turn
+    tool_config: Option<ToolConfig>,
+    /// Safety settings applied to every turn
+    safety_settings: Option<Vec<SafetySetting>>,
+    /// Name of the cached content referenced by every turn
+    cached_content: Option<String>,
+    /// Labels attached to every request of the session
+    labels: Option<HashMap<String, String>>,
+    /// Token usage of the last completed turn
+    last_usage_metadata: Option<UsageMetadata>,
+    /// Compaction of old turns into a summary
+    summarization: Option<Summarization>,
+    /// Prompt used to summarize old turns instead of the default one
+    summarization_prompt: Option<String>,
+    /// Candidates of the last turn that have not been committed yet
+    pending_candidates: Option<PendingCandidates>,
+    /// Total tokens used by all turns of the session
+    total_token_count: i64,
+    /// Observers notified as the session progresses
+    observers: observer::Observers,
 }
 
 impl ChatSession {
@@ -27,20 +144,187 @@ impl ChatSession {
         Self {
             model,
             history: Vec::new(),
+            metadata: Vec::new(),
             system_instruction: None,
+            generation_config: None,
+            tools: None,
+            tool_config: None,
+            safety_settings: None,
+            cached_content: None,
+            labels: None,
+            last_usage_metadata: None,
+            summarization: None,
+            summarization_prompt: None,
+            pending_candidates: None,
+            total_token_count: 0,
+            observers: observer::Observers::default(),
         }
     }
 
+    /// Creates a chat session that continues an existing conversation.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The Gemini AI model to use
+    /// * `history` - The earlier messages of the conversation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the roles of the history do not alternate sensibly, see
+    /// [`set_history`](Self::set_history).
+    pub fn with_history(
+        model: GenerativeModel,
+        history: Vec<Content>,
+    ) -> Result<Self, GoogleGenerativeAIError> {
+        let mut session = Self::new(model);
+        session.set_history(history)?;
+        Ok(session)
+    }
+
+    /// Restores a chat session from its saved state.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The Gemini AI model to use
+    /// * `saved` - The saved history and system instruction
+    pub fn from_saved(model: GenerativeModel, saved: SavedChat) -> Self {
+        let mut session = Self::new(model);
+        session.history = saved.history;
+        session.metadata = saved.metadata;
+        session.metadata.resize(session.history.len(), None);
+        session.system_instruction = saved.system_instruction;
+        session
+    }
+
+    /// Loads a chat session saved with [`save_to_file`](Self::save_to_file).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not contain a saved chat.
+    pub async fn load_from_file(
+        model: GenerativeModel,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, GoogleGenerativeAIError> {
+        let data = tokio::fs::read(path).await?;
+        let saved: SavedChat = serde_json::from_slice(&data)?;
+        Ok(Self::from_saved(model, saved))
+    }
+
     /// Sets a system instruction for the chat session.
     ///
     /// # Arguments
     ///
     /// * `instruction` - The system instruction text
     pub fn with_system_instruction(mut self, instruction: impl Into<String>) -> Self {
-        self.system_instruction = Some(SystemInstruction::Content(Content {
-            role: Some(Role::System),
-            parts: vec![Part::text(instruction.into())],
-        }));
+        self.set_system_instruction(Some(instruction.into()));
+        self
+    }
+
+    /// Replaces or removes the system instruction without touching the history.
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The system instruction text, or `None` to remove it
+    pub fn set_system_instruction(&mut self, instruction: Option<String>) {
+        self.system_instruction = instruction.map(|instruction| {
+            SystemInstruction::Content(Content {
+                role: Some(Role::System),
+                parts: vec![Part::text(instruction)],
+            })
+        });
+    }
+
+    /// Sets the generation config used for every turn.
+    ///
+    /// It takes precedence over the generation config of the model.
+    pub fn with_generation_config(mut self, generation_config: GenerationConfig) -> Self {
+        self.generation_config = Some(generation_config);
+        self
+    }
+
+    /// Sets the tools available to the model on every turn.
+    pub fn with_tools(mut self, tools: impl Into<Vec<Tool>>) -> Self {
+        self.tools = Some(tools.into());
+        self
+    }
+
+    /// Sets the function calling configuration used for every turn.
+    pub fn with_tool_config(mut self, tool_config: ToolConfig) -> Self {
+        self.tool_config = Some(tool_config);
+        self
+    }
+
+    /// Sets the safety settings used for every turn.
+    pub fn with_safety_settings(mut self, safety_settings: impl Into<Vec<SafetySetting>>) -> Self {
+        self.safety_settings = Some(safety_settings.into());
+        self
+    }
+
+    /// Retries failed turns according to the given policy.
+    ///
+    /// The request of a turn is sent again unchanged, and the history is only updated
+    /// once a response arrived, so retries never duplicate the user message. The
+    /// policy replaces any retry policy of the session's copy of the model.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.model = self.model.with_retry_policy(retry_policy);
+        self
+    }
+
+    /// References cached content, e.g. a large document, on every turn.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the cached content, e.g. `cachedContents/abc123`, as
+    ///   returned in [`CacheInfo::name`](crate::cache::CacheInfo::name)
+    pub fn with_cached_content(mut self, name: impl Into<String>) -> Self {
+        self.cached_content = Some(name.into());
+        self
+    }
+
+    /// Attaches labels to every request of the session, e.g. to attribute costs to a
+    /// tenant. Summarization requests carry them as well.
+    ///
+    /// See [`Request::labels`] for the rules labels must follow.
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Registers an observer that is notified as the session progresses.
+    ///
+    /// Observers are shared with sessions created by [`fork`](Self::fork).
+    pub fn with_observer(mut self, observer: impl ChatObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Compacts old turns into a summary once the conversation grows too long.
+    ///
+    /// Before a message is sent, if the last turn used at least `threshold_tokens`
+    /// tokens in total, every turn except the most recent `keep_recent_turns` is sent
+    /// to the model with a summarization prompt. The summarized turns are replaced by
+    /// a single model content starting with [`SUMMARY_LABEL`]. Turns are never split,
+    /// so function calls stay together with their responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold_tokens` - The total token count of a turn that triggers compaction
+    /// * `keep_recent_turns` - The number of recent turns that are kept verbatim
+    pub fn with_summarization(mut self, threshold_tokens: i32, keep_recent_turns: usize) -> Self {
+        self.summarization = Some(Summarization {
+            threshold_tokens,
+            keep_recent_turns,
+        });
+        self
+    }
+
+    /// Replaces the prompt used to summarize old turns.
+    ///
+    /// The transcript of the turns is appended to the prompt. Only used when
+    /// summarization is enabled with [`with_summarization`](Self::with_summarization),
+    /// which may be called before or after this method.
+    pub fn with_summarization_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.summarization_prompt = Some(prompt.into());
         self
     }
 
@@ -57,48 +341,432 @@ impl ChatSession {
         &mut self,
         message: impl Into<String>,
     ) -> Result<String, GoogleGenerativeAIError> {
+        self.send_content(vec![Part::text(message)]).await
+    }
+
+    /// Sends a message made of arbitrary parts, e.g. an image and a question, and
+    /// gets a response.
+    ///
+    /// The parts are sent as one user turn and kept in the history, so later turns
+    /// can refer to them.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails.
+    pub async fn send_content(
+        &mut self,
+        parts: Vec<Part>,
+    ) -> Result<String, GoogleGenerativeAIError> {
+        self.send_content_with(parts, TurnOverrides::default())
+            .await
+    }
+
+    /// Sends a message made of arbitrary parts with settings that replace the
+    /// session's defaults for this turn only.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message
+    /// * `overrides` - The settings for this turn
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails.
+    pub async fn send_content_with(
+        &mut self,
+        parts: Vec<Part>,
+        overrides: TurnOverrides,
+    ) -> Result<String, GoogleGenerativeAIError> {
+        let response = self.send_turn(parts, overrides).await?;
+        match response.text_parts() {
+            text_parts if !text_parts.is_empty() => Ok(text_parts.concat()),
+            _ => Err(GoogleGenerativeAIError::new(
+                "No valid response from the model".to_string(),
+            )),
+        }
+    }
+
+    /// Sends a message together with uploaded files as one user turn.
+    ///
+    /// Each file is referenced by its `uri` and `mime_type` in a file data part
+    /// following the message text.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message text to send
+    /// * `files` - Files uploaded with [`GoogleAIFileManager`](crate::GoogleAIFileManager)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing the files that are not
+    /// [`Active`](FileState::Active) yet, e.g. still processing or failed, without
+    /// sending anything. Otherwise returns an error if the API request fails.
+    pub async fn send_message_with_files(
+        &mut self,
+        message: impl Into<String>,
+        files: &[FileInfo],
+    ) -> Result<String, GoogleGenerativeAIError> {
+        let inactive: Vec<String> = files
+            .iter()
+            .filter(|file| !matches!(file.state, FileState::Active))
+            .map(|file| format!("{} ({})", file.name, file.state))
+            .collect();
+        if !inactive.is_empty() {
+            return Err(GoogleGenerativeAIError::new(format!(
+                "Files are not ready for use: {}",
+                inactive.join(", ")
+            )));
+        }
+
+        let mut parts = Vec::with_capacity(files.len() + 1);
+        parts.push(Part::text(message));
+        parts.extend(
+            files
+                .iter()
+                .map(|file| Part::file_data(file.mime_type.clone(), file.uri.clone())),
+        );
+        self.send_content(parts).await
+    }
+
+    /// Sends a message to the chat and returns the full response, including safety
+    /// ratings, citations, grounding metadata and token usage.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message text to send
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails, or
+    /// [`GenerationStopped`](GoogleGenerativeAIError::GenerationStopped) if the reply
+    /// was blocked or truncated. The history is only updated on success.
+    pub async fn send_message_full(
+        &mut self,
+        message: impl Into<String>,
+    ) -> Result<Response, GoogleGenerativeAIError> {
+        self.send_turn(vec![Part::text(message)], TurnOverrides::default())
+            .await
+    }
+
+    /// Answers the function calls of the last reply and gets the next response.
+    ///
+    /// All responses are sent in one turn, one part per call, which is what the
+    /// API expects when the model calls several functions at once. The next
+    /// response may contain more function calls, so it is returned in full.
+    ///
+    /// # Arguments
+    ///
+    /// * `responses` - The responses, one per function call of the last reply
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails, or
+    /// [`GenerationStopped`](GoogleGenerativeAIError::GenerationStopped) if the reply
+    /// was blocked or truncated.
+    pub async fn send_function_responses(
+        &mut self,
+        responses: Vec<FunctionResponse>,
+    ) -> Result<Response, GoogleGenerativeAIError> {
+        let parts = responses.into_iter().map(Part::function_response).collect();
+        self.send_turn(parts, TurnOverrides::default()).await
+    }
+
+    /// Sends a message and parses the reply as JSON conforming to `schema`.
+    ///
+    /// The JSON response MIME type and the schema apply to this turn only; they are
+    /// added to the session's (or else the model's) generation config. The JSON reply
+    /// is kept in the history like any other model turn. A reply that is not valid
+    /// JSON as is gets repaired with [`parse_model_json`](crate::structured::parse_model_json).
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message text to send
+    /// * `schema` - The schema the reply must conform to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the reply cannot be parsed into
+    /// `T`; the error message then includes the raw reply. With
+    /// [`GenerativeModel::with_structured_output_validation`], returns
+    /// [`GoogleGenerativeAIError::SchemaViolations`] if the reply does not conform
+    /// to `schema`.
+    pub async fn send_message_structured<T: DeserializeOwned>(
+        &mut self,
+        message: impl Into<String>,
+        schema: ResponseSchema,
+    ) -> Result<T, GoogleGenerativeAIError> {
+        let mut generation_config = self
+            .generation_config
+            .clone()
+            .or_else(|| self.model.params().generation_config.clone())
+            .unwrap_or_else(|| GenerationConfig::builder().build());
+        generation_config.response_mime_type = Some("application/json".to_string());
+        generation_config.response_schema = Some(schema.clone());
+
+        let overrides = TurnOverrides::builder()
+            .generation_config(generation_config)
+            .build();
+        let response = self.send_turn(vec![Part::text(message)], overrides).await?;
+        self.model
+            .parse_structured(response.text(), &schema)
+            .map(|structured| structured.value)
+    }
+
+    /// Sends a message and returns which of `labels` the model picked for it.
+    ///
+    /// Like [`GenerativeModel::classify`], the `text/x.enum` response MIME type and
+    /// the label schema apply to this turn only. The chosen label is kept in the
+    /// history like any other model turn.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the reply is not one of
+    /// `labels`.
+    pub async fn classify(
+        &mut self,
+        message: impl Into<String>,
+        labels: &[&str],
+    ) -> Result<String, GoogleGenerativeAIError> {
+        let generation_config = self
+            .generation_config
+            .clone()
+            .or_else(|| self.model.params().generation_config.clone());
+        let overrides = TurnOverrides::builder()
+            .generation_config(classification_config(generation_config, labels))
+            .build();
+        let response = self.send_turn(vec![Part::text(message)], overrides).await?;
+        parse_label(response.text(), labels)
+    }
+
+    /// Sends a message and returns the text of every candidate of the reply.
+    ///
+    /// Use this with a [`GenerationConfig::candidate_count`] above 1. The turn is not
+    /// recorded until one of the candidates is chosen with
+    /// [`commit_candidate`](Self::commit_candidate). If none is chosen before the
+    /// next turn, the first candidate is committed and the observers are notified
+    /// through [`ChatObserver::on_candidate_fallback`].
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The message text to send
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or no candidate has any content.
+    pub async fn send_message_candidates(
+        &mut self,
+        message: impl Into<String>,
+    ) -> Result<Vec<String>, GoogleGenerativeAIError> {
+        let result = self.try_send_candidates(vec![Part::text(message)]).await;
+        if let Err(e) = &result {
+            self.observers.error(e);
+        }
+        result
+    }
+
+    /// Sends one turn and keeps its candidates pending.
+    async fn try_send_candidates(
+        &mut self,
+        parts: Vec<Part>,
+    ) -> Result<Vec<String>, GoogleGenerativeAIError> {
+        self.commit_pending_candidates();
+        self.compact_history().await?;
         let user_message = Content {
             role: Some(Role::User),
-            parts: vec![Part::Text {
-                text: message.into(),
-            }],
+            parts,
         };
 
-        // Build the complete message history
-        let mut messages = Vec::new();
-        messages.extend(self.history.clone());
-        messages.push(user_message.clone());
+        let user_metadata = TurnMetadata::user();
+        let request = self.build_request(user_message.clone(), TurnOverrides::default());
+        let started = Instant::now();
+        let response = self.model.generate_response(request).await?;
+        self.record_usage(response.usage_metadata.clone());
+        let reply_metadata = TurnMetadata::reply(
+            response.usage_metadata.as_ref(),
+            started.elapsed(),
+            response.model_version.clone(),
+        );
 
-        // Create the request
-        let request = Request::builder()
-            .system_instruction(self.system_instruction.as_ref().cloned())
-            .contents(messages)
-            .build();
+        let candidates: Vec<Content> = response
+            .candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|candidate| candidate.content)
+            .filter(|content| !content.parts.is_empty())
+            .map(|mut content| {
+                content.role.get_or_insert(Role::Model);
+                content
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(GoogleGenerativeAIError::new(
+                "No valid response from the model".to_string(),
+            ));
+        }
+
+        let texts = candidates
+            .iter()
+            .map(|content| {
+                content
+                    .parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        Part::Text {
+                            text,
+                            thought: None | Some(false),
+                        } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        self.pending_candidates = Some(PendingCandidates {
+            user_message,
+            user_metadata,
+            candidates,
+            reply_metadata,
+        });
+        Ok(texts)
+    }
+
+    /// Records the candidate at `index` of the last
+    /// [`send_message_candidates`](Self::send_message_candidates) turn in the history.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no pending candidates or `index` is out of
+    /// range; the candidates then stay pending.
+    pub fn commit_candidate(&mut self, index: usize) -> Result<(), GoogleGenerativeAIError> {
+        let count = match &self.pending_candidates {
+            Some(pending) => pending.candidates.len(),
+            None => {
+                return Err(GoogleGenerativeAIError::new(
+                    "There are no candidates to commit",
+                ))
+            }
+        };
+        if index >= count {
+            return Err(GoogleGenerativeAIError::new(format!(
+                "Candidate index {} is out of range for {} candidates",
+                index, count
+            )));
+        }
+
+        if let Some(PendingCandidates {
+            user_message,
+            user_metadata,
+            mut candidates,
+            reply_metadata,
+        }) = self.pending_candidates.take()
+        {
+            let reply = candidates.swap_remove(index);
+            self.observers.user_message(&user_message);
+            self.push_entry(user_message, Some(user_metadata));
+            self.observers.reply(&reply);
+            self.push_entry(reply, Some(reply_metadata));
+        }
+        Ok(())
+    }
+
+    /// Commits the first pending candidate, if any, before the next turn.
+    fn commit_pending_candidates(&mut self) {
+        if let Some(pending) = &self.pending_candidates {
+            self.observers.candidate_fallback(pending.candidates.len());
+            let _ = self.commit_candidate(0);
+        }
+    }
+
+    /// Sends one turn and records it in the history if the model replied normally.
+    async fn send_turn(
+        &mut self,
+        parts: Vec<Part>,
+        overrides: TurnOverrides,
+    ) -> Result<Response, GoogleGenerativeAIError> {
+        let result = self.try_send_turn(parts, overrides).await;
+        if let Err(e) = &result {
+            self.observers.error(e);
+        }
+        result
+    }
+
+    /// Sends one turn; [`send_turn`](Self::send_turn) reports its errors.
+    async fn try_send_turn(
+        &mut self,
+        parts: Vec<Part>,
+        overrides: TurnOverrides,
+    ) -> Result<Response, GoogleGenerativeAIError> {
+        self.commit_pending_candidates();
+        self.compact_history().await?;
+        let user_message = Content {
+            role: Some(Role::User),
+            parts,
+        };
 
         // Send the request
+        let user_metadata = TurnMetadata::user();
+        let request = self.build_request(user_message.clone(), overrides);
+        let started = Instant::now();
         let response = self.model.generate_response(request).await?;
+        let latency = started.elapsed();
+        self.record_usage(response.usage_metadata.clone());
 
-        // Extract the response text
-        if let Some(candidates) = response.candidates.as_ref() {
-            if let Some(candidate) = candidates.first() {
-                if let Some(content) = candidate.content.as_ref() {
-                    if let Some(Part::Text { text }) = content.parts.first() {
-                        // Update history
-                        self.history.push(user_message);
-                        self.history.push(content.clone());
-                        return Ok(text.clone());
-                    }
-                }
-            }
+        let Some(candidate) = response
+            .candidates
+            .as_ref()
+            .and_then(|candidates| candidates.first())
+        else {
+            return Err(GoogleGenerativeAIError::new(
+                "No valid response from the model".to_string(),
+            ));
+        };
+
+        if let Some(
+            reason @ (FinishReason::Safety
+            | FinishReason::MaxTokens
+            | FinishReason::ProhibitedContent
+            | FinishReason::Blocklist
+            | FinishReason::Spii
+            | FinishReason::Recitation),
+        ) = &candidate.finish_reason
+        {
+            return Err(GoogleGenerativeAIError::GenerationStopped {
+                reason: reason.clone(),
+                message: candidate.finish_message.clone(),
+                response: Box::new(response),
+            });
         }
 
-        Err(GoogleGenerativeAIError::new(
-            "No valid response from the model".to_string(),
-        ))
+        match candidate.content.as_ref() {
+            Some(content) if !content.parts.is_empty() => {
+                // Update history
+                let reply_metadata = TurnMetadata::reply(
+                    response.usage_metadata.as_ref(),
+                    latency,
+                    response.model_version.clone(),
+                );
+                self.observers.user_message(&user_message);
+                self.push_entry(user_message, Some(user_metadata));
+                self.observers.reply(content);
+                self.push_entry(content.clone(), Some(reply_metadata));
+                Ok(response)
+            }
+            _ => Err(GoogleGenerativeAIError::new(
+                "No valid response from the model".to_string(),
+            )),
+        }
     }
 
     /// Starts a streaming chat session.
     ///
+    /// The reply is added to the history when the returned [`ChatStream`] is dropped.
+    /// Once it has been drained,
+    /// [`usage_metadata`](crate::models::ResponseStream::usage_metadata) reports the
+    /// token counts of the turn.
+    ///
     /// # Arguments
     ///
     /// * `message` - The message text to send
@@ -109,35 +777,322 @@ impl ChatSession {
     pub async fn send_message_streaming(
         &mut self,
         message: impl Into<String>,
-    ) -> Result<ResponseStream, GoogleGenerativeAIError> {
+    ) -> Result<ChatStream<'_>, GoogleGenerativeAIError> {
+        self.send_content_streaming(vec![Part::text(message)]).await
+    }
+
+    /// Sends a message made of arbitrary parts and streams the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails.
+    pub async fn send_content_streaming(
+        &mut self,
+        parts: Vec<Part>,
+    ) -> Result<ChatStream<'_>, GoogleGenerativeAIError> {
+        self.send_content_streaming_with(parts, TurnOverrides::default())
+            .await
+    }
+
+    /// Sends a message made of arbitrary parts with settings that replace the
+    /// session's defaults for this turn only, and streams the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The parts of the message
+    /// * `overrides` - The settings for this turn
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails.
+    pub async fn send_content_streaming_with(
+        &mut self,
+        parts: Vec<Part>,
+        overrides: TurnOverrides,
+    ) -> Result<ChatStream<'_>, GoogleGenerativeAIError> {
         let user_message = Content {
             role: Some(Role::User),
-            parts: vec![Part::Text {
-                text: message.into(),
-            }],
+            parts,
         };
 
-        // Build the complete message history
-        let mut messages = Vec::new();
-        messages.extend(self.history.clone());
-        messages.push(user_message.clone());
-
-        // Create the request
-        let request = Request::builder()
-            .system_instruction(self.system_instruction.as_ref().cloned())
-            .contents(messages)
-            .build();
+        // Start streaming
+        let user_metadata = TurnMetadata::user();
+        let started = Instant::now();
+        let stream = match self.start_stream(user_message.clone(), overrides).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.observers.error(&e);
+                return Err(e);
+            }
+        };
 
-        // Update history with user message
-        self.history.push(user_message);
+        // Update history with user message; the stream records the reply
+        self.observers.user_message(&user_message);
+        self.push_entry(user_message, Some(user_metadata));
+        Ok(ChatStream::new(self, stream, started))
+    }
 
-        // Start streaming
+    /// Compacts the history and opens the response stream for a turn.
+    async fn start_stream(
+        &mut self,
+        user_message: Content,
+        overrides: TurnOverrides,
+    ) -> Result<ResponseStream, GoogleGenerativeAIError> {
+        self.commit_pending_candidates();
+        self.compact_history().await?;
+        let request = self.build_request(user_message, overrides);
         self.model.stream_generate_response(request).await
     }
 
-    /// Clears the chat history while keeping the system instruction.
-    pub fn clear_history(&mut self) {
+    /// Counts the tokens the next turn would consume if `message` were sent.
+    ///
+    /// The request is built exactly like [`send_message`](Self::send_message) would,
+    /// including the history and the system instruction; the history is not changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails.
+    pub async fn pending_token_count(&self, message: &str) -> Result<i32, GoogleGenerativeAIError> {
+        let user_message = Content {
+            role: Some(Role::User),
+            parts: vec![Part::text(message)],
+        };
+        let request = self.build_request(user_message, TurnOverrides::default());
+        Ok(self.model.count_tokens(request).await?.total_tokens)
+    }
+
+    /// Replaces old turns by a summary if summarization is configured and the last
+    /// turn reached the token threshold.
+    async fn compact_history(&mut self) -> Result<(), GoogleGenerativeAIError> {
+        let Some(summarization) = &self.summarization else {
+            return Ok(());
+        };
+        let over_threshold = self
+            .last_usage_metadata
+            .as_ref()
+            .is_some_and(|usage| usage.total_token_count >= summarization.threshold_tokens);
+        if !over_threshold {
+            return Ok(());
+        }
+        let Some(split) = history::compaction_split(&self.history, summarization.keep_recent_turns)
+        else {
+            return Ok(());
+        };
+
+        let prompt = format!(
+            "{}\n\n{}",
+            self.summarization_prompt
+                .as_deref()
+                .unwrap_or(DEFAULT_SUMMARIZATION_PROMPT),
+            history::transcript(&self.history[..split])
+        );
+        let mut request = Request::with_prompt(prompt);
+        request.labels = self.labels.clone();
+        let summary = self.model.generate_response(request).await?.text();
+
+        let summary = Content {
+            role: Some(Role::Model),
+            parts: vec![Part::text(format!("{}\n{}", SUMMARY_LABEL, summary))],
+        };
+        self.metadata.resize(self.history.len(), None);
+        self.metadata.splice(..split, [None]);
+        self.history.splice(..split, [summary]);
+        self.last_usage_metadata = None;
+        Ok(())
+    }
+
+    /// Builds a request from the history followed by the new user message, applying
+    /// the session's settings unless overridden for this turn.
+    fn build_request(&self, user_message: Content, overrides: TurnOverrides) -> Request {
+        let mut messages = Vec::with_capacity(self.history.len() + 1);
+        messages.extend(self.history.iter().cloned());
+        messages.push(user_message);
+
+        let mut request = Request::builder()
+            .system_instruction(self.system_instruction.as_ref().cloned())
+            .contents(messages)
+            .build();
+        request.generation_config = overrides
+            .generation_config
+            .or_else(|| self.generation_config.clone());
+        request.tools = overrides.tools.or_else(|| self.tools.clone());
+        request.tool_config = overrides.tool_config.or_else(|| self.tool_config.clone());
+        request.safety_settings = overrides
+            .safety_settings
+            .or_else(|| self.safety_settings.clone());
+        request.cached_content = self.cached_content.clone();
+        request.labels = overrides.labels.or_else(|| self.labels.clone());
+        request
+    }
+
+    /// Clears the chat history while keeping the system instruction.
+    pub fn clear_history(&mut self) {
         self.history.clear();
+        self.metadata.clear();
+        self.pending_candidates = None;
+    }
+
+    /// Removes the last turn from the history and returns it.
+    ///
+    /// A turn is the user message together with everything that followed it, including
+    /// function calls and responses and the model's reply, so no function response is
+    /// ever left behind without its call. Returns an empty vector if the history is
+    /// empty.
+    pub fn pop_last_turn(&mut self) -> Vec<Content> {
+        match history::last_turn_start(&self.history) {
+            Some(start) => self.split_history_off(start).0,
+            None => Vec::new(),
+        }
+    }
+
+    /// Removes the last turn and sends its user message again.
+    ///
+    /// If the request fails, the removed turn is restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history contains no user message or the API request
+    /// fails.
+    pub async fn retry_last(&mut self) -> Result<String, GoogleGenerativeAIError> {
+        let start = history::last_turn_start(&self.history).unwrap_or(self.history.len());
+        let (turn, metadata) = self.split_history_off(start);
+        let Some(user_message) = turn.first().filter(|content| history::starts_turn(content))
+        else {
+            self.history.extend(turn);
+            self.metadata.extend(metadata);
+            return Err(GoogleGenerativeAIError::new("There is no turn to retry"));
+        };
+
+        let parts = user_message.parts.clone();
+        match self.send_content(parts).await {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                self.history.extend(turn);
+                self.metadata.extend(metadata);
+                Err(e)
+            }
+        }
+    }
+
+    /// Replaces the chat history while keeping the system instruction.
+    ///
+    /// Every content needs a user, model or function role, and user messages must be
+    /// answered by the model before the next one. In particular the history may not
+    /// end with an unanswered user message. Use
+    /// [`set_history_unchecked`](Self::set_history_unchecked) to skip these checks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first problem found; the history is left
+    /// unchanged.
+    pub fn set_history(&mut self, history: Vec<Content>) -> Result<(), GoogleGenerativeAIError> {
+        history::validate(&history)
+            .map_err(|e| GoogleGenerativeAIError::new(format!("Invalid chat history: {}", e)))?;
+        self.set_history_unchecked(history);
+        Ok(())
+    }
+
+    /// Replaces the chat history without validating it.
+    pub fn set_history_unchecked(&mut self, history: Vec<Content>) {
+        self.metadata = vec![None; history.len()];
+        self.history = history;
+    }
+
+    /// Returns the token usage of the last completed turn.
+    ///
+    /// With cached content, [`UsageMetadata::cached_content_token_count`] tells how
+    /// many of the prompt tokens were served from the cache.
+    pub fn last_usage_metadata(&self) -> Option<&UsageMetadata> {
+        self.last_usage_metadata.as_ref()
+    }
+
+    /// Returns the number of turns in the history.
+    ///
+    /// A turn starts with a user message and includes everything up to the next one,
+    /// such as function calls and responses and the model's reply.
+    pub fn turn_count(&self) -> usize {
+        history::turn_starts(&self.history).len()
+    }
+
+    /// Rewinds the history to the first `turns` turns and returns the removed
+    /// contents.
+    ///
+    /// Nothing is removed if the history has no more than `turns` turns. Content that
+    /// precedes the first turn, such as a summary, is kept.
+    pub fn truncate_to(&mut self, turns: usize) -> Vec<Content> {
+        match history::turn_starts(&self.history).get(turns) {
+            Some(&start) => self.split_history_off(start).0,
+            None => Vec::new(),
+        }
+    }
+
+    /// Drops the oldest turns so that at most `max_contents` contents remain, and
+    /// returns the removed contents.
+    ///
+    /// Only whole turns are removed: a user message is dropped together with the
+    /// function calls, function responses and replies that follow it, since the API
+    /// rejects a function response whose call is missing. The history may therefore
+    /// end up shorter than `max_contents`.
+    pub fn truncate_history(&mut self, max_contents: usize) -> Vec<Content> {
+        let split = history::trim_split(&self.history, max_contents);
+        self.metadata.resize(self.history.len(), None);
+        self.metadata.drain(..split);
+        self.history.drain(..split).collect()
+    }
+
+    /// Creates an independent copy of the session to explore a different continuation.
+    ///
+    /// The history, system instruction and configuration are copied, while the
+    /// [`GenerativeModel`] and its HTTP client and key pool are shared. Turns sent on
+    /// the fork do not affect this session and vice versa.
+    pub fn fork(&self) -> ChatSession {
+        self.clone()
+    }
+
+    /// Returns the total number of tokens used by the turns of this session.
+    pub fn total_token_count(&self) -> i64 {
+        self.total_token_count
+    }
+
+    /// Exports the history, the system instruction and usage totals.
+    ///
+    /// [`ExportFormat::Json`] keeps every part and can be read back with
+    /// `serde_json` into a [`SavedChat`]. [`ExportFormat::Markdown`] produces a
+    /// readable transcript, rendering function calls and responses as collapsible
+    /// JSON blocks.
+    pub fn export(&self, format: ExportFormat) -> String {
+        export::ChatExport {
+            model: self.model.model_name(),
+            total_token_count: self.total_token_count,
+            system_instruction: self.system_instruction.as_ref(),
+            history: &self.history,
+        }
+        .render(format)
+    }
+
+    /// Records the token usage of a completed turn.
+    fn record_usage(&mut self, usage_metadata: Option<UsageMetadata>) {
+        if let Some(usage) = &usage_metadata {
+            self.total_token_count += i64::from(usage.total_token_count);
+        }
+        self.last_usage_metadata = usage_metadata;
+    }
+
+    /// Appends an entry to the history together with its metadata.
+    fn push_entry(&mut self, content: Content, metadata: Option<TurnMetadata>) {
+        self.metadata.resize(self.history.len(), None);
+        self.history.push(content);
+        self.metadata.push(metadata);
+    }
+
+    /// Removes the history entries from `at` on and returns them with their metadata.
+    fn split_history_off(&mut self, at: usize) -> (Vec<Content>, Vec<Option<TurnMetadata>>) {
+        self.metadata.resize(self.history.len(), None);
+        (self.history.split_off(at), self.metadata.split_off(at))
     }
 
     /// Returns the current chat history.
@@ -145,8 +1100,1340 @@ impl ChatSession {
         &self.history
     }
 
+    /// Returns the history entries together with their metadata.
+    ///
+    /// Entries that were restored without metadata, e.g. with
+    /// [`set_history`](Self::set_history), or that replace summarized turns have
+    /// none.
+    pub fn history_with_metadata(
+        &self,
+    ) -> impl Iterator<Item = (&Content, Option<&TurnMetadata>)> + '_ {
+        self.history.iter().zip(
+            self.metadata
+                .iter()
+                .map(Option::as_ref)
+                .chain(std::iter::repeat(None)),
+        )
+    }
+
     /// Returns the system instruction if set.
     pub fn system_instruction(&self) -> Option<&SystemInstruction> {
         self.system_instruction.as_ref()
     }
+
+    /// Returns the history and system instruction in a form that can be persisted.
+    pub fn to_saved(&self) -> SavedChat {
+        SavedChat {
+            history: self.history.clone(),
+            system_instruction: self.system_instruction.clone(),
+            metadata: if self.metadata.iter().all(Option::is_none) {
+                Vec::new()
+            } else {
+                self.metadata.clone()
+            },
+        }
+    }
+
+    /// Saves the history and system instruction as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub async fn save_to_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), GoogleGenerativeAIError> {
+        let data = serde_json::to_vec_pretty(&self.to_saved())?;
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{
+        FunctionCall, FunctionResponse, HarmCategory, InlineData, ModelParams, SafetyThreshold,
+        SchemaType, SchemaViolation,
+    };
+    use crate::test_utils::{MockResponse, MockServer};
+
+    fn model() -> GenerativeModel {
+        GenerativeModel::new("key", ModelParams::default())
+    }
+
+    fn text_response(text: &str) -> serde_json::Value {
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": text }] },
+                "finishReason": "STOP"
+            }]
+        })
+    }
+
+    fn mixed_history() -> Vec<Content> {
+        vec![
+            Content {
+                role: Some(Role::User),
+                parts: vec![
+                    Part::text("What is in this image?"),
+                    Part::InlineData {
+                        inline_data: InlineData {
+                            mime_type: "image/png".into(),
+                            data: "iVBORw0KGgo=".into(),
+                        },
+                    },
+                    Part::file_data("application/pdf", "https://example.com/files/abc"),
+                ],
+            },
+            Content {
+                role: Some(Role::Model),
+                parts: vec![Part::function_call(FunctionCall {
+                    name: "describe".into(),
+                    args: json!({ "detail": "high" }),
+                })],
+            },
+            Content {
+                role: Some(Role::Function),
+                parts: vec![Part::function_response(FunctionResponse {
+                    name: "describe".into(),
+                    response: json!({ "labels": ["cat"] }),
+                })],
+            },
+        ]
+    }
+
+    fn mixed_history_with_reply() -> Vec<Content> {
+        let mut history = mixed_history();
+        history.push(Content {
+            role: Some(Role::Model),
+            parts: vec![Part::text("It is a cat")],
+        });
+        history
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_round_trip() {
+        let session = ChatSession::from_saved(
+            model(),
+            SavedChat {
+                history: mixed_history(),
+                system_instruction: None,
+                metadata: Vec::new(),
+            },
+        )
+        .with_system_instruction("Be brief");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.json");
+        session.save_to_file(&path).await.unwrap();
+        let restored = ChatSession::load_from_file(model(), &path).await.unwrap();
+
+        let original = serde_json::to_vec(&session.to_saved()).unwrap();
+        let round_tripped = serde_json::to_vec(&restored.to_saved()).unwrap();
+        assert_eq!(original, round_tripped);
+        assert_eq!(restored.history().len(), 3);
+        assert!(restored.system_instruction().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_send_content_keeps_multimodal_turn_in_history() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("A cat"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let reply = session
+            .send_content(vec![
+                Part::inline_data("image/png", b"\x89PNG"),
+                Part::text("What is this?"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(reply, "A cat");
+
+        session
+            .send_content(vec![Part::function_response(FunctionResponse {
+                name: "lookup".into(),
+                response: json!({ "ok": true }),
+            })])
+            .await
+            .unwrap();
+
+        let body = server.requests()[1].json();
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[0]["parts"][0]["inline_data"]["data"], "iVBORw==");
+        assert_eq!(contents[0]["parts"][1]["text"], "What is this?");
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"]["name"],
+            "lookup"
+        );
+        assert_eq!(session.history().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_session_settings_apply_to_every_turn() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("ok"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_generation_config(GenerationConfig::builder().temperature(0.2).build())
+            .with_tools(vec![Tool::GOOGLE_SEARCH])
+            .with_safety_settings(vec![SafetySetting {
+                category: HarmCategory::HarmCategoryHarassment,
+                threshold: SafetyThreshold::BlockOnlyHigh,
+            }]);
+
+        session.send_message("one").await.unwrap();
+        session
+            .send_content_with(
+                vec![Part::text("two")],
+                TurnOverrides::builder()
+                    .generation_config(GenerationConfig::builder().temperature(0.9).build())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        session.send_message("three").await.unwrap();
+
+        let temperatures: Vec<f64> = server
+            .requests()
+            .iter()
+            .map(|request| {
+                let body = request.json();
+                assert!(body["tools"][0]["google_search"].is_object());
+                assert_eq!(body["safety_settings"][0]["threshold"], "BLOCK_ONLY_HIGH");
+                (body["generation_config"]["temperature"].as_f64().unwrap() * 10.0).round()
+            })
+            .collect();
+        assert_eq!(temperatures, [2.0, 9.0, 2.0]);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_reply_is_recorded_in_history() {
+        let body = format!(
+            "data: {}\n\ndata: {}\n\n",
+            text_response("Once upon"),
+            text_response(" a time")
+        );
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(vec![(Duration::ZERO, body.clone().into_bytes())])
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let text = session
+            .send_message_streaming("Tell me a story")
+            .await
+            .unwrap()
+            .collect_text()
+            .await
+            .unwrap();
+
+        assert_eq!(text, "Once upon a time");
+        let history = session.history();
+        assert_eq!(history.len(), 2);
+        assert!(matches!(history[1].role, Some(Role::Model)));
+        assert!(
+            matches!(&history[1].parts[..], [Part::Text { text, .. }] if text == "Once upon a time")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream_rolls_back_user_message() {
+        let server = MockServer::start(|_| {
+            MockResponse::chunked(vec![(Duration::ZERO, b"data: {broken\n\n".to_vec())])
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let result = session
+            .send_message_streaming("Hello")
+            .await
+            .unwrap()
+            .collect_text()
+            .await;
+
+        assert!(result.is_err());
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pop_last_turn_and_retry() {
+        let replies = Arc::new(AtomicUsize::new(0));
+        let server = MockServer::start({
+            let replies = replies.clone();
+            move |_| {
+                let n = replies.fetch_add(1, Ordering::SeqCst);
+                MockResponse::json(200, text_response(&format!("reply {}", n)))
+            }
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+        assert!(session.pop_last_turn().is_empty());
+
+        session.send_message("first").await.unwrap();
+        session.send_message("second").await.unwrap();
+        let retried = session.retry_last().await.unwrap();
+
+        assert_eq!(retried, "reply 2");
+        assert_eq!(session.history().len(), 4);
+        let body = server.requests()[2].json();
+        assert_eq!(body["contents"].as_array().unwrap().len(), 3);
+        assert_eq!(body["contents"][2]["parts"][0]["text"], "second");
+
+        let removed = session.pop_last_turn();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(session.history().len(), 2);
+    }
+
+    #[test]
+    fn test_pop_last_turn_removes_tool_use() {
+        let history = mixed_history_with_reply();
+        let mut saved = history.clone();
+        saved.splice(
+            0..0,
+            [
+                Content {
+                    role: Some(Role::User),
+                    parts: vec![Part::text("Hi")],
+                },
+                Content {
+                    role: Some(Role::Model),
+                    parts: vec![Part::text("Hello")],
+                },
+            ],
+        );
+        let mut session = ChatSession::from_saved(
+            model(),
+            SavedChat {
+                history: saved,
+                system_instruction: None,
+                metadata: Vec::new(),
+            },
+        );
+
+        let removed = session.pop_last_turn();
+        assert_eq!(removed.len(), history.len());
+        assert_eq!(session.history().len(), 2);
+        assert!(matches!(session.history()[1].role, Some(Role::Model)));
+    }
+
+    #[tokio::test]
+    async fn test_restored_history_is_sent_with_next_turn() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("Blue"))).await;
+        let history = vec![
+            Content {
+                role: Some(Role::User),
+                parts: vec![Part::text("My favourite colour is blue.")],
+            },
+            Content {
+                role: Some(Role::Model),
+                parts: vec![Part::text("Noted!")],
+            },
+        ];
+        let mut session = ChatSession::with_history(model().with_base_url(server.url()), history)
+            .unwrap()
+            .with_system_instruction("Remember everything");
+
+        session
+            .send_message("What is my favourite colour?")
+            .await
+            .unwrap();
+
+        let body = server.requests()[0].json();
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(
+            contents[0]["parts"][0]["text"],
+            "My favourite colour is blue."
+        );
+        assert_eq!(contents[1]["parts"][0]["text"], "Noted!");
+        assert_eq!(
+            body["system_instruction"]["parts"][0]["text"],
+            "Remember everything"
+        );
+    }
+
+    #[test]
+    fn test_set_history_rejects_unanswered_message() {
+        let unanswered = vec![Content {
+            role: Some(Role::User),
+            parts: vec![Part::text("Hello?")],
+        }];
+        let mut session = ChatSession::new(model());
+
+        assert!(session.set_history(unanswered.clone()).is_err());
+        assert!(session.history().is_empty());
+
+        session.set_history_unchecked(unanswered);
+        assert_eq!(session.history().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cached_content_is_sent_on_every_turn() {
+        let server = MockServer::start(|_| {
+            let mut response = text_response("ok");
+            response["usageMetadata"] = json!({
+                "promptTokenCount": 1200,
+                "cachedContentTokenCount": 1000,
+                "candidatesTokenCount": 1,
+                "totalTokenCount": 1201
+            });
+            MockResponse::json(200, response)
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_cached_content("cachedContents/abc123");
+
+        session.send_message("one").await.unwrap();
+        session.send_message("two").await.unwrap();
+
+        for request in server.requests() {
+            assert_eq!(request.json()["cachedContent"], "cachedContents/abc123");
+        }
+        let usage = session.last_usage_metadata().unwrap();
+        assert_eq!(usage.cached_content_token_count, Some(1000));
+    }
+
+    #[tokio::test]
+    async fn test_labels_are_sent_on_every_turn() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("ok"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_labels(HashMap::from([("tenant".to_string(), "acme".to_string())]));
+
+        session.send_message("one").await.unwrap();
+        let overrides = TurnOverrides::builder()
+            .labels(HashMap::from([(
+                "tenant".to_string(),
+                "globex".to_string(),
+            )]))
+            .build();
+        session
+            .send_content_with(vec![Part::text("two")], overrides)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].json()["labels"], json!({ "tenant": "acme" }));
+        assert_eq!(requests[1].json()["labels"], json!({ "tenant": "globex" }));
+    }
+
+    #[tokio::test]
+    async fn test_pending_token_count_uses_the_request_send_message_would_send() {
+        let server = MockServer::start(|request| {
+            if request.path().ends_with(":countTokens") {
+                MockResponse::json(200, json!({ "totalTokens": 42 }))
+            } else {
+                MockResponse::json(200, text_response("ok"))
+            }
+        })
+        .await;
+        let mut session = ChatSession::with_history(
+            model().with_base_url(server.url()),
+            mixed_history_with_reply(),
+        )
+        .unwrap()
+        .with_system_instruction("Be brief");
+
+        let count = session.pending_token_count("How many?").await.unwrap();
+        assert_eq!(count, 42);
+        assert_eq!(session.history().len(), 4);
+
+        session.send_message("How many?").await.unwrap();
+
+        let requests = server.requests();
+        let mut counted = requests[0].json()["generateContentRequest"].clone();
+        assert_eq!(counted["model"], "models/gemini-1.5-flash");
+        counted.as_object_mut().unwrap().remove("model");
+        assert_eq!(counted, requests[1].json());
+        assert_eq!(
+            counted["system_instruction"]["parts"][0]["text"],
+            "Be brief"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_old_turns_are_summarized() {
+        let server = MockServer::start(|request| {
+            let body = request.json();
+            let prompt = body["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .unwrap_or_default();
+            if prompt.starts_with("Summarize please") {
+                return MockResponse::json(200, text_response("The user said one."));
+            }
+            let mut response = text_response("ok");
+            response["usageMetadata"] = json!({ "promptTokenCount": 150, "totalTokenCount": 151 });
+            MockResponse::json(200, response)
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_summarization_prompt("Summarize please")
+            .with_summarization(100, 1);
+
+        session.send_message("one").await.unwrap();
+        session.send_message("two").await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+
+        session.send_message("three").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        let summarization = requests[2].json();
+        let prompt = summarization["contents"][0]["parts"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(prompt.starts_with("Summarize please\n\n"));
+        assert!(prompt.contains("User: one\nModel: ok\n"));
+        assert!(!prompt.contains("two"));
+
+        let contents = requests[3].json()["contents"].clone();
+        assert_eq!(contents.as_array().unwrap().len(), 4);
+        assert_eq!(
+            contents[0]["parts"][0]["text"],
+            format!("{}\nThe user said one.", SUMMARY_LABEL)
+        );
+        assert_eq!(contents[1]["parts"][0]["text"], "two");
+        assert_eq!(session.history().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_fork_diverges_from_original() {
+        let server = MockServer::start(|request| {
+            let body = request.json();
+            let contents = body["contents"].as_array().unwrap();
+            let last = contents.last().unwrap()["parts"][0]["text"]
+                .as_str()
+                .unwrap();
+            MockResponse::json(200, text_response(&format!("re: {}", last)))
+        })
+        .await;
+        let mut original = ChatSession::new(model().with_base_url(server.url()))
+            .with_system_instruction("Be brief");
+        original.send_message("hello").await.unwrap();
+        original.send_message("tell me about cats").await.unwrap();
+
+        let mut fork = original.fork();
+        assert_eq!(fork.turn_count(), 2);
+        let removed = fork.truncate_to(1);
+        assert_eq!(removed.len(), 2);
+        fork.send_message("tell me about dogs").await.unwrap();
+
+        assert_eq!(original.history().len(), 4);
+        assert!(
+            matches!(&original.history()[3].parts[0], Part::Text { text, .. } if text == "re: tell me about cats")
+        );
+        assert_eq!(fork.history().len(), 4);
+        assert!(
+            matches!(&fork.history()[3].parts[0], Part::Text { text, .. } if text == "re: tell me about dogs")
+        );
+        assert!(fork.system_instruction().is_some());
+        assert!(fork.truncate_to(5).is_empty());
+    }
+
+    fn stopped_response(reason: &str, text: &str) -> serde_json::Value {
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": text }] },
+                "finishReason": reason,
+                "safetyRatings": [{ "category": "HARM_CATEGORY_HARASSMENT", "probability": "HIGH" }]
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_send_message_full_reports_safety_block() {
+        let server = MockServer::start(|_| {
+            let mut response = stopped_response("SAFETY", "");
+            response["candidates"][0]
+                .as_object_mut()
+                .unwrap()
+                .remove("content");
+            MockResponse::json(200, response)
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let error = session
+            .send_message_full("something rude")
+            .await
+            .unwrap_err();
+
+        match error {
+            GoogleGenerativeAIError::GenerationStopped {
+                reason, response, ..
+            } => {
+                assert_eq!(reason, FinishReason::Safety);
+                let candidates = response.candidates.unwrap();
+                assert_eq!(candidates[0].safety_ratings.as_ref().unwrap().len(), 1);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_message_full_reports_max_tokens() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(200, stopped_response("MAX_TOKENS", "Once upon a"))
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let error = session.send_message("Tell me a story").await.unwrap_err();
+
+        match error {
+            GoogleGenerativeAIError::GenerationStopped {
+                reason, response, ..
+            } => {
+                assert_eq!(reason, FinishReason::MaxTokens);
+                assert_eq!(response.text(), "Once upon a");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_message_full_returns_response() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, stopped_response("STOP", "Hi"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let response = session.send_message_full("Hello").await.unwrap();
+
+        assert_eq!(response.text(), "Hi");
+        assert!(response.candidates.unwrap()[0].safety_ratings.is_some());
+        assert_eq!(session.history().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_retries_turn_without_duplicating_history() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let server = MockServer::start({
+            let attempts = attempts.clone();
+            move |_| {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    MockResponse::json(
+                        503,
+                        json!({ "error": { "code": 503, "message": "Overloaded", "status": "UNAVAILABLE" } }),
+                    )
+                } else {
+                    MockResponse::json(200, text_response("ok"))
+                }
+            }
+        })
+        .await;
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(1))
+            .build();
+        let mut session =
+            ChatSession::new(model().with_base_url(server.url())).with_retry_policy(policy);
+
+        assert_eq!(session.send_message("hello").await.unwrap(), "ok");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body, requests[1].body);
+        assert_eq!(session.history().len(), 2);
+    }
+
+    fn export_session() -> ChatSession {
+        let history = vec![
+            Content {
+                role: Some(Role::User),
+                parts: vec![
+                    Part::text("What is in this image?"),
+                    Part::inline_data("image/png", b"png"),
+                ],
+            },
+            Content {
+                role: Some(Role::Model),
+                parts: vec![Part::function_call(FunctionCall {
+                    name: "describe".into(),
+                    args: json!({ "detail": "high" }),
+                })],
+            },
+            Content {
+                role: Some(Role::Model),
+                parts: vec![Part::text("A cat:\n```rust\nlet cat = 1;\n```")],
+            },
+        ];
+        ChatSession::with_history(model(), history)
+            .unwrap()
+            .with_system_instruction("Be brief")
+    }
+
+    #[test]
+    fn test_export_markdown() {
+        let expected = r#"# Chat export
+
+- Model: gemini-1.5-flash
+- Messages: 3
+- Total tokens: 0
+
+## System
+
+Be brief
+
+## User
+
+What is in this image?
+
+*[image/png attachment]*
+
+## Model
+
+<details>
+<summary>Function call: describe</summary>
+
+```json
+{
+  "detail": "high"
+}
+```
+
+</details>
+
+## Model
+
+A cat:
+```rust
+let cat = 1;
+```
+"#;
+        assert_eq!(export_session().export(ExportFormat::Markdown), expected);
+    }
+
+    #[test]
+    fn test_export_json() {
+        let exported: serde_json::Value =
+            serde_json::from_str(&export_session().export(ExportFormat::Json)).unwrap();
+        assert_eq!(
+            exported,
+            json!({
+                "model": "gemini-1.5-flash",
+                "total_token_count": 0,
+                "system_instruction": { "role": "system", "parts": [{ "text": "Be brief" }] },
+                "history": [
+                    {
+                        "role": "user",
+                        "parts": [
+                            { "text": "What is in this image?" },
+                            { "inline_data": { "mime_type": "image/png", "data": "cG5n" } }
+                        ]
+                    },
+                    {
+                        "role": "model",
+                        "parts": [{ "functionCall": { "name": "describe", "args": { "detail": "high" } } }]
+                    },
+                    {
+                        "role": "model",
+                        "parts": [{ "text": "A cat:\n```rust\nlet cat = 1;\n```" }]
+                    }
+                ]
+            })
+        );
+
+        let saved: SavedChat = serde_json::from_value(exported).unwrap();
+        assert_eq!(saved.history.len(), 3);
+        assert!(saved.system_instruction.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_structured_turn_does_not_affect_later_turns() {
+        let server = MockServer::start(|request| {
+            let body = request.json();
+            if body["generation_config"]["response_mime_type"] == "application/json" {
+                MockResponse::json(
+                    200,
+                    text_response(r#"{"city":"Paris","population":2100000}"#),
+                )
+            } else {
+                MockResponse::json(200, text_response("It is the capital of France."))
+            }
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_generation_config(GenerationConfig::builder().temperature(0.3).build());
+
+        #[derive(Deserialize)]
+        struct City {
+            city: String,
+            population: u64,
+        }
+        let schema = ResponseSchema::builder()
+            .r#type(SchemaType::Object)
+            .properties([
+                (
+                    "city".to_string(),
+                    ResponseSchema::builder().r#type(SchemaType::String).build(),
+                ),
+                (
+                    "population".to_string(),
+                    ResponseSchema::builder()
+                        .r#type(SchemaType::Integer)
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let city: City = session
+            .send_message_structured("Largest city in France?", schema)
+            .await
+            .unwrap();
+        assert_eq!(city.city, "Paris");
+        assert_eq!(city.population, 2_100_000);
+
+        let reply = session.send_message("Tell me more").await.unwrap();
+        assert_eq!(reply, "It is the capital of France.");
+
+        let requests = server.requests();
+        let structured = requests[0].json();
+        assert_eq!(structured["generation_config"]["temperature"], 0.3);
+        assert_eq!(
+            structured["generation_config"]["response_schema"]["type"],
+            "object"
+        );
+        let plain = requests[1].json();
+        assert!(plain["generation_config"]
+            .get("response_mime_type")
+            .is_none());
+        assert!(plain["generation_config"].get("response_schema").is_none());
+        assert_eq!(
+            plain["contents"][1]["parts"][0]["text"],
+            r#"{"city":"Paris","population":2100000}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_structured_reply_with_any_of_schema() {
+        let server = MockServer::start(|request| {
+            let prompt = request.json()["contents"]
+                .as_array()
+                .and_then(|contents| contents.last())
+                .map(|content| content["parts"][0]["text"].clone())
+                .unwrap_or_default();
+            let reply = if prompt == "Book the flight" {
+                r#"{"success":{"confirmation":"ABC123"}}"#
+            } else {
+                r#"{"error":{"message":"No seats left"}}"#
+            };
+            MockResponse::json(200, text_response(reply))
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum Outcome {
+            Success { confirmation: String },
+            Error { message: String },
+        }
+        let string = || ResponseSchema::builder().r#type(SchemaType::String).build();
+        let object = |properties: &[(&str, ResponseSchema)]| {
+            ResponseSchema::builder()
+                .r#type(SchemaType::Object)
+                .build()
+                .ordered_properties(properties)
+        };
+        let schema = ResponseSchema::builder()
+            .any_of(vec![
+                object(&[("success", object(&[("confirmation", string())]))]),
+                object(&[("error", object(&[("message", string())]))]),
+            ])
+            .build();
+
+        let booked: Outcome = session
+            .send_message_structured("Book the flight", schema.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            booked,
+            Outcome::Success {
+                confirmation: "ABC123".into()
+            }
+        );
+        let failed: Outcome = session
+            .send_message_structured("Book another one", schema)
+            .await
+            .unwrap();
+        assert_eq!(
+            failed,
+            Outcome::Error {
+                message: "No seats left".into()
+            }
+        );
+
+        let body = server.requests()[0].json();
+        let any_of = &body["generation_config"]["response_schema"]["anyOf"];
+        assert_eq!(any_of[0]["propertyOrdering"], json!(["success"]));
+        assert_eq!(any_of[1]["propertyOrdering"], json!(["error"]));
+    }
+
+    #[tokio::test]
+    async fn test_classify_keeps_label_in_history() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("spam"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let label = session
+            .classify("WIN A FREE CRUISE", &["spam", "ham"])
+            .await
+            .unwrap();
+        assert_eq!(label, "spam");
+        assert_eq!(session.history().len(), 2);
+
+        let error = session
+            .classify("Lunch tomorrow?", &["ham", "unsure"])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("\"spam\""));
+        let body = server.requests()[1].json();
+        assert_eq!(
+            body["generation_config"]["response_schema"]["enum"],
+            json!(["ham", "unsure"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_structured_reply_violations_are_reported_when_enabled() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(200, text_response(r#"{"name":"Ada","age":"unknown"}"#))
+        })
+        .await;
+        let schema = ResponseSchema::builder()
+            .r#type(SchemaType::Object)
+            .required(vec!["name".into(), "age".into()])
+            .build()
+            .ordered_properties(&[
+                (
+                    "name",
+                    ResponseSchema::builder().r#type(SchemaType::String).build(),
+                ),
+                (
+                    "age",
+                    ResponseSchema::builder()
+                        .r#type(SchemaType::Integer)
+                        .build(),
+                ),
+            ]);
+
+        let mut session = ChatSession::new(
+            model()
+                .with_base_url(server.url())
+                .with_structured_output_validation(true),
+        );
+        let err = session
+            .send_message_structured::<serde_json::Value>("Who?", schema.clone())
+            .await
+            .unwrap_err();
+        match err {
+            GoogleGenerativeAIError::SchemaViolations { violations, raw } => {
+                assert_eq!(
+                    violations,
+                    vec![SchemaViolation::TypeMismatch {
+                        path: "#/age".into(),
+                        expected: SchemaType::Integer,
+                        found: "string".into(),
+                    }]
+                );
+                assert_eq!(raw, r#"{"name":"Ada","age":"unknown"}"#);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        let mut unchecked = ChatSession::new(model().with_base_url(server.url()));
+        let value: serde_json::Value = unchecked
+            .send_message_structured("Who?", schema)
+            .await
+            .unwrap();
+        assert_eq!(value["age"], "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_structured_parse_failure_includes_raw_reply() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, text_response("not json"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let error = session
+            .send_message_structured::<serde_json::Value>(
+                "hi",
+                ResponseSchema::builder().r#type(SchemaType::Object).build(),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("raw reply: not json"));
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver(std::sync::Mutex<Vec<String>>);
+
+    impl ChatObserver for RecordingObserver {
+        fn on_user_message(&self, content: &Content) {
+            let text = content.parts.iter().find_map(|part| match part {
+                Part::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            });
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("user {}", text.unwrap_or_default()));
+        }
+
+        fn on_model_delta(&self, text: &str) {
+            self.0.lock().unwrap().push(format!("delta {}", text));
+        }
+
+        fn on_tool_call(&self, call: &FunctionCall) {
+            self.0.lock().unwrap().push(format!("tool {}", call.name));
+        }
+
+        fn on_model_reply(&self, content: &Content) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("reply {}", content.parts.len()));
+        }
+
+        fn on_error(&self, _error: &GoogleGenerativeAIError) {
+            self.0.lock().unwrap().push("error".to_string());
+        }
+
+        fn on_candidate_fallback(&self, candidate_count: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("fallback {}", candidate_count));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_callbacks_during_streamed_turn() {
+        let call = json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{ "functionCall": { "name": "lookup", "args": {} } }]
+                }
+            }]
+        });
+        let body = format!(
+            "data: {}\n\ndata: {}\n\ndata: {}\n\n",
+            text_response("Let me"),
+            text_response(" check."),
+            call
+        );
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(vec![(Duration::ZERO, body.clone().into_bytes())])
+        })
+        .await;
+        let observer = Arc::new(RecordingObserver::default());
+        let mut session =
+            ChatSession::new(model().with_base_url(server.url())).with_observer(observer.clone());
+
+        session
+            .send_message_streaming("Weather?")
+            .await
+            .unwrap()
+            .collect_text()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *observer.0.lock().unwrap(),
+            [
+                "user Weather?",
+                "delta Let me",
+                "delta  check.",
+                "tool lookup",
+                "reply 2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_observer_is_notified_of_errors() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(400, json!({ "error": { "message": "Invalid" } }))
+        })
+        .await;
+        let observer = Arc::new(RecordingObserver::default());
+        let mut session =
+            ChatSession::new(model().with_base_url(server.url())).with_observer(observer.clone());
+
+        assert!(session.send_message("Hello").await.is_err());
+        assert!(session.send_message_streaming("Hello").await.is_err());
+        assert_eq!(*observer.0.lock().unwrap(), ["error", "error"]);
+    }
+
+    fn two_candidates() -> serde_json::Value {
+        json!({
+            "candidates": [
+                { "index": 0, "content": { "role": "model", "parts": [{ "text": "Blue" }] } },
+                { "index": 1, "content": { "role": "model", "parts": [{ "text": "Green" }] } }
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_commit_candidate_records_only_the_chosen_one() {
+        let server = MockServer::start(|_| MockResponse::json(200, two_candidates())).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()))
+            .with_generation_config(GenerationConfig::builder().candidate_count(2).build());
+
+        let texts = session
+            .send_message_candidates("Favourite colour?")
+            .await
+            .unwrap();
+        assert_eq!(texts, ["Blue", "Green"]);
+        assert!(session.history().is_empty());
+
+        assert!(session.commit_candidate(2).is_err());
+        session.commit_candidate(1).unwrap();
+        assert!(session.commit_candidate(0).is_err());
+
+        let history = session.history();
+        assert_eq!(history.len(), 2);
+        assert!(matches!(&history[1].parts[..], [Part::Text { text, .. }] if text == "Green"));
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_candidates_default_to_the_first() {
+        let server = MockServer::start(|request| {
+            if request.json()["contents"].as_array().unwrap().len() == 1 {
+                MockResponse::json(200, two_candidates())
+            } else {
+                MockResponse::json(200, text_response("Nice choice."))
+            }
+        })
+        .await;
+        let observer = Arc::new(RecordingObserver::default());
+        let mut session =
+            ChatSession::new(model().with_base_url(server.url())).with_observer(observer.clone());
+
+        session
+            .send_message_candidates("Favourite colour?")
+            .await
+            .unwrap();
+        session.send_message("Why?").await.unwrap();
+
+        assert_eq!(
+            observer.0.lock().unwrap()[..3],
+            ["fallback 2", "user Favourite colour?", "reply 1"]
+        );
+
+        let contents = server.requests()[1].json()["contents"].clone();
+        assert_eq!(contents.as_array().unwrap().len(), 3);
+        assert_eq!(contents[1]["parts"][0]["text"], "Blue");
+        assert_eq!(session.history().len(), 4);
+    }
+
+    fn file_info(name: &str, mime_type: &str, state: FileState) -> FileInfo {
+        serde_json::from_value(json!({
+            "name": name,
+            "mimeType": mime_type,
+            "sizeBytes": "1024",
+            "createTime": "2024-01-01T00:00:00Z",
+            "updateTime": "2024-01-01T00:00:00Z",
+            "uri": format!("https://example.com/v1beta/{}", name),
+            "state": state,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_message_with_files() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, text_response("Two cats."))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+        let files = [
+            file_info("files/a", "image/png", FileState::Active),
+            file_info("files/b", "application/pdf", FileState::Active),
+        ];
+
+        let reply = session
+            .send_message_with_files("What do these show?", &files)
+            .await
+            .unwrap();
+
+        assert_eq!(reply, "Two cats.");
+        let parts = server.requests()[0].json()["contents"][0]["parts"].clone();
+        assert_eq!(parts[0]["text"], "What do these show?");
+        assert_eq!(parts[1]["file_data"]["mime_type"], "image/png");
+        assert_eq!(
+            parts[2]["file_data"]["file_uri"],
+            "https://example.com/v1beta/files/b"
+        );
+        assert_eq!(session.history().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_message_with_inactive_files_is_rejected() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("Hi"))).await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+        let files = [
+            file_info("files/a", "image/png", FileState::Active),
+            file_info("files/b", "video/mp4", FileState::Processing),
+            file_info("files/c", "image/png", FileState::Failed),
+        ];
+
+        let error = session
+            .send_message_with_files("Describe", &files)
+            .await
+            .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("files/b (Processing), files/c (Failed)"));
+        assert!(server.requests().is_empty());
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_turn_metadata_is_recorded_and_saved() {
+        let server = MockServer::start(|_| {
+            let mut response = text_response("Hi there");
+            response["usageMetadata"] = json!({
+                "promptTokenCount": 4,
+                "candidatesTokenCount": 2,
+                "totalTokenCount": 6
+            });
+            response["modelVersion"] = json!("gemini-1.5-flash-002");
+            MockResponse::json(200, response)
+        })
+        .await;
+        let mut session = ChatSession::with_history(model().with_base_url(server.url()), {
+            let mut history = mixed_history();
+            history.push(Content {
+                role: Some(Role::Model),
+                parts: vec![Part::text("A cat.")],
+            });
+            history
+        })
+        .unwrap();
+
+        session.send_message("Hello").await.unwrap();
+
+        let entries: Vec<_> = session.history_with_metadata().collect();
+        assert_eq!(entries.len(), 6);
+        assert!(entries[..4].iter().all(|(_, metadata)| metadata.is_none()));
+        let user = entries[4].1.unwrap();
+        assert_eq!(user.prompt_token_count, None);
+        let reply = entries[5].1.unwrap();
+        assert_eq!(reply.prompt_token_count, Some(4));
+        assert_eq!(reply.candidates_token_count, Some(2));
+        assert_eq!(reply.model_version.as_deref(), Some("gemini-1.5-flash-002"));
+        assert!(reply.latency.is_some());
+        assert!(reply.created_at >= user.created_at);
+
+        let json = serde_json::to_string(&session.to_saved()).unwrap();
+        let restored = ChatSession::from_saved(model(), serde_json::from_str(&json).unwrap());
+        let restored: Vec<_> = restored
+            .history_with_metadata()
+            .map(|(_, metadata)| metadata.cloned())
+            .collect();
+        assert_eq!(restored[5].as_ref(), Some(reply));
+        assert!(restored[3].is_none());
+    }
+
+    #[test]
+    fn test_truncate_history_keeps_tool_use_turns_whole() {
+        let mut history = vec![
+            Content {
+                role: Some(Role::User),
+                parts: vec![Part::text("Hi")],
+            },
+            Content {
+                role: Some(Role::Model),
+                parts: vec![Part::text("Hello")],
+            },
+        ];
+        history.extend(mixed_history_with_reply());
+        let mut session = ChatSession::with_history(model(), history).unwrap();
+
+        assert!(session.truncate_history(6).is_empty());
+        let removed = session.truncate_history(5);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(session.history().len(), 4);
+        assert!(history::validate(session.history()).is_ok());
+        assert_eq!(session.history_with_metadata().count(), 4);
+
+        // Keeping 3 contents would orphan the function response, so the whole
+        // tool-use turn goes.
+        let removed = session.truncate_history(3);
+        assert_eq!(removed.len(), 4);
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_function_calls_are_answered_in_one_turn() {
+        let server = MockServer::start(|request| {
+            let body = match request.json()["contents"].as_array().unwrap().len() {
+                1 => json!({
+                    "candidates": [{
+                        "content": {
+                            "role": "model",
+                            "parts": [
+                                { "functionCall": { "name": "get_weather", "args": { "city": "Paris" } } },
+                                { "functionCall": { "name": "get_weather", "args": { "city": "Rome" } } }
+                            ]
+                        },
+                        "finishReason": "STOP"
+                    }]
+                }),
+                _ => text_response("Sunny in both cities"),
+            };
+            MockResponse::json(200, body)
+        })
+        .await;
+        let mut session = ChatSession::new(model().with_base_url(server.url()));
+
+        let response = session
+            .send_message_full("Weather in Paris and Rome?")
+            .await
+            .unwrap();
+        let responses = response
+            .function_calls()
+            .into_iter()
+            .map(|call| FunctionResponse {
+                response: json!({ "forecast": format!("Sunny in {}", call.args["city"]) }),
+                name: call.name,
+            })
+            .collect();
+        let reply = session.send_function_responses(responses).await.unwrap();
+        assert_eq!(reply.text(), "Sunny in both cities");
+
+        let body = server.requests()[1].json();
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["parts"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            contents[2]["parts"],
+            json!([
+                {
+                    "functionResponse": {
+                        "name": "get_weather",
+                        "response": { "forecast": "Sunny in \"Paris\"" }
+                    }
+                },
+                {
+                    "functionResponse": {
+                        "name": "get_weather",
+                        "response": { "forecast": "Sunny in \"Rome\"" }
+                    }
+                }
+            ])
+        );
+        assert_eq!(session.history().len(), 4);
+    }
 }
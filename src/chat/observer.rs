@@ -0,0 +1,293 @@
+//! Callbacks fired while a chat session progresses.
+
+use std::{
+    fmt,
+    io::Write,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::GoogleGenerativeAIError,
+    models::{Content, FunctionCall, Part},
+};
+
+/// Receives notifications about the turns of a [`ChatSession`](super::ChatSession).
+///
+/// Every method has an empty default implementation, so observers only implement
+/// the events they care about. Observers are called synchronously from the session;
+/// they should return quickly and hand longer work off to another task. A panic in
+/// an observer is caught and does not affect the turn.
+pub trait ChatObserver: Send + Sync {
+    /// Called when a user message is appended to the history.
+    fn on_user_message(&self, _content: &Content) {}
+
+    /// Called for every piece of text received while a reply is streamed.
+    fn on_model_delta(&self, _text: &str) {}
+
+    /// Called for every function call the model requests.
+    fn on_tool_call(&self, _call: &FunctionCall) {}
+
+    /// Called when the model's reply is complete and appended to the history.
+    fn on_model_reply(&self, _content: &Content) {}
+
+    /// Called when a turn fails.
+    fn on_error(&self, _error: &GoogleGenerativeAIError) {}
+
+    /// Called when none of the `candidate_count` candidates of a
+    /// [`send_message_candidates`](super::ChatSession::send_message_candidates) turn
+    /// was committed before the next turn, so the first one is kept.
+    fn on_candidate_fallback(&self, _candidate_count: usize) {}
+}
+
+impl<T: ChatObserver + ?Sized> ChatObserver for Arc<T> {
+    fn on_user_message(&self, content: &Content) {
+        (**self).on_user_message(content)
+    }
+
+    fn on_model_delta(&self, text: &str) {
+        (**self).on_model_delta(text)
+    }
+
+    fn on_tool_call(&self, call: &FunctionCall) {
+        (**self).on_tool_call(call)
+    }
+
+    fn on_model_reply(&self, content: &Content) {
+        (**self).on_model_reply(content)
+    }
+
+    fn on_error(&self, error: &GoogleGenerativeAIError) {
+        (**self).on_error(error)
+    }
+
+    fn on_candidate_fallback(&self, candidate_count: usize) {
+        (**self).on_candidate_fallback(candidate_count)
+    }
+}
+
+/// An observer that writes one line per event, to stderr by default.
+pub struct LoggingObserver {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl LoggingObserver {
+    /// Creates an observer that logs to stderr.
+    pub fn new() -> Self {
+        Self::with_writer(std::io::stderr())
+    }
+
+    /// Creates an observer that logs to the given writer.
+    pub fn with_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    fn log(&self, line: fmt::Arguments<'_>) {
+        if let Ok(mut writer) = self.writer.lock() {
+            // Logging must never fail a turn.
+            let _ = writeln!(writer, "{}", line);
+        }
+    }
+}
+
+impl Default for LoggingObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for LoggingObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoggingObserver").finish_non_exhaustive()
+    }
+}
+
+impl ChatObserver for LoggingObserver {
+    fn on_user_message(&self, content: &Content) {
+        self.log(format_args!("[chat] user: {}", summarize(content)));
+    }
+
+    fn on_model_delta(&self, text: &str) {
+        self.log(format_args!("[chat] delta: {:?}", text));
+    }
+
+    fn on_tool_call(&self, call: &FunctionCall) {
+        self.log(format_args!(
+            "[chat] tool call: {}({})",
+            call.name, call.args
+        ));
+    }
+
+    fn on_model_reply(&self, content: &Content) {
+        self.log(format_args!("[chat] model: {}", summarize(content)));
+    }
+
+    fn on_error(&self, error: &GoogleGenerativeAIError) {
+        self.log(format_args!("[chat] error: {}", error));
+    }
+
+    fn on_candidate_fallback(&self, candidate_count: usize) {
+        self.log(format_args!(
+            "[chat] warning: none of {} candidates was committed, keeping the first one",
+            candidate_count
+        ));
+    }
+}
+
+/// Describes a content on one line: its text, followed by a count of other parts.
+fn summarize(content: &Content) -> String {
+    let text: String = content
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text { text, .. } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let others = content
+        .parts
+        .iter()
+        .filter(|part| !matches!(part, Part::Text { .. }))
+        .count();
+
+    let text = format!("{:?}", text);
+    match others {
+        0 => text,
+        n => format!("{} (+{} other parts)", text, n),
+    }
+}
+
+/// The observers registered on a session.
+#[derive(Clone, Default)]
+pub(crate) struct Observers(Vec<Arc<dyn ChatObserver>>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Arc<dyn ChatObserver>) {
+        self.0.push(observer);
+    }
+
+    /// Calls `f` for every observer, ignoring panics.
+    fn notify(&self, f: impl Fn(&dyn ChatObserver)) {
+        for observer in &self.0 {
+            let _ = catch_unwind(AssertUnwindSafe(|| f(observer.as_ref())));
+        }
+    }
+
+    pub(crate) fn user_message(&self, content: &Content) {
+        self.notify(|observer| observer.on_user_message(content));
+    }
+
+    /// Reports the text and function calls of a received chunk.
+    pub(crate) fn chunk(&self, content: &Content) {
+        for part in &content.parts {
+            match part {
+                Part::Text {
+                    text,
+                    thought: None | Some(false),
+                } => self.notify(|observer| observer.on_model_delta(text)),
+                Part::FunctionCall { function_call } => {
+                    self.notify(|observer| observer.on_tool_call(function_call))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reports the function calls and the completion of a reply.
+    pub(crate) fn reply(&self, content: &Content) {
+        for part in &content.parts {
+            if let Part::FunctionCall { function_call } = part {
+                self.notify(|observer| observer.on_tool_call(function_call));
+            }
+        }
+        self.model_reply(content);
+    }
+
+    pub(crate) fn model_reply(&self, content: &Content) {
+        self.notify(|observer| observer.on_model_reply(content));
+    }
+
+    pub(crate) fn error(&self, error: &GoogleGenerativeAIError) {
+        self.notify(|observer| observer.on_error(error));
+    }
+
+    pub(crate) fn candidate_fallback(&self, candidate_count: usize) {
+        self.notify(|observer| observer.on_candidate_fallback(candidate_count));
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Role;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logging_observer_writes_one_line_per_event() {
+        let buffer = SharedBuffer::default();
+        let observer = LoggingObserver::with_writer(buffer.clone());
+
+        observer.on_user_message(&Content {
+            role: Some(Role::User),
+            parts: vec![Part::text("Hi"), Part::file_data("image/png", "uri")],
+        });
+        observer.on_tool_call(&FunctionCall {
+            name: "lookup".into(),
+            args: serde_json::json!({ "q": 1 }),
+        });
+        observer.on_error(&GoogleGenerativeAIError::new("boom"));
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            log.lines().collect::<Vec<_>>(),
+            [
+                r#"[chat] user: "Hi" (+1 other parts)"#,
+                r#"[chat] tool call: lookup({"q":1})"#,
+                "[chat] error: [GoogleGenerativeAI Error]: boom",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panicking_observer_does_not_stop_others() {
+        struct Panicking;
+        impl ChatObserver for Panicking {
+            fn on_model_delta(&self, _text: &str) {
+                panic!("observer failed");
+            }
+        }
+
+        let buffer = SharedBuffer::default();
+        let mut observers = Observers::default();
+        observers.push(Arc::new(Panicking));
+        observers.push(Arc::new(LoggingObserver::with_writer(buffer.clone())));
+
+        observers.chunk(&Content {
+            role: Some(Role::Model),
+            parts: vec![Part::text("Hello")],
+        });
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(log, "[chat] delta: \"Hello\"\n");
+    }
+}
@@ -0,0 +1,369 @@
+//! External storage of chat histories.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    error::GoogleGenerativeAIError,
+    models::{Content, Part},
+};
+
+use super::ChatSession;
+
+/// Storage for the histories of chat sessions, identified by a session id.
+///
+/// Stores are append-only: a turn only ever adds contents to the end of a history,
+/// and each [`append`](Self::append) is atomic, so concurrent writers never corrupt
+/// a history. Turns on the same session are not serialized, though. Two turns that
+/// run concurrently both see the history as it was when they started, and their
+/// contents are appended as they finish, so they may interleave. [`clear`](Self::clear)
+/// removes everything appended before it; the last writer wins.
+pub trait ChatStore: Send + Sync {
+    /// Returns the history of a session, or an empty history for an unknown session.
+    fn load(
+        &self,
+        session_id: &str,
+    ) -> impl Future<Output = Result<Vec<Content>, GoogleGenerativeAIError>> + Send;
+
+    /// Appends a content to the history of a session.
+    fn append(
+        &self,
+        session_id: &str,
+        content: Content,
+    ) -> impl Future<Output = Result<(), GoogleGenerativeAIError>> + Send;
+
+    /// Removes the history of a session.
+    fn clear(
+        &self,
+        session_id: &str,
+    ) -> impl Future<Output = Result<(), GoogleGenerativeAIError>> + Send;
+}
+
+/// A [`ChatStore`] that keeps histories in memory.
+///
+/// Clones share the same histories, so sessions of one process can hand a
+/// conversation to each other.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryChatStore {
+    histories: Arc<Mutex<HashMap<String, Vec<Content>>>>,
+}
+
+impl InMemoryChatStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ChatStore for InMemoryChatStore {
+    async fn load(&self, session_id: &str) -> Result<Vec<Content>, GoogleGenerativeAIError> {
+        let histories = self.histories.lock().unwrap();
+        Ok(histories.get(session_id).cloned().unwrap_or_default())
+    }
+
+    async fn append(
+        &self,
+        session_id: &str,
+        content: Content,
+    ) -> Result<(), GoogleGenerativeAIError> {
+        let mut histories = self.histories.lock().unwrap();
+        histories
+            .entry(session_id.to_string())
+            .or_default()
+            .push(content);
+        Ok(())
+    }
+
+    async fn clear(&self, session_id: &str) -> Result<(), GoogleGenerativeAIError> {
+        self.histories.lock().unwrap().remove(session_id);
+        Ok(())
+    }
+}
+
+/// A [`ChatStore`] that keeps every history in a JSON Lines file in a directory.
+///
+/// Each content is written as one line with a single append, so several processes
+/// may share the directory. Session ids may only contain ASCII letters, digits, `-`,
+/// `_` and `.`, and may not start with `.`.
+#[derive(Debug, Clone)]
+pub struct FileChatStore {
+    dir: PathBuf,
+}
+
+impl FileChatStore {
+    /// Creates a store that keeps its files in `dir`, which is created on the first
+    /// append if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the path of the file holding a session's history.
+    fn path(&self, session_id: &str) -> Result<PathBuf, GoogleGenerativeAIError> {
+        let valid = !session_id.is_empty()
+            && !session_id.starts_with('.')
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(GoogleGenerativeAIError::new(format!(
+                "Invalid chat session id: {:?}",
+                session_id
+            )));
+        }
+        Ok(self.dir.join(format!("{}.jsonl", session_id)))
+    }
+}
+
+impl ChatStore for FileChatStore {
+    async fn load(&self, session_id: &str) -> Result<Vec<Content>, GoogleGenerativeAIError> {
+        let data = match tokio::fs::read_to_string(self.path(session_id)?).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        data.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Into::into))
+            .collect()
+    }
+
+    async fn append(
+        &self,
+        session_id: &str,
+        content: Content,
+    ) -> Result<(), GoogleGenerativeAIError> {
+        let path = self.path(session_id)?;
+        let mut line = serde_json::to_vec(&content)?;
+        line.push(b'\n');
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn clear(&self, session_id: &str) -> Result<(), GoogleGenerativeAIError> {
+        match tokio::fs::remove_file(self.path(session_id)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A chat session whose history lives in a [`ChatStore`].
+///
+/// Every turn loads the current history from the store, sends the message with the
+/// settings of the wrapped [`ChatSession`] and appends the new contents to the store,
+/// so several instances of a server can continue the same conversation. See
+/// [`ChatStore`] for what happens when turns run concurrently. Since the store is
+/// append-only, old turns are never summarized.
+#[derive(Debug)]
+pub struct PersistentChatSession<S> {
+    session: ChatSession,
+    store: S,
+    session_id: String,
+}
+
+impl<S: ChatStore> PersistentChatSession<S> {
+    /// Creates a session that stores its history under `session_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session providing the model and settings; its own history
+    ///   is replaced by the stored one on every turn and summarization is disabled
+    /// * `store` - The store holding the history
+    /// * `session_id` - The id of the conversation in the store
+    pub fn new(mut session: ChatSession, store: S, session_id: impl Into<String>) -> Self {
+        session.summarization = None;
+        Self {
+            session,
+            store,
+            session_id: session_id.into(),
+        }
+    }
+
+    /// Sends a message to the chat and returns the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be read or written or the API request
+    /// fails. Nothing is appended if the request fails.
+    pub async fn send_message(
+        &mut self,
+        message: impl Into<String>,
+    ) -> Result<String, GoogleGenerativeAIError> {
+        self.send_content(vec![Part::text(message)]).await
+    }
+
+    /// Sends a message made of arbitrary parts and returns the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be read or written or the API request
+    /// fails. Nothing is appended if the request fails.
+    pub async fn send_content(
+        &mut self,
+        parts: Vec<Part>,
+    ) -> Result<String, GoogleGenerativeAIError> {
+        let history = self.store.load(&self.session_id).await?;
+        let start = history.len();
+        self.session.set_history_unchecked(history);
+
+        let reply = self.session.send_content(parts).await?;
+        for content in &self.session.history()[start..] {
+            self.store.append(&self.session_id, content.clone()).await?;
+        }
+        Ok(reply)
+    }
+
+    /// Loads the current history from the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be read.
+    pub async fn history(&self) -> Result<Vec<Content>, GoogleGenerativeAIError> {
+        self.store.load(&self.session_id).await
+    }
+
+    /// Removes the history from the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be written.
+    pub async fn clear_history(&mut self) -> Result<(), GoogleGenerativeAIError> {
+        self.session.clear_history();
+        self.store.clear(&self.session_id).await
+    }
+
+    /// Returns the id of the conversation in the store.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Returns the store holding the history.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        models::{ModelParams, Role},
+        test_utils::{MockResponse, MockServer},
+        GenerativeModel,
+    };
+
+    fn text(role: Role, text: &str) -> Content {
+        Content {
+            role: Some(role),
+            parts: vec![Part::text(text)],
+        }
+    }
+
+    fn texts(history: &[Content]) -> Vec<String> {
+        history
+            .iter()
+            .map(|content| match &content.parts[0] {
+                Part::Text { text, .. } => text.clone(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_file_store_concurrent_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileChatStore::new(dir.path().join("chats"));
+
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store
+                        .append("shared", text(Role::User, &format!("message {}", i)))
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let mut loaded = texts(&store.load("shared").await.unwrap());
+        loaded.sort();
+        let mut expected: Vec<_> = (0..20).map(|i| format!("message {}", i)).collect();
+        expected.sort();
+        assert_eq!(loaded, expected);
+        assert!(store.load("other").await.unwrap().is_empty());
+
+        store.clear("shared").await.unwrap();
+        store.clear("shared").await.unwrap();
+        store
+            .append("shared", text(Role::User, "again"))
+            .await
+            .unwrap();
+        assert_eq!(texts(&store.load("shared").await.unwrap()), ["again"]);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_rejects_path_like_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileChatStore::new(dir.path());
+
+        for id in ["", "../escape", "a/b", ".hidden"] {
+            assert!(store.append(id, text(Role::User, "x")).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_share_history_through_the_store() {
+        let server = MockServer::start(|request| {
+            let turns = request.json()["contents"].as_array().unwrap().len();
+            MockResponse::json(
+                200,
+                json!({
+                    "candidates": [{
+                        "content": { "role": "model", "parts": [{ "text": format!("seen {}", turns) }] }
+                    }]
+                }),
+            )
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let session = || {
+            ChatSession::new(
+                GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url()),
+            )
+        };
+        let mut first = PersistentChatSession::new(session(), FileChatStore::new(dir.path()), "c1");
+        let mut second =
+            PersistentChatSession::new(session(), FileChatStore::new(dir.path()), "c1");
+
+        assert_eq!(first.send_message("Hi").await.unwrap(), "seen 1");
+        assert_eq!(second.send_message("Again").await.unwrap(), "seen 3");
+        assert_eq!(
+            texts(&first.history().await.unwrap()),
+            ["Hi", "seen 1", "Again", "seen 3"]
+        );
+
+        second.clear_history().await.unwrap();
+        assert_eq!(first.send_message("Fresh").await.unwrap(), "seen 1");
+    }
+}
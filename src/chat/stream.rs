@@ -0,0 +1,164 @@
+//! Streaming replies that are recorded in the chat history.
+
+use std::{
+    ops::Deref,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    error::GoogleGenerativeAIError,
+    models::{Part, Response, ResponseStream, Role},
+};
+
+use super::{ChatSession, TurnMetadata};
+
+/// A streamed chat reply that records itself in the session's history.
+///
+/// The chunks are merged as they are read. When the stream is dropped, the reply
+/// received so far is appended to the history as the model's turn. If no content
+/// was received, e.g. because the stream failed right away, the user message of the
+/// turn is removed again so the history never ends with an unanswered message.
+///
+/// It dereferences to the underlying [`ResponseStream`], e.g. to read the
+/// [`usage_metadata`](ResponseStream::usage_metadata) of the turn.
+pub struct ChatStream<'a> {
+    session: &'a mut ChatSession,
+    stream: ResponseStream,
+    reply: Option<Response>,
+    turn_start: usize,
+    started: Instant,
+}
+
+impl<'a> ChatStream<'a> {
+    /// Wraps a stream whose user message is the last entry of the history and whose
+    /// request was sent at `started`.
+    pub(crate) fn new(
+        session: &'a mut ChatSession,
+        stream: ResponseStream,
+        started: Instant,
+    ) -> Self {
+        let turn_start = session.history.len().saturating_sub(1);
+        Self {
+            session,
+            stream,
+            reply: None,
+            turn_start,
+            started,
+        }
+    }
+
+    /// Drains the stream and returns the concatenated text of the reply.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error yielded by the stream. Any content received before the
+    /// error is still recorded in the history.
+    pub async fn collect_text(mut self) -> Result<String, GoogleGenerativeAIError> {
+        while let Some(chunk) = self.next().await {
+            chunk?;
+        }
+
+        let text = self
+            .reply_content()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        Part::Text {
+                            text,
+                            thought: None | Some(false),
+                        } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(text)
+    }
+
+    /// Returns the parts of the reply received so far.
+    fn reply_content(&self) -> Option<&[Part]> {
+        self.reply
+            .as_ref()?
+            .candidates
+            .as_ref()?
+            .first()?
+            .content
+            .as_ref()
+            .map(|content| content.parts.as_slice())
+            .filter(|parts| !parts.is_empty())
+    }
+}
+
+impl Deref for ChatStream<'_> {
+    type Target = ResponseStream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl Stream for ChatStream<'_> {
+    type Item = Result<Response, GoogleGenerativeAIError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = this.stream.poll_next_unpin(cx);
+        if let Poll::Ready(Some(result)) = &item {
+            let chunk = match result {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    this.session.observers.error(e);
+                    return item;
+                }
+            };
+            if let Some(content) = chunk
+                .candidates
+                .as_ref()
+                .and_then(|candidates| candidates.first())
+                .and_then(|candidate| candidate.content.as_ref())
+            {
+                this.session.observers.chunk(content);
+            }
+            match &mut this.reply {
+                Some(reply) => reply.merge(chunk.clone()),
+                None => this.reply = Some(chunk.clone()),
+            }
+        }
+        item
+    }
+}
+
+impl Drop for ChatStream<'_> {
+    fn drop(&mut self) {
+        let usage_metadata = self.stream.usage_metadata().cloned();
+        self.session.record_usage(usage_metadata);
+        let content = self
+            .reply
+            .take()
+            .and_then(|reply| reply.candidates)
+            .and_then(|candidates| candidates.into_iter().next())
+            .and_then(|candidate| candidate.content)
+            .filter(|content| !content.parts.is_empty());
+
+        match content {
+            Some(mut content) => {
+                content.role.get_or_insert(Role::Model);
+                let metadata = TurnMetadata::reply(
+                    self.stream.usage_metadata(),
+                    self.started.elapsed(),
+                    self.stream.model_version().map(str::to_string),
+                );
+                self.session.observers.model_reply(&content);
+                self.session.push_entry(content, Some(metadata));
+            }
+            None => {
+                self.session.split_history_off(self.turn_start);
+            }
+        }
+    }
+}
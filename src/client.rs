@@ -1,16 +1,29 @@
 //! Client implementation for the Gemini AI API.
 
+use std::time::Duration;
+
 use futures::StreamExt;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
+use crate::cache::CacheManager;
+use crate::decoder::{FrameDecoder, JsonArrayDecoder, SseDecoder};
+use crate::error::{ApiError, StreamErrorKind};
+use crate::key_pool::{KeyPool, PooledKey};
 use crate::models::{
     BatchEmbedContentRequest, BatchEmbedContentResponse, EmbedContentRequest, EmbedContentResponse,
     ListModelsResponse, ModelInfo, ResponseStream,
 };
+use crate::retry::RetryPolicy;
+use crate::structured::{parse_model_json_detailed, Structured};
+use crate::tools::{ToolRegistry, ToolRound, ToolRun};
+use crate::GoogleAIFileManager;
 use crate::{
     error::GoogleGenerativeAIError,
-    models::{ModelParams, Request, RequestType, Response, TokenCountResponse},
+    models::{
+        Content, GenerationConfig, ModelParams, Part, Request, RequestType, Response, Role, Schema,
+        SchemaType, StreamingFormat, SystemInstruction, TokenCountResponse,
+    },
 };
 
 /// Default API endpoint for Google's Generative AI service
@@ -19,15 +32,55 @@ const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
 const DEFAULT_API_VERSION: &str = "v1beta";
 /// Default channel buffer size for streaming responses
 const DEFAULT_CHANNEL_BUFFER_SIZE: usize = 16;
-/// Default buffer capacity for JSON parsing
-const DEFAULT_JSON_BUFFER_CAPACITY: usize = 4096;
+
+/// Returns `config` constrained to reply with exactly one of `labels`.
+pub(crate) fn classification_config(
+    config: Option<GenerationConfig>,
+    labels: &[&str],
+) -> GenerationConfig {
+    let mut config = config.unwrap_or_else(|| GenerationConfig::builder().build());
+    config.response_mime_type = Some("text/x.enum".to_string());
+    config.response_schema = Some(
+        Schema::builder()
+            .r#type(SchemaType::String)
+            .enum_values(
+                labels
+                    .iter()
+                    .map(|label| label.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .build(),
+    );
+    config
+}
+
+/// Returns the label a classification reply chose, or an error if it is not one of
+/// `labels`.
+pub(crate) fn parse_label(
+    text: String,
+    labels: &[&str],
+) -> Result<String, GoogleGenerativeAIError> {
+    let label = text.trim();
+    if labels.contains(&label) {
+        Ok(label.to_string())
+    } else {
+        Err(GoogleGenerativeAIError::new(format!(
+            "Reply {:?} is not one of the labels {:?}",
+            text, labels
+        )))
+    }
+}
 
 /// A client for interacting with the Gemini AI API.
 #[derive(Debug, Clone)]
 pub struct GenerativeModel {
-    api_key: String,
+    keys: KeyPool,
     params: ModelParams,
     client: reqwest::Client,
+    base_url: String,
+    retry_policy: Option<RetryPolicy>,
+    validate_requests: bool,
+    validate_structured_output: bool,
 }
 
 impl GenerativeModel {
@@ -38,13 +91,111 @@ impl GenerativeModel {
     /// * `api_key` - The API key for authentication
     /// * `params` - The model parameters
     pub fn new(api_key: impl Into<String>, params: impl Into<ModelParams>) -> Self {
+        Self::with_key_pool(KeyPool::from(api_key.into()), params)
+    }
+
+    /// Creates a new GenerativeModel that rotates between the keys of a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The pool of API keys, used round-robin per request
+    /// * `params` - The model parameters
+    pub fn with_key_pool(keys: KeyPool, params: impl Into<ModelParams>) -> Self {
+        Self::with_key_pool_and_client(keys, params, reqwest::Client::new())
+    }
+
+    /// Creates a new GenerativeModel that sends its requests through an existing HTTP client.
+    ///
+    /// Cloning a `reqwest::Client` shares its connection pool, so the same client can be
+    /// given to a [`GoogleAIFileManager`](crate::GoogleAIFileManager) and a
+    /// [`CacheManager`](crate::cache::CacheManager) as well, see [`GeminiClients`].
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The API key for authentication
+    /// * `params` - The model parameters
+    /// * `client` - The HTTP client to send requests with
+    pub fn with_client(
+        api_key: impl Into<String>,
+        params: impl Into<ModelParams>,
+        client: reqwest::Client,
+    ) -> Self {
+        Self::with_key_pool_and_client(KeyPool::from(api_key.into()), params, client)
+    }
+
+    /// Creates a new GenerativeModel from a pool of API keys and an existing HTTP client.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The pool of API keys, used round-robin per request
+    /// * `params` - The model parameters
+    /// * `client` - The HTTP client to send requests with
+    pub fn with_key_pool_and_client(
+        keys: KeyPool,
+        params: impl Into<ModelParams>,
+        client: reqwest::Client,
+    ) -> Self {
         Self {
-            api_key: api_key.into(),
+            keys,
             params: params.into(),
-            client: reqwest::Client::new(),
+            client,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            retry_policy: None,
+            validate_requests: true,
+            validate_structured_output: false,
         }
     }
 
+    /// Retries failed requests according to the given policy.
+    ///
+    /// Without a policy (the default) every failure is returned immediately.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Overrides the base URL of the API, e.g. to go through a proxy.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Sets whether requests are checked with [`Request::validate`] before they are
+    /// sent. Enabled by default; disable it to let the API judge requests this crate
+    /// considers invalid.
+    pub fn with_request_validation(mut self, validate_requests: bool) -> Self {
+        self.validate_requests = validate_requests;
+        self
+    }
+
+    /// Sets whether structured replies are checked with
+    /// [`Schema::validate`](crate::models::Schema::validate) before they are
+    /// deserialized. Disabled by default.
+    ///
+    /// Applies to `generate_structured` and to
+    /// [`ChatSession::send_message_structured`](crate::chat::ChatSession::send_message_structured);
+    /// a reply that does not conform fails with
+    /// [`GoogleGenerativeAIError::SchemaViolations`].
+    pub fn with_structured_output_validation(mut self, validate: bool) -> Self {
+        self.validate_structured_output = validate;
+        self
+    }
+
+    /// Returns the parameters of this model.
+    pub fn params(&self) -> &ModelParams {
+        &self.params
+    }
+
+    /// Returns the model identifier, e.g. `gemini-1.5-flash`.
+    pub fn model_name(&self) -> &str {
+        &self.params.model
+    }
+
+    /// Returns the pool of API keys used by this model.
+    pub fn key_pool(&self) -> &KeyPool {
+        &self.keys
+    }
+
     /// Creates a new GenerativeModel from environment variables.
     ///
     /// # Environment Variables
@@ -66,7 +217,7 @@ impl GenerativeModel {
         ))
     }
 
-    /// Makes a request to the Gemini AI API.
+    /// Makes a request to the Gemini AI API, retrying according to the retry policy.
     ///
     /// # Arguments
     ///
@@ -79,28 +230,38 @@ impl GenerativeModel {
         &self,
         url: &str,
         request: T,
-    ) -> Result<reqwest::Response, GoogleGenerativeAIError>
+    ) -> Result<(reqwest::Response, PooledKey), GoogleGenerativeAIError>
     where
         T: Serialize,
     {
-        let response = self
-            .client
-            .post(url)
-            .header("x-goog-api-key", &self.api_key)
-            .json(&request)
-            .send()
-            .await?;
+        let mut attempt = 0;
+        loop {
+            let key = self.keys.acquire();
+            let response = self
+                .client
+                .post(url)
+                .header("x-goog-api-key", key.as_str())
+                .json(&request)
+                .send()
+                .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_default();
-            return Err(GoogleGenerativeAIError::new(format!(
-                "Request failed with status {}: {}",
-                status, error_body
-            )));
-        }
+            let status = response.status();
+            if status.is_success() {
+                return Ok((response, key));
+            }
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                self.keys.report_rate_limited(&key);
+            }
 
-        Ok(response)
+            let error = ApiError::from_response(response).await;
+            match &self.retry_policy {
+                Some(policy) if policy.should_retry(attempt, &error) => {
+                    tokio::time::sleep(policy.delay_for(attempt, &error)).await;
+                    attempt += 1;
+                }
+                _ => return Err(error.into()),
+            }
+        }
     }
 
     /// Sends the HTTP request and processes the response.
@@ -112,16 +273,53 @@ impl GenerativeModel {
     where
         R: Serialize,
     {
-        Ok(self.make_request(url, request).await?.json::<T>().await?)
+        Ok(self.make_request(url, request).await?.0.json::<T>().await?)
+    }
+
+    /// Sends a generation request and records which key served it.
+    async fn send_generate_request(
+        &self,
+        url: &str,
+        request: Request,
+        keep_raw: bool,
+    ) -> Result<Response, GoogleGenerativeAIError> {
+        let (response, key) = self.make_request(url, request).await?;
+        let bytes = response.bytes().await?;
+        let mut response = parse_response(&bytes, keep_raw)?;
+        response.served_by = Some(key.label());
+        Ok(response)
     }
 
     fn build_url(&self, model: &str, request_type: RequestType) -> String {
         format!(
-            "{}/{}/models/{}:{}?key={}",
-            DEFAULT_BASE_URL, DEFAULT_API_VERSION, model, request_type, self.api_key
+            "{}/{}/models/{}:{}",
+            self.base_url, DEFAULT_API_VERSION, model, request_type
         )
     }
 
+    /// Applies the model-level defaults to a request, e.g. the generation config when
+    /// the request has none of its own, and validates the result unless disabled.
+    ///
+    /// The seed and media resolution of the model apply even if the request has a
+    /// generation config, so a seeded model stays reproducible and a global
+    /// resolution keeps controlling the cost of media inputs.
+    fn prepare_request(&self, mut request: Request) -> Result<Request, GoogleGenerativeAIError> {
+        let defaults = self.params.generation_config.as_ref();
+        match &mut request.generation_config {
+            Some(config) => {
+                config.seed = config.seed.or(defaults.and_then(|defaults| defaults.seed));
+                config.media_resolution = config
+                    .media_resolution
+                    .or(defaults.and_then(|defaults| defaults.media_resolution));
+            }
+            None => request.generation_config = defaults.cloned(),
+        }
+        if self.validate_requests {
+            request.validate()?;
+        }
+        Ok(request)
+    }
+
     /// Generates content using the Gemini AI API.
     ///
     /// # Arguments
@@ -135,13 +333,11 @@ impl GenerativeModel {
         &self,
         prompt: impl Into<String>,
     ) -> Result<Response, GoogleGenerativeAIError> {
-        let mut request = Request::with_prompt(prompt);
-        request.generation_config = request
-            .generation_config
-            .or_else(|| self.params.generation_config.clone());
+        let request = self.prepare_request(Request::with_prompt(prompt))?;
         let url = self.build_url(self.params.model.as_str(), RequestType::GenerateContent);
 
-        self.send_request(&url, request).await
+        self.send_generate_request(&url, request, self.params.keep_raw)
+            .await
     }
 
     /// Generates response using the Gemini AI API with a system instruction.
@@ -153,20 +349,237 @@ impl GenerativeModel {
     ///
     /// # Errors
     ///
-    /// Returns an error if the API request fails or if the response cannot be parsed.
+    /// Returns [`GoogleGenerativeAIError::InvalidRequest`] without contacting the API if
+    /// the request fails [`Request::validate`], and an error if the API request fails
+    /// or if the response cannot be parsed.
     pub async fn generate_response(
         &self,
         request: impl Into<Request>,
     ) -> Result<Response, GoogleGenerativeAIError> {
         let url = self.build_url(self.params.model.as_str(), RequestType::GenerateContent);
+        let request = self.prepare_request(request.into())?;
+        self.send_generate_request(&url, request, self.params.keep_raw)
+            .await
+    }
+
+    /// Generates a reply conforming to the schema of `T` and parses it into `T`.
+    ///
+    /// The JSON response MIME type and the schema derived with
+    /// [`Schema::for_type`](crate::models::Schema::for_type) are added to the
+    /// request's (or else the model's) generation config. A reply that is not valid
+    /// JSON as is gets repaired with
+    /// [`parse_model_json`](crate::structured::parse_model_json); use
+    /// [`generate_structured_with_metadata`](Self::generate_structured_with_metadata)
+    /// to find out whether that happened.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run(model: gemini_ai_rust::GenerativeModel) -> Result<(), gemini_ai_rust::error::GoogleGenerativeAIError> {
+    /// #[derive(serde::Deserialize, schemars::JsonSchema)]
+    /// struct Recipe {
+    ///     name: String,
+    ///     ingredients: Vec<String>,
+    /// }
+    ///
+    /// let recipe: Recipe = model.generate_structured("Give me a cookie recipe").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the reply cannot be parsed into
+    /// `T`; the error message then includes the raw reply. With
+    /// [`with_structured_output_validation`](Self::with_structured_output_validation),
+    /// returns [`GoogleGenerativeAIError::SchemaViolations`] if the reply does not
+    /// conform to the schema.
+    #[cfg(feature = "schemars")]
+    pub async fn generate_structured<T>(
+        &self,
+        request: impl Into<Request>,
+    ) -> Result<T, GoogleGenerativeAIError>
+    where
+        T: schemars::JsonSchema + serde::de::DeserializeOwned,
+    {
+        self.generate_structured_with_metadata(request)
+            .await
+            .map(|structured| structured.value)
+    }
+
+    /// Like [`generate_structured`](Self::generate_structured), but also returns the
+    /// raw reply and whether it had to be repaired before it could be parsed.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`generate_structured`](Self::generate_structured).
+    #[cfg(feature = "schemars")]
+    pub async fn generate_structured_with_metadata<T>(
+        &self,
+        request: impl Into<Request>,
+    ) -> Result<Structured<T>, GoogleGenerativeAIError>
+    where
+        T: schemars::JsonSchema + serde::de::DeserializeOwned,
+    {
+        let mut request = request.into();
+        let mut generation_config = request
+            .generation_config
+            .take()
+            .or_else(|| self.params.generation_config.clone())
+            .unwrap_or_else(|| GenerationConfig::builder().build());
+        let schema = Schema::for_type::<T>();
+        generation_config.response_mime_type = Some("application/json".to_string());
+        generation_config.response_schema = Some(schema.clone());
+        request.generation_config = Some(generation_config);
+
+        let text = self.generate_response(request).await?.text();
+        self.parse_structured(text, &schema)
+    }
+
+    /// Asks the model to pick one of `labels` for the prompt and returns the chosen
+    /// label.
+    ///
+    /// The `text/x.enum` response MIME type and a string schema listing the labels
+    /// are added to the request's (or else the model's) generation config, which
+    /// constrains the whole reply to a single label.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run(model: gemini_ai_rust::GenerativeModel) -> Result<(), gemini_ai_rust::error::GoogleGenerativeAIError> {
+    /// let sentiment = model
+    ///     .classify("The food was cold and bland.", &["positive", "neutral", "negative"])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the reply is not one of
+    /// `labels`.
+    pub async fn classify(
+        &self,
+        request: impl Into<Request>,
+        labels: &[&str],
+    ) -> Result<String, GoogleGenerativeAIError> {
         let mut request = request.into();
-        request.generation_config = request
+        let generation_config = request
             .generation_config
+            .take()
             .or_else(|| self.params.generation_config.clone());
-        self.send_request(&url, request).await
+        request.generation_config = Some(classification_config(generation_config, labels));
+
+        let text = self.generate_response(request).await?.text();
+        parse_label(text, labels)
+    }
+
+    /// Parses a structured reply, repairing it if it is not valid JSON as is and
+    /// validating it against `schema` first if
+    /// [`with_structured_output_validation`](Self::with_structured_output_validation)
+    /// is enabled.
+    pub(crate) fn parse_structured<T: serde::de::DeserializeOwned>(
+        &self,
+        text: String,
+        schema: &Schema,
+    ) -> Result<Structured<T>, GoogleGenerativeAIError> {
+        let parse_error = |e: serde_json::Error, text: &str| {
+            GoogleGenerativeAIError::new(format!(
+                "Failed to parse structured reply: {}; raw reply: {}",
+                e, text
+            ))
+        };
+        let (value, repaired) =
+            parse_model_json_detailed(&text).map_err(|e| parse_error(e, &text))?;
+        if self.validate_structured_output {
+            if let Err(violations) = schema.validate(&value) {
+                return Err(GoogleGenerativeAIError::SchemaViolations {
+                    violations,
+                    raw: text,
+                });
+            }
+        }
+        let value = serde_json::from_value(value).map_err(|e| parse_error(e, &text))?;
+        Ok(Structured {
+            value,
+            repaired,
+            raw: text,
+        })
+    }
+
+    /// Generates a response, answering the model's function calls with the functions
+    /// of `registry` until it replies with text.
+    ///
+    /// The registry's declarations are added to the request's tools. Each round, the
+    /// calls of the model are run concurrently, and the model turn and the function
+    /// responses are appended to the contents before the request is sent again. After
+    /// [`ToolRegistry::max_rounds`] rounds, the latest response is returned even if it
+    /// still contains function calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an API request fails. Failing or unknown functions are not
+    /// errors; they are reported to the model, see [`ToolRegistry::call`].
+    pub async fn generate_with_tools(
+        &self,
+        request: impl Into<Request>,
+        registry: &ToolRegistry,
+    ) -> Result<ToolRun, GoogleGenerativeAIError> {
+        let mut request = request.into();
+        request
+            .tools
+            .get_or_insert_with(Vec::new)
+            .push(registry.tool());
+
+        let mut rounds = Vec::new();
+        loop {
+            let response = self.generate_response(request.clone()).await?;
+            let calls = response.function_calls();
+            if calls.is_empty() || rounds.len() >= registry.max_rounds() {
+                return Ok(ToolRun { response, rounds });
+            }
+
+            let responses = registry.call_all(&calls).await;
+            let model_turn = response
+                .candidates
+                .as_ref()
+                .and_then(|candidates| candidates.first())
+                .and_then(|candidate| candidate.content.clone())
+                .unwrap_or_else(|| Content {
+                    role: Some(Role::Model),
+                    parts: calls.iter().cloned().map(Part::function_call).collect(),
+                });
+            request.contents.push(model_turn);
+            request
+                .contents
+                .push(Content::function_responses(responses.clone()));
+            rounds.push(ToolRound { calls, responses });
+        }
+    }
+
+    /// Generates a response and also returns the raw JSON body it was parsed from.
+    ///
+    /// This gives access to fields the typed [`Response`] does not model yet. The body
+    /// is parsed only once, into the JSON value that the typed response is read from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or if the response cannot be parsed.
+    pub async fn generate_response_raw(
+        &self,
+        request: impl Into<Request>,
+    ) -> Result<(Response, serde_json::Value), GoogleGenerativeAIError> {
+        let url = self.build_url(self.params.model.as_str(), RequestType::GenerateContent);
+        let request = self.prepare_request(request.into())?;
+        let mut response = self.send_generate_request(&url, request, true).await?;
+        let raw = response.raw.take().unwrap_or_default();
+        Ok((response, raw))
     }
 
     /// Generates streaming content using the Gemini AI API.
+    ///
+    /// The wire format is chosen by [`ModelParams::streaming_format`]; either way the
+    /// chunks are delivered through the returned [`ResponseStream`].
     pub async fn stream_generate_response(
         &self,
         request: impl Into<Request>,
@@ -175,107 +588,94 @@ impl GenerativeModel {
             self.params.model.as_str(),
             RequestType::StreamGenerateContent,
         );
-        let response = self.make_request(&url, request.into()).await?;
+        let (decoder, url): (Box<dyn FrameDecoder>, _) = match self.params.streaming_format {
+            StreamingFormat::Sse => (Box::new(SseDecoder::default()), format!("{}?alt=sse", url)),
+            StreamingFormat::JsonArray => (Box::new(JsonArrayDecoder::default()), url),
+        };
+        let (response, key) = self
+            .make_request(&url, self.prepare_request(request.into())?)
+            .await?;
 
         let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_BUFFER_SIZE);
-        let mut stream = response.bytes_stream();
-
-        tokio::spawn(async move {
-            let mut buffer = String::with_capacity(DEFAULT_JSON_BUFFER_CAPACITY);
-            let mut in_object = false;
-            let mut object_depth = 0;
-            let mut in_string = false;
-            let mut escaped = false;
-
-            while let Some(chunk_result) = stream.next().await {
-                match chunk_result {
-                    Ok(chunk) => match std::str::from_utf8(&chunk) {
-                        Ok(chunk_str) => {
-                            for c in chunk_str.chars() {
-                                match c {
-                                    '"' if !escaped => {
-                                        in_string = !in_string;
-                                        buffer.push(c);
-                                    }
-                                    '\\' if !escaped => {
-                                        escaped = true;
-                                        buffer.push(c);
-                                    }
-                                    '{' if !in_string => {
-                                        if !in_object {
-                                            in_object = true;
-                                            buffer.clear();
-                                        }
-                                        object_depth += 1;
-                                        buffer.push(c);
-                                    }
-                                    '}' if !in_string => {
-                                        object_depth -= 1;
-                                        buffer.push(c);
-
-                                        if object_depth == 0 && in_object {
-                                            in_object = false;
-                                            match serde_json::from_str(&buffer) {
-                                                Ok(response) => {
-                                                    if tx.send(Ok(response)).await.is_err() {
-                                                        return;
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    if tx
-                                                        .send(Err(GoogleGenerativeAIError::new(
-                                                            format!(
-                                                                "Failed to parse response: {}",
-                                                                e
-                                                            ),
-                                                        )))
-                                                        .await
-                                                        .is_err()
-                                                    {
-                                                        return;
-                                                    }
-                                                }
-                                            }
-                                            buffer.clear();
-                                            buffer.reserve(DEFAULT_JSON_BUFFER_CAPACITY);
-                                        }
-                                    }
-                                    '[' if !in_string && !in_object => buffer.clear(),
-                                    ']' if !in_string && !in_object => buffer.clear(),
-                                    _ => {
-                                        if in_object {
-                                            buffer.push(c);
-                                        }
-                                        escaped = false;
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            if let Err(e) = tx
-                                .send(Err(GoogleGenerativeAIError::new(format!(
-                                    "UTF-8 decode error: {}",
-                                    e
-                                ))))
-                                .await
-                            {
-                                eprintln!("Error sending error: {}", e);
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        if let Err(e) = tx
-                            .send(Err(GoogleGenerativeAIError::new(e.to_string())))
-                            .await
-                        {
-                            eprintln!("Error sending error: {}", e);
-                        }
-                    }
-                }
-            }
-        });
+        let producer = tokio::spawn(forward_stream(
+            response,
+            decoder,
+            key.label(),
+            self.params.keep_raw,
+            self.params.stream_idle_timeout,
+            tx,
+        ));
 
-        Ok(ResponseStream::new(rx))
+        Ok(ResponseStream::new(rx).with_producer(producer.abort_handle()))
+    }
+
+    /// Streams the response to a text prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The text prompt to generate content from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream cannot be established.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), gemini_ai_rust::error::GoogleGenerativeAIError> {
+    /// use futures::StreamExt;
+    /// use gemini_ai_rust::{models::ModelParams, GenerativeModel};
+    ///
+    /// let model = GenerativeModel::new("api-key", ModelParams::default());
+    /// let mut stream = model.stream_send_message("Tell me a story").await?;
+    /// while let Some(chunk) = stream.next().await {
+    ///     print!("{}", chunk?.text());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stream_send_message(
+        &self,
+        prompt: impl Into<String>,
+    ) -> Result<ResponseStream, GoogleGenerativeAIError> {
+        self.stream_generate_response(Request::with_prompt(prompt))
+            .await
+    }
+
+    /// Streams the response to a text prompt with a system instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `system_instruction` - The system instruction for the model
+    /// * `prompt` - The text prompt to generate content from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream cannot be established.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), gemini_ai_rust::error::GoogleGenerativeAIError> {
+    /// use gemini_ai_rust::{models::ModelParams, GenerativeModel};
+    ///
+    /// let model = GenerativeModel::new("api-key", ModelParams::default());
+    /// let stream = model
+    ///     .stream_send_message_with_system("Answer like a pirate", "Where is the treasure?")
+    ///     .await?;
+    /// println!("{}", stream.collect_text().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stream_send_message_with_system(
+        &self,
+        system_instruction: impl Into<String>,
+        prompt: impl Into<String>,
+    ) -> Result<ResponseStream, GoogleGenerativeAIError> {
+        let mut request = Request::with_prompt(prompt);
+        request.system_instruction =
+            Some(SystemInstruction::from(system_instruction.into().as_str()));
+        self.stream_generate_response(request).await
     }
 
     /// Counts the number of tokens in the given content.
@@ -292,27 +692,36 @@ impl GenerativeModel {
         request: impl Into<Request>,
     ) -> Result<TokenCountResponse, GoogleGenerativeAIError> {
         let url = self.build_url(self.params.model.as_str(), RequestType::CountTokens);
-        let mut request = request.into();
-        request.generation_config = request
-            .generation_config
-            .or_else(|| self.params.generation_config.clone());
+        let model = if self.params.model.starts_with("models/") {
+            self.params.model.clone()
+        } else {
+            format!("models/{}", self.params.model)
+        };
+        // Wrapping the request counts the system instruction and tools as well, which
+        // a bare `contents` list would leave out.
+        let request = CountTokensRequest {
+            generate_content_request: GenerateContentRequest {
+                model,
+                request: self.prepare_request(request.into())?,
+            },
+        };
         self.send_request(&url, request).await
     }
 
     /// List all available models
     pub async fn list_models(&self) -> Result<ListModelsResponse, GoogleGenerativeAIError> {
-        let url = format!("{}/{}/models", DEFAULT_BASE_URL, DEFAULT_API_VERSION);
-        let url = format!("{}?key={}", url, self.api_key);
-
-        let response = self.client.get(&url).send().await?;
-
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_default();
-            return Err(GoogleGenerativeAIError::new(format!(
-                "Failed to list models: {} - {}",
-                status, error_body
-            )));
+        let url = format!("{}/{}/models", self.base_url, DEFAULT_API_VERSION);
+        let key = self.keys.acquire();
+
+        let response = self
+            .client
+            .get(&url)
+            .header("x-goog-api-key", key.as_str())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(response).await.into());
         }
 
         Ok(response.json().await?)
@@ -325,19 +734,19 @@ impl GenerativeModel {
     ) -> Result<ModelInfo, GoogleGenerativeAIError> {
         let url = format!(
             "{}/{}/models/{}",
-            DEFAULT_BASE_URL, DEFAULT_API_VERSION, model_name
+            self.base_url, DEFAULT_API_VERSION, model_name
         );
-        let url = format!("{}?key={}", url, self.api_key);
+        let key = self.keys.acquire();
 
-        let response = self.client.get(&url).send().await?;
+        let response = self
+            .client
+            .get(&url)
+            .header("x-goog-api-key", key.as_str())
+            .send()
+            .await?;
 
-        let status = response.status();
-        if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_default();
-            return Err(GoogleGenerativeAIError::new(format!(
-                "Failed to get model {}: {} - {}",
-                model_name, status, error_body
-            )));
+        if !response.status().is_success() {
+            return Err(ApiError::from_response(response).await.into());
         }
 
         Ok(response.json().await?)
@@ -377,3 +786,985 @@ impl GenerativeModel {
         self.send_request(&url, request).await
     }
 }
+
+/// The body of a `countTokens` request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CountTokensRequest {
+    generate_content_request: GenerateContentRequest,
+}
+
+/// A full `generateContent` request, as embedded in a `countTokens` request.
+#[derive(Serialize)]
+struct GenerateContentRequest {
+    model: String,
+    #[serde(flatten)]
+    request: Request,
+}
+
+/// Decodes a streamed body into responses and forwards them to the channel until the
+/// body ends, the idle timeout elapses or the receiver is dropped.
+async fn forward_stream(
+    response: reqwest::Response,
+    mut decoder: Box<dyn FrameDecoder>,
+    served_by: String,
+    keep_raw: bool,
+    idle_timeout: Option<Duration>,
+    tx: mpsc::Sender<Result<Response, GoogleGenerativeAIError>>,
+) {
+    let mut stream = response.bytes_stream();
+    let mut finished = false;
+
+    while !finished {
+        let next = match idle_timeout {
+            Some(idle_timeout) => match tokio::time::timeout(idle_timeout, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    let message = format!("No stream chunk received within {:?}", idle_timeout);
+                    let error =
+                        GoogleGenerativeAIError::stream(StreamErrorKind::Transport, message);
+                    let _ = tx.send(Err(error)).await;
+                    return;
+                }
+            },
+            None => stream.next().await,
+        };
+
+        let frames = match next {
+            Some(Ok(chunk)) => decoder.feed(&chunk),
+            Some(Err(e)) => {
+                let error =
+                    GoogleGenerativeAIError::stream(StreamErrorKind::Transport, e.to_string());
+                let _ = tx.send(Err(error)).await;
+                return;
+            }
+            None => {
+                finished = true;
+                decoder.finish()
+            }
+        };
+
+        for frame in frames {
+            let item = frame
+                .map_err(|e| GoogleGenerativeAIError::stream(StreamErrorKind::Protocol, e))
+                .and_then(|frame| parse_stream_chunk(&frame, keep_raw))
+                .map(|mut response| {
+                    response.served_by = Some(served_by.clone());
+                    response
+                });
+            if tx.send(item).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// A factory for the clients of this crate that share one HTTP connection pool and
+/// one pool of API keys.
+///
+/// Creating a [`GenerativeModel`], a [`GoogleAIFileManager`] and a [`CacheManager`]
+/// separately gives each its own connection pool. The clients created by a
+/// `GeminiClients` all send their requests through the same `reqwest::Client`.
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::{client::GeminiClients, models::ModelParams};
+///
+/// let clients = GeminiClients::new("your-api-key");
+/// let model = clients.generative_model(ModelParams::default());
+/// let files = clients.file_manager();
+/// let caches = clients.cache_manager();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GeminiClients {
+    client: reqwest::Client,
+    keys: KeyPool,
+    base_url: Option<String>,
+}
+
+impl GeminiClients {
+    /// Creates a factory for the given API key with a new HTTP client.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_key_pool(KeyPool::from(api_key.into()))
+    }
+
+    /// Creates a factory that rotates between the keys of a pool with a new HTTP client.
+    pub fn with_key_pool(keys: KeyPool) -> Self {
+        Self::with_key_pool_and_client(keys, reqwest::Client::new())
+    }
+
+    /// Creates a factory from a pool of API keys and an existing HTTP client, e.g. one
+    /// configured with a proxy or timeouts.
+    pub fn with_key_pool_and_client(keys: KeyPool, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            keys,
+            base_url: None,
+        }
+    }
+
+    /// Creates a factory from environment variables.
+    ///
+    /// # Environment Variables
+    ///
+    /// * `GOOGLE_API_KEY` - The API key for authentication
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the required environment variable is not set.
+    pub fn from_env() -> Result<Self, GoogleGenerativeAIError> {
+        let api_key = std::env::var("GOOGLE_API_KEY")?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Overrides the base URL of the API for every client, e.g. to go through a proxy.
+    ///
+    /// The URL is the host without the API version, e.g.
+    /// `https://generativelanguage.googleapis.com`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Returns the shared HTTP client.
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Returns the shared pool of API keys.
+    pub fn key_pool(&self) -> &KeyPool {
+        &self.keys
+    }
+
+    /// Creates a generative model using the shared HTTP client and keys.
+    pub fn generative_model(&self, params: impl Into<ModelParams>) -> GenerativeModel {
+        let model = GenerativeModel::with_key_pool_and_client(
+            self.keys.clone(),
+            params,
+            self.client.clone(),
+        );
+        match &self.base_url {
+            Some(base_url) => model.with_base_url(base_url),
+            None => model,
+        }
+    }
+
+    /// Creates a file manager using the shared HTTP client and keys.
+    pub fn file_manager(&self) -> GoogleAIFileManager {
+        let manager =
+            GoogleAIFileManager::with_key_pool_and_client(self.keys.clone(), self.client.clone());
+        match &self.base_url {
+            Some(base_url) => manager.with_base_url(base_url),
+            None => manager,
+        }
+    }
+
+    /// Creates a cache manager using the shared HTTP client and keys.
+    pub fn cache_manager(&self) -> CacheManager {
+        let manager =
+            CacheManager::with_key_pool_and_client(self.keys.clone(), self.client.clone());
+        match &self.base_url {
+            Some(base_url) => {
+                manager.with_base_url(format!("{}/{}", base_url, DEFAULT_API_VERSION))
+            }
+            None => manager,
+        }
+    }
+}
+
+/// Parses one chunk of a stream, telling malformed chunks apart from errors the
+/// server reported inside the stream.
+fn parse_stream_chunk(chunk: &[u8], keep_raw: bool) -> Result<Response, GoogleGenerativeAIError> {
+    let response = parse_response(chunk, keep_raw).map_err(|e| {
+        GoogleGenerativeAIError::stream(
+            StreamErrorKind::Protocol,
+            format!("Failed to parse response: {}", e),
+        )
+    })?;
+
+    // An error object deserializes into an empty response, so only those are checked.
+    if response.candidates.is_none() && response.usage_metadata.is_none() {
+        if let Some(error) = ApiError::from_stream_chunk(chunk) {
+            return Err(GoogleGenerativeAIError::stream(
+                StreamErrorKind::Server,
+                error.to_string(),
+            ));
+        }
+    }
+    Ok(response)
+}
+
+/// Parses a response body, keeping the raw JSON value when requested.
+///
+/// The body is parsed exactly once: either straight into a [`Response`], or into a
+/// [`serde_json::Value`] that the typed response is then read from.
+fn parse_response(bytes: &[u8], keep_raw: bool) -> Result<Response, serde_json::Error> {
+    if !keep_raw {
+        return serde_json::from_slice(bytes);
+    }
+    let raw: serde_json::Value = serde_json::from_slice(bytes)?;
+    let mut response = Response::deserialize(&raw)?;
+    response.raw = Some(raw);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GenerationConfig, MediaResolution, RequestValidationError};
+    use crate::test_utils::{MockResponse, MockServer};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn text_response(text: &str) -> serde_json::Value {
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": text }] },
+                "finishReason": "STOP"
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_key_is_skipped() {
+        let server = MockServer::start(|request| {
+            if request.header("x-goog-api-key") == Some("test-key-a") {
+                MockResponse::json(429, json!({ "error": { "code": 429 } }))
+            } else {
+                MockResponse::json(200, text_response("ok"))
+            }
+        })
+        .await;
+
+        let pool = KeyPool::new(["test-key-a", "test-key-b"]).unwrap();
+        let model = GenerativeModel::with_key_pool(pool, ModelParams::default())
+            .with_base_url(server.url());
+
+        assert!(model.send_message("first").await.is_err());
+        let second = model.send_message("second").await.unwrap();
+        let third = model.send_message("third").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].path(),
+            "/v1beta/models/gemini-1.5-flash:generateContent"
+        );
+        assert_eq!(
+            requests[1].json()["contents"][0]["parts"][0]["text"],
+            "second"
+        );
+
+        let keys: Vec<_> = requests
+            .iter()
+            .map(|r| r.header("x-goog-api-key").unwrap().to_string())
+            .collect();
+        assert_eq!(keys, ["test-key-a", "test-key-b", "test-key-b"]);
+        assert_eq!(second.served_by.as_deref(), Some("...ey-b"));
+        assert_eq!(third.text(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_retry_prefers_server_delay() {
+        let attempts = AtomicUsize::new(0);
+        let server = MockServer::start(move |_| {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                MockResponse::json(
+                    503,
+                    json!({ "error": { "code": 503, "message": "Overloaded", "status": "UNAVAILABLE" } }),
+                )
+                .with_header("Retry-After", "0")
+            } else {
+                MockResponse::json(200, text_response("recovered"))
+            }
+        })
+        .await;
+
+        // The computed backoff would stall the test; the server asks for no delay.
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_secs(60))
+            .build();
+        let model = GenerativeModel::new("key", ModelParams::default())
+            .with_base_url(server.url())
+            .with_retry_policy(policy);
+
+        let response = tokio::time::timeout(Duration::from_secs(5), model.send_message("hi"))
+            .await
+            .expect("retry did not honor Retry-After")
+            .unwrap();
+
+        assert_eq!(response.text(), "recovered");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failure_without_retry_policy_returns_api_error() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                400,
+                json!({ "error": { "code": 400, "message": "Bad prompt", "status": "INVALID_ARGUMENT" } }),
+            )
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let error = model.send_message("hi").await.unwrap_err();
+        let api_error = error.api_error().unwrap();
+
+        assert_eq!(api_error.status, 400);
+        assert_eq!(api_error.code.as_deref(), Some("INVALID_ARGUMENT"));
+        assert_eq!(api_error.message, "Bad prompt");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[cfg(feature = "schemars")]
+    #[tokio::test]
+    async fn test_generate_structured() {
+        #[derive(Debug, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+        struct Color {
+            name: String,
+            hex: Option<String>,
+        }
+
+        let server = MockServer::start(|_| {
+            MockResponse::json(200, text_response(r##"{"name": "red", "hex": "#ff0000"}"##))
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let color: Color = model.generate_structured("A color").await.unwrap();
+
+        assert_eq!(
+            color,
+            Color {
+                name: "red".into(),
+                hex: Some("#ff0000".into())
+            }
+        );
+        let body = server.requests()[0].json();
+        assert_eq!(
+            body["generation_config"]["response_mime_type"],
+            "application/json"
+        );
+        assert_eq!(
+            body["generation_config"]["response_schema"]["required"],
+            json!(["name"])
+        );
+    }
+
+    #[cfg(feature = "schemars")]
+    #[tokio::test]
+    async fn test_generate_structured_repairs_fenced_reply() {
+        #[derive(Debug, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+        struct Tags {
+            tags: Vec<String>,
+        }
+
+        let reply = "Here is the JSON:\n```json\n{\"tags\": [\"rust\", \"ai\",],}\n```";
+        let server =
+            MockServer::start(move |_| MockResponse::json(200, text_response(reply))).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let structured = model
+            .generate_structured_with_metadata::<Tags>("Tag this")
+            .await
+            .unwrap();
+        assert!(structured.repaired);
+        assert_eq!(structured.raw, reply);
+        assert_eq!(structured.value.tags, ["rust", "ai"]);
+    }
+
+    fn function_call_response(calls: serde_json::Value) -> serde_json::Value {
+        let parts: Vec<_> = calls
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|call| json!({ "functionCall": call }))
+            .collect();
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": parts },
+                "finishReason": "STOP"
+            }]
+        })
+    }
+
+    fn weather_registry() -> ToolRegistry {
+        ToolRegistry::new()
+            .with_function(
+                crate::models::FunctionDeclaration::new()
+                    .with_name("get_weather")
+                    .with_parameters(&["city, string, The city"])
+                    .unwrap(),
+                |args: serde_json::Value| async move {
+                    match args["city"].as_str() {
+                        Some(city) => Ok(json!({ "forecast": format!("Sunny in {city}") })),
+                        None => Err("missing city"),
+                    }
+                },
+            )
+            .with_function(
+                crate::models::FunctionDeclaration::new().with_name("get_time"),
+                |_| async { Ok::<_, String>(json!("12:00")) },
+            )
+    }
+
+    #[tokio::test]
+    async fn test_generate_with_tools_runs_two_rounds() {
+        let server = MockServer::start(|request| {
+            let contents = request.json()["contents"].as_array().unwrap().len();
+            let body = match contents {
+                1 => function_call_response(json!([
+                    { "name": "get_weather", "args": { "city": "Paris" } },
+                    { "name": "get_time", "args": {} }
+                ])),
+                3 => function_call_response(json!([
+                    { "name": "book_table", "args": { "time": "12:00" } },
+                    { "name": "get_weather", "args": {} }
+                ])),
+                _ => text_response("Sunny at noon, but I could not book a table."),
+            };
+            MockResponse::json(200, body)
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let run = model
+            .generate_with_tools("Plan lunch in Paris", &weather_registry())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            run.response.text(),
+            "Sunny at noon, but I could not book a table."
+        );
+        assert_eq!(run.rounds.len(), 2);
+        assert_eq!(run.rounds[0].calls.len(), 2);
+        assert_eq!(
+            run.rounds[0].responses[0].response,
+            json!({ "forecast": "Sunny in Paris" })
+        );
+        assert_eq!(
+            run.rounds[0].responses[1].response,
+            json!({ "result": "12:00" })
+        );
+        assert_eq!(
+            run.rounds[1].responses[0].response,
+            json!({ "error": "Unknown function `book_table`" })
+        );
+        assert_eq!(
+            run.rounds[1].responses[1].response,
+            json!({ "error": "missing city" })
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let last = requests[2].json();
+        let declarations = &last["tools"][0]["function_declarations"];
+        assert_eq!(declarations[0]["name"], "get_weather");
+        assert_eq!(declarations[1]["name"], "get_time");
+        let contents = last["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 5);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[2]["role"], "function");
+        assert_eq!(
+            contents[2]["parts"][1]["functionResponse"],
+            json!({ "name": "get_time", "response": { "result": "12:00" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_with_tools_rejects_invalid_calls() {
+        let server = MockServer::start(|request| {
+            let contents = request.json()["contents"].as_array().unwrap().len();
+            let body = match contents {
+                1 => function_call_response(json!([
+                    { "name": "get_weather", "args": { "town": "Paris" } }
+                ])),
+                3 => function_call_response(json!([
+                    { "name": "get_weather", "args": { "city": "Paris" } }
+                ])),
+                _ => text_response("Sunny in Paris."),
+            };
+            MockResponse::json(200, body)
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+        let registry = weather_registry().with_call_validation(true);
+
+        let run = model
+            .generate_with_tools("Weather in Paris?", &registry)
+            .await
+            .unwrap();
+
+        assert_eq!(run.response.text(), "Sunny in Paris.");
+        assert_eq!(
+            run.rounds[0].responses[0].response,
+            json!({
+                "error": "Invalid arguments",
+                "violations": [
+                    "#: missing required property `city`",
+                    "#: unknown property `town`"
+                ]
+            })
+        );
+        assert_eq!(
+            run.rounds[1].responses[0].response,
+            json!({ "forecast": "Sunny in Paris" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_with_tools_stops_at_round_limit() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                200,
+                function_call_response(json!([{ "name": "get_time", "args": {} }])),
+            )
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let run = model
+            .generate_with_tools("What time is it?", &weather_registry().with_max_rounds(1))
+            .await
+            .unwrap();
+
+        assert_eq!(run.rounds.len(), 1);
+        assert_eq!(run.response.function_calls().len(), 1);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_is_rejected_before_sending() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("hello"))).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let error = model
+            .generate_response(Request::from_contents(vec![]))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            GoogleGenerativeAIError::InvalidRequest(RequestValidationError::EmptyContents)
+        ));
+        assert!(server.requests().is_empty());
+
+        let model = model.with_request_validation(false);
+        model
+            .generate_response(Request::from_contents(vec![]))
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_classify_returns_chosen_label() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, text_response("negative\n"))).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let label = model
+            .classify("The food was cold.", &["positive", "neutral", "negative"])
+            .await
+            .unwrap();
+        assert_eq!(label, "negative");
+
+        let config = &server.requests()[0].json()["generation_config"];
+        assert_eq!(config["response_mime_type"], "text/x.enum");
+        assert_eq!(config["response_schema"]["type"], "string");
+        assert_eq!(
+            config["response_schema"]["enum"],
+            json!(["positive", "neutral", "negative"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_classify_rejects_unknown_label() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("mixed"))).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let error = model
+            .classify("The food was cold.", &["positive", "negative"])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("\"mixed\""));
+    }
+
+    fn response_with_unknown_field() -> serde_json::Value {
+        let mut body = text_response("hello");
+        body["experimentalField"] = json!({ "answer": 42 });
+        body
+    }
+
+    #[tokio::test]
+    async fn test_generate_response_raw() {
+        let server =
+            MockServer::start(|_| MockResponse::json(200, response_with_unknown_field())).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let (response, raw) = model
+            .generate_response_raw(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.text(), "hello");
+        assert!(response.raw.is_none());
+        assert_eq!(raw["experimentalField"]["answer"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_keep_raw_on_responses_and_stream_chunks() {
+        let server = MockServer::start(|request| {
+            if request.path().ends_with(":streamGenerateContent") {
+                let chunks = json!([response_with_unknown_field(), response_with_unknown_field()]);
+                MockResponse::json(200, chunks)
+            } else {
+                MockResponse::json(200, response_with_unknown_field())
+            }
+        })
+        .await;
+        let params = ModelParams::builder()
+            .keep_raw(true)
+            .streaming_format(StreamingFormat::JsonArray)
+            .build();
+        let model = GenerativeModel::new("key", params).with_base_url(server.url());
+
+        let response = model.send_message("hi").await.unwrap();
+        assert_eq!(response.raw.unwrap()["experimentalField"]["answer"], 42);
+
+        let stream = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+        assert_eq!(chunks.len(), 2);
+        for chunk in chunks {
+            assert_eq!(
+                chunk.unwrap().raw.unwrap()["experimentalField"]["answer"],
+                42
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_with_frames_split_across_chunks() {
+        let first = format!("data: {}\r\n\r\n", text_response("Hello"));
+        let (head, tail) = first.split_at(first.len() / 2);
+        let chunks = [
+            b": keep-alive\n\n".to_vec(),
+            head.as_bytes().to_vec(),
+            tail.as_bytes().to_vec(),
+            format!("data: {}\n\n", text_response(" world")).into_bytes(),
+        ];
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(
+                chunks
+                    .iter()
+                    .map(|chunk| (Duration::from_millis(5), chunk.clone()))
+                    .collect(),
+            )
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let stream = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+        let texts: Vec<String> = stream.map(|chunk| chunk.unwrap().text()).collect().await;
+
+        assert_eq!(texts, ["Hello", " world"]);
+        let request = &server.requests()[0];
+        assert!(request.path().ends_with(":streamGenerateContent"));
+        assert_eq!(request.query("alt").as_deref(), Some("sse"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_timeout() {
+        let chunk = format!("data: {}\n\n", text_response("Hello")).into_bytes();
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(vec![(Duration::ZERO, chunk.clone())]).hang()
+        })
+        .await;
+        let params = ModelParams::builder()
+            .stream_idle_timeout(Duration::from_millis(100))
+            .build();
+        let model = GenerativeModel::new("key", params).with_base_url(server.url());
+
+        let mut stream = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+
+        let items = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut items = Vec::new();
+            while let Some(item) = stream.next().await {
+                items.push(item);
+            }
+            items
+        })
+        .await
+        .expect("stream did not time out");
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap().text(), "Hello");
+        assert!(items[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("No stream chunk"));
+    }
+
+    #[tokio::test]
+    async fn test_dropping_stream_closes_connection() {
+        // Keep-alives produce no chunks, so without the abort the task would keep
+        // reading them without ever noticing that the receiver is gone.
+        let mut chunks = vec![(Duration::from_millis(50), b": ping\n\n".to_vec()); 100];
+        chunks.insert(
+            0,
+            (
+                Duration::ZERO,
+                format!("data: {}\n\n", text_response("tick")).into_bytes(),
+            ),
+        );
+        let server = MockServer::start(move |_| MockResponse::chunked(chunks.clone()).hang()).await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let mut stream = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while server.disconnects() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection was not closed after the stream was dropped");
+    }
+
+    #[tokio::test]
+    async fn test_stream_setup_failure_returns_api_error() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                400,
+                json!({ "error": { "code": 400, "message": "Bad prompt", "status": "INVALID_ARGUMENT" } }),
+            )
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let error = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.api_error().unwrap().status, 400);
+        assert_eq!(error.stream_error_kind(), None);
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_error_kinds() {
+        let body = format!(
+            "data: {}\n\ndata: {{not json\n\ndata: {}\n\n",
+            text_response("Hello"),
+            json!({ "error": { "code": 503, "message": "Overloaded", "status": "UNAVAILABLE" } })
+        );
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(vec![(Duration::ZERO, body.clone().into_bytes())])
+        })
+        .await;
+        let model = GenerativeModel::new("key", ModelParams::default()).with_base_url(server.url());
+
+        let stream = model
+            .stream_generate_response(Request::with_prompt("hi"))
+            .await
+            .unwrap();
+        let items: Vec<_> = stream.collect().await;
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap().text(), "Hello");
+        let protocol = items[1].as_ref().unwrap_err();
+        assert_eq!(
+            protocol.stream_error_kind(),
+            Some(StreamErrorKind::Protocol)
+        );
+        let server_error = items[2].as_ref().unwrap_err();
+        assert_eq!(
+            server_error.stream_error_kind(),
+            Some(StreamErrorKind::Server)
+        );
+        assert!(server_error.to_string().contains("Overloaded"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_send_message_with_system_request_body() {
+        let body = format!("data: {}\n\n", text_response("Arr"));
+        let server = MockServer::start(move |_| {
+            MockResponse::chunked(vec![(Duration::ZERO, body.clone().into_bytes())])
+        })
+        .await;
+        let params = ModelParams::builder()
+            .generation_config(GenerationConfig::builder().temperature(0.5).build())
+            .build();
+        let model = GenerativeModel::new("key", params).with_base_url(server.url());
+
+        let stream = model
+            .stream_send_message_with_system("Answer like a pirate", "Hello")
+            .await
+            .unwrap();
+        assert_eq!(stream.collect_text().await.unwrap(), "Arr");
+
+        let body = server.requests()[0].json();
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "Hello");
+        assert_eq!(body["contents"][0]["role"], "user");
+        assert_eq!(
+            body["system_instruction"]["parts"][0]["text"],
+            "Answer like a pirate"
+        );
+        assert_eq!(body["generation_config"]["temperature"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_model_seed_applies_to_every_request() {
+        let server = MockServer::start(|request| {
+            if request.path().ends_with(":countTokens") {
+                MockResponse::json(200, json!({ "totalTokens": 3 }))
+            } else {
+                MockResponse::json(200, text_response("ok"))
+            }
+        })
+        .await;
+        let params = ModelParams::builder()
+            .generation_config(GenerationConfig::builder().seed(42_i64).build())
+            .build();
+        let model = GenerativeModel::new("key", params).with_base_url(server.url());
+
+        model.generate_response("Hello").await.unwrap();
+        model
+            .generate_response(
+                Request::builder()
+                    .contents(vec![Content::user("Hello")])
+                    .generation_config(GenerationConfig::builder().temperature(0.9).build())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        model
+            .generate_response(
+                Request::builder()
+                    .contents(vec![Content::user("Hello")])
+                    .generation_config(GenerationConfig::builder().seed(7_i64).build())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let tokens = model.count_tokens("Hello").await.unwrap();
+        assert_eq!(tokens.total_tokens, 3);
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].json()["generation_config"],
+            json!({ "seed": 42 })
+        );
+        assert_eq!(
+            requests[1].json()["generation_config"],
+            json!({ "temperature": 0.9, "seed": 42 })
+        );
+        assert_eq!(requests[2].json()["generation_config"]["seed"], 7);
+        assert_eq!(
+            requests[3].json()["generateContentRequest"]["generation_config"],
+            json!({ "seed": 42 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_media_resolution_applies_to_every_request() {
+        let server = MockServer::start(|_| MockResponse::json(200, text_response("ok"))).await;
+        let params = ModelParams::builder()
+            .generation_config(
+                GenerationConfig::builder()
+                    .media_resolution(MediaResolution::Low)
+                    .build(),
+            )
+            .build();
+        let model = GenerativeModel::new("key", params).with_base_url(server.url());
+
+        for resolution in [None, Some(MediaResolution::High)] {
+            let mut config = GenerationConfig::builder().temperature(0.5).build();
+            config.media_resolution = resolution;
+            model
+                .generate_response(
+                    Request::builder()
+                        .contents(vec![Content::user("Describe the image")])
+                        .generation_config(config)
+                        .build(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].json()["generation_config"]["media_resolution"],
+            "MEDIA_RESOLUTION_LOW"
+        );
+        assert_eq!(
+            requests[1].json()["generation_config"]["media_resolution"],
+            "MEDIA_RESOLUTION_HIGH"
+        );
+    }
+
+    #[test]
+    fn test_parse_response_without_raw() {
+        let body = response_with_unknown_field().to_string();
+        let response = parse_response(body.as_bytes(), false).unwrap();
+        assert!(response.raw.is_none());
+        assert_eq!(response.text(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_gemini_clients_share_the_injected_client() {
+        let server = MockServer::start(|request| {
+            if request.path().ends_with("/models") {
+                MockResponse::json(200, json!({ "models": [] }))
+            } else {
+                MockResponse::json(200, json!({}))
+            }
+        })
+        .await;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-shared-client", "yes".parse().unwrap());
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let clients =
+            GeminiClients::with_key_pool_and_client(KeyPool::from("key".to_string()), client)
+                .with_base_url(server.url());
+
+        clients
+            .generative_model(ModelParams::default())
+            .list_models()
+            .await
+            .unwrap();
+        clients.file_manager().list_files().await.unwrap();
+        clients.cache_manager().list_caches().await.unwrap();
+
+        let requests = server.requests();
+        let paths: Vec<_> = requests.iter().map(|request| request.path()).collect();
+        assert_eq!(
+            paths,
+            ["/v1beta/models", "/v1beta/files", "/v1beta/cachedContents"]
+        );
+        assert!(requests
+            .iter()
+            .all(|request| request.header("x-shared-client") == Some("yes")));
+    }
+}
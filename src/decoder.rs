@@ -0,0 +1,270 @@
+//! Framing of streamed response bodies into individual JSON documents.
+
+/// Default buffer capacity for JSON parsing
+const DEFAULT_JSON_BUFFER_CAPACITY: usize = 4096;
+
+/// Splits a streamed body into the JSON documents of the individual chunks.
+pub(crate) trait FrameDecoder: Send {
+    /// Feeds the next piece of the body and returns the frames it completed.
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Result<Vec<u8>, String>>;
+
+    /// Signals the end of the body and returns any frame that is still buffered.
+    fn finish(&mut self) -> Vec<Result<Vec<u8>, String>>;
+}
+
+/// Decodes a `text/event-stream` body as sent with `alt=sse`.
+///
+/// Every event carries one JSON document in its `data:` lines. Comments (lines
+/// starting with `:`, used as keep-alives) and other fields are ignored.
+#[derive(Debug, Default)]
+pub(crate) struct SseDecoder {
+    line: Vec<u8>,
+    data: Vec<u8>,
+    has_data: bool,
+}
+
+impl SseDecoder {
+    fn process_line(&mut self, frames: &mut Vec<Result<Vec<u8>, String>>) {
+        let mut line = std::mem::take(&mut self.line);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        if line.is_empty() {
+            self.dispatch(frames);
+        } else if line.starts_with(b":") {
+            // Comment, e.g. a keep-alive.
+        } else if let Some(value) = line.strip_prefix(b"data:") {
+            let value = value.strip_prefix(b" ").unwrap_or(value);
+            if self.has_data {
+                self.data.push(b'\n');
+            }
+            self.data.extend_from_slice(value);
+            self.has_data = true;
+        }
+    }
+
+    fn dispatch(&mut self, frames: &mut Vec<Result<Vec<u8>, String>>) {
+        if self.has_data {
+            frames.push(Ok(std::mem::take(&mut self.data)));
+            self.has_data = false;
+        }
+    }
+}
+
+impl FrameDecoder for SseDecoder {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Result<Vec<u8>, String>> {
+        let mut frames = Vec::new();
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.process_line(&mut frames);
+            } else {
+                self.line.push(byte);
+            }
+        }
+        frames
+    }
+
+    fn finish(&mut self) -> Vec<Result<Vec<u8>, String>> {
+        let mut frames = Vec::new();
+        if !self.line.is_empty() {
+            self.process_line(&mut frames);
+        }
+        self.dispatch(&mut frames);
+        frames
+    }
+}
+
+/// Decodes the default `streamGenerateContent` body, a JSON array of responses.
+///
+/// The decoder works on raw bytes and tracks only string, escape and nesting state,
+/// so objects, strings and multi-byte characters may be split anywhere across the
+/// network chunks. Every top-level element is handed out once its closing brace
+/// arrives.
+#[derive(Debug)]
+pub(crate) struct JsonArrayDecoder {
+    buffer: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Default for JsonArrayDecoder {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::with_capacity(DEFAULT_JSON_BUFFER_CAPACITY),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+}
+
+impl FrameDecoder for JsonArrayDecoder {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Result<Vec<u8>, String>> {
+        let mut frames = Vec::new();
+        for &byte in bytes {
+            if self.depth == 0 {
+                // Between elements only the opening brace of the next one matters;
+                // the array brackets, commas and whitespace are skipped.
+                if byte == b'{' {
+                    self.buffer.push(byte);
+                    self.depth = 1;
+                }
+                continue;
+            }
+
+            self.buffer.push(byte);
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        frames.push(Ok(std::mem::take(&mut self.buffer)));
+                        self.buffer.reserve(DEFAULT_JSON_BUFFER_CAPACITY);
+                    }
+                }
+                _ => {}
+            }
+        }
+        frames
+    }
+
+    fn finish(&mut self) -> Vec<Result<Vec<u8>, String>> {
+        if self.depth == 0 {
+            return Vec::new();
+        }
+
+        self.buffer.clear();
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+        vec![Err(
+            "Stream ended in the middle of a JSON object".to_string()
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(decoder: &mut dyn FrameDecoder, pieces: &[&[u8]]) -> Vec<String> {
+        let mut frames: Vec<_> = pieces.iter().flat_map(|p| decoder.feed(p)).collect();
+        frames.extend(decoder.finish());
+        frames
+            .into_iter()
+            .map(|frame| String::from_utf8(frame.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sse_frames() {
+        let body = b"data: {\"a\":1}\r\n\r\ndata: {\"a\":2}\r\n\r\n";
+        let frames = feed_all(&mut SseDecoder::default(), &[body]);
+        assert_eq!(frames, [r#"{"a":1}"#, r#"{"a":2}"#]);
+    }
+
+    #[test]
+    fn test_sse_frames_split_across_chunks() {
+        let pieces: &[&[u8]] = &[
+            b"da",
+            b"ta: {\"text\":",
+            b"\"hel",
+            b"lo\"}\r",
+            b"\n\r\nda",
+            b"ta: {}\n\n",
+        ];
+        let frames = feed_all(&mut SseDecoder::default(), pieces);
+        assert_eq!(frames, [r#"{"text":"hello"}"#, "{}"]);
+    }
+
+    #[test]
+    fn test_sse_ignores_comments_and_other_fields() {
+        let body = b": keep-alive\n\nevent: message\nid: 7\ndata: {\"a\":1}\n\n: ping\n\n";
+        let frames = feed_all(&mut SseDecoder::default(), &[body]);
+        assert_eq!(frames, [r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn test_sse_multiline_data() {
+        let body = b"data: {\"a\":\ndata: 1}\n\n";
+        let frames = feed_all(&mut SseDecoder::default(), &[body]);
+        assert_eq!(frames, ["{\"a\":\n1}"]);
+    }
+
+    #[test]
+    fn test_sse_final_event_without_blank_line() {
+        let frames = feed_all(&mut SseDecoder::default(), &[b"data: {\"a\":1}"]);
+        assert_eq!(frames, [r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn test_json_array_frames() {
+        let body = br#"[{"a":1}
+,
+{"b":{"c":2}}]"#;
+        let frames = feed_all(&mut JsonArrayDecoder::default(), &[body]);
+        assert_eq!(frames, [r#"{"a":1}"#, r#"{"b":{"c":2}}"#]);
+    }
+
+    #[test]
+    fn test_json_array_escaped_backslash_before_quote() {
+        let body = br#"[{"text":"C:\\","next":"{"},{"text":"\\\"}"}]"#;
+        let frames = feed_all(&mut JsonArrayDecoder::default(), &[body]);
+        assert_eq!(
+            frames,
+            [r#"{"text":"C:\\","next":"{"}"#, r#"{"text":"\\\"}"}"#]
+        );
+        for frame in frames {
+            serde_json::from_str::<serde_json::Value>(&frame).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_json_array_braces_and_brackets_inside_strings() {
+        let body = br#"[{"text":"} ] { [","list":[{"a":"]"}]}]"#;
+        let frames = feed_all(&mut JsonArrayDecoder::default(), &[body]);
+        assert_eq!(frames, [r#"{"text":"} ] { [","list":[{"a":"]"}]}"#]);
+    }
+
+    #[test]
+    fn test_json_array_split_at_every_byte() {
+        let body = r#"[{"text":"h\"é\\llo 👋"},{"text":"}"}]"#.as_bytes();
+        let pieces: Vec<&[u8]> = body.chunks(1).collect();
+        let frames = feed_all(&mut JsonArrayDecoder::default(), &pieces);
+        assert_eq!(frames, [r#"{"text":"h\"é\\llo 👋"}"#, r#"{"text":"}"}"#]);
+    }
+
+    #[test]
+    fn test_json_array_multibyte_split_across_chunks() {
+        let body = "[{\"text\":\"日本\"}]".as_bytes();
+        let split = body.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        let frames = feed_all(
+            &mut JsonArrayDecoder::default(),
+            &[&body[..split], &body[split..]],
+        );
+        assert_eq!(frames, ["{\"text\":\"日本\"}"]);
+    }
+
+    #[test]
+    fn test_json_array_truncated_object_is_an_error() {
+        let mut decoder = JsonArrayDecoder::default();
+        assert!(decoder.feed(br#"[{"text":"unfinished"#).is_empty());
+        let frames = decoder.finish();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_err());
+    }
+}
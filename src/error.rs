@@ -1,7 +1,15 @@
 //! Error types for the Gemini AI client.
 
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::Deserialize;
 use thiserror::Error;
 
+use crate::models::{
+    parse_duration, FinishReason, RequestValidationError, Response, SchemaViolation,
+};
+
 /// Errors that can occur when using the Gemini AI client.
 #[derive(Debug, Error)]
 pub enum GoogleGenerativeAIError {
@@ -16,6 +24,14 @@ pub enum GoogleGenerativeAIError {
     #[error("API request failed: {0}")]
     RequestError(#[from] reqwest::Error),
 
+    /// The API answered with a non-success status.
+    #[error("{0}")]
+    Api(#[from] ApiError),
+
+    /// The request was rejected locally before it was sent.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(#[from] RequestValidationError),
+
     /// Error occurred when accessing environment variables.
     #[error("Environment variable not found: {0}")]
     EnvError(#[from] std::env::VarError),
@@ -23,6 +39,43 @@ pub enum GoogleGenerativeAIError {
     /// Error occurred when parsing JSON.
     #[error("JSON parsing error: {0}")]
     JsonError(#[from] serde_json::Error),
+
+    /// The model stopped without a usable reply, e.g. because it was blocked for
+    /// safety reasons or cut off at the token limit.
+    #[error("Generation stopped with finish reason {reason:?}")]
+    GenerationStopped {
+        /// Why the model stopped.
+        reason: FinishReason,
+        /// The finish message of the candidate, if any.
+        message: Option<String>,
+        /// The full response, e.g. to read the safety ratings or the truncated text.
+        response: Box<Response>,
+    },
+
+    /// A structured reply does not conform to the response schema.
+    #[error(
+        "Structured reply violates the schema: {}; raw reply: {raw}",
+        violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    SchemaViolations {
+        /// Every way in which the reply does not conform.
+        violations: Vec<SchemaViolation>,
+        /// The raw text of the reply.
+        raw: String,
+    },
+
+    /// Error occurred after a response stream was established.
+    #[error("Stream {kind} error: {message}")]
+    Stream {
+        /// What went wrong, which tells whether restarting the stream may help.
+        kind: StreamErrorKind,
+        /// Error message
+        message: String,
+    },
+
+    /// Error occurred when reading or writing local data.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl GoogleGenerativeAIError {
@@ -32,4 +85,253 @@ impl GoogleGenerativeAIError {
             message: message.into(),
         }
     }
+
+    /// Creates a new Stream error of the given kind.
+    pub(crate) fn stream(kind: StreamErrorKind, message: impl Into<String>) -> Self {
+        Self::Stream {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the kind of a mid-stream error.
+    ///
+    /// Errors while setting up a stream are reported like any other request error,
+    /// typically as [`Api`](Self::Api), and return `None` here.
+    pub fn stream_error_kind(&self) -> Option<StreamErrorKind> {
+        match self {
+            Self::Stream { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// Returns the structured API error, if this error was returned by the API.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::Api(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of an error that occurred in the middle of a response stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamErrorKind {
+    /// A chunk could not be decoded, e.g. malformed JSON or a truncated object. The
+    /// stream is corrupt and retrying it is unlikely to help.
+    Protocol,
+    /// The server reported an error inside the stream.
+    Server,
+    /// The connection failed or stalled before the stream was complete.
+    Transport,
+}
+
+impl std::fmt::Display for StreamErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Protocol => write!(f, "protocol"),
+            Self::Server => write!(f, "server"),
+            Self::Transport => write!(f, "transport"),
+        }
+    }
+}
+
+/// A structured error returned by the Gemini API.
+///
+/// The API reports errors as `{"error": {"code", "message", "status", "details"}}`.
+/// Bodies that do not follow this shape are kept verbatim in `message`.
+#[derive(Debug, Clone, Error)]
+#[error("Request failed with status {status}: {message}")]
+pub struct ApiError {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The canonical error status, e.g. `RESOURCE_EXHAUSTED`.
+    pub code: Option<String>,
+    /// The error message.
+    pub message: String,
+    /// The error details, e.g. `google.rpc.RetryInfo` or `google.rpc.ErrorInfo`.
+    pub details: Vec<serde_json::Value>,
+    /// How long the server asked the client to wait before retrying, taken from the
+    /// `Retry-After` header or a `google.rpc.RetryInfo` detail.
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: Option<u16>,
+    #[serde(default)]
+    message: String,
+    status: Option<String>,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+impl ApiError {
+    /// Reads the body of a failed response and parses it into an `ApiError`.
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        Self::from_parts(status, &headers, &body)
+    }
+
+    /// Builds an `ApiError` from the status, headers and body of a response.
+    pub(crate) fn from_parts(status: u16, headers: &HeaderMap, body: &str) -> Self {
+        let parsed = serde_json::from_str::<ErrorEnvelope>(body).ok();
+        let (message, code, details) = match parsed {
+            Some(ErrorEnvelope { error }) => (error.message, error.status, error.details),
+            None => (body.to_string(), None, Vec::new()),
+        };
+
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after)
+            .or_else(|| retry_info_delay(&details));
+
+        Self {
+            status,
+            code,
+            message,
+            details,
+            retry_after,
+        }
+    }
+
+    /// Parses an error object the server sent as a chunk of a stream.
+    ///
+    /// Returns `None` if the chunk is not an error.
+    pub(crate) fn from_stream_chunk(chunk: &[u8]) -> Option<Self> {
+        let ErrorEnvelope { error } = serde_json::from_slice(chunk).ok()?;
+        let retry_after = retry_info_delay(&error.details);
+        Some(Self {
+            status: error.code.unwrap_or(500),
+            code: error.status,
+            message: error.message,
+            details: error.details,
+            retry_after,
+        })
+    }
+
+    /// Returns `true` for statuses that are worth retrying (429 and 5xx).
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.status, 429 | 500 | 502 | 503 | 504)
+    }
+}
+
+/// Parses a `Retry-After` header, either delay-seconds or an HTTP-date, into the
+/// delay from now. A date in the past means no delay.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Extracts the `retryDelay` of a `google.rpc.RetryInfo` detail.
+fn retry_info_delay(details: &[serde_json::Value]) -> Option<Duration> {
+    details
+        .iter()
+        .filter(|detail| {
+            detail["@type"]
+                .as_str()
+                .is_some_and(|t| t.ends_with("google.rpc.RetryInfo"))
+        })
+        .find_map(|detail| detail["retryDelay"].as_str().and_then(parse_duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE_LIMITED_BODY: &str = r#"{
+        "error": {
+            "code": 429,
+            "message": "Resource has been exhausted (e.g. check quota).",
+            "status": "RESOURCE_EXHAUSTED",
+            "details": [
+                {
+                    "@type": "type.googleapis.com/google.rpc.QuotaFailure",
+                    "violations": []
+                },
+                {
+                    "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                    "retryDelay": "34s"
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_retry_info_detail() {
+        let error = ApiError::from_parts(429, &HeaderMap::new(), RATE_LIMITED_BODY);
+
+        assert_eq!(error.status, 429);
+        assert_eq!(error.code.as_deref(), Some("RESOURCE_EXHAUSTED"));
+        assert_eq!(
+            error.message,
+            "Resource has been exhausted (e.g. check quota)."
+        );
+        assert_eq!(error.details.len(), 2);
+        assert_eq!(error.retry_after, Some(Duration::from_secs(34)));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after_header_takes_precedence() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        let error = ApiError::from_parts(429, &headers, RATE_LIMITED_BODY);
+
+        assert_eq!(error.retry_after, Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn test_retry_after_header_forms() {
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("1e300"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(60))
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        let delay = parse_retry_after(&date).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_non_json_body_is_kept_verbatim() {
+        let error = ApiError::from_parts(502, &HeaderMap::new(), "Bad Gateway");
+
+        assert_eq!(error.message, "Bad Gateway");
+        assert_eq!(error.code, None);
+        assert_eq!(error.retry_after, None);
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_client_errors_are_not_retryable() {
+        let body =
+            r#"{"error": {"code": 400, "message": "Invalid", "status": "INVALID_ARGUMENT"}}"#;
+        let error = ApiError::from_parts(400, &HeaderMap::new(), body);
+
+        assert_eq!(error.code.as_deref(), Some("INVALID_ARGUMENT"));
+        assert!(!error.is_retryable());
+    }
 }
@@ -1,18 +1,44 @@
 //! File models for the Gemini AI API.
 
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt, TryStreamExt};
 use mime_guess;
 use reqwest;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
+use std::future::Future;
 use std::io;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use typed_builder::TypedBuilder;
+
+use crate::error::ApiError;
+use crate::key_pool::{KeyPool, PooledKey};
+use crate::models::duration_string;
+use crate::retry::RetryPolicy;
+
+mod upload_request;
+
+use upload_request::UploadSource;
+pub use upload_request::{UploadRequest, UploadRequestBuilder};
 
 const FILE_API_VERSION: &str = "v1beta";
 const FILE_API_URL: &str = "https://generativelanguage.googleapis.com";
 
+/// How many files [`GoogleAIFileManager::delete_files_older_than`] deletes at once.
+const DELETE_CONCURRENCY: usize = 8;
+/// Default size of the chunks of a resumable upload (8 MiB).
+const DEFAULT_UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Every chunk of an upload but the last must be a multiple of this size.
+const UPLOAD_CHUNK_GRANULARITY: usize = 256 * 1024;
+
 /// Represents possible errors that can occur during file operations.
 #[derive(Error, Debug)]
 pub enum FileError {
@@ -31,12 +57,83 @@ pub enum FileError {
     /// Error occurred during file upload process.
     #[error("Upload failed: {0}")]
     UploadError(String),
+    /// The API rejected the request starting an upload.
+    #[error("Upload initiation failed: {0}")]
+    InitiationFailed(ApiError),
+    /// The API rejected the content of an upload.
+    #[error("Upload of the file content failed: {0}")]
+    ContentUploadFailed(ApiError),
+    /// The API answered an upload request with a response that could not be understood.
+    #[error("Unexpected upload response: {body}")]
+    UnexpectedResponse {
+        /// The body of the response.
+        body: String,
+    },
     /// Invalid file ID provided.
     #[error("Invalid file ID: {0}")]
     InvalidFileId(String),
     /// Error occurred during file processing.
     #[error("File processing error: {0}")]
     ProcessingError(String),
+    /// The file does not exist, e.g. because it was deleted or has expired.
+    #[error("File not found: {0}")]
+    NotFound(String),
+    /// The API rejected a request.
+    #[error("API request failed: {0}")]
+    Api(ApiError),
+    /// The file was still not processed when waiting for it gave up.
+    #[error("Timed out waiting for file {name} to process; its state is {last_state}")]
+    Timeout {
+        /// The name of the file.
+        name: String,
+        /// The state of the file at the last poll.
+        last_state: FileState,
+    },
+    /// The GOOGLE_API_KEY environment variable is not set.
+    #[error("The GOOGLE_API_KEY environment variable is not set")]
+    MissingApiKey,
+    /// An [`UploadRequest`] could not be built.
+    #[error("Invalid upload request: {0}")]
+    InvalidUploadRequest(String),
+    /// The hash of the uploaded content reported by the server differs from the hash
+    /// of the local content.
+    #[error("Checksum mismatch: the server reported SHA-256 {expected}, the uploaded content has {actual}")]
+    ChecksumMismatch {
+        /// The hex-encoded SHA-256 hash reported by the server.
+        expected: String,
+        /// The hex-encoded SHA-256 hash of the local content.
+        actual: String,
+    },
+    /// The file can not be used in a request because it is not active, e.g. because it
+    /// is still processing.
+    #[error("File {name} is not active; its state is {state}")]
+    NotActive {
+        /// The name of the file.
+        name: String,
+        /// The state of the file.
+        state: FileState,
+    },
+}
+
+impl FileError {
+    /// Returns the structured API error, if this error was returned by the API.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::Api(error) | Self::InitiationFailed(error) | Self::ContentUploadFailed(error) => {
+                Some(error)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the failure is transient, i.e. the request may succeed when
+    /// it is sent again: connection failures and timeouts, 429 and 5xx responses.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RequestError(e) => !e.is_decode() && !e.is_builder() && !e.is_status(),
+            _ => self.api_error().is_some_and(ApiError::is_retryable),
+        }
+    }
 }
 
 /// Information about a file stored in the Gemini AI system.
@@ -50,15 +147,16 @@ pub struct FileInfo {
     pub display_name: Option<String>,
     /// MIME type of the file content.
     pub mime_type: String,
-    /// Size of the file in bytes as a string.
-    pub size_bytes: String,
+    /// Size of the file in bytes, sent by the API as a quoted integer.
+    #[serde(with = "int64_string")]
+    pub size_bytes: u64,
     /// Time when the file was created.
-    pub create_time: String,
+    pub create_time: DateTime<Utc>,
     /// Time when the file was last updated.
-    pub update_time: String,
+    pub update_time: DateTime<Utc>,
     /// Optional expiration time for the file.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub expiration_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_time: Option<DateTime<Utc>>,
     /// Optional SHA256 hash of the file content.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sha256_hash: Option<String>,
@@ -66,19 +164,98 @@ pub struct FileInfo {
     pub uri: String,
     /// Current processing state of the file.
     pub state: FileState,
-    /// Optional error message if the file processing failed.
+    /// Why processing the file failed, if it did.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub error: Option<FileErrorStatus>,
     /// Optional metadata for video files.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub video_metadata: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_metadata: Option<VideoFileMetadata>,
     /// Optional description of the file.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 }
 
+impl FileInfo {
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size_bytes
+    }
+
+    /// Returns the duration of a video file, or `None` for other files.
+    pub fn video_duration(&self) -> Option<Duration> {
+        self.video_metadata
+            .as_ref()
+            .map(|metadata| metadata.video_duration)
+    }
+
+    /// Returns how long the file is kept until it expires, or `None` if it does not
+    /// expire. Returns [`Duration::ZERO`] for expired files.
+    pub fn expires_in(&self) -> Option<Duration> {
+        let remaining = self.expiration_time? - Utc::now();
+        Some(remaining.to_std().unwrap_or(Duration::ZERO))
+    }
+
+    /// Returns the SHA-256 hash of the file reported by the server, or `None` if it
+    /// is missing or malformed.
+    ///
+    /// The API reports the hash base64 encoded, either as the raw digest or as its
+    /// hex encoding; both are accepted, as is a plain hex string.
+    pub fn sha256_bytes(&self) -> Option<[u8; 32]> {
+        decode_sha256(self.sha256_hash.as_deref()?)
+    }
+}
+
+/// Metadata of a video file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoFileMetadata {
+    /// The duration of the video.
+    #[serde(with = "duration_string")]
+    pub video_duration: Duration,
+    /// Fields of the metadata this version of the crate does not know yet.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// (De)serializes an `int64`, which the API encodes as a string.
+mod int64_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Int64 {
+            Number(u64),
+            String(String),
+        }
+
+        match Int64::deserialize(deserializer)? {
+            Int64::Number(value) => Ok(value),
+            Int64::String(value) => value.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+/// The error status of a file whose processing failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileErrorStatus {
+    /// The status code of the error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    /// The error message.
+    #[serde(default)]
+    pub message: String,
+    /// Further details of the error.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<serde_json::Value>,
+}
+
 /// Represents the processing state of a file in the system.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum FileState {
     /// State is not specified.
@@ -103,12 +280,97 @@ impl std::fmt::Display for FileState {
     }
 }
 
+/// The stage an upload has reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadPhase {
+    /// The upload session is being started.
+    Initiating,
+    /// A chunk was committed; chunks are numbered from 1.
+    Uploading {
+        /// The number of the committed chunk.
+        chunk: usize,
+    },
+    /// The last chunk was committed and the file was created.
+    Finalizing,
+    /// The file was created but the API is still processing it, e.g. a video.
+    Processing,
+}
+
+/// Progress of an upload, reported to the callback of the `*_with_progress` upload methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// The number of bytes the API has committed so far.
+    pub bytes_sent: u64,
+    /// The size of the upload in bytes.
+    pub total_bytes: u64,
+    /// The stage the upload has reached.
+    pub phase: UploadPhase,
+}
+
+/// The outcome of deleting many files at once.
+#[derive(Debug, Default)]
+pub struct DeleteSummary {
+    /// The number of files deleted.
+    pub deleted: usize,
+    /// The number of files that were already gone, e.g. because they expired.
+    pub missing: usize,
+    /// The files that could not be deleted, with the reason.
+    pub failures: Vec<(String, FileError)>,
+}
+
+impl DeleteSummary {
+    /// Returns the number of files that could not be deleted.
+    pub fn failed(&self) -> usize {
+        self.failures.len()
+    }
+}
+
+/// How [`GoogleAIFileManager::wait_for_file_processing_with`] polls a file.
+///
+/// The delay between two polls starts at `initial_delay` and grows by `multiplier`
+/// after every poll, up to `max_delay`.
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct WaitOptions {
+    /// The delay after the first poll, one second by default.
+    #[builder(default = Duration::from_secs(1))]
+    pub initial_delay: Duration,
+    /// The longest delay between two polls, 30 seconds by default.
+    #[builder(default = Duration::from_secs(30))]
+    pub max_delay: Duration,
+    /// The factor by which the delay grows after every poll, 2 by default.
+    #[builder(default = 2.0)]
+    pub multiplier: f64,
+    /// How long to wait in total, 10 minutes by default; `None` waits indefinitely.
+    #[builder(default = Some(Duration::from_secs(600)), setter(strip_option))]
+    pub deadline: Option<Duration>,
+    /// The maximum number of polls, unlimited by default.
+    #[builder(default, setter(strip_option))]
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Metadata and options of an upload that do not depend on its source.
+struct UploadOptions {
+    display_name: Option<String>,
+    description: Option<String>,
+    verify_checksum: bool,
+}
+
 /// Manager for handling file operations with the Gemini AI API.
 #[derive(Debug)]
 pub struct GoogleAIFileManager {
     client: reqwest::Client,
-    api_key: String,
+    keys: KeyPool,
     base_url: String,
+    chunk_size: usize,
+    retry_policy: Option<RetryPolicy>,
+    verify_checksum: bool,
 }
 
 impl GoogleAIFileManager {
@@ -131,36 +393,113 @@ impl GoogleAIFileManager {
     /// let file_manager = GoogleAIFileManager::new(api_key);
     /// ```
     pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_key_pool(KeyPool::from(api_key.into()))
+    }
+
+    /// Creates a new instance of the file manager that rotates between the keys of a pool.
+    pub fn with_key_pool(keys: KeyPool) -> Self {
+        Self::with_key_pool_and_client(keys, reqwest::Client::new())
+    }
+
+    /// Creates a new instance of the file manager that sends its requests through an
+    /// existing HTTP client, sharing its connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The Google AI API key to use for authentication.
+    /// * `client` - The HTTP client to send requests with.
+    pub fn with_client(api_key: impl Into<String>, client: reqwest::Client) -> Self {
+        Self::with_key_pool_and_client(KeyPool::from(api_key.into()), client)
+    }
+
+    /// Creates a new instance of the file manager from a pool of API keys and an
+    /// existing HTTP client.
+    pub fn with_key_pool_and_client(keys: KeyPool, client: reqwest::Client) -> Self {
         let base_url =
             std::env::var("GOOGLE_BASE_URL").unwrap_or_else(|_| FILE_API_URL.to_string());
 
         Self {
-            client: reqwest::Client::new(),
-            api_key: api_key.into(),
+            client,
+            keys,
             base_url,
+            chunk_size: DEFAULT_UPLOAD_CHUNK_SIZE,
+            retry_policy: None,
+            verify_checksum: false,
         }
     }
 
+    /// Retries failed requests according to the given policy.
+    ///
+    /// Without a policy (the default) failed metadata requests (getting, listing and
+    /// deleting files, starting an upload) are returned immediately, and uploads resume
+    /// a failed chunk up to three times. With a policy, both follow the policy.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Overrides the base URL of the file API.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Sets the size of the chunks in which files are uploaded, 8 MiB by default.
+    ///
+    /// At most one chunk is held in memory during an upload. The API expects every
+    /// chunk but the last to be a multiple of 256 KiB, so `chunk_size` is rounded up
+    /// to the next multiple, and a zero size becomes 256 KiB.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        let chunks = chunk_size.max(1).div_ceil(UPLOAD_CHUNK_GRANULARITY);
+        self.chunk_size = chunks
+            .checked_mul(UPLOAD_CHUNK_GRANULARITY)
+            .unwrap_or((chunks - 1) * UPLOAD_CHUNK_GRANULARITY);
+        self
+    }
+
+    /// Sets whether uploads are verified against the SHA-256 hash reported by the
+    /// server, off by default.
+    ///
+    /// The hash of the content is computed while it is uploaded. If it differs from
+    /// the server's, the upload fails with [`FileError::ChecksumMismatch`]. Uploads
+    /// for which the server reports no hash are not checked.
+    pub fn with_checksum_verification(mut self, verify_checksum: bool) -> Self {
+        self.verify_checksum = verify_checksum;
+        self
+    }
+
     /// Creates a new instance of the file manager using the GOOGLE_API_KEY environment variable.
+    ///
+    /// # Panics
+    /// Panics if the environment variable is not set.
+    #[deprecated(note = "use `try_from_env`, which returns an error instead of panicking")]
     pub fn from_env() -> Self {
-        let api_key = std::env::var("GOOGLE_API_KEY")
-            .expect("GOOGLE_API_KEY environment variable must be set must be set");
-        Self::new(api_key)
+        Self::try_from_env().expect("GOOGLE_API_KEY environment variable must be set")
+    }
+
+    /// Creates a new instance of the file manager using the GOOGLE_API_KEY environment variable.
+    ///
+    /// # Returns
+    /// The file manager, or [`FileError::MissingApiKey`] if the environment variable is
+    /// not set or not valid unicode.
+    pub fn try_from_env() -> Result<Self, FileError> {
+        let api_key = std::env::var("GOOGLE_API_KEY").map_err(|_| FileError::MissingApiKey)?;
+        Ok(Self::new(api_key))
     }
 
     /// Deletes all files with the specified display name.
-    /// Returns the number of files deleted.
+    /// Returns the number of files deleted; files that disappear before they are
+    /// deleted, e.g. because they expired, are not counted.
     pub async fn delete_files_by_display_name(
         &self,
         display_name: &str,
     ) -> Result<usize, FileError> {
-        let files = self.list_files().await?;
+        let files = self.list_all_files().await?;
         let mut deleted_count = 0;
 
         for file in files {
             if let Some(name) = &file.display_name {
-                if name == display_name {
-                    self.delete_file(&file.name).await?;
+                if name == display_name && self.delete_file_if_exists(&file.name).await? {
                     deleted_count += 1;
                 }
             }
@@ -169,8 +508,65 @@ impl GoogleAIFileManager {
         Ok(deleted_count)
     }
 
+    /// Deletes every file of the project.
+    ///
+    /// All pages of files are listed first, then up to `concurrency` files are
+    /// deleted at a time. Failures to delete single files do not stop the others;
+    /// they are collected in the returned summary.
+    ///
+    /// # Arguments
+    /// * `concurrency` - How many delete requests may run at once; 0 is treated as 1
+    pub async fn delete_all_files(&self, concurrency: usize) -> Result<DeleteSummary, FileError> {
+        let files = self.list_all_files().await?;
+        Ok(self.delete_files(files, concurrency).await)
+    }
+
+    /// Deletes every file created longer than `age` ago.
+    ///
+    /// See [`delete_all_files`](Self::delete_all_files) for how the files are deleted.
+    pub async fn delete_files_older_than(&self, age: Duration) -> Result<DeleteSummary, FileError> {
+        let cutoff = chrono::Duration::from_std(age)
+            .ok()
+            .and_then(|age| Utc::now().checked_sub_signed(age));
+        let Some(cutoff) = cutoff else {
+            return Ok(DeleteSummary::default());
+        };
+
+        let files = self
+            .list_all_files()
+            .await?
+            .into_iter()
+            .filter(|file| file.create_time < cutoff)
+            .collect();
+        Ok(self.delete_files(files, DELETE_CONCURRENCY).await)
+    }
+
+    /// Deletes files with at most `concurrency` requests at a time.
+    async fn delete_files(&self, files: Vec<FileInfo>, concurrency: usize) -> DeleteSummary {
+        let mut outcomes = futures::stream::iter(files)
+            .map(|file| async move {
+                let outcome = self.delete_file_if_exists(&file.name).await;
+                (file.name, outcome)
+            })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut summary = DeleteSummary::default();
+        while let Some((name, outcome)) = outcomes.next().await {
+            match outcome {
+                Ok(true) => summary.deleted += 1,
+                Ok(false) => summary.missing += 1,
+                Err(e) => summary.failures.push((name, e)),
+            }
+        }
+        summary
+    }
+
     /// Uploads a file to the Gemini AI system.
     ///
+    /// The file is streamed in chunks (see [`with_chunk_size`](Self::with_chunk_size)),
+    /// so large videos do not need to fit in memory. After a transient failure the
+    /// upload resumes from the last byte the API committed.
+    ///
     /// # Arguments
     /// * `file_path` - Path to the file to upload
     /// * `display_name` - Optional display name for the file
@@ -182,145 +578,740 @@ impl GoogleAIFileManager {
         file_path: A,
         display_name: I,
     ) -> Result<FileInfo, FileError> {
-        let file_path = file_path.as_ref();
+        self.upload_file_with_progress(file_path, display_name, |_| {})
+            .await
+    }
+
+    /// Uploads a file to the Gemini AI system, reporting the progress to a callback.
+    ///
+    /// The callback is invoked on the uploading task once the session is started,
+    /// after every committed chunk, after the last chunk and, if the API is still
+    /// processing the file, once more. It should return quickly, e.g. by updating a
+    /// progress bar or sending to a channel.
+    ///
+    /// # Arguments
+    /// * `file_path` - Path to the file to upload
+    /// * `display_name` - Optional display name for the file
+    /// * `progress` - Callback receiving the progress of the upload
+    ///
+    /// # Returns
+    /// Information about the uploaded file.
+    pub async fn upload_file_with_progress<A: AsRef<Path>, I: Into<Option<String>>>(
+        &self,
+        file_path: A,
+        display_name: I,
+        progress: impl Fn(UploadProgress) + Send + Sync,
+    ) -> Result<FileInfo, FileError> {
+        let options = self.upload_options(display_name.into());
+        self.upload_path(file_path.as_ref(), None, options, progress)
+            .await
+    }
+
+    /// Uploads a file to the Gemini AI system with an explicit MIME type.
+    ///
+    /// Use this for files whose type cannot be guessed from the extension, e.g.
+    /// extensionless temporary files.
+    ///
+    /// # Arguments
+    /// * `file_path` - Path to the file to upload
+    /// * `mime_type` - MIME type of the file, e.g. `video/webm`
+    /// * `display_name` - Optional display name for the file
+    ///
+    /// # Returns
+    /// Information about the uploaded file, or [`FileError::MimeTypeError`] if
+    /// `mime_type` is not a valid MIME type.
+    pub async fn upload_file_with_mime<A: AsRef<Path>, I: Into<Option<String>>>(
+        &self,
+        file_path: A,
+        mime_type: &str,
+        display_name: I,
+    ) -> Result<FileInfo, FileError> {
+        let options = self.upload_options(display_name.into());
+        self.upload_path(file_path.as_ref(), Some(mime_type), options, |_| {})
+            .await
+    }
+
+    /// Uploads content described by an [`UploadRequest`].
+    ///
+    /// Uploads from a path stream the file in chunks like
+    /// [`upload_file`](Self::upload_file), guessing the MIME type from the extension
+    /// unless one is set.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use gemini_ai_rust::file::{GoogleAIFileManager, UploadRequest};
+    ///
+    /// # async fn run() -> Result<(), gemini_ai_rust::file::FileError> {
+    /// let manager = GoogleAIFileManager::new("your-api-key");
+    /// let request = UploadRequest::builder()
+    ///     .path("report.pdf")
+    ///     .display_name("Quarterly report")
+    ///     .verify_checksum(true)
+    ///     .build()?;
+    /// let file = manager.upload(request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// Information about the uploaded file.
+    pub async fn upload(&self, request: UploadRequest) -> Result<FileInfo, FileError> {
+        let UploadRequest {
+            source,
+            mime_type,
+            display_name,
+            description,
+            verify_checksum,
+            progress,
+        } = request;
+        let options = UploadOptions {
+            display_name,
+            description,
+            verify_checksum: verify_checksum.unwrap_or(self.verify_checksum),
+        };
+        let progress = |upload_progress| {
+            if let Some(progress) = &progress {
+                progress(upload_progress)
+            }
+        };
+
+        let (reader, len): (Box<dyn AsyncRead + Send + Unpin>, u64) = match source {
+            UploadSource::Path(path) => {
+                return self
+                    .upload_path(&path, mime_type.as_deref(), options, progress)
+                    .await
+            }
+            UploadSource::Bytes(data) => {
+                let len = data.len() as u64;
+                (Box::new(io::Cursor::new(data)), len)
+            }
+            UploadSource::Reader { reader, len } => (reader, len),
+        };
+        // The builder ensures the MIME type is set for these sources.
+        let mime_type = mime_type.unwrap_or_default();
+        self.upload_content(reader, len, &mime_type, options, progress)
+            .await
+    }
+
+    /// Returns the options of an upload with only a display name.
+    fn upload_options(&self, display_name: Option<String>) -> UploadOptions {
+        UploadOptions {
+            display_name,
+            description: None,
+            verify_checksum: self.verify_checksum,
+        }
+    }
+
+    /// Uploads a file, guessing its MIME type from the extension unless one is given.
+    async fn upload_path(
+        &self,
+        file_path: &Path,
+        mime_type: Option<&str>,
+        mut options: UploadOptions,
+        progress: impl Fn(UploadProgress) + Send + Sync,
+    ) -> Result<FileInfo, FileError> {
         let file_size = fs::metadata(file_path)
             .map_err(FileError::FileSizeError)?
             .len();
 
-        let mime_type = mime_guess::from_path(file_path)
-            .first()
-            .ok_or_else(|| {
+        let mime_type = match mime_type {
+            Some(mime_type) => mime_type.to_string(),
+            None => guess_mime_type(file_path).ok_or_else(|| {
                 FileError::MimeTypeError(format!("Unknown MIME type for {:?}", file_path))
-            })?
-            .to_string();
+            })?,
+        };
+
+        options.display_name.get_or_insert_with(|| {
+            file_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unnamed")
+                .to_string()
+        });
+        let file = tokio::fs::File::open(file_path)
+            .await
+            .map_err(FileError::FileReadError)?;
+
+        self.upload_content(file, file_size, &mime_type, options, progress)
+            .await
+    }
+
+    /// Uploads data held in memory to the Gemini AI system.
+    ///
+    /// # Arguments
+    /// * `data` - The content of the file
+    /// * `mime_type` - MIME type of the content, e.g. `image/png`
+    /// * `display_name` - Optional display name for the file
+    ///
+    /// # Returns
+    /// Information about the uploaded file.
+    pub async fn upload_bytes(
+        &self,
+        data: Vec<u8>,
+        mime_type: &str,
+        display_name: Option<String>,
+    ) -> Result<FileInfo, FileError> {
+        let len = data.len() as u64;
+        self.upload_reader(io::Cursor::new(data), len, mime_type, display_name)
+            .await
+    }
+
+    /// Uploads the content of a reader to the Gemini AI system, e.g. a download stream.
+    ///
+    /// The reader is consumed in chunks (see [`with_chunk_size`](Self::with_chunk_size)),
+    /// so only one chunk is held in memory at a time.
+    ///
+    /// # Arguments
+    /// * `reader` - The source of the content
+    /// * `len` - The exact number of bytes to read from the reader
+    /// * `mime_type` - MIME type of the content, e.g. `video/mp4`
+    /// * `display_name` - Optional display name for the file
+    ///
+    /// # Returns
+    /// Information about the uploaded file.
+    pub async fn upload_reader(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        len: u64,
+        mime_type: &str,
+        display_name: Option<String>,
+    ) -> Result<FileInfo, FileError> {
+        self.upload_reader_with_progress(reader, len, mime_type, display_name, |_| {})
+            .await
+    }
+
+    /// Uploads the content of a reader to the Gemini AI system, reporting the progress
+    /// to a callback.
+    ///
+    /// See [`upload_reader`](Self::upload_reader) for the arguments and
+    /// [`upload_file_with_progress`](Self::upload_file_with_progress) for when the
+    /// callback is invoked.
+    pub async fn upload_reader_with_progress(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        len: u64,
+        mime_type: &str,
+        display_name: Option<String>,
+        progress: impl Fn(UploadProgress) + Send + Sync,
+    ) -> Result<FileInfo, FileError> {
+        let options = self.upload_options(display_name);
+        self.upload_content(reader, len, mime_type, options, progress)
+            .await
+    }
+
+    /// Uploads the content of a reader in a resumable session.
+    async fn upload_content(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        len: u64,
+        mime_type: &str,
+        options: UploadOptions,
+        progress: impl Fn(UploadProgress) + Send + Sync,
+    ) -> Result<FileInfo, FileError> {
+        let report = |bytes_sent, phase| {
+            progress(UploadProgress {
+                bytes_sent,
+                total_bytes: len,
+                phase,
+            })
+        };
+
+        report(0, UploadPhase::Initiating);
+        // Only the initiation is retried here; once the session exists, failed chunks
+        // are resumed within it.
+        let upload_url = self
+            .retrying(|| self.start_upload(len, mime_type, &options))
+            .await?;
+        let file_info = self
+            .upload_chunks(
+                &upload_url,
+                reader,
+                len,
+                options.verify_checksum,
+                |bytes_sent, chunk| report(bytes_sent, UploadPhase::Uploading { chunk }),
+            )
+            .await?;
+
+        report(len, UploadPhase::Finalizing);
+        if matches!(file_info.state, FileState::Processing) {
+            report(len, UploadPhase::Processing);
+        }
+        Ok(file_info)
+    }
 
-        // Initial resumable upload request
+    /// Starts a resumable upload session and returns its URL.
+    async fn start_upload(
+        &self,
+        len: u64,
+        mime_type: &str,
+        options: &UploadOptions,
+    ) -> Result<String, FileError> {
         let upload_url = format!("{}/upload/{}/files", self.base_url, FILE_API_VERSION);
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert("X-Goog-Upload-Protocol", "resumable".parse().unwrap());
         headers.insert("X-Goog-Upload-Command", "start".parse().unwrap());
         headers.insert(
             "X-Goog-Upload-Header-Content-Length",
-            file_size.to_string().parse().unwrap(),
-        );
-        headers.insert(
-            "X-Goog-Upload-Header-Content-Type",
-            mime_type.parse().unwrap(),
+            len.to_string().parse().unwrap(),
         );
+        headers.insert("X-Goog-Upload-Header-Content-Type", mime_header(mime_type)?);
 
-        let metadata = serde_json::json!({
-            "file": {
-                "display_name": display_name.into().unwrap_or_else(|| file_path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unnamed")
-                    .to_string())
-            }
-        });
+        let mut file = serde_json::Map::new();
+        if let Some(display_name) = &options.display_name {
+            file.insert("display_name".into(), display_name.clone().into());
+        }
+        if let Some(description) = &options.description {
+            file.insert("description".into(), description.clone().into());
+        }
+        let metadata = serde_json::json!({ "file": file });
 
+        let key = self.keys.acquire();
         let response = self
             .client
             .post(&upload_url)
-            .query(&[("key", &self.api_key)])
+            .query(&[("key", key.as_str())])
             .headers(headers)
             .json(&metadata)
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            self.keys.report_rate_limited(&key);
+        }
+        if !response.status().is_success() {
+            return Err(FileError::InitiationFailed(
+                ApiError::from_response(response).await,
+            ));
+        }
+
         let upload_url = response
             .headers()
             .get("x-goog-upload-url")
             .and_then(|h| h.to_str().ok())
-            .ok_or_else(|| FileError::UploadError("Missing upload URL".into()))?
-            .to_string();
+            .map(str::to_string);
+        match upload_url {
+            Some(upload_url) => Ok(upload_url),
+            None => Err(FileError::UnexpectedResponse {
+                body: response.text().await.unwrap_or_default(),
+            }),
+        }
+    }
 
-        // Upload file content
-        let file_content = tokio::fs::read(file_path)
-            .await
-            .map_err(FileError::FileReadError)?;
+    /// Sends the content of a reader to an upload session in chunks, resuming after
+    /// transient failures.
+    ///
+    /// Only the current chunk is kept, so a resumed upload continues within it from
+    /// the offset the API committed. Failures of the chunks and of the status queries
+    /// made to resume share one retry budget.
+    async fn upload_chunks(
+        &self,
+        upload_url: &str,
+        mut reader: impl AsyncRead + Unpin,
+        len: u64,
+        verify_checksum: bool,
+        on_chunk_committed: impl Fn(u64, usize),
+    ) -> Result<FileInfo, FileError> {
+        // The offset of the first byte of `chunk`
+        let mut offset = 0;
+        let mut chunk = Vec::new();
+        let mut chunks_committed = 0;
+        let mut failures = 0;
+        let policy = self
+            .retry_policy
+            .clone()
+            .unwrap_or_else(default_upload_retry_policy);
+        let mut hasher = verify_checksum.then(Sha256::new);
 
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert("Content-Length", file_size.to_string().parse().unwrap());
-        headers.insert("X-Goog-Upload-Offset", "0".parse().unwrap());
-        headers.insert("X-Goog-Upload-Command", "upload, finalize".parse().unwrap());
+        let file_info = loop {
+            if chunk.is_empty() {
+                let chunk_len = (self.chunk_size as u64).min(len - offset);
+                (&mut reader)
+                    .take(chunk_len)
+                    .read_to_end(&mut chunk)
+                    .await
+                    .map_err(FileError::FileReadError)?;
+                if (chunk.len() as u64) < chunk_len {
+                    return Err(FileError::UploadError(format!(
+                        "Source ended after {} of {} bytes",
+                        offset + chunk.len() as u64,
+                        len
+                    )));
+                }
+                if let Some(hasher) = &mut hasher {
+                    hasher.update(&chunk);
+                }
+            }
+            let chunk_len = chunk.len() as u64;
+            let command = if offset + chunk_len >= len {
+                "upload, finalize"
+            } else {
+                "upload"
+            };
+
+            let result = self
+                .client
+                .post(upload_url)
+                .header("Content-Length", chunk_len)
+                .header("X-Goog-Upload-Offset", offset)
+                .header("X-Goog-Upload-Command", command)
+                .body(chunk.clone())
+                .send()
+                .await;
+
+            let failure = match result {
+                Ok(response) if response.status().is_success() => {
+                    if command == "upload, finalize" {
+                        break parse_upload_response(response).await?;
+                    }
+                    offset += chunk_len;
+                    chunk.clear();
+                    chunks_committed += 1;
+                    on_chunk_committed(offset, chunks_committed);
+                    failures = 0;
+                    continue;
+                }
+                Ok(response) => {
+                    FileError::ContentUploadFailed(ApiError::from_response(response).await)
+                }
+                Err(e) => FileError::RequestError(e),
+            };
+
+            let mut failure = failure;
+            let status = loop {
+                if failures >= policy.max_retries || !failure.is_retryable() {
+                    return Err(failure);
+                }
+                tokio::time::sleep(retry_delay(&policy, failures, &failure)).await;
+                failures += 1;
+
+                match self.query_upload(upload_url).await {
+                    Ok(status) => break status,
+                    Err(e) => failure = e,
+                }
+            };
+
+            match status {
+                UploadStatus::Active { received }
+                    if (offset..=offset + chunk_len).contains(&received) =>
+                {
+                    chunk.drain(..(received - offset) as usize);
+                    offset = received;
+                    if chunk.is_empty() {
+                        // The whole chunk was committed before the failure.
+                        chunks_committed += 1;
+                        on_chunk_committed(offset, chunks_committed);
+                    }
+                }
+                UploadStatus::Active { received } => {
+                    return Err(FileError::UploadError(format!(
+                        "Upload can not be resumed at offset {}, the current chunk starts at {}",
+                        received, offset
+                    )))
+                }
+                UploadStatus::Final(file_info) => break *file_info,
+            }
+        };
+
+        if let (Some(hasher), Some(expected)) = (hasher, file_info.sha256_bytes()) {
+            let actual: [u8; 32] = hasher.finalize().into();
+            if actual != expected {
+                return Err(FileError::ChecksumMismatch {
+                    expected: hex::encode(expected),
+                    actual: hex::encode(actual),
+                });
+            }
+        }
+        Ok(file_info)
+    }
 
+    /// Asks an upload session how many bytes it has committed.
+    async fn query_upload(&self, upload_url: &str) -> Result<UploadStatus, FileError> {
         let response = self
             .client
-            .post(&upload_url)
-            .headers(headers)
-            .body(file_content)
+            .post(upload_url)
+            .header("Content-Length", 0)
+            .header("X-Goog-Upload-Command", "query")
             .send()
             .await?;
-
-        let response_text = response.text().await?;
-        println!("Response: {}", response_text);
-
-        #[derive(Deserialize)]
-        struct FileResponse {
-            file: FileInfo,
+        if !response.status().is_success() {
+            return Err(FileError::ContentUploadFailed(
+                ApiError::from_response(response).await,
+            ));
         }
 
-        let file_response: FileResponse = serde_json::from_str(&response_text).map_err(|e| {
-            FileError::UploadError(format!(
-                "Failed to parse response: {}. Response: {}",
-                e, response_text
-            ))
-        })?;
-        Ok(file_response.file)
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        match header("x-goog-upload-status").as_deref() {
+            Some("active") => {
+                let received = header("x-goog-upload-size-received")
+                    .and_then(|size| size.parse().ok())
+                    .ok_or_else(|| {
+                        FileError::UploadError("Upload status lacks the received size".into())
+                    })?;
+                Ok(UploadStatus::Active { received })
+            }
+            Some("final") => Ok(UploadStatus::Final(Box::new(
+                parse_upload_response(response).await?,
+            ))),
+            status => Err(FileError::UploadError(format!(
+                "Upload can not be resumed, status {:?}",
+                status.unwrap_or("unknown")
+            ))),
+        }
     }
 
     /// Retrieves information about a file by its name.
+    ///
+    /// The name may be the resource name (`files/abc`), the bare id (`abc`) or the
+    /// file's URI. Returns [`FileError::NotFound`] if the file does not exist.
     pub async fn get_file(&self, name: &str) -> Result<FileInfo, FileError> {
-        let url = format!("{}/{}/files/{}", self.base_url, FILE_API_VERSION, name);
+        let url = format!(
+            "{}/{}/files/{}",
+            self.base_url,
+            FILE_API_VERSION,
+            parse_file_id(name)?
+        );
+        self.retrying(|| self.get_file_once(&url, name)).await
+    }
+
+    /// Sends a single request for the information about a file.
+    async fn get_file_once(&self, url: &str, name: &str) -> Result<FileInfo, FileError> {
+        let key = self.keys.acquire();
         let response = self
             .client
-            .get(&url)
-            .query(&[("key", &self.api_key)])
+            .get(url)
+            .query(&[("key", key.as_str())])
             .send()
             .await?;
+        let response = self.check_response(response, &key, name).await?;
 
         let file_info: FileInfo = response.json().await?;
         Ok(file_info)
     }
 
-    /// Deletes a file from the system.
+    /// Runs a request, retrying retryable failures according to the retry policy.
+    async fn retrying<T, F, Fut>(&self, mut request: F) -> Result<T, FileError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FileError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = request().await;
+            match (&self.retry_policy, &result) {
+                (Some(policy), Err(e)) if attempt < policy.max_retries && e.is_retryable() => {
+                    tokio::time::sleep(retry_delay(policy, attempt, e)).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Turns a failed response into [`FileError::NotFound`] or [`FileError::Api`].
+    async fn check_response(
+        &self,
+        response: reqwest::Response,
+        key: &PooledKey,
+        file_id: &str,
+    ) -> Result<reqwest::Response, FileError> {
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            reqwest::StatusCode::NOT_FOUND => Err(FileError::NotFound(file_id.to_string())),
+            status => {
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    self.keys.report_rate_limited(key);
+                }
+                Err(FileError::Api(ApiError::from_response(response).await))
+            }
+        }
+    }
+
+    /// Deletes a file from the system.
+    ///
+    /// Returns [`FileError::NotFound`] if the file does not exist.
     pub async fn delete_file(&self, file_id: &str) -> Result<(), FileError> {
         let url = format!(
-            "{}/{}/files/{}?key={}",
+            "{}/{}/files/{}",
             self.base_url,
             FILE_API_VERSION,
-            parse_file_id(file_id)?,
-            self.api_key
+            parse_file_id(file_id)?
         );
-        self.client
-            .delete(&url)
-            .header("x-goog-api-key", &self.api_key)
-            .send()
-            .await?;
+        self.retrying(|| async {
+            let key = self.keys.acquire();
+            let response = self
+                .client
+                .delete(&url)
+                .query(&[("key", key.as_str())])
+                .send()
+                .await?;
+            self.check_response(response, &key, file_id).await?;
+            Ok(())
+        })
+        .await
+    }
 
-        Ok(())
+    /// Deletes a file from the system if it exists.
+    ///
+    /// Returns `true` if the file was deleted and `false` if it did not exist.
+    pub async fn delete_file_if_exists(&self, file_id: &str) -> Result<bool, FileError> {
+        match self.delete_file(file_id).await {
+            Ok(()) => Ok(true),
+            Err(FileError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
-    /// Lists all files available in the system.
+    /// Lists the first page of files, with the API's default page size.
+    ///
+    /// Use [`list_all_files`](Self::list_all_files) to get every file.
     pub async fn list_files(&self) -> Result<Vec<FileInfo>, FileError> {
+        let (files, _) = self.list_files_paged(None, None).await?;
+        Ok(files)
+    }
+
+    /// Lists one page of files.
+    ///
+    /// # Arguments
+    /// * `page_size` - Maximum number of files on the page, or `None` for the API's default
+    /// * `page_token` - The token returned with the previous page, or `None` for the first page
+    ///
+    /// # Returns
+    /// The files of the page and the token of the next page, if there is one.
+    pub async fn list_files_paged(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<FileInfo>, Option<String>), FileError> {
+        self.retrying(|| self.list_files_page_once(page_size, page_token))
+            .await
+    }
+
+    /// Sends a single request for one page of files.
+    async fn list_files_page_once(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<FileInfo>, Option<String>), FileError> {
         let url = format!("{}/{}/files", self.base_url, FILE_API_VERSION);
-        let response = self
-            .client
-            .get(&url)
-            .query(&[("key", &self.api_key)])
-            .send()
-            .await?;
+        let key = self.keys.acquire();
+        let mut request = self.client.get(&url).query(&[("key", key.as_str())]);
+        if let Some(page_size) = page_size {
+            request = request.query(&[("pageSize", page_size)]);
+        }
+        if let Some(page_token) = page_token {
+            request = request.query(&[("pageToken", page_token)]);
+        }
+        let response = request.send().await?;
+        let response = self.check_response(response, &key, "files").await?;
 
+        // An empty project is listed as `{}`.
         #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
         struct ListResponse {
+            #[serde(default)]
             files: Vec<FileInfo>,
+            next_page_token: Option<String>,
         }
 
         let list_response: ListResponse = response.json().await?;
-        Ok(list_response.files)
+        let next_page_token = list_response
+            .next_page_token
+            .filter(|token| !token.is_empty());
+        Ok((list_response.files, next_page_token))
+    }
+
+    /// Lists all files available in the system, following every page of the listing.
+    pub async fn list_all_files(&self) -> Result<Vec<FileInfo>, FileError> {
+        let mut all_files = Vec::new();
+        let mut page_token = None;
+        loop {
+            let (files, next_page_token) =
+                self.list_files_paged(None, page_token.as_deref()).await?;
+            all_files.extend(files);
+            match next_page_token {
+                Some(token) => page_token = Some(token),
+                None => return Ok(all_files),
+            }
+        }
+    }
+
+    /// Lists all files available in the system, keyed by their resource name.
+    pub async fn list_all_files_map(&self) -> Result<HashMap<String, FileInfo>, FileError> {
+        self.list_files_stream()
+            .map_ok(|file| (file.name.clone(), file))
+            .try_collect()
+            .await
+    }
+
+    /// Finds all files with the specified display name, following every page of the
+    /// listing.
+    pub async fn find_files_by_display_name(
+        &self,
+        display_name: &str,
+    ) -> Result<Vec<FileInfo>, FileError> {
+        self.list_files_stream()
+            .try_filter(|file| {
+                futures::future::ready(file.display_name.as_deref() == Some(display_name))
+            })
+            .try_collect()
+            .await
+    }
+
+    /// Finds a file whose content has the specified SHA-256 hash, e.g. to avoid
+    /// uploading the same content twice.
+    ///
+    /// The hash may be hex encoded or base64 encoded like [`FileInfo::sha256_hash`].
+    /// Pages are only fetched until a match is found. Returns `None` if no file
+    /// matches or the hash cannot be decoded.
+    pub async fn find_file_by_sha256(&self, hash: &str) -> Result<Option<FileInfo>, FileError> {
+        let Some(hash) = decode_sha256(hash) else {
+            return Ok(None);
+        };
+        let matches = self
+            .list_files_stream()
+            .try_filter(|file| futures::future::ready(file.sha256_bytes() == Some(hash)));
+        futures::pin_mut!(matches);
+        matches.try_next().await
+    }
+
+    /// Streams all files available in the system, fetching the pages as they are needed.
+    ///
+    /// The stream ends after the first error.
+    pub fn list_files_stream(&self) -> impl Stream<Item = Result<FileInfo, FileError>> + '_ {
+        enum Page {
+            First,
+            Next(String),
+            Done,
+        }
+
+        let pages = futures::stream::try_unfold(Page::First, move |page| async move {
+            let page_token = match page {
+                Page::First => None,
+                Page::Next(token) => Some(token),
+                Page::Done => return Ok::<_, FileError>(None),
+            };
+            let (files, next_page_token) =
+                self.list_files_paged(None, page_token.as_deref()).await?;
+            let next = next_page_token.map_or(Page::Done, Page::Next);
+            let files = futures::stream::iter(files.into_iter().map(Ok::<_, FileError>));
+            Ok(Some((files, next)))
+        });
+        futures::TryStreamExt::try_flatten(pages)
     }
 
     /// Waits for a file to finish processing, with configurable retries and delay.
     ///
+    /// Polls at a fixed interval; see
+    /// [`wait_for_file_processing_with`](Self::wait_for_file_processing_with) for
+    /// exponential backoff.
+    ///
     /// # Arguments
     /// * `name` - Name of the file to wait for
     /// * `max_retries` - Maximum number of times to check the file state
@@ -334,37 +1325,1320 @@ impl GoogleAIFileManager {
         max_retries: u32,
         delay_ms: u64,
     ) -> Result<FileInfo, FileError> {
-        for _ in 0..max_retries {
+        let delay = Duration::from_millis(delay_ms);
+        let options = WaitOptions {
+            initial_delay: delay,
+            max_delay: delay,
+            multiplier: 1.0,
+            deadline: None,
+            max_attempts: Some(max_retries),
+        };
+        self.wait_for_file_processing_with(name, &options).await
+    }
+
+    /// Waits for a file to finish processing, polling with exponential backoff.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the file to wait for
+    /// * `options` - How often and how long to poll
+    ///
+    /// # Returns
+    /// The file information once it is active, [`FileError::ProcessingError`] with the
+    /// reported reason if processing failed, or [`FileError::Timeout`] if the deadline
+    /// or the maximum number of polls was reached.
+    pub async fn wait_for_file_processing_with(
+        &self,
+        name: &str,
+        options: &WaitOptions,
+    ) -> Result<FileInfo, FileError> {
+        let started = Instant::now();
+        let mut delay = options.initial_delay;
+        let mut attempts = 0;
+
+        loop {
             let file_info = self.get_file(name).await?;
+            attempts += 1;
             match file_info.state {
                 FileState::Active => return Ok(file_info),
                 FileState::Failed => {
+                    let reason = file_info
+                        .error
+                        .map(|error| format!(": {}", error.message))
+                        .unwrap_or_default();
                     return Err(FileError::ProcessingError(format!(
-                        "File {} processing failed",
-                        name
-                    )))
-                }
-                FileState::Processing | FileState::Unspecified => {
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                    continue;
+                        "File {} processing failed{}",
+                        name, reason
+                    )));
                 }
+                FileState::Processing | FileState::Unspecified => {}
+            }
+
+            let remaining = options
+                .deadline
+                .map(|deadline| deadline.saturating_sub(started.elapsed()));
+            let out_of_attempts = options.max_attempts.is_some_and(|max| attempts >= max);
+            if out_of_attempts || remaining.is_some_and(|remaining| remaining.is_zero()) {
+                return Err(FileError::Timeout {
+                    name: name.to_string(),
+                    last_state: file_info.state,
+                });
             }
+
+            tokio::time::sleep(remaining.map_or(delay, |remaining| delay.min(remaining))).await;
+            delay = Duration::try_from_secs_f64(delay.as_secs_f64() * options.multiplier)
+                .unwrap_or(options.max_delay)
+                .min(options.max_delay);
         }
-        Err(FileError::ProcessingError(format!(
-            "Timeout waiting for file {} to process",
-            name
-        )))
     }
 }
 
-fn parse_file_id(file_id: &str) -> Result<&str, FileError> {
-    if let Some(stripped) = file_id.strip_prefix("files/") {
-        Ok(stripped)
-    } else if !file_id.is_empty() {
-        Ok(file_id)
+/// MIME types of files Gemini supports that `mime_guess` does not know or maps to
+/// types Gemini rejects, by lowercase extension.
+const GEMINI_MIME_TYPES: &[(&str, &str)] = &[
+    ("py", "text/x-python"),
+    ("java", "text/plain"),
+    ("go", "text/plain"),
+    ("kt", "text/plain"),
+    ("ts", "text/plain"),
+    ("tsx", "text/plain"),
+    ("jsonl", "text/plain"),
+    ("ipynb", "application/json"),
+    ("md", "text/md"),
+];
+
+/// Guesses the MIME type of a file from its extension.
+fn guess_mime_type(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    GEMINI_MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, mime_type)| mime_type.to_string())
+        .or_else(|| {
+            mime_guess::from_ext(&extension)
+                .first()
+                .map(|m| m.to_string())
+        })
+}
+
+/// The state of an upload session.
+enum UploadStatus {
+    /// The session accepts more data after the first `received` bytes.
+    Active { received: u64 },
+    /// The upload is complete.
+    Final(Box<FileInfo>),
+}
+
+/// Decodes a SHA-256 hash given as hex or as base64 of the digest or of its hex encoding.
+fn decode_sha256(hash: &str) -> Option<[u8; 32]> {
+    let decode_hex = |hex_hash: &[u8]| -> Option<[u8; 32]> {
+        let mut digest = [0; 32];
+        hex::decode_to_slice(hex_hash, &mut digest).ok()?;
+        Some(digest)
+    };
+    match base64_engine.decode(hash) {
+        Ok(bytes) if bytes.len() == 32 => bytes.try_into().ok(),
+        Ok(bytes) if bytes.len() == 64 => decode_hex(&bytes),
+        _ => decode_hex(hash.as_bytes()),
+    }
+}
+
+/// Parses a MIME type into the value of the upload content type header.
+fn mime_header(mime_type: &str) -> Result<reqwest::header::HeaderValue, FileError> {
+    mime_type
+        .parse::<mime_guess::mime::Mime>()
+        .ok()
+        .filter(|mime| !mime.subtype().as_str().is_empty())
+        .and_then(|mime| mime.as_ref().parse().ok())
+        .ok_or_else(|| FileError::MimeTypeError(format!("Invalid MIME type {:?}", mime_type)))
+}
+
+/// The policy for resuming failed upload chunks when the manager has none.
+fn default_upload_retry_policy() -> RetryPolicy {
+    RetryPolicy::builder()
+        .max_retries(3)
+        .initial_backoff(Duration::from_millis(250))
+        .build()
+}
+
+/// Returns how long to wait before retrying after `error`, preferring the delay
+/// requested by the server.
+fn retry_delay(policy: &RetryPolicy, attempt: u32, error: &FileError) -> Duration {
+    match error.api_error() {
+        Some(error) => policy.delay_for(attempt, error),
+        None => policy.backoff(attempt),
+    }
+}
+
+/// Parses the file of the response to the final chunk of an upload.
+async fn parse_upload_response(response: reqwest::Response) -> Result<FileInfo, FileError> {
+    let body = response.text().await?;
+
+    #[derive(Deserialize)]
+    struct FileResponse {
+        file: FileInfo,
+    }
+
+    match serde_json::from_str::<FileResponse>(&body) {
+        Ok(file_response) => Ok(file_response.file),
+        Err(_) => Err(FileError::UnexpectedResponse { body }),
+    }
+}
+
+/// Returns the bare id of a file, accepting the resource name (`files/abc`), the
+/// bare id (`abc`) and the file's URI (`https://.../v1beta/files/abc`).
+pub(crate) fn parse_file_id(file_id: &str) -> Result<&str, FileError> {
+    let trimmed = file_id.trim();
+    let id = match trimmed.rfind("/files/") {
+        Some(index) if trimmed.contains("://") => &trimmed[index + "/files/".len()..],
+        _ => trimmed.strip_prefix("files/").unwrap_or(trimmed),
+    };
+    let id = id.split(['?', '#']).next().unwrap_or_default();
+
+    if id.is_empty() {
+        Err(FileError::InvalidFileId(format!(
+            "File ID must not be empty: {:?}",
+            file_id
+        )))
+    } else if id.contains('/') {
+        Err(FileError::InvalidFileId(format!(
+            "File ID must not contain '/': {:?}",
+            file_id
+        )))
     } else {
-        Err(FileError::InvalidFileId(
-            "File ID must not be empty".to_string(),
-        ))
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{MockResponse, MockServer};
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    impl GoogleAIFileManager {
+        /// Sets a chunk size below the API's granularity, to upload in several
+        /// chunks without large test files.
+        fn with_test_chunk_size(mut self, chunk_size: usize) -> Self {
+            self.chunk_size = chunk_size;
+            self
+        }
+    }
+
+    #[test]
+    fn test_chunk_size_is_rounded_up() {
+        let chunk_size = |size| {
+            GoogleAIFileManager::new("key")
+                .with_chunk_size(size)
+                .chunk_size
+        };
+        assert_eq!(chunk_size(0), 256 * 1024);
+        assert_eq!(chunk_size(1), 256 * 1024);
+        assert_eq!(chunk_size(256 * 1024), 256 * 1024);
+        assert_eq!(chunk_size(1_000_000), 1024 * 1024);
+        assert_eq!(
+            chunk_size(usize::MAX),
+            usize::MAX / (256 * 1024) * (256 * 1024)
+        );
+    }
+
+    fn file_json(name: &str) -> serde_json::Value {
+        json!({
+            "name": name,
+            "mimeType": "text/plain",
+            "sizeBytes": "12",
+            "createTime": "2024-01-01T00:00:00Z",
+            "updateTime": "2024-01-01T00:00:00Z",
+            "uri": format!("https://example.com/v1beta/{}", name),
+            "state": "ACTIVE"
+        })
+    }
+
+    fn names(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|file| file.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_files_of_empty_project() {
+        let server = MockServer::start(|_| MockResponse::json(200, json!({}))).await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        assert!(manager.list_files().await.unwrap().is_empty());
+        assert!(manager.list_all_files().await.unwrap().is_empty());
+        let (files, next) = manager.list_files_paged(Some(5), None).await.unwrap();
+        assert!(files.is_empty() && next.is_none());
+        assert_eq!(server.requests()[2].query("pageSize").as_deref(), Some("5"));
+    }
+
+    #[tokio::test]
+    async fn test_list_files_single_page() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(200, json!({ "files": [file_json("files/a")] }))
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        assert_eq!(names(&manager.list_all_files().await.unwrap()), ["files/a"]);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_files_follows_page_tokens() {
+        let server = MockServer::start(|request| match request.query("pageToken").as_deref() {
+            None => MockResponse::json(
+                200,
+                json!({
+                    "files": [file_json("files/a"), file_json("files/b")],
+                    "nextPageToken": "page-2"
+                }),
+            ),
+            Some("page-2") => MockResponse::json(
+                200,
+                json!({ "files": [file_json("files/c")], "nextPageToken": "page-3" }),
+            ),
+            _ => MockResponse::json(200, json!({ "nextPageToken": "" })),
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let (first, next) = manager.list_files_paged(None, None).await.unwrap();
+        assert_eq!(names(&first), ["files/a", "files/b"]);
+        assert_eq!(next.as_deref(), Some("page-2"));
+
+        let all = manager.list_all_files().await.unwrap();
+        assert_eq!(names(&all), ["files/a", "files/b", "files/c"]);
+
+        let streamed: Vec<FileInfo> = manager.list_files_stream().try_collect().await.unwrap();
+        assert_eq!(names(&streamed), ["files/a", "files/b", "files/c"]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_file_in_chunks_resumes_after_failure() {
+        let committed = std::sync::Arc::new(std::sync::Mutex::new((0u64, false)));
+        let server = MockServer::start({
+            let committed = committed.clone();
+            move |request| {
+                if request.path().starts_with("/upload/") {
+                    let host = request.header("host").unwrap();
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-url", format!("http://{}/session", host));
+                }
+                let mut committed = committed.lock().unwrap();
+                let command = request.header("x-goog-upload-command").unwrap();
+                if command == "query" {
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-status", "active")
+                        .with_header("x-goog-upload-size-received", committed.0.to_string());
+                }
+                let offset: u64 = request
+                    .header("x-goog-upload-offset")
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if offset == 4 && !committed.1 {
+                    committed.1 = true;
+                    return MockResponse::json(503, json!({}));
+                }
+                committed.0 = offset + request.body.len() as u64;
+                if command == "upload, finalize" {
+                    MockResponse::json(200, json!({ "file": file_json("files/abc") }))
+                } else {
+                    MockResponse::json(200, json!({}))
+                }
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_test_chunk_size(4);
+
+        let file = manager.upload_file(&path, None).await.unwrap();
+        assert_eq!(file.name, "files/abc");
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].header("x-goog-upload-header-content-length"),
+            Some("10")
+        );
+        let chunks: Vec<_> = requests[1..]
+            .iter()
+            .map(|request| {
+                (
+                    request.header("x-goog-upload-command").unwrap().to_string(),
+                    request.header("x-goog-upload-offset").map(str::to_string),
+                    String::from_utf8(request.body.clone()).unwrap(),
+                )
+            })
+            .collect();
+        let chunk = |command: &str, offset: &str, body: &str| {
+            (
+                command.to_string(),
+                Some(offset.to_string()),
+                body.to_string(),
+            )
+        };
+        assert_eq!(
+            chunks,
+            [
+                chunk("upload", "0", "0123"),
+                chunk("upload", "4", "4567"),
+                ("query".to_string(), None, String::new()),
+                chunk("upload", "4", "4567"),
+                chunk("upload, finalize", "8", "89"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_bytes_resumes_within_a_chunk() {
+        let failed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let server = MockServer::start({
+            let failed = failed.clone();
+            move |request| {
+                if request.path().starts_with("/upload/") {
+                    let host = request.header("host").unwrap();
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-url", format!("http://{}/session", host));
+                }
+                match request.header("x-goog-upload-command").unwrap() {
+                    // Only the first 6 bytes of the failed chunk were committed.
+                    "query" => MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-status", "active")
+                        .with_header("x-goog-upload-size-received", "6"),
+                    _ if !failed.swap(true, std::sync::atomic::Ordering::SeqCst) => {
+                        MockResponse::json(503, json!({}))
+                    }
+                    "upload" => MockResponse::json(200, json!({})),
+                    _ => MockResponse::json(200, json!({ "file": file_json("files/mem") })),
+                }
+            }
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_test_chunk_size(8);
+
+        let file = manager
+            .upload_bytes(b"abcdefghij".to_vec(), "text/plain", None)
+            .await
+            .unwrap();
+        assert_eq!(file.name, "files/mem");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].json(), json!({ "file": {} }));
+        assert_eq!(
+            requests[0].header("x-goog-upload-header-content-type"),
+            Some("text/plain")
+        );
+        let uploads: Vec<_> = requests[1..]
+            .iter()
+            .filter(|request| request.header("x-goog-upload-command") != Some("query"))
+            .map(|request| {
+                (
+                    request.header("x-goog-upload-offset").unwrap().to_string(),
+                    String::from_utf8(request.body.clone()).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            uploads,
+            [
+                ("0".to_string(), "abcdefgh".to_string()),
+                ("6".to_string(), "gh".to_string()),
+                ("8".to_string(), "ij".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_retries_failed_status_queries() {
+        let queries = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = MockServer::start({
+            let queries = queries.clone();
+            move |request| {
+                if request.path().starts_with("/upload/") {
+                    let host = request.header("host").unwrap();
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-url", format!("http://{}/session", host));
+                }
+                match (
+                    request.header("x-goog-upload-command").unwrap(),
+                    request.header("x-goog-upload-offset"),
+                ) {
+                    // The first query fails, the second one reports that the whole
+                    // failed chunk was committed.
+                    ("query", _) if queries.fetch_add(1, Ordering::SeqCst) == 0 => {
+                        MockResponse::json(503, json!({}))
+                    }
+                    ("query", _) => MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-status", "active")
+                        .with_header("x-goog-upload-size-received", "8"),
+                    ("upload", Some("4")) => MockResponse::json(503, json!({})),
+                    ("upload", _) => MockResponse::json(200, json!({})),
+                    _ => MockResponse::json(200, json!({ "file": file_json("files/abc") })),
+                }
+            }
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_test_chunk_size(4)
+            .with_retry_policy(
+                RetryPolicy::builder()
+                    .initial_backoff(Duration::from_millis(1))
+                    .build(),
+            );
+
+        let reports = std::sync::Mutex::new(Vec::new());
+        let file = manager
+            .upload_reader_with_progress(&b"0123456789"[..], 10, "text/plain", None, |progress| {
+                reports
+                    .lock()
+                    .unwrap()
+                    .push((progress.bytes_sent, progress.phase))
+            })
+            .await
+            .unwrap();
+        assert_eq!(file.name, "files/abc");
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+        let reports = reports.into_inner().unwrap();
+        assert!(reports.contains(&(4, UploadPhase::Uploading { chunk: 1 })));
+        assert!(reports.contains(&(8, UploadPhase::Uploading { chunk: 2 })));
+
+        let offsets: Vec<_> = server
+            .requests()
+            .iter()
+            .filter_map(|request| request.header("x-goog-upload-offset").map(str::to_string))
+            .collect();
+        assert_eq!(offsets, ["0", "4", "8"]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_reader_rejects_short_source() {
+        let server = MockServer::start(|request| {
+            let host = request.header("host").unwrap();
+            MockResponse::json(200, json!({}))
+                .with_header("x-goog-upload-url", format!("http://{}/session", host))
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let result = manager
+            .upload_reader(&b"abc"[..], 5, "text/plain", Some("short".into()))
+            .await;
+        assert!(matches!(result, Err(FileError::UploadError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_upload_progress_is_monotonic() {
+        let server = MockServer::start(|request| {
+            if request.path().starts_with("/upload/") {
+                let host = request.header("host").unwrap();
+                return MockResponse::json(200, json!({}))
+                    .with_header("x-goog-upload-url", format!("http://{}/session", host));
+            }
+            if request.header("x-goog-upload-command") == Some("upload") {
+                return MockResponse::json(200, json!({}));
+            }
+            let mut file = file_json("files/video");
+            file["state"] = json!("PROCESSING");
+            MockResponse::json(200, json!({ "file": file }))
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_test_chunk_size(4);
+
+        let reports = std::sync::Mutex::new(Vec::new());
+        manager
+            .upload_file_with_progress(&path, None, |progress| {
+                reports.lock().unwrap().push(progress)
+            })
+            .await
+            .unwrap();
+
+        let reports = reports.into_inner().unwrap();
+        assert!(reports.iter().all(|report| report.total_bytes == 10));
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].bytes_sent <= pair[1].bytes_sent));
+        let phases: Vec<_> = reports
+            .iter()
+            .map(|report| (report.bytes_sent, report.phase))
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                (0, UploadPhase::Initiating),
+                (4, UploadPhase::Uploading { chunk: 1 }),
+                (8, UploadPhase::Uploading { chunk: 2 }),
+                (10, UploadPhase::Finalizing),
+                (10, UploadPhase::Processing),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_guess_mime_type() {
+        let guess = |path: &str| guess_mime_type(Path::new(path));
+        assert_eq!(guess("clip.MP4").as_deref(), Some("video/mp4"));
+        assert_eq!(guess("notes.txt").as_deref(), Some("text/plain"));
+        assert_eq!(guess("script.py").as_deref(), Some("text/x-python"));
+        assert_eq!(guess("main.ts").as_deref(), Some("text/plain"));
+        assert_eq!(guess("upload-1234"), None);
+        assert_eq!(guess("data.unknownext"), None);
+    }
+
+    #[tokio::test]
+    async fn test_upload_file_with_mime_override() {
+        let server = MockServer::start(|request| {
+            if request.path().starts_with("/upload/") {
+                let host = request.header("host").unwrap();
+                return MockResponse::json(200, json!({}))
+                    .with_header("x-goog-upload-url", format!("http://{}/session", host));
+            }
+            MockResponse::json(200, json!({ "file": file_json("files/clip") }))
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording");
+        std::fs::write(&path, "webm bytes").unwrap();
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        assert!(matches!(
+            manager.upload_file(&path, None).await,
+            Err(FileError::MimeTypeError(_))
+        ));
+        for invalid in ["webm", "video/", "video/web m"] {
+            assert!(matches!(
+                manager.upload_file_with_mime(&path, invalid, None).await,
+                Err(FileError::MimeTypeError(_))
+            ));
+        }
+        assert!(server.requests().is_empty());
+
+        manager
+            .upload_file_with_mime(&path, "video/webm", None)
+            .await
+            .unwrap();
+        let start = &server.requests()[0];
+        assert_eq!(
+            start.header("x-goog-upload-header-content-type"),
+            Some("video/webm")
+        );
+        assert_eq!(start.json()["file"]["display_name"], "recording");
+    }
+
+    /// Starts a server whose upload session answers every chunk with `chunk_response`.
+    async fn upload_server(
+        chunk_response: impl Fn() -> MockResponse + Send + Sync + 'static,
+    ) -> MockServer {
+        MockServer::start(move |request| {
+            if request.path().starts_with("/upload/") {
+                let host = request.header("host").unwrap();
+                return MockResponse::json(200, json!({}))
+                    .with_header("x-goog-upload-url", format!("http://{}/session", host));
+            }
+            chunk_response()
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_upload_initiation_failure_is_structured() {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                400,
+                json!({ "error": { "code": 400, "message": "bad metadata", "status": "INVALID_ARGUMENT" } }),
+            )
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        match manager
+            .upload_bytes(b"x".to_vec(), "text/plain", None)
+            .await
+        {
+            Err(FileError::InitiationFailed(error)) => {
+                assert_eq!(error.status, 400);
+                assert_eq!(error.code.as_deref(), Some("INVALID_ARGUMENT"));
+                assert_eq!(error.message, "bad metadata");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_without_session_url_is_unexpected() {
+        let server = MockServer::start(|_| MockResponse::json(200, json!({ "odd": true }))).await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        match manager
+            .upload_bytes(b"x".to_vec(), "text/plain", None)
+            .await
+        {
+            Err(FileError::UnexpectedResponse { body }) => assert!(body.contains("odd")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_content_is_structured() {
+        let server = upload_server(|| {
+            MockResponse::json(
+                403,
+                json!({ "error": { "code": 403, "message": "denied", "status": "PERMISSION_DENIED" } }),
+            )
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        match manager
+            .upload_bytes(b"x".to_vec(), "text/plain", None)
+            .await
+        {
+            Err(FileError::ContentUploadFailed(error)) => {
+                assert_eq!(error.status, 403);
+                assert_eq!(error.message, "denied");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // Permanent failures are not retried.
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_upload_response_is_unexpected() {
+        let server = upload_server(|| MockResponse::json(200, json!({ "file": "nope" }))).await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        match manager
+            .upload_bytes(b"x".to_vec(), "text/plain", None)
+            .await
+        {
+            Err(FileError::UnexpectedResponse { body }) => {
+                assert_eq!(body, r#"{"file":"nope"}"#)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_file_processing_backs_off() {
+        let polls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = MockServer::start({
+            let polls = polls.clone();
+            move |_| {
+                let mut file = file_json("files/video");
+                if polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 2 {
+                    file["state"] = json!("PROCESSING");
+                }
+                MockResponse::json(200, file)
+            }
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+        let options = WaitOptions::builder()
+            .initial_delay(Duration::from_millis(10))
+            .max_delay(Duration::from_millis(15))
+            .build();
+
+        let started = Instant::now();
+        let file = manager
+            .wait_for_file_processing_with("video", &options)
+            .await
+            .unwrap();
+        assert_eq!(file.state, FileState::Active);
+        assert_eq!(server.requests().len(), 3);
+        assert!(started.elapsed() >= Duration::from_millis(25));
+        assert_eq!(server.requests()[0].path(), "/v1beta/files/video");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_file_processing_times_out_with_last_state() {
+        let server = MockServer::start(|_| {
+            let mut file = file_json("files/video");
+            file["state"] = json!("PROCESSING");
+            MockResponse::json(200, file)
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let options = WaitOptions::builder()
+            .initial_delay(Duration::from_millis(5))
+            .deadline(Duration::from_millis(30))
+            .build();
+        match manager
+            .wait_for_file_processing_with("video", &options)
+            .await
+        {
+            Err(FileError::Timeout { name, last_state }) => {
+                assert_eq!(name, "video");
+                assert_eq!(last_state, FileState::Processing);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let polls = server.requests().len();
+        assert!(matches!(
+            manager.wait_for_file_processing("video", 2, 1).await,
+            Err(FileError::Timeout { .. })
+        ));
+        assert_eq!(server.requests().len(), polls + 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_file_processing_reports_failure_reason() {
+        let server = MockServer::start(|_| {
+            let mut file = file_json("files/video");
+            file["state"] = json!("FAILED");
+            file["error"] = json!({ "code": 3, "message": "unsupported codec" });
+            MockResponse::json(200, file)
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        match manager
+            .wait_for_file_processing_with("video", &WaitOptions::default())
+            .await
+        {
+            Err(FileError::ProcessingError(reason)) => {
+                assert!(reason.ends_with(": unsupported codec"), "{}", reason)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_file_checks_the_status() {
+        let server = MockServer::start(|request| match request.path() {
+            "/v1beta/files/present" => MockResponse::json(200, json!({})),
+            "/v1beta/files/missing" => MockResponse::json(
+                404,
+                json!({ "error": { "code": 404, "message": "not found", "status": "NOT_FOUND" } }),
+            ),
+            _ => MockResponse::json(
+                500,
+                json!({ "error": { "code": 500, "message": "boom", "status": "INTERNAL" } }),
+            ),
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        manager.delete_file("files/present").await.unwrap();
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "DELETE");
+        assert_eq!(request.query("key").as_deref(), Some("key"));
+        assert_eq!(request.header("x-goog-api-key"), None);
+
+        assert!(matches!(
+            manager.delete_file("files/missing").await,
+            Err(FileError::NotFound(name)) if name == "files/missing"
+        ));
+        assert!(!manager.delete_file_if_exists("missing").await.unwrap());
+        assert!(manager.delete_file_if_exists("present").await.unwrap());
+
+        match manager.delete_file("files/broken").await {
+            Err(FileError::Api(error)) => {
+                assert_eq!(error.status, 500);
+                assert_eq!(error.message, "boom");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(manager.delete_file_if_exists("broken").await.is_err());
+    }
+
+    #[test]
+    fn test_file_info_round_trip() {
+        let json = json!({
+            "name": "files/abc-123",
+            "displayName": "Clip",
+            "mimeType": "video/mp4",
+            "sizeBytes": "5368709120",
+            "createTime": "2024-05-01T10:00:00.123456Z",
+            "updateTime": "2024-05-01T10:00:05.000001Z",
+            "expirationTime": "2024-05-03T10:00:00.123456Z",
+            "sha256Hash": "ZGVhZGJlZWY=",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc-123",
+            "state": "ACTIVE"
+        });
+
+        let file: FileInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(file.size(), 5 * 1024 * 1024 * 1024);
+        assert_eq!(
+            file.create_time,
+            "2024-05-01T10:00:00.123456Z"
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        );
+        assert_eq!(file.expires_in(), Some(Duration::ZERO));
+        assert_eq!(serde_json::to_value(&file).unwrap(), json);
+
+        let numeric: FileInfo = serde_json::from_value(json!({
+            "name": "files/n",
+            "mimeType": "text/plain",
+            "sizeBytes": 12,
+            "createTime": "2024-05-01T10:00:00Z",
+            "updateTime": "2024-05-01T10:00:00Z",
+            "uri": "https://example.com/files/n",
+            "state": "PROCESSING"
+        }))
+        .unwrap();
+        assert_eq!(numeric.size_bytes, 12);
+        assert_eq!(numeric.expires_in(), None);
+    }
+
+    #[test]
+    fn test_expires_in_counts_down() {
+        let mut file: FileInfo = serde_json::from_value(file_json("files/a")).unwrap();
+        file.expiration_time = Some(Utc::now() + chrono::Duration::hours(48));
+        let remaining = file.expires_in().unwrap();
+        assert!(remaining > Duration::from_secs(47 * 3600));
+        assert!(remaining <= Duration::from_secs(48 * 3600));
+    }
+
+    #[test]
+    fn test_parse_file_id() {
+        assert_eq!(parse_file_id("files/abc").unwrap(), "abc");
+        assert_eq!(parse_file_id("abc").unwrap(), "abc");
+        assert_eq!(
+            parse_file_id("https://generativelanguage.googleapis.com/v1beta/files/abc").unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            parse_file_id("https://example.com/v1beta/files/abc?alt=media").unwrap(),
+            "abc"
+        );
+        for invalid in [
+            "",
+            "files/",
+            "  ",
+            "files/a/b",
+            "https://example.com/v1beta/files/",
+        ] {
+            assert!(
+                matches!(parse_file_id(invalid), Err(FileError::InvalidFileId(_))),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_file_normalizes_names() {
+        let server = MockServer::start(|request| match request.path() {
+            "/v1beta/files/abc" => MockResponse::json(200, file_json("files/abc")),
+            _ => MockResponse::json(
+                404,
+                json!({ "error": { "code": 404, "message": "not found", "status": "NOT_FOUND" } }),
+            ),
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let file = manager.get_file("files/abc").await.unwrap();
+        manager.get_file(&file.name).await.unwrap();
+        manager.get_file(&file.uri).await.unwrap();
+        manager.get_file("abc").await.unwrap();
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| request.path() == "/v1beta/files/abc"));
+
+        assert!(matches!(
+            manager.get_file("files/gone").await,
+            Err(FileError::NotFound(name)) if name == "files/gone"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_all_files_across_pages() {
+        let now = Utc::now().to_rfc3339();
+        let server = MockServer::start(move |request| {
+            if request.method == "GET" {
+                let page: usize = request
+                    .query("pageToken")
+                    .map_or(0, |token| token.parse().unwrap());
+                let files: Vec<_> = (page * 10..(page * 10 + 10).min(25))
+                    .map(|i| {
+                        let mut file = file_json(&format!("files/f{}", i));
+                        if i >= 5 {
+                            file["createTime"] = json!(now);
+                        }
+                        file
+                    })
+                    .collect();
+                let mut body = json!({ "files": files });
+                if page < 2 {
+                    body["nextPageToken"] = json!((page + 1).to_string());
+                }
+                return MockResponse::json(200, body);
+            }
+            match request.path() {
+                "/v1beta/files/f3" => MockResponse::json(
+                    404,
+                    json!({ "error": { "code": 404, "message": "gone", "status": "NOT_FOUND" } }),
+                ),
+                "/v1beta/files/f7" => MockResponse::json(
+                    500,
+                    json!({ "error": { "code": 500, "message": "boom", "status": "INTERNAL" } }),
+                ),
+                _ => MockResponse::json(200, json!({})),
+            }
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let summary = manager.delete_all_files(4).await.unwrap();
+        assert_eq!(summary.deleted, 23);
+        assert_eq!(summary.missing, 1);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(summary.failures[0].0, "files/f7");
+        assert!(matches!(summary.failures[0].1, FileError::Api(_)));
+
+        let requests = server.requests();
+        assert_eq!(requests.iter().filter(|r| r.method == "GET").count(), 3);
+        let mut deleted: Vec<_> = requests
+            .iter()
+            .filter(|r| r.method == "DELETE")
+            .map(|r| r.path().to_string())
+            .collect();
+        deleted.sort();
+        deleted.dedup();
+        assert_eq!(deleted.len(), 25);
+
+        // Only the first five files were not created just now.
+        let summary = manager
+            .delete_files_older_than(Duration::from_secs(24 * 3600))
+            .await
+            .unwrap();
+        assert_eq!((summary.deleted, summary.missing), (4, 1));
+        assert_eq!(server.requests().len(), requests.len() + 3 + 5);
+    }
+
+    #[test]
+    fn test_video_metadata() {
+        let file: FileInfo = serde_json::from_value(json!({
+            "name": "files/7v9gbgnkdn4k",
+            "displayName": "GreatRedSpot.mp4",
+            "mimeType": "video/mp4",
+            "sizeBytes": "238190",
+            "createTime": "2024-08-26T15:50:57.460453Z",
+            "updateTime": "2024-08-26T15:51:07.385216Z",
+            "expirationTime": "2024-08-28T15:50:57.445839527Z",
+            "sha256Hash": "ODQ4ZGFmNjAzZjQ1ZjQxNmMxYzFlODg0NmU0NzMzMGJmNGY1ZGU4Y2IzODRiNzNmMTc2YTNhZTJkZjVkNGQzZg==",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/7v9gbgnkdn4k",
+            "state": "ACTIVE",
+            "videoMetadata": { "videoDuration": "13.500s", "frameRate": 30 }
+        }))
+        .unwrap();
+
+        assert_eq!(file.video_duration(), Some(Duration::from_millis(13_500)));
+        let metadata = file.video_metadata.as_ref().unwrap();
+        assert_eq!(metadata.extra["frameRate"], 30);
+        assert_eq!(
+            serde_json::to_value(metadata).unwrap(),
+            json!({ "videoDuration": "13.5s", "frameRate": 30 })
+        );
+
+        let image: FileInfo = serde_json::from_value(file_json("files/image")).unwrap();
+        assert_eq!(image.video_duration(), None);
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy::builder()
+            .max_retries(2)
+            .initial_backoff(Duration::from_millis(1))
+            .build()
+    }
+
+    fn unavailable() -> MockResponse {
+        MockResponse::json(
+            503,
+            json!({ "error": { "code": 503, "message": "overloaded", "status": "UNAVAILABLE" } }),
+        )
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let api_error =
+            |status| ApiError::from_parts(status, &reqwest::header::HeaderMap::new(), "");
+        assert!(FileError::Api(api_error(503)).is_retryable());
+        assert!(FileError::ContentUploadFailed(api_error(429)).is_retryable());
+        assert!(FileError::InitiationFailed(api_error(500)).is_retryable());
+        assert!(!FileError::Api(api_error(400)).is_retryable());
+        assert!(!FileError::NotFound("files/a".into()).is_retryable());
+        assert!(!FileError::UnexpectedResponse {
+            body: String::new()
+        }
+        .is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_metadata_requests_follow_the_retry_policy() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = MockServer::start({
+            let calls = calls.clone();
+            move |_| {
+                if calls
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                    .is_multiple_of(2)
+                {
+                    unavailable()
+                } else {
+                    MockResponse::json(200, file_json("files/abc"))
+                }
+            }
+        })
+        .await;
+
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+        assert!(matches!(
+            manager.get_file("abc").await,
+            Err(FileError::Api(error)) if error.status == 503
+        ));
+
+        let manager = manager.with_retry_policy(fast_retries());
+        calls.store(0, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(manager.get_file("abc").await.unwrap().name, "files/abc");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_chunk_is_retried_without_new_session() {
+        let failed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let server = MockServer::start({
+            let failed = failed.clone();
+            move |request| {
+                if request.path().starts_with("/upload/") {
+                    let host = request.header("host").unwrap();
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-url", format!("http://{}/session", host));
+                }
+                if request.header("x-goog-upload-command") == Some("query") {
+                    return MockResponse::json(200, json!({}))
+                        .with_header("x-goog-upload-status", "active")
+                        .with_header("x-goog-upload-size-received", "0");
+                }
+                if failed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                    MockResponse::json(200, json!({ "file": file_json("files/abc") }))
+                } else {
+                    unavailable()
+                }
+            }
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_retry_policy(fast_retries());
+
+        let file = manager
+            .upload_bytes(b"hello".to_vec(), "text/plain", None)
+            .await
+            .unwrap();
+        assert_eq!(file.name, "files/abc");
+
+        let requests = server.requests();
+        let starts = requests
+            .iter()
+            .filter(|request| request.path().starts_with("/upload/"))
+            .count();
+        assert_eq!(starts, 1);
+        let commands: Vec<_> = requests[1..]
+            .iter()
+            .map(|request| request.header("x-goog-upload-command").unwrap())
+            .collect();
+        assert_eq!(commands, ["upload, finalize", "query", "upload, finalize"]);
+    }
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn file_with_hash(hash: &str) -> serde_json::Value {
+        let mut file = file_json("files/abc");
+        file["sha256Hash"] = json!(hash);
+        file
+    }
+
+    #[test]
+    fn test_sha256_bytes_accepts_server_encodings() {
+        let digest = hex::decode(HELLO_SHA256).unwrap();
+        for hash in [
+            base64_engine.encode(HELLO_SHA256),
+            base64_engine.encode(&digest),
+            HELLO_SHA256.to_string(),
+        ] {
+            let file: FileInfo = serde_json::from_value(file_with_hash(&hash)).unwrap();
+            assert_eq!(file.sha256_bytes().unwrap().as_slice(), digest.as_slice());
+        }
+
+        let file: FileInfo = serde_json::from_value(file_with_hash("bm9wZQ==")).unwrap();
+        assert_eq!(file.sha256_bytes(), None);
+    }
+
+    #[tokio::test]
+    async fn test_upload_verifies_checksum() {
+        let server = upload_server(|| {
+            let hash = base64_engine.encode(HELLO_SHA256);
+            MockResponse::json(200, json!({ "file": file_with_hash(&hash) }))
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key")
+            .with_base_url(server.url())
+            .with_checksum_verification(true);
+
+        let file = manager
+            .upload_bytes(b"hello".to_vec(), "text/plain", None)
+            .await
+            .unwrap();
+        assert_eq!(file.name, "files/abc");
+    }
+
+    #[tokio::test]
+    async fn test_upload_reports_checksum_mismatch() {
+        let server = upload_server(|| {
+            let hash = base64_engine.encode(HELLO_SHA256);
+            MockResponse::json(200, json!({ "file": file_with_hash(&hash) }))
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+        assert!(manager
+            .upload_bytes(b"hellO".to_vec(), "text/plain", None)
+            .await
+            .is_ok());
+
+        let manager = manager.with_checksum_verification(true);
+        match manager
+            .upload_bytes(b"hellO".to_vec(), "text/plain", None)
+            .await
+        {
+            Err(FileError::ChecksumMismatch { expected, actual }) => {
+                assert_eq!(expected, HELLO_SHA256);
+                assert_ne!(actual, HELLO_SHA256);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_request_from_bytes() {
+        let server = upload_server(|| {
+            let hash = base64_engine.encode(HELLO_SHA256);
+            MockResponse::json(200, json!({ "file": file_with_hash(&hash) }))
+        })
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+        let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let request = UploadRequest::builder()
+            .bytes(b"hello".to_vec())
+            .mime_type("text/plain")
+            .display_name("greeting")
+            .description("A friendly greeting")
+            .verify_checksum(true)
+            .progress({
+                let reported = reported.clone();
+                move |progress| reported.lock().unwrap().push(progress.phase)
+            })
+            .build()
+            .unwrap();
+
+        let file = manager.upload(request).await.unwrap();
+        assert_eq!(file.name, "files/abc");
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].json(),
+            json!({ "file": { "display_name": "greeting", "description": "A friendly greeting" } })
+        );
+        assert_eq!(
+            requests[0].header("x-goog-upload-header-content-type"),
+            Some("text/plain")
+        );
+        assert_eq!(requests[1].body, b"hello");
+        assert_eq!(
+            *reported.lock().unwrap(),
+            [UploadPhase::Initiating, UploadPhase::Finalizing]
+        );
+    }
+
+    /// Serves the listing in pages of two files, recording the requested pages.
+    async fn paged_server(files: Vec<serde_json::Value>) -> MockServer {
+        MockServer::start(move |request| {
+            let page: usize = request
+                .query("pageToken")
+                .map_or(0, |token| token.parse().unwrap());
+            let mut body = json!({ "files": files[page * 2..(page * 2 + 2).min(files.len())] });
+            if page * 2 + 2 < files.len() {
+                body["nextPageToken"] = json!((page + 1).to_string());
+            }
+            MockResponse::json(200, body)
+        })
+        .await
+    }
+
+    fn named_file(name: &str, display_name: &str, hash: &str) -> serde_json::Value {
+        let mut file = file_with_hash(hash);
+        file["name"] = json!(name);
+        file["displayName"] = json!(display_name);
+        file
+    }
+
+    #[tokio::test]
+    async fn test_find_files_across_pages() {
+        let hello_hash = base64_engine.encode(HELLO_SHA256);
+        let other_hash = base64_engine.encode([7u8; 32]);
+        let server = paged_server(vec![
+            named_file("files/a", "report", &other_hash),
+            named_file("files/b", "notes", &other_hash),
+            named_file("files/c", "report", &hello_hash),
+            named_file("files/d", "notes", &other_hash),
+            named_file("files/e", "report", &other_hash),
+        ])
+        .await;
+        let manager = GoogleAIFileManager::new("key").with_base_url(server.url());
+
+        let reports = manager.find_files_by_display_name("report").await.unwrap();
+        assert_eq!(names(&reports), ["files/a", "files/c", "files/e"]);
+        assert_eq!(server.requests().len(), 3);
+
+        let map = manager.list_all_files_map().await.unwrap();
+        assert_eq!(map.len(), 5);
+        assert_eq!(map["files/d"].display_name.as_deref(), Some("notes"));
+
+        let found = manager.find_file_by_sha256(HELLO_SHA256).await.unwrap();
+        assert_eq!(found.unwrap().name, "files/c");
+        // The search stops at the page containing the match.
+        assert_eq!(server.requests().len(), 8);
+
+        let missing = manager
+            .find_file_by_sha256(&hex::encode([1u8; 32]))
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn test_try_from_env_without_api_key() {
+        let saved = std::env::var_os("GOOGLE_API_KEY");
+        std::env::remove_var("GOOGLE_API_KEY");
+        assert!(matches!(
+            GoogleAIFileManager::try_from_env(),
+            Err(FileError::MissingApiKey)
+        ));
+
+        std::env::set_var("GOOGLE_API_KEY", "key");
+        assert!(GoogleAIFileManager::try_from_env().is_ok());
+
+        match saved {
+            Some(key) => std::env::set_var("GOOGLE_API_KEY", key),
+            None => std::env::remove_var("GOOGLE_API_KEY"),
+        }
     }
 }
@@ -0,0 +1,245 @@
+//! Options of a single upload, consumed by [`GoogleAIFileManager::upload`].
+
+use std::fmt;
+use std::path::PathBuf;
+
+use tokio::io::AsyncRead;
+
+#[cfg(doc)]
+use super::GoogleAIFileManager;
+use super::{mime_header, FileError, UploadProgress};
+
+/// Where the content of an upload comes from.
+pub(super) enum UploadSource {
+    /// A file on disk.
+    Path(PathBuf),
+    /// Data held in memory.
+    Bytes(Vec<u8>),
+    /// A reader yielding exactly `len` bytes.
+    Reader {
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        len: u64,
+    },
+}
+
+/// A callback receiving the progress of an upload.
+pub(super) type ProgressCallback = Box<dyn Fn(UploadProgress) + Send + Sync>;
+
+/// An upload to the Gemini AI system, created with [`UploadRequest::builder`].
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::file::UploadRequest;
+///
+/// let request = UploadRequest::builder()
+///     .path("report.pdf")
+///     .display_name("Quarterly report")
+///     .mime_type("application/pdf")
+///     .verify_checksum(true)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct UploadRequest {
+    pub(super) source: UploadSource,
+    pub(super) mime_type: Option<String>,
+    pub(super) display_name: Option<String>,
+    pub(super) description: Option<String>,
+    pub(super) verify_checksum: Option<bool>,
+    pub(super) progress: Option<ProgressCallback>,
+}
+
+impl UploadRequest {
+    /// Creates a builder for an upload.
+    pub fn builder() -> UploadRequestBuilder {
+        UploadRequestBuilder::default()
+    }
+}
+
+impl fmt::Debug for UploadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            UploadSource::Path(path) => format!("Path({:?})", path),
+            UploadSource::Bytes(data) => format!("Bytes({} bytes)", data.len()),
+            UploadSource::Reader { len, .. } => format!("Reader({} bytes)", len),
+        };
+        f.debug_struct("UploadRequest")
+            .field("source", &source)
+            .field("mime_type", &self.mime_type)
+            .field("display_name", &self.display_name)
+            .field("description", &self.description)
+            .field("verify_checksum", &self.verify_checksum)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Builder for [`UploadRequest`].
+///
+/// Exactly one source must be set: [`path`](Self::path), [`bytes`](Self::bytes) or
+/// [`reader`](Self::reader). Uploads from memory or a reader need a
+/// [`mime_type`](Self::mime_type); for files it is guessed from the extension unless
+/// given.
+#[derive(Default)]
+pub struct UploadRequestBuilder {
+    path: Option<PathBuf>,
+    bytes: Option<Vec<u8>>,
+    reader: Option<(Box<dyn AsyncRead + Send + Unpin>, u64)>,
+    mime_type: Option<String>,
+    display_name: Option<String>,
+    description: Option<String>,
+    verify_checksum: Option<bool>,
+    progress: Option<ProgressCallback>,
+}
+
+impl UploadRequestBuilder {
+    /// Uploads the file at `path`, streaming it in chunks.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Uploads data held in memory.
+    pub fn bytes(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.bytes = Some(data.into());
+        self
+    }
+
+    /// Uploads exactly `len` bytes read from `reader`, e.g. a download stream.
+    pub fn reader(mut self, reader: impl AsyncRead + Send + Unpin + 'static, len: u64) -> Self {
+        self.reader = Some((Box::new(reader), len));
+        self
+    }
+
+    /// Sets the MIME type of the content, e.g. `application/pdf`.
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Sets the display name of the file. Files uploaded from a path default to the
+    /// file name.
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    /// Sets the description of the file.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets whether the upload is verified against the SHA-256 hash reported by the
+    /// server, overriding
+    /// [`GoogleAIFileManager::with_checksum_verification`].
+    pub fn verify_checksum(mut self, verify_checksum: bool) -> Self {
+        self.verify_checksum = Some(verify_checksum);
+        self
+    }
+
+    /// Sets a callback receiving the progress of the upload.
+    ///
+    /// See [`GoogleAIFileManager::upload_file_with_progress`] for when it is invoked.
+    pub fn progress(mut self, progress: impl Fn(UploadProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Builds the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FileError::InvalidUploadRequest`] if not exactly one source is set, or
+    /// if the content comes from memory or a reader and no MIME type is set.
+    /// Returns [`FileError::MimeTypeError`] if the MIME type is invalid.
+    pub fn build(self) -> Result<UploadRequest, FileError> {
+        let source = match (self.path, self.bytes, self.reader) {
+            (Some(path), None, None) => UploadSource::Path(path),
+            (None, Some(data), None) => UploadSource::Bytes(data),
+            (None, None, Some((reader, len))) => UploadSource::Reader { reader, len },
+            (None, None, None) => {
+                return Err(FileError::InvalidUploadRequest(
+                    "one of path, bytes or reader must be set".into(),
+                ))
+            }
+            _ => {
+                return Err(FileError::InvalidUploadRequest(
+                    "path, bytes and reader are mutually exclusive".into(),
+                ))
+            }
+        };
+
+        match &self.mime_type {
+            Some(mime_type) => {
+                mime_header(mime_type)?;
+            }
+            None if !matches!(source, UploadSource::Path(_)) => {
+                return Err(FileError::InvalidUploadRequest(
+                    "a MIME type is required to upload bytes or a reader".into(),
+                ))
+            }
+            None => {}
+        }
+
+        Ok(UploadRequest {
+            source,
+            mime_type: self.mime_type,
+            display_name: self.display_name,
+            description: self.description,
+            verify_checksum: self.verify_checksum,
+            progress: self.progress,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_request_reason(builder: UploadRequestBuilder) -> String {
+        match builder.build() {
+            Err(FileError::InvalidUploadRequest(reason)) => reason,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_builder_requires_exactly_one_source() {
+        assert!(invalid_request_reason(UploadRequest::builder()).contains("must be set"));
+
+        let reason = invalid_request_reason(
+            UploadRequest::builder()
+                .path("notes.txt")
+                .bytes(b"hello".to_vec())
+                .mime_type("text/plain"),
+        );
+        assert!(reason.contains("mutually exclusive"));
+
+        let reason = invalid_request_reason(
+            UploadRequest::builder()
+                .bytes(b"hello".to_vec())
+                .reader(std::io::Cursor::new(b"hello".to_vec()), 5)
+                .mime_type("text/plain"),
+        );
+        assert!(reason.contains("mutually exclusive"));
+    }
+
+    #[test]
+    fn test_builder_validates_mime_type() {
+        let reason = invalid_request_reason(UploadRequest::builder().bytes(b"hello".to_vec()));
+        assert!(reason.contains("MIME type"));
+
+        assert!(matches!(
+            UploadRequest::builder()
+                .bytes(b"hello".to_vec())
+                .mime_type("video/")
+                .build(),
+            Err(FileError::MimeTypeError(_))
+        ));
+
+        let request = UploadRequest::builder().path("notes.txt").build().unwrap();
+        assert!(matches!(request.source, UploadSource::Path(_)));
+        assert_eq!(request.mime_type, None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Rotation of API keys across requests.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::error::GoogleGenerativeAIError;
+
+/// Default time a key is skipped after the API rejected it with `429 Too Many Requests`.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// A pool of API keys shared by the clients of this crate.
+///
+/// Keys are handed out round-robin, one per request. A key that receives a
+/// `429 Too Many Requests` response is skipped until its cooldown expires. When every
+/// key is cooling down, the one that becomes available first is used.
+///
+/// Cloning a pool is cheap and the clones share the rotation state, so the same pool
+/// can be given to a [`GenerativeModel`](crate::GenerativeModel), a
+/// [`GoogleAIFileManager`](crate::GoogleAIFileManager) and a
+/// [`CacheManager`](crate::cache::CacheManager).
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::{key_pool::KeyPool, models::ModelParams, GenerativeModel};
+///
+/// let pool = KeyPool::new(["key-a", "key-b"]).unwrap();
+/// let model = GenerativeModel::with_key_pool(pool, ModelParams::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyPool {
+    keys: Arc<[String]>,
+    cooldown: Duration,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    next: usize,
+    cooling_until: Vec<Option<Instant>>,
+}
+
+/// An API key handed out by a [`KeyPool`] for a single request.
+#[derive(Debug, Clone)]
+pub(crate) struct PooledKey {
+    index: usize,
+    key: String,
+}
+
+impl PooledKey {
+    /// Returns the key itself.
+    pub(crate) fn as_str(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns a redacted label of the key that is safe to log.
+    pub(crate) fn label(&self) -> String {
+        redact(&self.key)
+    }
+}
+
+impl KeyPool {
+    /// Creates a pool from the given keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no keys are given.
+    pub fn new<I, K>(keys: I) -> Result<Self, GoogleGenerativeAIError>
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        let keys: Vec<String> = keys.into_iter().map(Into::into).collect();
+        if keys.is_empty() {
+            return Err(GoogleGenerativeAIError::new(
+                "A key pool requires at least one API key",
+            ));
+        }
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(PoolState {
+                next: 0,
+                cooling_until: vec![None; keys.len()],
+            })),
+            keys: keys.into(),
+            cooldown: DEFAULT_COOLDOWN,
+        })
+    }
+
+    /// Sets how long a rate-limited key is skipped.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Returns the number of keys in the pool.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the pool contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the redacted labels of the keys that are currently cooling down.
+    pub fn cooling_down(&self) -> Vec<String> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .cooling_until
+            .iter()
+            .enumerate()
+            .filter(|(_, until)| until.is_some_and(|until| until > now))
+            .map(|(index, _)| redact(&self.keys[index]))
+            .collect()
+    }
+
+    /// Picks the key for the next request.
+    pub(crate) fn acquire(&self) -> PooledKey {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let len = self.keys.len();
+
+        let available = (0..len)
+            .map(|offset| (state.next + offset) % len)
+            .find(|&index| state.cooling_until[index].is_none_or(|until| until <= now));
+
+        let index = available.unwrap_or_else(|| {
+            (0..len)
+                .min_by_key(|&index| state.cooling_until[index])
+                .unwrap_or_default()
+        });
+
+        state.next = (index + 1) % len;
+        PooledKey {
+            index,
+            key: self.keys[index].clone(),
+        }
+    }
+
+    /// Marks a key as rate limited so that it is skipped for the cooldown period.
+    pub(crate) fn report_rate_limited(&self, key: &PooledKey) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = state.cooling_until.get_mut(key.index) {
+            *until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+impl From<String> for KeyPool {
+    fn from(key: String) -> Self {
+        Self {
+            keys: vec![key].into(),
+            cooldown: DEFAULT_COOLDOWN,
+            state: Arc::new(Mutex::new(PoolState {
+                next: 0,
+                cooling_until: vec![None],
+            })),
+        }
+    }
+}
+
+impl From<&str> for KeyPool {
+    fn from(key: &str) -> Self {
+        Self::from(key.to_string())
+    }
+}
+
+/// Keys shorter than this are masked entirely by [`redact`].
+const MIN_REDACTED_KEY_LEN: usize = 8;
+
+/// Keeps only the last four characters of a key, or none of a short key.
+fn redact(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < MIN_REDACTED_KEY_LEN {
+        return "***".to_string();
+    }
+    let visible: String = chars[chars.len() - 4..].iter().collect();
+    format!("...{}", visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin() {
+        let pool = KeyPool::new(["a", "b", "c"]).unwrap();
+        let keys: Vec<String> = (0..4).map(|_| pool.acquire().key).collect();
+        assert_eq!(keys, ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_rate_limited_key_is_skipped() {
+        let pool = KeyPool::new(["a", "b"]).unwrap();
+        let first = pool.acquire();
+        pool.report_rate_limited(&first);
+
+        assert_eq!(pool.acquire().key, "b");
+        assert_eq!(pool.acquire().key, "b");
+        assert_eq!(pool.cooling_down(), vec!["***".to_string()]);
+    }
+
+    #[test]
+    fn test_all_keys_cooling_down_uses_earliest() {
+        let pool = KeyPool::new(["a", "b"]).unwrap();
+        let a = pool.acquire();
+        pool.report_rate_limited(&a);
+        let b = pool.acquire();
+        pool.report_rate_limited(&b);
+
+        assert_eq!(pool.acquire().key, "a");
+    }
+
+    #[test]
+    fn test_cooldown_expires() {
+        let pool = KeyPool::new(["a", "b"])
+            .unwrap()
+            .with_cooldown(Duration::ZERO);
+        let a = pool.acquire();
+        pool.report_rate_limited(&a);
+
+        assert_eq!(pool.acquire().key, "b");
+        assert_eq!(pool.acquire().key, "a");
+    }
+
+    #[test]
+    fn test_empty_pool_is_rejected() {
+        assert!(KeyPool::new(Vec::<String>::new()).is_err());
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("AIzaSyExample1234"), "...1234");
+        assert_eq!(redact("12345678"), "...5678");
+        assert_eq!(redact("1234567"), "***");
+        assert_eq!(redact("abcd"), "***");
+        assert_eq!(redact(""), "***");
+    }
+}
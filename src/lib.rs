@@ -8,9 +8,18 @@
 pub mod cache;
 pub mod chat;
 pub mod client;
+mod decoder;
 pub mod error;
 pub mod file;
+pub mod key_pool;
 pub mod models;
+pub mod retry;
+pub mod structured;
+pub mod tools;
 
-pub use client::GenerativeModel;
+#[cfg(test)]
+mod test_utils;
+
+pub use client::{GeminiClients, GenerativeModel};
 pub use file::GoogleAIFileManager;
+pub use key_pool::KeyPool;
@@ -1,4 +1,7 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 /// A tool that enables the model to execute code as part of generation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,12 +22,70 @@ pub struct CodeExecutionConfig {}
 pub struct CodeExecutionResult {
     /// The outcome of the code execution.
     pub outcome: CodeExecutionOutcome,
-    /// The output produced by the code execution.
+    /// The output produced by the code execution: stdout if it succeeded, stderr or
+    /// another description otherwise. Empty if there was none.
+    #[serde(default)]
     pub output: String,
 }
 
+impl CodeExecutionResult {
+    /// Returns `true` if the code ran successfully.
+    pub fn is_success(&self) -> bool {
+        self.outcome.is_success()
+    }
+
+    /// Returns the output of a successful execution, or an error carrying the
+    /// outcome and the stderr output of a failed one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{CodeExecutionOutcome, CodeExecutionResult};
+    ///
+    /// let result = CodeExecutionResult {
+    ///     outcome: CodeExecutionOutcome::Error,
+    ///     output: "ZeroDivisionError: division by zero".to_string(),
+    /// };
+    ///
+    /// assert_eq!(
+    ///     result.into_result().unwrap_err().to_string(),
+    ///     "Code execution failed: ZeroDivisionError: division by zero"
+    /// );
+    /// ```
+    pub fn into_result(self) -> Result<String, CodeExecutionError> {
+        if self.is_success() {
+            Ok(self.output)
+        } else {
+            Err(CodeExecutionError {
+                outcome: self.outcome,
+                output: self.output,
+            })
+        }
+    }
+}
+
+/// Code run by the model did not succeed, see [`CodeExecutionResult::into_result`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Code execution {outcome}{}", failure_details(output))]
+pub struct CodeExecutionError {
+    /// The outcome of the execution.
+    pub outcome: CodeExecutionOutcome,
+    /// The stderr output or other description of the failure.
+    pub output: String,
+}
+
+/// Formats the output of a failed execution for [`CodeExecutionError`].
+fn failure_details(output: &str) -> String {
+    let output = output.trim_end();
+    if output.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", output)
+    }
+}
+
 /// Possible outcomes of code execution.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum CodeExecutionOutcome {
     /// Code executed successfully.
@@ -36,6 +97,31 @@ pub enum CodeExecutionOutcome {
     /// Code execution was blocked.
     #[serde(rename = "OUTCOME_BLOCKED")]
     Blocked,
+    /// Code execution ran too long and was cancelled.
+    #[serde(rename = "OUTCOME_DEADLINE_EXCEEDED")]
+    DeadlineExceeded,
+    /// The outcome was not specified.
+    #[serde(rename = "OUTCOME_UNSPECIFIED")]
+    Unspecified,
+}
+
+impl CodeExecutionOutcome {
+    /// Returns `true` if the code ran successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Ok)
+    }
+}
+
+impl fmt::Display for CodeExecutionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "succeeded",
+            Self::Error => "failed",
+            Self::Blocked => "was blocked",
+            Self::DeadlineExceeded => "exceeded the deadline",
+            Self::Unspecified => "ended with an unspecified outcome",
+        })
+    }
 }
 
 /// Represents executable code in a specific programming language.
@@ -47,3 +133,83 @@ pub struct ExecutableCode {
     /// The actual code to be executed.
     pub code: String,
 }
+
+/// Code the model ran, paired with its result, see
+/// [`Response::code_execution_summary`](super::Response::code_execution_summary).
+#[derive(Debug, Clone)]
+pub struct CodeExecution {
+    /// The code the model generated.
+    pub code: ExecutableCode,
+    /// The result of running it, if the response contains one.
+    pub result: Option<CodeExecutionResult>,
+}
+
+impl CodeExecution {
+    /// Returns the outcome of the execution, if there is a result.
+    pub fn outcome(&self) -> Option<CodeExecutionOutcome> {
+        self.result.as_ref().map(|result| result.outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_outcomes() {
+        let outcomes = [
+            ("OUTCOME_OK", CodeExecutionOutcome::Ok, true),
+            ("OUTCOME_ERROR", CodeExecutionOutcome::Error, false),
+            ("OUTCOME_BLOCKED", CodeExecutionOutcome::Blocked, false),
+            (
+                "OUTCOME_DEADLINE_EXCEEDED",
+                CodeExecutionOutcome::DeadlineExceeded,
+                false,
+            ),
+            (
+                "OUTCOME_UNSPECIFIED",
+                CodeExecutionOutcome::Unspecified,
+                false,
+            ),
+        ];
+        for (name, outcome, success) in outcomes {
+            assert_eq!(
+                serde_json::from_value::<CodeExecutionOutcome>(json!(name)).unwrap(),
+                outcome
+            );
+            assert_eq!(outcome.is_success(), success, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_into_result() {
+        let result = |outcome, output: &str| CodeExecutionResult {
+            outcome,
+            output: output.to_string(),
+        };
+
+        assert_eq!(
+            result(CodeExecutionOutcome::Ok, "42\n").into_result(),
+            Ok("42\n".to_string())
+        );
+        assert_eq!(
+            result(
+                CodeExecutionOutcome::Error,
+                "Traceback (most recent call last):\nNameError: name 'x' is not defined\n"
+            )
+            .into_result()
+            .unwrap_err()
+            .to_string(),
+            "Code execution failed: Traceback (most recent call last):\nNameError: name 'x' is not defined"
+        );
+        assert_eq!(
+            result(CodeExecutionOutcome::DeadlineExceeded, "")
+                .into_result()
+                .unwrap_err()
+                .to_string(),
+            "Code execution exceeded the deadline"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Helpers for the protobuf JSON duration format (e.g. `"3.5s"`).
+
+use std::time::Duration;
+
+/// Parses a duration in the protobuf JSON format, e.g. `"30s"` or `"0.250s"`.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let seconds: f64 = value.trim().strip_suffix('s')?.parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Formats a duration in the protobuf JSON format, e.g. `"30s"` or `"0.25s"`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let nanos = duration.subsec_nanos();
+    if nanos == 0 {
+        return format!("{}s", duration.as_secs());
+    }
+    let fraction = format!("{:09}", nanos);
+    format!("{}.{}s", duration.as_secs(), fraction.trim_end_matches('0'))
+}
+
+/// (De)serializes a [`Duration`] in the protobuf JSON format, for `#[serde(with)]`.
+pub(crate) mod duration_string {
+    use std::time::Duration;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_duration(*duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        super::parse_duration(&value)
+            .ok_or_else(|| de::Error::custom(format!("invalid duration {:?}", value)))
+    }
+}
+
+/// (De)serializes an optional [`Duration`] in the protobuf JSON format, for
+/// `#[serde(with)]` together with `#[serde(default)]`.
+pub(crate) mod option_duration_string {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::duration_string::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::duration_string")] Duration);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(duration)| duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("0.250s"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("abcs"), None);
+    }
+
+    #[test]
+    fn test_format_duration_round_trips() {
+        for duration in [
+            Duration::from_millis(250),
+            Duration::from_secs(45),
+            Duration::from_secs(300),
+            Duration::from_secs(3 * 24 * 60 * 60),
+            Duration::new(90, 5),
+        ] {
+            assert_eq!(parse_duration(&format_duration(duration)), Some(duration));
+        }
+        assert_eq!(format_duration(Duration::from_secs(300)), "300s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+    }
+}
@@ -1,31 +1,19 @@
 //! Function declarations and related types for the Gemini AI API.
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
 use typed_builder::TypedBuilder;
 
-use super::schema::{Schema, SchemaType};
+use super::schema::{Schema, SchemaType, SchemaViolation};
 
 /// A function declaration schema that can be passed to the model.
-#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
-#[serde(rename_all = "camelCase")]
-pub struct FunctionDeclarationSchema {
-    /// The type of the parameter.
-    pub r#type: SchemaType,
-
-    /// The properties of the parameter.
-    #[builder(setter(into))]
-    pub properties: std::collections::HashMap<String, Schema>,
-
-    /// Optional. Description of the parameter.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default, setter(strip_option, into))]
-    pub description: Option<String>,
-
-    /// Optional. Array of required parameters.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default, setter(strip_option))]
-    pub required: Option<Vec<String>>,
-}
+///
+/// [`FunctionDeclaration::parameters`] is a plain [`Schema`], so every schema
+/// helper works for function parameters too. Code built with
+/// `FunctionDeclarationSchema::builder()` keeps compiling and serializes the same.
+#[deprecated(note = "use `Schema` for function parameters")]
+pub type FunctionDeclarationSchema = Schema;
 
 /// A function declaration that can be passed to the model.
 ///
@@ -39,6 +27,7 @@ pub struct FunctionDeclarationSchema {
 #[serde(rename_all = "camelCase")]
 pub struct FunctionDeclaration {
     /// The name of the function.
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub name: Option<String>,
 
@@ -49,8 +38,8 @@ pub struct FunctionDeclaration {
 
     /// The parameters of the function in JSON Schema format.
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default, setter(strip_option))]
-    pub parameters: Option<FunctionDeclarationSchema>,
+    #[builder(default, setter(strip_option, into))]
+    pub parameters: Option<Schema>,
 }
 
 impl Default for FunctionDeclaration {
@@ -77,13 +66,51 @@ impl FunctionDeclaration {
         self
     }
 
+    /// Checks the arguments of `call` against the declared parameters.
+    ///
+    /// Besides the checks of [`Schema::validate`], arguments the parameters do not
+    /// declare are reported as [`SchemaViolation::UnknownProperty`]. A function
+    /// without parameters accepts no arguments.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{FunctionCall, FunctionDeclaration};
+    ///
+    /// let get_weather = FunctionDeclaration::new()
+    ///     .with_name("get_weather")
+    ///     .with_parameters(&["unit, string:enum(celsius,fahrenheit), The unit"])
+    ///     .unwrap();
+    /// let call = FunctionCall {
+    ///     name: "get_weather".to_string(),
+    ///     args: serde_json::json!({ "unit": "kelvin" }),
+    /// };
+    ///
+    /// assert!(get_weather.validate_call(&call).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns every [`SchemaViolation`] found.
+    pub fn validate_call(&self, call: &FunctionCall) -> Result<(), Vec<SchemaViolation>> {
+        match &self.parameters {
+            Some(parameters) => parameters.validate_with(&call.args, true),
+            None => Schema::builder()
+                .r#type(SchemaType::Object)
+                .properties(std::collections::HashMap::new())
+                .build()
+                .validate_with(&call.args, true),
+        }
+    }
+
     /// Parses a schema type and any modifiers from a type string
     /// Format: "type[:modifier(value1,value2,...)]"
-    fn parse_schema_type_with_modifiers(type_str: &str) -> (SchemaType, Option<Vec<String>>) {
-        println!("\nParsing type string: {}", type_str);
+    fn parse_schema_type_with_modifiers(
+        type_str: &str,
+        lenient: bool,
+    ) -> Result<(SchemaType, Option<Vec<String>>), ParameterError> {
         let parts: Vec<&str> = type_str.split(':').collect();
         let base_type = parts[0].trim();
-        println!("Base type: {}", base_type);
 
         let schema_type = match base_type {
             "string" => SchemaType::String,
@@ -92,7 +119,12 @@ impl FunctionDeclaration {
             "boolean" => SchemaType::Boolean,
             "array" => SchemaType::Array,
             "object" => SchemaType::Object,
-            _ => SchemaType::String, // Default to string for unknown types
+            _ if lenient => SchemaType::String, // Default to string for unknown types
+            _ => {
+                return Err(ParameterError::UnknownType {
+                    type_name: base_type.to_string(),
+                })
+            }
         };
 
         // Check for enum modifier
@@ -127,7 +159,6 @@ impl FunctionDeclaration {
             // If we found a complete enum modifier, parse it
             if let (Some(start), Some(end)) = (enum_start, enum_end) {
                 let enum_str = &modifier[start..end];
-                println!("Found enum values: '{}'", enum_str);
 
                 // Split by comma but handle special cases
                 let mut values = Vec::new();
@@ -164,21 +195,26 @@ impl FunctionDeclaration {
                 }
 
                 enum_values = Some(values);
-                println!("Parsed enum values: {:?}", enum_values);
-            } else {
-                println!("No valid enum modifier found");
+            } else if enum_start.is_some() && !lenient {
+                return Err(ParameterError::UnterminatedEnum {
+                    type_str: type_str.to_string(),
+                });
             }
         }
 
-        (schema_type, enum_values)
+        Ok((schema_type, enum_values))
     }
 
-    /// Parses a parameter definition into a Schema.
-    fn parse_parameter(param_str: &str) -> Option<(String, Schema)> {
-        let parts: Vec<&str> = param_str.split('|').map(str::trim).collect();
+    /// Parses a parameter definition into a Schema, and whether the parameter is
+    /// required.
+    fn parse_parameter(
+        param_str: &str,
+        lenient: bool,
+    ) -> Result<(String, Schema, bool), ParameterError> {
+        let (definition, object_properties) = split_outside_brackets(param_str, '|');
 
         // First split by comma, but handle the case where we have enum values
-        let mut remaining = parts[0];
+        let mut remaining = definition.trim();
         let mut base_parts = Vec::new();
 
         // Extract name (everything up to first comma)
@@ -186,8 +222,22 @@ impl FunctionDeclaration {
             base_parts.push(name.trim());
             remaining = rest.trim();
 
-            // Extract type and enum values if present
-            if remaining.contains("enum(") {
+            // Extract the array type, which may contain commas itself
+            let array_end = remaining
+                .starts_with("array<")
+                .then(|| closing_bracket(remaining, '<', '>'))
+                .flatten();
+            if let Some(end) = array_end {
+                base_parts.push(remaining[..=end].trim());
+                remaining = remaining[end + 1..].trim_start();
+                if let Some(description) = remaining.strip_prefix(',') {
+                    remaining = description.trim();
+                }
+            } else if remaining.starts_with("array<") && !lenient {
+                return Err(ParameterError::UnterminatedArray {
+                    type_str: remaining.to_string(),
+                });
+            } else if remaining.contains("enum(") {
                 if let Some(end_paren) = remaining.rfind(')') {
                     let type_and_enum = &remaining[..=end_paren];
                     base_parts.push(type_and_enum.trim());
@@ -210,28 +260,39 @@ impl FunctionDeclaration {
         }
 
         if base_parts.len() < 2 {
-            return None;
+            return Err(ParameterError::MissingType {
+                definition: param_str.trim().to_string(),
+            });
         }
 
-        let name = base_parts[0].to_string();
+        let (name, required) = parse_name(base_parts[0]);
         let type_str = base_parts[1];
         let description = base_parts
             .get(2)
             .map(|s| s.trim().to_string())
             .unwrap_or_default();
-        let (schema_type, enum_values) = Self::parse_schema_type_with_modifiers(type_str);
+
+        if type_str.starts_with("array<") {
+            let schema = Schema {
+                description: Some(description),
+                ..Self::parse_array_type(type_str, lenient)?
+            };
+            return Ok((name, schema, required));
+        }
+
+        let (schema_type, enum_values) = Self::parse_schema_type_with_modifiers(type_str, lenient)?;
 
         // Handle object type with properties
-        if schema_type == SchemaType::Object && parts.len() > 1 {
-            let properties = Self::parse_object_properties(parts[1]);
-            let required: Vec<String> = properties.keys().cloned().collect();
+        if let (SchemaType::Object, Some(object_properties)) = (&schema_type, object_properties) {
+            let (properties, required_properties) =
+                Self::parse_object_properties(object_properties, lenient)?;
             let schema = Schema::builder()
                 .r#type(schema_type)
                 .description(description)
                 .properties(properties)
-                .required(required)
+                .required(required_properties)
                 .build();
-            Some((name, schema))
+            Ok((name, schema, required))
         } else {
             let schema = if let Some(values) = enum_values {
                 Schema::builder()
@@ -245,19 +306,71 @@ impl FunctionDeclaration {
                     .description(description)
                     .build()
             };
-            Some((name, schema))
+            Ok((name, schema, required))
         }
     }
 
-    /// Parses object properties string into a HashMap of property schemas.
+    /// Parses an `array<item>` type into an array schema.
     ///
-    /// Format: "prop1:type[:desc], prop2:type[:desc], prop3:{subprop1:type, subprop2:type}"
-    fn parse_object_properties(props_str: &str) -> std::collections::HashMap<String, Schema> {
-        println!("\nParsing object properties: {}", props_str);
+    /// The item is any type, including another array, or `object|properties` for
+    /// object items.
+    fn parse_array_type(type_str: &str, lenient: bool) -> Result<Schema, ParameterError> {
+        let item = type_str
+            .strip_prefix("array<")
+            .and_then(|rest| rest.trim_end().strip_suffix('>'));
+        let Some(item) = item else {
+            if lenient {
+                return Ok(Schema::builder().r#type(SchemaType::Array).build());
+            }
+            return Err(ParameterError::UnterminatedArray {
+                type_str: type_str.to_string(),
+            });
+        };
+
+        let (item_type, item_properties) = split_outside_brackets(item.trim(), '|');
+        let item_type = item_type.trim();
+        let items = if item_type.starts_with("array<") {
+            Self::parse_array_type(item_type, lenient)?
+        } else {
+            let (schema_type, enum_values) =
+                Self::parse_schema_type_with_modifiers(item_type, lenient)?;
+            match (schema_type, item_properties) {
+                (SchemaType::Object, Some(item_properties)) => {
+                    let (properties, required) =
+                        Self::parse_object_properties(item_properties, lenient)?;
+                    Schema::builder()
+                        .r#type(SchemaType::Object)
+                        .properties(properties)
+                        .required(required)
+                        .build()
+                }
+                (schema_type, _) => Schema {
+                    enum_values,
+                    ..Schema::builder().r#type(schema_type).build()
+                },
+            }
+        };
+
+        Ok(Schema::builder()
+            .r#type(SchemaType::Array)
+            .items(Box::new(items))
+            .build())
+    }
+
+    /// Parses object properties string into a HashMap of property schemas and the
+    /// names of the required properties.
+    ///
+    /// Format: "prop1:type[:desc], prop2?:type[:desc], prop3:{subprop1:type, subprop2:type}"
+    fn parse_object_properties(
+        props_str: &str,
+        lenient: bool,
+    ) -> Result<(std::collections::HashMap<String, Schema>, Vec<String>), ParameterError> {
         let mut properties = std::collections::HashMap::new();
+        let mut required = Vec::new();
         let mut current_prop = String::new();
         let mut brace_count = 0;
         let mut paren_count = 0;
+        let mut angle_count = 0;
 
         // First, properly split the properties handling nested braces and parentheses
         let mut props = Vec::new();
@@ -279,9 +392,16 @@ impl FunctionDeclaration {
                     paren_count -= 1;
                     current_prop.push(c);
                 }
-                ',' if brace_count == 0 && paren_count == 0 => {
+                '<' => {
+                    angle_count += 1;
+                    current_prop.push(c);
+                }
+                '>' if angle_count > 0 => {
+                    angle_count -= 1;
+                    current_prop.push(c);
+                }
+                ',' if brace_count == 0 && paren_count == 0 && angle_count == 0 => {
                     if !current_prop.trim().is_empty() {
-                        println!("Found property: {}", current_prop.trim());
                         props.push(current_prop.trim().to_string());
                         current_prop.clear();
                     }
@@ -292,25 +412,22 @@ impl FunctionDeclaration {
             }
         }
         if !current_prop.trim().is_empty() {
-            println!("Found property: {}", current_prop.trim());
             props.push(current_prop.trim().to_string());
         }
 
         // Now process each property
         for prop in props {
             let prop = prop.trim();
-            println!("\nProcessing property: {}", prop);
 
             // Check if this is a nested object
-            if prop.contains('{') {
+            let is_array = prop
+                .split_once(':')
+                .is_some_and(|(_, rest)| rest.trim_start().starts_with("array<"));
+            if prop.contains('{') && !is_array {
                 let nested_parts: Vec<&str> = prop.splitn(2, ':').collect();
                 if nested_parts.len() == 2 {
-                    let prop_name = nested_parts[0].to_string();
+                    let (prop_name, prop_required) = parse_name(nested_parts[0]);
                     let mut nested_props_str = nested_parts[1].to_string();
-                    println!(
-                        "Found nested object - name: {}, props: {}",
-                        prop_name, nested_props_str
-                    );
 
                     // Remove outer braces and any trailing comma
                     nested_props_str = nested_props_str
@@ -318,26 +435,33 @@ impl FunctionDeclaration {
                         .trim_end_matches('}')
                         .trim_end_matches(',')
                         .to_string();
-                    println!("Cleaned nested props: {}", nested_props_str);
 
-                    let nested_properties = Self::parse_object_properties(&nested_props_str);
-                    let required: Vec<String> = nested_properties.keys().cloned().collect();
+                    let (nested_properties, nested_required) =
+                        Self::parse_object_properties(&nested_props_str, lenient)?;
                     let schema = Schema::builder()
                         .r#type(SchemaType::Object)
                         .properties(nested_properties)
-                        .required(required)
+                        .required(nested_required)
                         .build();
+                    if prop_required {
+                        required.push(prop_name.clone());
+                    }
                     properties.insert(prop_name, schema);
+                } else if !lenient {
+                    return Err(ParameterError::MalformedProperty {
+                        property: prop.to_string(),
+                    });
                 }
             } else {
                 // Handle basic property by finding the last colon that's not inside enum()
                 let mut parts = Vec::new();
                 let mut current_part = String::new();
                 let mut paren_count = 0;
+                let mut angle_count = 0;
 
                 for c in prop.chars() {
                     match c {
-                        ':' if paren_count == 0 => {
+                        ':' if paren_count == 0 && angle_count == 0 => {
                             if !current_part.is_empty() {
                                 parts.push(current_part.trim().to_string());
                                 current_part.clear();
@@ -351,6 +475,14 @@ impl FunctionDeclaration {
                             paren_count -= 1;
                             current_part.push(c);
                         }
+                        '<' => {
+                            angle_count += 1;
+                            current_part.push(c);
+                        }
+                        '>' if angle_count > 0 => {
+                            angle_count -= 1;
+                            current_part.push(c);
+                        }
                         _ => {
                             current_part.push(c);
                         }
@@ -361,10 +493,8 @@ impl FunctionDeclaration {
                     parts.push(current_part.trim().to_string());
                 }
 
-                println!("Property parts: {:?}", parts);
-
                 if parts.len() >= 2 {
-                    let prop_name = parts[0].to_string();
+                    let (prop_name, prop_required) = parse_name(&parts[0]);
                     let type_str = if parts.len() > 2 {
                         parts[1..parts.len() - 1].join(":")
                     } else {
@@ -376,25 +506,34 @@ impl FunctionDeclaration {
                         None
                     };
 
-                    println!(
-                        "Name: {}, Type: {}, Description: {:?}",
-                        prop_name, type_str, description
-                    );
-
-                    let (schema_type, enum_values) =
-                        Self::parse_schema_type_with_modifiers(&type_str);
-                    let schema = Schema::builder()
-                        .r#type(schema_type)
-                        .description(description.unwrap_or_default())
-                        .enum_values(enum_values.unwrap_or_default())
-                        .build();
+                    let schema = if type_str.starts_with("array<") {
+                        Schema {
+                            description,
+                            ..Self::parse_array_type(&type_str, lenient)?
+                        }
+                    } else {
+                        let (schema_type, enum_values) =
+                            Self::parse_schema_type_with_modifiers(&type_str, lenient)?;
+                        Schema {
+                            description,
+                            enum_values,
+                            ..Schema::builder().r#type(schema_type).build()
+                        }
+                    };
 
+                    if prop_required {
+                        required.push(prop_name.clone());
+                    }
                     properties.insert(prop_name, schema);
+                } else if !lenient {
+                    return Err(ParameterError::MalformedProperty {
+                        property: prop.to_string(),
+                    });
                 }
             }
         }
 
-        properties
+        Ok((properties, required))
     }
 
     /// Sets the parameters of the function using a slice of parameter definitions.
@@ -415,22 +554,60 @@ impl FunctionDeclaration {
     ///         "address, object, User's address | street:string:Street name, city:string, country:string",
     ///         
     ///         // Object with nested properties
-    ///         "settings, object, User settings | preferences:{theme:string:UI theme, notifications:boolean:Enable notifications}"
-    ///     ]);
+    ///         "settings, object, User settings | preferences:{theme:string:UI theme, notifications:boolean:Enable notifications}",
+    ///
+    ///         // Optional parameter, marked with `?`
+    ///         "limit?, integer, Max results",
+    ///
+    ///         // Arrays with typed items, including objects
+    ///         "tags, array<string>, List of tags",
+    ///         "orders, array<object|id:string, quantity?:integer>, Past orders"
+    ///     ])
+    ///     .unwrap();
     /// ```
-    pub fn with_parameters(mut self, parameters: &[&str]) -> Self {
+    ///
+    /// Parameters and object properties are required unless their name ends with `?`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a definition without a type, an unknown type, an
+    /// unterminated `enum(...)` or `array<...>` or an object property without a
+    /// type; see
+    /// [`with_parameters_lenient`](Self::with_parameters_lenient) to skip or default
+    /// those instead.
+    pub fn with_parameters(self, parameters: &[&str]) -> Result<Self, ParameterError> {
+        let parameters = parameters
+            .iter()
+            .map(|param_str| Self::parse_parameter(param_str, false))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.with_parsed_parameters(parameters))
+    }
+
+    /// Like [`with_parameters`](Self::with_parameters), but skips malformed
+    /// definitions and properties, treats unknown types as strings, ignores an
+    /// unterminated `enum(...)` and leaves the items of an unterminated `array<...>`
+    /// untyped.
+    pub fn with_parameters_lenient(self, parameters: &[&str]) -> Self {
+        let parameters = parameters
+            .iter()
+            .filter_map(|param_str| Self::parse_parameter(param_str, true).ok())
+            .collect();
+        self.with_parsed_parameters(parameters)
+    }
+
+    fn with_parsed_parameters(mut self, parameters: Vec<(String, Schema, bool)>) -> Self {
         let mut properties = std::collections::HashMap::new();
         let mut required = Vec::new();
 
-        for param_str in parameters {
-            if let Some((name, schema)) = Self::parse_parameter(param_str) {
-                properties.insert(name.clone(), schema);
-                required.push(name);
+        for (name, schema, is_required) in parameters {
+            if is_required {
+                required.push(name.clone());
             }
+            properties.insert(name, schema);
         }
 
         self.parameters = Some(
-            FunctionDeclarationSchema::builder()
+            Schema::builder()
                 .r#type(SchemaType::Object)
                 .properties(properties)
                 .required(required)
@@ -441,6 +618,88 @@ impl FunctionDeclaration {
     }
 }
 
+/// Splits a parameter or property name into the name and whether it is required,
+/// i.e. does not end with `?`.
+fn parse_name(name: &str) -> (String, bool) {
+    let name = name.trim();
+    match name.strip_suffix('?') {
+        Some(optional) => (optional.trim_end().to_string(), false),
+        None => (name.to_string(), true),
+    }
+}
+
+/// Splits `s` at the first `separator` that is not inside `<>`, `{}` or `()`.
+fn split_outside_brackets(s: &str, separator: char) -> (&str, Option<&str>) {
+    let mut depth = 0usize;
+    for (index, c) in s.char_indices() {
+        match c {
+            '<' | '{' | '(' => depth += 1,
+            '>' | '}' | ')' => depth = depth.saturating_sub(1),
+            _ if c == separator && depth == 0 => {
+                return (&s[..index], Some(&s[index + c.len_utf8()..]));
+            }
+            _ => {}
+        }
+    }
+    (s, None)
+}
+
+/// Returns the index of the bracket closing the first `open` in `s`.
+fn closing_bracket(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, c) in s.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth = depth.checked_sub(1)?;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// A parameter definition passed to [`FunctionDeclaration::with_parameters`] is
+/// malformed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParameterError {
+    /// The definition does not have both a name and a type.
+    #[error("parameter `{definition}` needs a name and a type")]
+    MissingType {
+        /// The malformed definition.
+        definition: String,
+    },
+
+    /// The type is not one of string, integer, number, boolean, array or object.
+    #[error("unknown parameter type `{type_name}`")]
+    UnknownType {
+        /// The unknown type.
+        type_name: String,
+    },
+
+    /// An `enum(` modifier is not closed.
+    #[error("unterminated enum(...) in `{type_str}`")]
+    UnterminatedEnum {
+        /// The type with the modifier.
+        type_str: String,
+    },
+
+    /// An `array<` type is not closed.
+    #[error("unterminated array<...> in `{type_str}`")]
+    UnterminatedArray {
+        /// The type with the item type.
+        type_str: String,
+    },
+
+    /// An object property does not have both a name and a type.
+    #[error("object property `{property}` needs a name and a type")]
+    MalformedProperty {
+        /// The malformed property.
+        property: String,
+    },
+}
+
 /// A function call made by the model.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -453,6 +712,76 @@ pub struct FunctionCall<T = serde_json::Value> {
     pub args: T,
 }
 
+impl FunctionCall {
+    /// Deserializes the arguments of the call into `T`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::FunctionCall;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct WeatherParams {
+    ///     city: String,
+    /// }
+    ///
+    /// let call = FunctionCall {
+    ///     name: "get_weather".to_string(),
+    ///     args: serde_json::json!({ "city": "Paris" }),
+    /// };
+    /// let params: WeatherParams = call.args_as().unwrap();
+    /// assert_eq!(params.city, "Paris");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the function name and the raw arguments if they do not
+    /// deserialize into `T`.
+    pub fn args_as<T: DeserializeOwned>(&self) -> Result<T, FunctionArgsError> {
+        serde_json::from_value(self.args.clone()).map_err(|source| self.args_error(source))
+    }
+
+    /// Deserializes the argument `key` into `T`.
+    ///
+    /// A missing argument deserializes as `null`, so it is `None` for an `Option`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the function name and the raw arguments if the argument
+    /// is missing (and `T` is not an `Option`) or does not deserialize into `T`.
+    pub fn arg<T: DeserializeOwned>(&self, key: &str) -> Result<T, FunctionArgsError> {
+        match self.args.get(key) {
+            Some(value) => {
+                serde_json::from_value(value.clone()).map_err(|source| self.args_error(source))
+            }
+            None => serde_json::from_value(Value::Null).map_err(|_| {
+                self.args_error(serde::de::Error::custom(format!("missing field `{key}`")))
+            }),
+        }
+    }
+
+    fn args_error(&self, source: serde_json::Error) -> FunctionArgsError {
+        FunctionArgsError {
+            name: self.name.clone(),
+            args: self.args.clone(),
+            source,
+        }
+    }
+}
+
+/// The arguments of a [`FunctionCall`] do not match the expected type.
+#[derive(Debug, Error)]
+#[error("Invalid arguments for `{name}`: {source}; arguments: {args}")]
+pub struct FunctionArgsError {
+    /// The name of the called function.
+    pub name: String,
+    /// The raw arguments of the call.
+    pub args: Value,
+    /// The deserialization error.
+    #[source]
+    pub source: serde_json::Error,
+}
+
 /// A response to a function call.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -465,6 +794,23 @@ pub struct FunctionResponse<T = serde_json::Value> {
     pub response: T,
 }
 
+impl FunctionResponse {
+    /// Creates a response to the function `name` from any serializable value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `response` cannot be serialized to JSON.
+    pub fn from_serialize(
+        name: impl Into<String>,
+        response: &impl Serialize,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            name: name.into(),
+            response: serde_json::to_value(response)?,
+        })
+    }
+}
+
 /// Specifies how the model should handle function calling behavior.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
@@ -516,12 +862,14 @@ mod tests {
 
     #[test]
     fn test_parameter_parsing_basic() {
-        let func = FunctionDeclaration::new().with_parameters(&["location, string, The city name"]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&["location, string, The city name"])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        assert_eq!(params.r#type, SchemaType::Object);
+        assert_eq!(params.r#type, Some(SchemaType::Object));
 
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
         assert_eq!(properties.len(), 1);
 
         let location_param = properties.get("location").unwrap();
@@ -534,14 +882,16 @@ mod tests {
 
     #[test]
     fn test_parameter_parsing_multiple() {
-        let func = FunctionDeclaration::new().with_parameters(&[
-            "location, string, The city name",
-            "temperature, number, Temperature in degrees",
-            "is_metric, boolean, Use metric units",
-        ]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "location, string, The city name",
+                "temperature, number, Temperature in degrees",
+                "is_metric, boolean, Use metric units",
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
         assert_eq!(properties.len(), 3);
 
         // Check location parameter
@@ -572,10 +922,12 @@ mod tests {
 
     #[test]
     fn test_parameter_without_description() {
-        let func = FunctionDeclaration::new().with_parameters(&["age, integer"]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&["age, integer"])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
         let age_param = properties.get("age").unwrap();
 
         assert_eq!(age_param.r#type, Some(SchemaType::Integer));
@@ -584,10 +936,11 @@ mod tests {
 
     #[test]
     fn test_parameter_invalid_type() {
-        let func = FunctionDeclaration::new().with_parameters(&["data, invalid_type, Some data"]);
+        let func =
+            FunctionDeclaration::new().with_parameters_lenient(&["data, invalid_type, Some data"]);
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
         let data_param = properties.get("data").unwrap();
 
         // Should default to string type for invalid types
@@ -597,17 +950,19 @@ mod tests {
 
     #[test]
     fn test_parameter_all_types() {
-        let func = FunctionDeclaration::new().with_parameters(&[
-            "text, string, A string value",
-            "count, integer, An integer value",
-            "amount, number, A number value",
-            "flag, boolean, A boolean value",
-            "list, array, An array value",
-            "data, object, An object value",
-        ]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "text, string, A string value",
+                "count, integer, An integer value",
+                "amount, number, A number value",
+                "flag, boolean, A boolean value",
+                "list, array, An array value",
+                "data, object, An object value",
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         let type_checks = vec![
             ("text", SchemaType::String),
@@ -628,10 +983,10 @@ mod tests {
 
     #[test]
     fn test_empty_parameters() {
-        let func = FunctionDeclaration::new().with_parameters(&[]);
+        let func = FunctionDeclaration::new().with_parameters(&[]).unwrap();
 
         let params = func.parameters.unwrap();
-        assert_eq!(params.properties.len(), 0);
+        assert_eq!(params.properties.unwrap().len(), 0);
         assert_eq!(params.required.unwrap().len(), 0);
     }
 
@@ -641,6 +996,7 @@ mod tests {
             .with_name("weather")
             .with_description("Get weather info")
             .with_parameters(&["city, string, City name"])
+            .unwrap()
             .with_description("Updated description"); // Should override previous description
 
         assert_eq!(func.name, Some("weather".to_string()));
@@ -650,10 +1006,10 @@ mod tests {
 
     #[test]
     fn test_malformed_parameter() {
-        let func = FunctionDeclaration::new().with_parameters(&["malformed_param"]); // Missing type and description
+        let func = FunctionDeclaration::new().with_parameters_lenient(&["malformed_param"]); // Missing type and description
 
         let params = func.parameters.unwrap();
-        assert_eq!(params.properties.len(), 0); // Should skip malformed parameter
+        assert_eq!(params.properties.unwrap().len(), 0); // Should skip malformed parameter
     }
 
     #[test]
@@ -661,10 +1017,11 @@ mod tests {
         let func = FunctionDeclaration::new()
             .with_parameters(&[
                 "address, object, User's address | street:string:Street name, city:string:City name, country:string"
-            ]);
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
         let address = properties.get("address").unwrap();
 
         // Check address schema
@@ -687,7 +1044,8 @@ mod tests {
         // Check country property
         let country = addr_props.get("country").unwrap();
         assert_eq!(country.r#type, Some(SchemaType::String));
-        assert_eq!(country.description, Some("".to_string()));
+        assert_eq!(country.description, None);
+        assert_eq!(country.enum_values, None);
 
         // Check required fields
         let required = address.required.as_ref().unwrap();
@@ -703,10 +1061,11 @@ mod tests {
                 "settings, object, User settings | \
                  theme:{mode:string:enum(light,dark):Theme mode, accent:string:enum(red,blue):Accent color}, \
                  notifications:boolean:Enable notifications"
-            ]);
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         let settings = properties.get("settings").unwrap();
         assert_eq!(settings.r#type, Some(SchemaType::Object));
@@ -747,10 +1106,11 @@ mod tests {
             "name, string, User's name",
             "age, integer, User's age",
             "preferences, object, User preferences | theme:string:UI theme, notifications:boolean",
-        ]);
+        ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         // Check basic parameters
         let name = properties.get("name").unwrap();
@@ -772,18 +1132,20 @@ mod tests {
 
         let notifications = pref_props.get("notifications").unwrap();
         assert_eq!(notifications.r#type, Some(SchemaType::Boolean));
-        assert_eq!(notifications.description, Some("".to_string()));
+        assert_eq!(notifications.description, None);
     }
 
     #[test]
     fn test_parameter_with_enum() {
-        let func = FunctionDeclaration::new().with_parameters(&[
-            "mode, string:enum(light,dark), Display mode",
-            "unit, string:enum(celsius,fahrenheit), Temperature unit",
-        ]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "mode, string:enum(light,dark), Display mode",
+                "unit, string:enum(celsius,fahrenheit), Temperature unit",
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         // Check mode parameter
         let mode = properties.get("mode").unwrap();
@@ -806,19 +1168,21 @@ mod tests {
 
     #[test]
     fn test_enum_with_spaces() {
-        let func = FunctionDeclaration::new().with_parameters(&[
-            // Empty enum values
-            "status1, string:enum(), Status",
-            // Single enum value
-            "status2, string:enum(active), Status",
-            // Enum values with special characters
-            "status3, string:enum(in-progress,not_started,done!), Status",
-            // Enum value containing parentheses
-            "status4, string:enum((pending),(in-progress)), Status",
-        ]);
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                // Empty enum values
+                "status1, string:enum(), Status",
+                // Single enum value
+                "status2, string:enum(active), Status",
+                // Enum values with special characters
+                "status3, string:enum(in-progress,not_started,done!), Status",
+                // Enum value containing parentheses
+                "status4, string:enum((pending),(in-progress)), Status",
+            ])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         let status1 = properties.get("status1").unwrap();
         assert_eq!(status1.enum_values, Some(vec![] as Vec<String>));
@@ -845,14 +1209,15 @@ mod tests {
 
     #[test]
     fn test_nested_object_with_enum() {
-        let func =
-            FunctionDeclaration::new().with_parameters(&["settings, object, User settings | \
+        let func = FunctionDeclaration::new()
+            .with_parameters(&["settings, object, User settings | \
              theme:{mode:string:enum(light,dark):Theme mode, \
                    colors:{primary:string:enum(red,blue,green):Primary color}}, \
-             notifications:boolean"]);
+             notifications:boolean"])
+            .unwrap();
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         let settings = properties.get("settings").unwrap();
         assert_eq!(settings.r#type, Some(SchemaType::Object));
@@ -889,7 +1254,7 @@ mod tests {
 
     #[test]
     fn test_enum_edge_cases() {
-        let func = FunctionDeclaration::new().with_parameters(&[
+        let func = FunctionDeclaration::new().with_parameters_lenient(&[
             // Nested objects with multiple levels of enums
             "config, object, Complex config | \
              ui:{
@@ -932,7 +1297,7 @@ mod tests {
         ]);
 
         let params = func.parameters.unwrap();
-        let properties = params.properties;
+        let properties = params.properties.unwrap();
 
         // Test nested objects with multiple levels of enums
         let config = properties.get("config").unwrap();
@@ -1039,4 +1404,408 @@ mod tests {
             Some(vec!["UTC+2:00".to_string(), "UTC-5:00".to_string()])
         );
     }
+
+    #[test]
+    fn test_legacy_parameters_serialize_identically() {
+        #[allow(deprecated)]
+        let legacy = FunctionDeclarationSchema::builder()
+            .r#type(SchemaType::Object)
+            .properties([(
+                "unit".to_string(),
+                Schema::builder()
+                    .r#type(SchemaType::String)
+                    .enum_values(vec!["celsius".to_string(), "fahrenheit".to_string()])
+                    .build(),
+            )])
+            .required(vec!["unit".to_string()])
+            .build();
+        let golden = r#"{"type":"object","properties":{"unit":{"type":"string","enum":["celsius","fahrenheit"]}},"required":["unit"]}"#;
+        assert_eq!(serde_json::to_string(&legacy).unwrap(), golden);
+
+        let declaration = FunctionDeclaration::builder()
+            .name("set_unit")
+            .parameters(legacy)
+            .build();
+        assert_eq!(
+            serde_json::to_string(&declaration).unwrap(),
+            format!(r#"{{"name":"set_unit","parameters":{golden}}}"#)
+        );
+    }
+
+    #[test]
+    fn test_with_parameters_serializes_as_before() {
+        let func = FunctionDeclaration::new()
+            .with_name("find_city")
+            .with_parameters(&["city, string, The city"])
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&func).unwrap(),
+            r#"{"name":"find_city","parameters":{"type":"object","properties":{"city":{"type":"string","description":"The city"}},"required":["city"]}}"#
+        );
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct WeatherParams {
+        city: String,
+        days: Option<u32>,
+    }
+
+    fn weather_call(args: Value) -> FunctionCall {
+        FunctionCall {
+            name: "get_weather".to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_args_as() {
+        let call = weather_call(serde_json::json!({ "city": "Paris", "days": 3 }));
+        let params: WeatherParams = call.args_as().unwrap();
+        assert_eq!(params.city, "Paris");
+        assert_eq!(params.days, Some(3));
+
+        let call = weather_call(serde_json::json!({ "city": 42 }));
+        let error = call.args_as::<WeatherParams>().unwrap_err();
+        assert_eq!(error.name, "get_weather");
+        assert_eq!(error.args, serde_json::json!({ "city": 42 }));
+        assert!(error.to_string().contains("invalid type: integer `42`"));
+
+        let error = weather_call(serde_json::json!({ "days": 3 }))
+            .args_as::<WeatherParams>()
+            .unwrap_err();
+        assert!(error.to_string().contains("missing field `city`"));
+    }
+
+    #[test]
+    fn test_arg() {
+        let call = weather_call(serde_json::json!({ "city": "Paris", "days": 3 }));
+        assert_eq!(call.arg::<String>("city").unwrap(), "Paris");
+        assert_eq!(call.arg::<u32>("days").unwrap(), 3);
+        assert_eq!(call.arg::<Option<bool>>("metric").unwrap(), None);
+
+        let error = call.arg::<u32>("city").unwrap_err();
+        assert!(error.to_string().contains("invalid type: string \"Paris\""));
+        let error = call.arg::<bool>("metric").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid arguments for `get_weather`: missing field `metric`"));
+    }
+
+    #[test]
+    fn test_function_response_from_serialize() {
+        #[derive(Serialize)]
+        struct Forecast {
+            city: &'static str,
+            temperature: f64,
+        }
+
+        let response = FunctionResponse::from_serialize(
+            "get_weather",
+            &Forecast {
+                city: "Paris",
+                temperature: 21.5,
+            },
+        )
+        .unwrap();
+        assert_eq!(response.name, "get_weather");
+        assert_eq!(
+            response.response,
+            serde_json::json!({ "city": "Paris", "temperature": 21.5 })
+        );
+    }
+
+    #[test]
+    fn test_with_parameters_rejects_malformed_definitions() {
+        let error = |parameters: &[&str]| {
+            FunctionDeclaration::new()
+                .with_parameters(parameters)
+                .unwrap_err()
+        };
+
+        assert_eq!(
+            error(&["city, string, The city", "missing_type"]),
+            ParameterError::MissingType {
+                definition: "missing_type".into()
+            }
+        );
+        assert_eq!(
+            error(&["data, text, Some data"]),
+            ParameterError::UnknownType {
+                type_name: "text".into()
+            }
+        );
+        assert_eq!(
+            error(&["unit, string:enum(celsius,fahrenheit, The unit"]),
+            ParameterError::UnterminatedEnum {
+                type_str: "string:enum(celsius,fahrenheit, The unit".into()
+            }
+        );
+        assert_eq!(
+            error(&["address, object, The address | street:string, city"]),
+            ParameterError::MalformedProperty {
+                property: "city".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_with_parameters_lenient_skips_malformed_definitions() {
+        let func = FunctionDeclaration::new().with_parameters_lenient(&[
+            "city, string, The city",
+            "missing_type",
+            "address, object, The address | street:string, city",
+        ]);
+
+        let params = func.parameters.unwrap();
+        assert_eq!(params.required.unwrap(), ["city", "address"]);
+        let address = &params.properties.unwrap()["address"];
+        assert_eq!(address.properties.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parsing_writes_nothing_to_stdout() {
+        // The parameter parsing lives in this file; no code in it may print. The
+        // macro names are split so this test does not match itself.
+        let printing = [
+            concat!("print", "ln!("),
+            concat!("print", "!("),
+            concat!("dbg", "!("),
+        ];
+        let offending: Vec<_> = include_str!("function.rs")
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim_start().starts_with("//"))
+            .filter(|(_, line)| printing.iter().any(|name| line.contains(name)))
+            .map(|(index, line)| format!("{}: {}", index + 1, line.trim()))
+            .collect();
+        assert!(offending.is_empty(), "printing code: {offending:?}");
+    }
+
+    fn weather_declaration() -> FunctionDeclaration {
+        FunctionDeclaration::new()
+            .with_name("get_weather")
+            .with_parameters(&[
+                "city, string, The city",
+                "unit, string:enum(celsius,fahrenheit), The unit",
+            ])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_validate_call() {
+        let declaration = weather_declaration();
+        let call = weather_call;
+
+        assert_eq!(
+            declaration.validate_call(&call(serde_json::json!({
+                "city": "Paris",
+                "unit": "celsius"
+            }))),
+            Ok(())
+        );
+        assert_eq!(
+            declaration.validate_call(&call(serde_json::json!({
+                "city": "Paris",
+                "unit": "kelvin"
+            }))),
+            Err(vec![SchemaViolation::NotInEnum {
+                path: "#/unit".into(),
+                value: "kelvin".into(),
+                allowed: vec!["celsius".into(), "fahrenheit".into()],
+            }])
+        );
+        assert_eq!(
+            declaration.validate_call(&call(serde_json::json!({ "unit": "celsius" }))),
+            Err(vec![SchemaViolation::MissingProperty {
+                path: "#".into(),
+                name: "city".into(),
+            }])
+        );
+        assert_eq!(
+            declaration.validate_call(&call(serde_json::json!({
+                "city": "Paris",
+                "unit": "celsius",
+                "days": 3
+            }))),
+            Err(vec![SchemaViolation::UnknownProperty {
+                path: "#".into(),
+                name: "days".into(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_call_without_parameters() {
+        let declaration = FunctionDeclaration::new().with_name("get_time");
+
+        assert_eq!(
+            declaration.validate_call(&weather_call(serde_json::json!({}))),
+            Ok(())
+        );
+        assert_eq!(
+            declaration.validate_call(&weather_call(serde_json::json!({ "zone": "UTC" }))),
+            Err(vec![SchemaViolation::UnknownProperty {
+                path: "#".into(),
+                name: "zone".into(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_call_on_parsed_objects() {
+        let declaration = FunctionDeclaration::new()
+            .with_name("ship")
+            .with_parameters(&[
+                "address, object, Address | street:string:Street name, city:string",
+                "items, array<object|id:string, size:string:enum(S,M):Size>, Items",
+            ])
+            .unwrap();
+
+        let call = weather_call(serde_json::json!({
+            "address": { "street": "Main", "city": "Paris" },
+            "items": [{ "id": "a1", "size": "M" }]
+        }));
+        assert_eq!(declaration.validate_call(&call), Ok(()));
+
+        let call = weather_call(serde_json::json!({
+            "address": { "street": "Main", "city": "Paris" },
+            "items": [{ "id": "a1", "size": "XL" }]
+        }));
+        assert_eq!(
+            declaration.validate_call(&call),
+            Err(vec![SchemaViolation::NotInEnum {
+                path: "#/items/0/size".into(),
+                value: "XL".into(),
+                allowed: vec!["S".into(), "M".into()],
+            }])
+        );
+
+        let parameters = serde_json::to_value(&declaration.parameters).unwrap();
+        assert_eq!(
+            parameters["properties"]["address"]["properties"]["city"],
+            serde_json::json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn test_optional_parameters() {
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "query, string, Search query",
+                "limit?, integer, Max results",
+                "filter ?, object, Filters | author?:string, year:integer",
+            ])
+            .unwrap();
+
+        let params = func.parameters.unwrap();
+        assert_eq!(params.required.unwrap(), ["query"]);
+
+        let properties = params.properties.unwrap();
+        let limit = properties.get("limit").unwrap();
+        assert_eq!(limit.r#type, Some(SchemaType::Integer));
+        assert_eq!(limit.description, Some("Max results".to_string()));
+
+        let filter = properties.get("filter").unwrap();
+        assert_eq!(filter.required.as_ref().unwrap(), &["year"]);
+        assert!(filter.properties.as_ref().unwrap().contains_key("author"));
+    }
+
+    #[test]
+    fn test_typed_array_parameters() {
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "tags, array<string>, List of tags",
+                "sizes, array<string:enum(S,M,L)>, Sizes",
+                "matrix, array<array<number>>",
+                "untyped, array, Anything",
+            ])
+            .unwrap();
+
+        let properties = func.parameters.unwrap().properties.unwrap();
+
+        let tags = properties.get("tags").unwrap();
+        assert_eq!(tags.r#type, Some(SchemaType::Array));
+        assert_eq!(tags.description, Some("List of tags".to_string()));
+        assert_eq!(
+            tags.items.as_ref().unwrap().r#type,
+            Some(SchemaType::String)
+        );
+
+        let sizes = properties.get("sizes").unwrap().items.as_ref().unwrap();
+        assert_eq!(sizes.enum_values.as_ref().unwrap(), &["S", "M", "L"]);
+
+        let matrix = properties.get("matrix").unwrap();
+        assert_eq!(
+            serde_json::to_value(matrix.items.as_ref().unwrap()).unwrap(),
+            serde_json::json!({ "type": "array", "items": { "type": "number" } })
+        );
+
+        assert!(properties.get("untyped").unwrap().items.is_none());
+    }
+
+    #[test]
+    fn test_nested_object_array_parameters() {
+        let func = FunctionDeclaration::new()
+            .with_parameters(&[
+                "orders, array<object|id:string:Order id, quantity?:integer, tags:array<string>>, Past orders",
+                "customer, object, The customer | addresses:array<object|city:string, zip?:string>:Known addresses",
+            ])
+            .unwrap();
+
+        let properties = func.parameters.unwrap().properties.unwrap();
+
+        let orders = properties.get("orders").unwrap();
+        assert_eq!(orders.description, Some("Past orders".to_string()));
+        let order = orders.items.as_ref().unwrap();
+        assert_eq!(order.r#type, Some(SchemaType::Object));
+        assert_eq!(order.required.as_ref().unwrap(), &["id", "tags"]);
+        let order_properties = order.properties.as_ref().unwrap();
+        assert_eq!(
+            order_properties.get("id").unwrap().description,
+            Some("Order id".to_string())
+        );
+        assert_eq!(
+            order_properties
+                .get("tags")
+                .unwrap()
+                .items
+                .as_ref()
+                .unwrap()
+                .r#type,
+            Some(SchemaType::String)
+        );
+
+        let customer = properties.get("customer").unwrap();
+        let addresses = customer
+            .properties
+            .as_ref()
+            .unwrap()
+            .get("addresses")
+            .unwrap();
+        assert_eq!(addresses.r#type, Some(SchemaType::Array));
+        assert_eq!(addresses.description, Some("Known addresses".to_string()));
+        let address = addresses.items.as_ref().unwrap();
+        assert_eq!(address.required.as_ref().unwrap(), &["city"]);
+        assert_eq!(address.properties.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_unterminated_array_type() {
+        assert_eq!(
+            FunctionDeclaration::new()
+                .with_parameters(&["tags, array<string, Tags"])
+                .unwrap_err(),
+            ParameterError::UnterminatedArray {
+                type_str: "array<string, Tags".to_string()
+            }
+        );
+
+        let func =
+            FunctionDeclaration::new().with_parameters_lenient(&["tags, array<string, Tags"]);
+        let properties = func.parameters.unwrap().properties.unwrap();
+        let tags = properties.get("tags").unwrap();
+        assert_eq!(tags.r#type, Some(SchemaType::Array));
+        assert!(tags.items.is_none());
+    }
 }
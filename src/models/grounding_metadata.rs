@@ -5,14 +5,19 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct GroundingMetadata {
     /// Google search entry for the following-up web searches.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub search_entry_point: Option<SearchEntryPoint>,
     /// List of supporting references retrieved from specified grounding source.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub grounding_chunks: Option<Vec<GroundingChunk>>,
     /// List of grounding support.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub grounding_supports: Option<Vec<GroundingSupport>>,
     /// Metadata related to retrieval in the grounding flow.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub retrieval_metadata: Option<RetrievalMetadata>,
     /// Web search queries for the following-up web search.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub web_search_queries: Option<Vec<String>>,
 }
 
@@ -21,8 +26,10 @@ pub struct GroundingMetadata {
 #[serde(rename_all = "camelCase")]
 pub struct SearchEntryPoint {
     /// Web content snippet that can be embedded in a web page or an app webview.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub rendered_content: Option<String>,
     /// Base64 encoded JSON representing array of <search term, search url> tuple.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub sdk_blob: Option<String>,
 }
 
@@ -31,6 +38,7 @@ pub struct SearchEntryPoint {
 #[serde(rename_all = "camelCase")]
 pub struct GroundingChunk {
     /// Chunk from the web.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub web: Option<GroundingChunkWeb>,
 }
 
@@ -39,8 +47,10 @@ pub struct GroundingChunk {
 #[serde(rename_all = "camelCase")]
 pub struct GroundingChunkWeb {
     /// URI of the web page.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub uri: Option<String>,
     /// Title of the web page.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
 }
 
@@ -49,15 +59,18 @@ pub struct GroundingChunkWeb {
 #[serde(rename_all = "camelCase")]
 pub struct GroundingSupport {
     /// The segment of the content.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub segment: Option<GroundingSupportSegment>,
     /// A list of indices (into 'grounding_chunk') specifying the citations
     /// associated with the claim. For instance [1, 3, 4] means that
     /// grounding_chunk[1], grounding_chunk[3], grounding_chunk[4] are the
     /// retrieved content attributed to the claim.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub grounding_chunk_indices: Option<Vec<i64>>,
     /// Confidence score of the support references. Ranges from 0 to 1. 1 is the
     /// most confident. This list must have the same size as the
     /// grounding_chunk_indices.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub confidence_scores: Option<Vec<f64>>,
 }
 
@@ -70,6 +83,7 @@ pub struct RetrievalMetadata {
     /// likely and 1 is the most likely. This score is only populated when google
     /// search grounding and dynamic retrieval is enabled. It will be compared to
     /// the threshold to determine whether to trigger google search.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub google_search_dynamic_retrieval_score: Option<f64>,
 }
 
@@ -78,13 +92,17 @@ pub struct RetrievalMetadata {
 #[serde(rename_all = "camelCase")]
 pub struct GroundingSupportSegment {
     /// The index of a Part object within its parent Content object.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub part_index: Option<i64>,
     /// Start index in the given Part, measured in bytes. Offset from the start of
     /// the Part, inclusive, starting at zero.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub start_index: Option<i64>,
     /// End index in the given Part, measured in bytes. Offset from the start of
     /// the Part, exclusive, starting at zero.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub end_index: Option<i64>,
     /// The text corresponding to the segment from the response.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
 }
@@ -0,0 +1,698 @@
+//! Conversion between JSON Schema documents, e.g. generated by `schemars`, and
+//! [`Schema`].
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{Schema, SchemaType};
+
+/// The formats the API understands; other formats, e.g. `uint32`, are dropped from
+/// schemas generated by `schemars`.
+const SUPPORTED_FORMATS: [&str; 6] = ["int32", "int64", "float", "double", "enum", "date-time"];
+
+/// The keywords [`Schema::from_json_value`] converts, or ignores because they only
+/// annotate the document.
+const SUPPORTED_KEYWORDS: [&str; 23] = [
+    "type",
+    "properties",
+    "required",
+    "items",
+    "enum",
+    "description",
+    "format",
+    "nullable",
+    "minimum",
+    "maximum",
+    "minItems",
+    "maxItems",
+    "pattern",
+    "propertyOrdering",
+    "anyOf",
+    "example",
+    "$ref",
+    "$defs",
+    "definitions",
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+];
+
+/// Errors that can occur when importing or checking a schema.
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    /// The document is not valid JSON.
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The document uses parts of JSON Schema the API does not support.
+    #[error("unsupported JSON Schema: {}", .0.join(", "))]
+    Unsupported(Vec<String>),
+
+    /// The schema has mistakes the API rejects, found by [`Schema::check`].
+    #[error("invalid schema: {}", .0.join(", "))]
+    Invalid(Vec<String>),
+}
+
+impl Schema {
+    /// Converts a JSON Schema document into a schema.
+    ///
+    /// The supported subset is `type` (optionally together with `"null"`),
+    /// `properties`, `required`, `items`, `enum` with string values, `description`,
+    /// `format`, `nullable`, `minimum`, `maximum`, `minItems`, `maxItems`, `pattern`,
+    /// `propertyOrdering`, `anyOf` and `example`. References into `$defs` or `definitions`
+    /// are inlined, and `$schema`, `$id`, `$comment` and `title` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SchemaError::Unsupported`] listing every unsupported keyword with the
+    /// location it was found at, e.g. ``"`exclusiveMinimum` at #/properties/age"``.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{Schema, SchemaType};
+    /// use serde_json::json;
+    ///
+    /// let schema = Schema::from_json_value(&json!({
+    ///     "type": "object",
+    ///     "properties": { "name": { "type": "string" } },
+    ///     "required": ["name"]
+    /// }))
+    /// .unwrap();
+    /// assert_eq!(schema.r#type, Some(SchemaType::Object));
+    /// ```
+    pub fn from_json_value(value: &Value) -> Result<Self, SchemaError> {
+        let mut converter = Converter::new(value, true);
+        let schema = converter.convert(value, "#");
+        match converter.unsupported {
+            unsupported if unsupported.is_empty() => Ok(schema),
+            unsupported => Err(SchemaError::Unsupported(unsupported)),
+        }
+    }
+
+    /// Parses a JSON Schema document, see [`from_json_value`](Self::from_json_value).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SchemaError::Json`] if `json` is not valid JSON and
+    /// [`SchemaError::Unsupported`] if it uses unsupported keywords.
+    pub fn from_json_str(json: &str) -> Result<Self, SchemaError> {
+        Self::from_json_value(&serde_json::from_str(json)?)
+    }
+
+    /// Returns the schema as a JSON Schema document, which
+    /// [`from_json_value`](Self::from_json_value) turns back into an equal schema.
+    pub fn to_json_value(&self) -> Value {
+        serde_json::to_value(self).expect("a schema always serializes to JSON")
+    }
+
+    /// Creates a schema from a JSON Schema generated by `schemars`.
+    ///
+    /// Doc comments become descriptions, `Option` fields are left out of `required`
+    /// and marked nullable, and enums of unit variants become `enum_values`.
+    /// References to definitions are inlined, since the API does not support them;
+    /// a recursive type is cut off at the first repetition as a plain object.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{Schema, SchemaType};
+    ///
+    /// #[derive(schemars::JsonSchema)]
+    /// struct Recipe {
+    ///     /// The name of the recipe
+    ///     name: String,
+    ///     ingredients: Vec<String>,
+    /// }
+    ///
+    /// let schema = Schema::from_json_schema(&schemars::schema_for!(Recipe));
+    /// assert_eq!(schema.r#type, Some(SchemaType::Object));
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn from_json_schema(schema: &schemars::Schema) -> Self {
+        let root = schema.as_value();
+        Converter::new(root, false).convert(root, "#")
+    }
+
+    /// Creates the schema of a type implementing [`schemars::JsonSchema`], see
+    /// [`from_json_schema`](Self::from_json_schema).
+    #[cfg(feature = "schemars")]
+    pub fn for_type<T: schemars::JsonSchema>() -> Self {
+        Self::from_json_schema(&schemars::schema_for!(T))
+    }
+}
+
+impl TryFrom<Value> for Schema {
+    type Error = SchemaError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Self::from_json_value(&value)
+    }
+}
+
+/// Converts JSON Schema objects, resolving references against the root document.
+///
+/// A strict converter records every keyword outside [`SUPPORTED_KEYWORDS`]; a lenient
+/// one, used for schemas generated by `schemars`, makes the best of them instead.
+struct Converter<'a> {
+    root: &'a Value,
+    /// The references being converted, to detect recursive types.
+    visiting: Vec<&'a str>,
+    strict: bool,
+    /// The unsupported keywords found by a strict converter, with their location.
+    unsupported: Vec<String>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(root: &'a Value, strict: bool) -> Self {
+        Self {
+            root,
+            visiting: vec!["#"],
+            strict,
+            unsupported: Vec::new(),
+        }
+    }
+
+    fn convert(&mut self, value: &'a Value, path: &str) -> Schema {
+        let Some(object) = value.as_object() else {
+            // `true` accepts anything, which the API cannot express.
+            if self.strict {
+                self.unsupported.push(format!("boolean schema at {}", path));
+            }
+            return Schema::builder().build();
+        };
+
+        if self.strict {
+            self.unsupported.extend(
+                object
+                    .keys()
+                    .filter(|keyword| !SUPPORTED_KEYWORDS.contains(&keyword.as_str()))
+                    .map(|keyword| format!("`{}` at {}", keyword, path)),
+            );
+        }
+
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            let mut schema = self.convert_reference(reference, path);
+            if let Some(description) = object.get("description").and_then(Value::as_str) {
+                schema.description = Some(description.to_string());
+            }
+            return schema;
+        }
+
+        if let Some(variants) = object
+            .get("anyOf")
+            .or_else(|| object.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            let mut schema = self.convert_union(variants, path);
+            if let Some(description) = object.get("description").and_then(Value::as_str) {
+                schema.description = Some(description.to_string());
+            }
+            return schema;
+        }
+
+        let mut schema = Schema::builder().build();
+        let names = match object.get("type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        for name in names {
+            if name == "null" {
+                schema.nullable = Some(true);
+            } else if schema.r#type.is_some() {
+                self.record(format!("several types at {}", path));
+            } else {
+                schema.r#type = schema_type(name);
+                if schema.r#type.is_none() {
+                    self.record(format!("type `{}` at {}", name, path));
+                }
+            }
+        }
+
+        schema.format = object
+            .get("format")
+            .and_then(Value::as_str)
+            .filter(|format| self.strict || SUPPORTED_FORMATS.contains(format))
+            .map(str::to_string);
+        schema.description = object
+            .get("description")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        if let Some(nullable) = object.get("nullable").and_then(Value::as_bool) {
+            schema.nullable = Some(nullable);
+        }
+        schema.minimum = object.get("minimum").and_then(Value::as_f64);
+        schema.maximum = object.get("maximum").and_then(Value::as_f64);
+        schema.min_items = object.get("minItems").and_then(Value::as_i64);
+        schema.max_items = object.get("maxItems").and_then(Value::as_i64);
+        schema.pattern = object
+            .get("pattern")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let enum_values = match (object.get("enum"), object.get("const")) {
+            (Some(Value::Array(values)), _) => {
+                if values.iter().any(|value| !value.is_string()) {
+                    self.record(format!("`enum` with values other than strings at {}", path));
+                }
+                Some(
+                    values
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            (_, Some(Value::String(value))) => Some(vec![value.clone()]),
+            _ => None,
+        };
+        if let Some(values) = enum_values.filter(|values| !values.is_empty()) {
+            schema.r#type.get_or_insert(SchemaType::String);
+            schema.enum_values = Some(values);
+        }
+
+        if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+            schema.properties = Some(
+                properties
+                    .iter()
+                    .map(|(name, property)| {
+                        let path = format!("{}/properties/{}", path, name);
+                        (name.clone(), self.convert(property, &path))
+                    })
+                    .collect::<HashMap<_, _>>(),
+            );
+        }
+        schema.property_ordering = object
+            .get("propertyOrdering")
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            });
+        schema.required = object
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            });
+
+        let items = match object.get("items") {
+            Some(Value::Array(items)) => {
+                self.record(format!("tuple `items` at {}", path));
+                items.first()
+            }
+            items => items,
+        };
+        if let Some(items) = items {
+            let path = format!("{}/items", path);
+            schema.items = Some(Box::new(self.convert(items, &path)));
+        }
+
+        schema.example = object
+            .get("example")
+            .cloned()
+            .or_else(|| object.get("examples")?.as_array()?.first().cloned());
+
+        schema
+    }
+
+    /// Records a problem found by a strict converter.
+    fn record(&mut self, problem: String) {
+        if self.strict {
+            self.unsupported.push(problem);
+        }
+    }
+
+    fn convert_reference(&mut self, reference: &'a str, path: &str) -> Schema {
+        let Some(target) = self.resolve(reference) else {
+            self.record(format!("unresolved `$ref` {} at {}", reference, path));
+            return Schema::builder().build();
+        };
+        if self.visiting.contains(&reference) {
+            self.record(format!("recursive `$ref` {} at {}", reference, path));
+            return Schema::builder().r#type(SchemaType::Object).build();
+        }
+        self.visiting.push(reference);
+        let schema = self.convert(target, reference);
+        self.visiting.pop();
+        schema
+    }
+
+    /// Returns the schema a `$ref` points to: the root or one of its definitions.
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        if reference == "#" {
+            return Some(self.root);
+        }
+        let (definitions, name) = match reference.strip_prefix("#/$defs/") {
+            Some(name) => ("$defs", name),
+            None => ("definitions", reference.strip_prefix("#/definitions/")?),
+        };
+        self.root.get(definitions)?.get(name)
+    }
+
+    /// Converts `anyOf`/`oneOf`: a union with `null` becomes a nullable schema, and a
+    /// union of string constants, e.g. an enum with documented variants, becomes an
+    /// enum. Other unions become `any_of`.
+    fn convert_union(&mut self, variants: &'a [Value], path: &str) -> Schema {
+        let is_null = |variant: &Value| variant.get("type").and_then(Value::as_str) == Some("null");
+        let non_null = variants
+            .iter()
+            .filter(|variant| !is_null(variant))
+            .collect::<Vec<_>>();
+
+        let constants = non_null
+            .iter()
+            .map(
+                |variant| match (variant.get("const"), variant.get("enum")) {
+                    (Some(Value::String(value)), _) => Some(vec![value.clone()]),
+                    (_, Some(Value::Array(values))) => values
+                        .iter()
+                        .map(|value| value.as_str().map(str::to_string))
+                        .collect(),
+                    _ => None,
+                },
+            )
+            .collect::<Option<Vec<_>>>();
+
+        let mut schema = match constants {
+            Some(constants) if non_null.len() > 1 => Schema::builder()
+                .r#type(SchemaType::String)
+                .enum_values(constants.concat())
+                .build(),
+            _ if non_null.len() == 1 => self.convert(non_null[0], path),
+            _ => Schema::builder()
+                .any_of(
+                    non_null
+                        .iter()
+                        .enumerate()
+                        .map(|(index, variant)| {
+                            self.convert(variant, &format!("{}/anyOf/{}", path, index))
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .build(),
+        };
+        if non_null.len() < variants.len() {
+            schema.nullable = Some(true);
+        }
+        schema
+    }
+}
+
+fn schema_type(name: &str) -> Option<SchemaType> {
+    match name {
+        "string" => Some(SchemaType::String),
+        "number" => Some(SchemaType::Number),
+        "integer" => Some(SchemaType::Integer),
+        "boolean" => Some(SchemaType::Boolean),
+        "array" => Some(SchemaType::Array),
+        "object" => Some(SchemaType::Object),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn inventory_schema() -> Value {
+        json!({
+            "type": "object",
+            "description": "The stock of a store",
+            "properties": {
+                "store": {
+                    "type": "string",
+                    "description": "The name of the store",
+                    "pattern": "^[A-Za-z ]+$"
+                },
+                "updated": { "type": "string", "format": "date-time" },
+                "items": {
+                    "type": "array",
+                    "minItems": 1,
+                    "maxItems": 100,
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string", "example": "apple" },
+                            "quantity": { "type": "integer", "format": "int32" },
+                            "unit": { "type": "string", "enum": ["kg", "piece"], "nullable": true },
+                            "price": { "type": "number", "minimum": 0.01, "maximum": 9999.99 },
+                            "organic": { "type": "boolean" }
+                        },
+                        "required": ["name", "quantity"]
+                    }
+                }
+            },
+            "required": ["store", "items"]
+        })
+    }
+
+    #[test]
+    fn test_json_schema_round_trip() {
+        let value = inventory_schema();
+
+        let schema = Schema::try_from(value.clone()).unwrap();
+        assert_eq!(schema.to_json_value(), value);
+
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(Schema::from_json_str(&json).unwrap().to_json_value(), value);
+    }
+
+    #[test]
+    fn test_json_schema_refs_and_null_types() {
+        let schema = Schema::from_json_value(&json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Order",
+            "type": "object",
+            "properties": {
+                "customer": { "$ref": "#/$defs/Customer" },
+                "note": { "type": ["string", "null"] }
+            },
+            "$defs": {
+                "Customer": {
+                    "type": "object",
+                    "properties": { "email": { "type": "string" } }
+                }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            schema.to_json_value(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "customer": {
+                        "type": "object",
+                        "properties": { "email": { "type": "string" } }
+                    },
+                    "note": { "type": "string", "nullable": true }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_schema_lists_unsupported_keywords() {
+        let error = Schema::from_json_value(&json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "age": { "type": "integer", "exclusiveMinimum": 0 },
+                "tags": {
+                    "type": "array",
+                    "uniqueItems": true,
+                    "items": { "type": "string", "minLength": 1 }
+                },
+                "id": { "type": "uuid" }
+            }
+        }))
+        .unwrap_err();
+
+        match error {
+            SchemaError::Unsupported(mut problems) => {
+                problems.sort();
+                assert_eq!(
+                    problems,
+                    [
+                        "`additionalProperties` at #",
+                        "`exclusiveMinimum` at #/properties/age",
+                        "`minLength` at #/properties/tags/items",
+                        "`uniqueItems` at #/properties/tags",
+                        "type `uuid` at #/properties/id",
+                    ]
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error_message(r#"{"type": "string", "const": "x"}"#).contains("`const` at #"));
+        assert!(error_message("{").starts_with("invalid JSON"));
+    }
+
+    #[test]
+    fn test_json_schema_any_of_round_trip() {
+        let value = json!({
+            "anyOf": [
+                { "type": "object", "properties": { "id": { "type": "string" } } },
+                { "type": "integer" }
+            ]
+        });
+
+        let schema = Schema::from_json_value(&value).unwrap();
+        assert_eq!(schema.any_of.as_ref().map(Vec::len), Some(2));
+        assert_eq!(schema.to_json_value(), value);
+    }
+
+    fn error_message(json: &str) -> String {
+        Schema::from_json_str(json).unwrap_err().to_string()
+    }
+
+    #[cfg(feature = "schemars")]
+    mod derive {
+        use schemars::JsonSchema;
+        use serde_json::json;
+
+        use super::super::*;
+
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        #[serde(rename_all = "snake_case")]
+        enum Category {
+            /// Fresh produce
+            Produce,
+            Dairy,
+        }
+
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        enum Unit {
+            Kg,
+            Piece,
+        }
+
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        struct Item {
+            /// The name of the item
+            name: String,
+            quantity: u32,
+            unit: Option<Unit>,
+            price: f64,
+        }
+
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        struct Inventory {
+            /// The items in stock
+            items: Vec<Item>,
+            category: Category,
+            notes: Option<String>,
+        }
+
+        #[test]
+        fn test_derived_schema_matches_hand_built_schema() {
+            let item = Schema::builder()
+                .r#type(SchemaType::Object)
+                .properties(HashMap::from([
+                    (
+                        "name".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::String)
+                            .description("The name of the item")
+                            .build(),
+                    ),
+                    (
+                        "quantity".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::Integer)
+                            .minimum(0)
+                            .build(),
+                    ),
+                    (
+                        "unit".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::String)
+                            .nullable(true)
+                            .enum_values(vec!["Kg".to_string(), "Piece".to_string()])
+                            .build(),
+                    ),
+                    (
+                        "price".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::Number)
+                            .format("double")
+                            .build(),
+                    ),
+                ]))
+                .required(vec![
+                    "name".to_string(),
+                    "quantity".to_string(),
+                    "price".to_string(),
+                ])
+                .build();
+            let expected = Schema::builder()
+                .r#type(SchemaType::Object)
+                .properties(HashMap::from([
+                    (
+                        "items".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::Array)
+                            .description("The items in stock")
+                            .items(Box::new(item))
+                            .build(),
+                    ),
+                    (
+                        "category".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::String)
+                            .enum_values(vec!["dairy".to_string(), "produce".to_string()])
+                            .build(),
+                    ),
+                    (
+                        "notes".to_string(),
+                        Schema::builder()
+                            .r#type(SchemaType::String)
+                            .nullable(true)
+                            .build(),
+                    ),
+                ]))
+                .required(vec!["items".to_string(), "category".to_string()])
+                .build();
+
+            assert_eq!(
+                serde_json::to_value(Schema::for_type::<Inventory>()).unwrap(),
+                serde_json::to_value(expected).unwrap()
+            );
+        }
+
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        struct Node {
+            label: String,
+            children: Vec<Node>,
+        }
+
+        #[test]
+        fn test_recursive_type_is_cut_off() {
+            let schema = serde_json::to_value(Schema::for_type::<Node>()).unwrap();
+
+            assert_eq!(
+                schema["properties"]["children"],
+                json!({ "type": "array", "items": { "type": "object" } })
+            );
+        }
+    }
+}
@@ -1,10 +1,12 @@
 //! Data structures for the Gemini AI API requests and responses.
 
 mod code_execution;
+mod duration;
 mod function;
 mod google_search;
 mod grounding_metadata;
 mod info;
+mod json_schema;
 mod model_params;
 mod part;
 mod request;
@@ -12,34 +14,48 @@ mod request_type;
 mod response;
 mod safety;
 mod schema;
+mod schema_macro;
 mod stream;
 mod system_instruction;
 mod tool;
 
 pub use code_execution::{
-    CodeExecutionConfig, CodeExecutionOutcome, CodeExecutionResult, CodeExecutionTool,
+    CodeExecution, CodeExecutionConfig, CodeExecutionError, CodeExecutionOutcome,
+    CodeExecutionResult, CodeExecutionTool, ExecutableCode,
 };
+pub(crate) use duration::{
+    duration_string, format_duration, option_duration_string, parse_duration,
+};
+#[allow(deprecated)]
+pub use function::FunctionDeclarationSchema;
 pub use function::{
-    FunctionCall, FunctionCallingConfig, FunctionCallingMode, FunctionDeclaration,
-    FunctionDeclarationSchema, FunctionResponse,
+    FunctionArgsError, FunctionCall, FunctionCallingConfig, FunctionCallingMode,
+    FunctionDeclaration, FunctionResponse, ParameterError,
 };
 pub use google_search::GoogleSearch;
 pub use info::ModelInfo;
-pub use model_params::{GenerationConfig, ModelParams};
-pub use part::{FileData, InlineData, Part};
+pub use json_schema::SchemaError;
+pub use model_params::{
+    GenerationConfig, MediaResolution, Modality, ModelParams, PrebuiltVoiceConfig, SpeechConfig,
+    StreamingFormat, ThinkingConfig, VoiceConfig,
+};
+pub use part::{FileData, InlineData, Part, VideoSegment, MAX_INLINE_DATA_SIZE};
 pub use request::{
-    BatchEmbedContentRequest, Content, EmbedContentRequest, Request, Role, TaskType,
+    BatchEmbedContentRequest, Content, EmbedContentRequest, Request, RequestValidationError, Role,
+    TaskType, MAX_FUNCTION_DECLARATIONS, MAX_LABELS, MAX_LABEL_LENGTH, MAX_LOGPROBS,
+    MAX_STOP_SEQUENCES, PENALTY_RANGE,
 };
 pub use request_type::RequestType;
 pub use response::{
-    BatchEmbedContentResponse, Candidate, EmbedContentResponse, Embedding, ListModelsResponse,
-    Response, SafetyProbability, SafetyRating, TokenCountResponse, UsageMetadata,
+    BatchEmbedContentResponse, Candidate, EmbedContentResponse, Embedding, FinishReason,
+    ListModelsResponse, LogprobsCandidate, LogprobsResult, Response, SafetyProbability,
+    SafetyRating, TokenCountResponse, ToolUsage, TopCandidates, UsageMetadata,
 };
 pub use safety::{HarmCategory, SafetySetting, SafetyThreshold};
-pub use schema::{Schema, SchemaType};
-pub use stream::ResponseStream;
+pub use schema::{Schema, SchemaType, SchemaViolation};
+pub use stream::{ResponseStream, StreamEvent};
 pub use system_instruction::SystemInstruction;
-pub use tool::{Tool, ToolConfig};
+pub use tool::{Tool, ToolConfig, Tools};
 
 /// Alias for the Schema type
 pub type ResponseSchema = schema::Schema;
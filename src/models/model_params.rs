@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
@@ -57,15 +59,141 @@ pub struct GenerationConfig {
     #[builder(default, setter(strip_option, into))]
     pub frequency_penalty: Option<f32>,
 
-    /// If true, export the logprobs results in response.
+    /// If true, export the logprobs results in response, see
+    /// [`Candidate::logprobs_result`](super::Candidate::logprobs_result).
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub response_logprobs: Option<bool>,
 
     /// Valid if response_logprobs is set to true. This will set the number of top logprobs to return at each decoding step in the logprobsResult.
+    /// At most [`MAX_LOGPROBS`](super::MAX_LOGPROBS).
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub logprobs: Option<i32>,
+
+    /// The kinds of output the model may reply with, e.g. text and images with
+    /// Gemini 2.0 Flash image generation. Text only when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub response_modalities: Option<Vec<Modality>>,
+
+    /// The voice of spoken replies, for models that reply with
+    /// [`Modality::Audio`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub speech_config: Option<SpeechConfig>,
+
+    /// The resolution at which images, video frames and PDF pages of the input are
+    /// processed. Lower resolutions cost fewer input tokens per image, e.g. 64
+    /// instead of 256, at the expense of detail. A resolution in the model params
+    /// also applies to requests with a generation config of their own without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub media_resolution: Option<MediaResolution>,
+
+    /// Seed used in decoding, for reproducible generations.
+    ///
+    /// With the same seed, request and model version the API makes a best effort
+    /// to return the same response, but does not guarantee it. The seed fixes the
+    /// random sampling, so it matters most with a non-zero temperature; at
+    /// temperature 0 the output is already close to deterministic. A seed in the
+    /// generation config of [`ModelParams`] also applies to requests that bring a
+    /// generation config of their own without a seed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub seed: Option<i64>,
+
+    /// Configuration of the thinking of thinking models.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub thinking_config: Option<ThinkingConfig>,
+}
+
+/// A kind of model output, see [`GenerationConfig::response_modalities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Modality {
+    /// Text output.
+    Text,
+    /// Image output.
+    Image,
+    /// Audio output.
+    Audio,
+}
+
+/// Configuration of the thinking of thinking models, e.g. Gemini 2.5.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypedBuilder)]
+#[builder(doc)]
+pub struct ThinkingConfig {
+    /// The number of tokens the model may spend on thinking; 0 disables thinking and
+    /// -1 lets the model decide.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub thinking_budget: Option<i32>,
+
+    /// Whether the response includes the thoughts of the model, as parts for which
+    /// [`Part::is_thought`](super::Part::is_thought) is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub include_thoughts: Option<bool>,
+}
+
+/// The resolution of media inputs, see [`GenerationConfig::media_resolution`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MediaResolution {
+    /// Low resolution, the fewest tokens per image.
+    #[serde(rename = "MEDIA_RESOLUTION_LOW")]
+    Low,
+    /// Medium resolution.
+    #[serde(rename = "MEDIA_RESOLUTION_MEDIUM")]
+    Medium,
+    /// High resolution, the most detail and tokens per image.
+    #[serde(rename = "MEDIA_RESOLUTION_HIGH")]
+    High,
+}
+
+/// Configuration of spoken replies, see [`GenerationConfig::speech_config`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypedBuilder)]
+#[builder(doc)]
+pub struct SpeechConfig {
+    /// The voice to speak with.
+    #[builder(setter(into))]
+    pub voice_config: VoiceConfig,
+}
+
+impl SpeechConfig {
+    /// Creates a configuration speaking with the prebuilt voice `voice_name`,
+    /// e.g. `Kore` or `Puck`.
+    pub fn voice(voice_name: impl Into<String>) -> Self {
+        Self {
+            voice_config: VoiceConfig::builder()
+                .prebuilt_voice_config(
+                    PrebuiltVoiceConfig::builder()
+                        .voice_name(voice_name)
+                        .build(),
+                )
+                .build(),
+        }
+    }
+}
+
+/// The voice of spoken replies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypedBuilder)]
+#[builder(doc)]
+pub struct VoiceConfig {
+    /// One of the voices provided by the API.
+    #[builder(setter(into))]
+    pub prebuilt_voice_config: PrebuiltVoiceConfig,
+}
+
+/// A voice provided by the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypedBuilder)]
+#[builder(doc)]
+pub struct PrebuiltVoiceConfig {
+    /// The name of the voice, e.g. `Kore`.
+    #[builder(setter(into))]
+    pub voice_name: String,
 }
 
 /// Parameters for configuring a generative model.
@@ -80,6 +208,34 @@ pub struct ModelParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub generation_config: Option<GenerationConfig>,
+
+    /// Keep the raw JSON of every response in `Response::raw`, e.g. to read
+    /// experimental fields the typed response does not model yet.
+    #[serde(default)]
+    #[builder(default)]
+    pub keep_raw: bool,
+
+    /// The wire format used by `stream_generate_response`.
+    #[serde(default)]
+    #[builder(default)]
+    pub streaming_format: StreamingFormat,
+
+    /// The longest time to wait for the next chunk of a streamed response before the
+    /// stream fails with a timeout error. Waits indefinitely when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub stream_idle_timeout: Option<Duration>,
+}
+
+/// The wire format of streamed responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamingFormat {
+    /// Server-Sent Events, requested with `alt=sse`. Every chunk arrives in its own
+    /// `data:` frame.
+    #[default]
+    Sse,
+    /// A single JSON array whose elements arrive incrementally.
+    JsonArray,
 }
 
 impl Default for ModelParams {
@@ -87,3 +243,103 @@ impl Default for ModelParams {
         Self::builder().build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_thinking_config_serialization() {
+        let config = GenerationConfig::builder()
+            .thinking_config(ThinkingConfig {
+                thinking_budget: Some(1024),
+                include_thoughts: Some(true),
+            })
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({ "thinking_config": { "thinking_budget": 1024, "include_thoughts": true } })
+        );
+    }
+
+    #[test]
+    fn test_response_modalities_serialization() {
+        let config = GenerationConfig::builder()
+            .response_modalities(vec![Modality::Text, Modality::Image])
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({ "response_modalities": ["TEXT", "IMAGE"] })
+        );
+        assert_eq!(
+            serde_json::from_value::<Modality>(json!("AUDIO")).unwrap(),
+            Modality::Audio
+        );
+    }
+
+    #[test]
+    fn test_media_resolution_serialization() {
+        let config = GenerationConfig::builder()
+            .media_resolution(MediaResolution::Low)
+            .build();
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({ "media_resolution": "MEDIA_RESOLUTION_LOW" })
+        );
+
+        for (name, resolution) in [
+            ("MEDIA_RESOLUTION_MEDIUM", MediaResolution::Medium),
+            ("MEDIA_RESOLUTION_HIGH", MediaResolution::High),
+        ] {
+            assert_eq!(serde_json::to_value(resolution).unwrap(), json!(name));
+        }
+    }
+
+    #[test]
+    fn test_speech_config_serialization() {
+        let config = GenerationConfig::builder()
+            .response_modalities(vec![Modality::Audio])
+            .speech_config(SpeechConfig::voice("Kore"))
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "response_modalities": ["AUDIO"],
+                "speech_config": {
+                    "voice_config": { "prebuilt_voice_config": { "voice_name": "Kore" } }
+                }
+            })
+        );
+
+        let built = SpeechConfig::builder()
+            .voice_config(
+                VoiceConfig::builder()
+                    .prebuilt_voice_config(
+                        PrebuiltVoiceConfig::builder().voice_name("Kore").build(),
+                    )
+                    .build(),
+            )
+            .build();
+        assert_eq!(built, SpeechConfig::voice("Kore"));
+    }
+
+    #[test]
+    fn test_seed_serialization() {
+        let config = GenerationConfig::builder()
+            .temperature(0.7)
+            .seed(1234567890123_i64)
+            .build();
+
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"temperature":0.7,"seed":1234567890123}"#
+        );
+        let parsed: GenerationConfig = serde_json::from_str(r#"{"seed":-7}"#).unwrap();
+        assert_eq!(parsed.seed, Some(-7));
+    }
+}
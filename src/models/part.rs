@@ -2,13 +2,23 @@
 
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
 use std::path::Path;
+use std::time::Duration;
+
+use crate::file::{FileError, FileInfo, FileState};
 
 use super::{
     code_execution::{CodeExecutionResult, ExecutableCode},
     function::{FunctionCall, FunctionResponse},
+    option_duration_string,
 };
 
+/// The largest file, in bytes, that can be sent inline; larger files must be uploaded
+/// with the File API.
+pub const MAX_INLINE_DATA_SIZE: u64 = 20 * 1024 * 1024;
+
 /// A part containing text content.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -17,10 +27,14 @@ pub enum Part {
     Text {
         /// The text content of the part
         text: String,
+        /// Whether the text is a thought of a thinking model rather than part of the answer
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thought: Option<bool>,
     },
     /// A part containing inline data
     InlineData {
         /// The inline data content of the part
+        #[serde(alias = "inlineData")]
         inline_data: InlineData,
     },
 
@@ -28,6 +42,13 @@ pub enum Part {
     FileData {
         /// The file data content of the part
         file_data: FileData,
+        /// The clip of a video file to use
+        #[serde(
+            rename = "videoMetadata",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        video_metadata: Option<VideoSegment>,
     },
 
     /// A part containing a function call
@@ -59,20 +80,109 @@ pub enum Part {
 impl Part {
     /// Creates a new text part.
     pub fn text(text: impl Into<String>) -> Self {
-        Self::Text { text: text.into() }
+        Self::Text {
+            text: text.into(),
+            thought: None,
+        }
+    }
+
+    /// Returns `true` if this is a thought of a thinking model.
+    pub fn is_thought(&self) -> bool {
+        matches!(
+            self,
+            Self::Text {
+                thought: Some(true),
+                ..
+            }
+        )
+    }
+
+    /// Creates a new inline data part from raw bytes, which are base64 encoded.
+    pub fn inline_data(mime_type: impl Into<String>, data: impl AsRef<[u8]>) -> Self {
+        Self::InlineData {
+            inline_data: InlineData {
+                mime_type: mime_type.into(),
+                data: base64_engine.encode(data),
+            },
+        }
+    }
+
+    /// Creates a new inline data part from encoded image bytes, e.g. a PNG screenshot
+    /// held in memory.
+    pub fn image_from_bytes(bytes: &[u8], mime_type: &str) -> Self {
+        Self::inline_data(mime_type, bytes)
+    }
+
+    /// Creates a new inline data part by encoding an image in memory.
+    ///
+    /// Raw pixel buffers can be wrapped in a [`image::DynamicImage`] first, e.g. with
+    /// `DynamicImage::ImageRgba8`. JPEG does not support an alpha channel, so RGBA
+    /// images should be encoded as PNG.
+    #[cfg(feature = "image")]
+    pub fn from_dynamic_image(
+        image: &image::DynamicImage,
+        format: image::ImageFormat,
+    ) -> image::ImageResult<Self> {
+        let mut data = io::Cursor::new(Vec::new());
+        image.write_to(&mut data, format)?;
+        Ok(Self::inline_data(format.to_mime_type(), data.into_inner()))
     }
 
     /// Creates a new inline data part from a file path.
     pub fn image_from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::inline_from_path(path, None)
+    }
+
+    /// Creates a new inline data part from an audio file, e.g. an MP3 or WAV file.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the extension is not that of an
+    /// audio file.
+    pub fn audio_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mime_type = mime_guess::from_path(path).first_or_octet_stream();
+        if mime_type.type_() != mime_guess::mime::AUDIO {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an audio file", path.display()),
+            ));
+        }
+        Self::inline_from_path(path, Some(mime_type.as_ref()))
+    }
+
+    /// Creates a new inline data part from a PDF document.
+    pub fn pdf_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::inline_from_path(path, Some("application/pdf"))
+    }
+
+    /// Creates a new inline data part from a file path.
+    ///
+    /// The MIME type is guessed from the extension unless `mime_type` is given.
+    /// Fails with [`io::ErrorKind::FileTooLarge`] if the file is larger than
+    /// [`MAX_INLINE_DATA_SIZE`]; upload such files with the
+    /// [`GoogleAIFileManager`](crate::GoogleAIFileManager) and use
+    /// [`Part::from_file_info`] instead.
+    pub fn inline_from_path(path: impl AsRef<Path>, mime_type: Option<&str>) -> io::Result<Self> {
         let path = path.as_ref();
-        let data = std::fs::read(path)?;
-        let mime_type = mime_guess::from_path(path)
-            .first_or_octet_stream()
-            .to_string();
-        let data = base64_engine.encode(data);
-        Ok(Self::InlineData {
-            inline_data: InlineData { mime_type, data },
-        })
+        let size = std::fs::metadata(path)?.len();
+        if size > MAX_INLINE_DATA_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!(
+                    "{} is {} bytes, more than the {} bytes that can be sent inline; \
+                     upload it with the File API instead",
+                    path.display(),
+                    size,
+                    MAX_INLINE_DATA_SIZE
+                ),
+            ));
+        }
+        let mime_type = match mime_type {
+            Some(mime_type) => mime_type.to_string(),
+            None => mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string(),
+        };
+        Ok(Self::inline_data(mime_type, std::fs::read(path)?))
     }
 
     /// Creates a new file data part.
@@ -82,9 +192,56 @@ impl Part {
                 mime_type: mime_type.into(),
                 file_uri: file_uri.into(),
             },
+            video_metadata: None,
+        }
+    }
+
+    /// Creates a new file data part referencing a clip of an uploaded video.
+    ///
+    /// # Arguments
+    ///
+    /// * `mime_type` - The MIME type of the video, e.g. `video/mp4`
+    /// * `file_uri` - The URI of the uploaded video
+    /// * `start` - The offset of the start of the clip
+    /// * `end` - The offset of the end of the clip
+    /// * `fps` - The frame rate at which the clip is sampled, 1 by default
+    pub fn video_segment(
+        mime_type: impl Into<String>,
+        file_uri: impl Into<String>,
+        start: Duration,
+        end: Duration,
+        fps: Option<f32>,
+    ) -> Self {
+        Self::FileData {
+            file_data: FileData {
+                mime_type: mime_type.into(),
+                file_uri: file_uri.into(),
+            },
+            video_metadata: Some(VideoSegment {
+                start_offset: Some(start),
+                end_offset: Some(end),
+                fps,
+            }),
         }
     }
 
+    /// Creates a new file data part referencing an uploaded file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FileError::NotActive`] if the file is not active yet, e.g. because it
+    /// is still processing; wait for it with
+    /// [`GoogleAIFileManager::wait_for_file_processing`](crate::GoogleAIFileManager::wait_for_file_processing).
+    pub fn from_file_info(file: &FileInfo) -> Result<Self, FileError> {
+        if file.state != FileState::Active {
+            return Err(FileError::NotActive {
+                name: file.name.clone(),
+                state: file.state,
+            });
+        }
+        Ok(Self::file_data(file.mime_type.clone(), file.uri.clone()))
+    }
+
     /// Creates a new function call part.
     pub fn function_call(function_call: FunctionCall) -> Self {
         Self::FunctionCall { function_call }
@@ -97,14 +254,64 @@ impl Part {
 }
 
 /// Inline data (base64 encoded)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The `Debug` output shows only the start of the data and its size, so logging a
+/// request with an inline image does not dump the whole image; read
+/// [`data`](Self::data) for the full payload.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InlineData {
     /// The MIME type of the inline data
+    #[serde(alias = "mimeType")]
     pub mime_type: String,
     /// Base64 encoded data
     pub data: String,
 }
 
+/// How many characters of inline data are shown in `Debug` output.
+const DEBUG_DATA_PREFIX: usize = 16;
+
+impl fmt::Debug for InlineData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = if self.data.len() > DEBUG_DATA_PREFIX {
+            let prefix: String = self.data.chars().take(DEBUG_DATA_PREFIX).collect();
+            format!(
+                "{}... ({} bytes)",
+                prefix,
+                super::request::decoded_len(&self.data)
+            )
+        } else {
+            self.data.clone()
+        };
+        f.debug_struct("InlineData")
+            .field("mime_type", &self.mime_type)
+            .field("data", &format_args!("{}", data))
+            .finish()
+    }
+}
+
+/// The clip of a video a file data part refers to, and how it is sampled
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoSegment {
+    /// The offset of the start of the clip, the start of the video if unset
+    #[serde(
+        default,
+        with = "option_duration_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start_offset: Option<Duration>,
+    /// The offset of the end of the clip, the end of the video if unset
+    #[serde(
+        default,
+        with = "option_duration_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end_offset: Option<Duration>,
+    /// The number of frames per second sampled from the clip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
+}
+
 /// File data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileData {
@@ -113,3 +320,150 @@ pub struct FileData {
     /// The URI of the file
     pub file_uri: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inline_mime_type(part: &Part) -> &str {
+        match part {
+            Part::InlineData { inline_data } => &inline_data.mime_type,
+            other => panic!("unexpected part: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_inline_parts_from_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"data").unwrap();
+            path
+        };
+
+        let part = Part::audio_from_path(write("speech.mp3")).unwrap();
+        assert_eq!(inline_mime_type(&part), "audio/mpeg");
+        let part = Part::pdf_from_path(write("report")).unwrap();
+        assert_eq!(inline_mime_type(&part), "application/pdf");
+        let part = Part::inline_from_path(write("clip.mp4"), None).unwrap();
+        assert_eq!(inline_mime_type(&part), "video/mp4");
+        let part = Part::inline_from_path(write("clip.bin"), Some("video/webm")).unwrap();
+        assert_eq!(inline_mime_type(&part), "video/webm");
+        assert_eq!(
+            serde_json::to_value(&part).unwrap()["inline_data"]["data"],
+            "ZGF0YQ=="
+        );
+
+        let error = Part::audio_from_path(write("notes.txt")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_inline_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("movie.mp4");
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(MAX_INLINE_DATA_SIZE).unwrap();
+        assert!(Part::inline_from_path(&path, None).is_ok());
+
+        file.set_len(MAX_INLINE_DATA_SIZE + 1).unwrap();
+        let error = Part::inline_from_path(&path, None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+        assert!(error.to_string().contains("File API"));
+    }
+
+    #[test]
+    fn test_image_from_bytes_round_trip() {
+        let bytes = [0x89, b'P', b'N', b'G', 0, 1, 2, 255];
+        let part = Part::image_from_bytes(&bytes, "image/png");
+        assert_eq!(inline_mime_type(&part), "image/png");
+
+        let Part::InlineData { inline_data } = part else {
+            unreachable!()
+        };
+        assert_eq!(base64_engine.decode(inline_data.data).unwrap(), bytes);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_from_dynamic_image_round_trip() {
+        let pixels =
+            image::RgbaImage::from_fn(2, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 255]));
+        let image = image::DynamicImage::ImageRgba8(pixels);
+        let part = Part::from_dynamic_image(&image, image::ImageFormat::Png).unwrap();
+        assert_eq!(inline_mime_type(&part), "image/png");
+
+        let Part::InlineData { inline_data } = part else {
+            unreachable!()
+        };
+        let decoded =
+            image::load_from_memory(&base64_engine.decode(inline_data.data).unwrap()).unwrap();
+        assert_eq!(decoded.to_rgba8(), image.to_rgba8());
+    }
+
+    #[test]
+    fn test_video_segment_serialization() {
+        let part = Part::video_segment(
+            "video/mp4",
+            "https://generativelanguage.googleapis.com/v1beta/files/abc",
+            Duration::from_millis(3500),
+            Duration::from_secs(40),
+            Some(2.0),
+        );
+        let value = serde_json::to_value(&part).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "file_data": {
+                    "mime_type": "video/mp4",
+                    "file_uri": "https://generativelanguage.googleapis.com/v1beta/files/abc"
+                },
+                "videoMetadata": { "startOffset": "3.5s", "endOffset": "40s", "fps": 2.0 }
+            })
+        );
+
+        let parsed: Part = serde_json::from_value(value).unwrap();
+        let Part::FileData { video_metadata, .. } = parsed else {
+            panic!("unexpected part: {:?}", parsed);
+        };
+        assert_eq!(
+            video_metadata.unwrap().start_offset,
+            Some(Duration::from_millis(3500))
+        );
+    }
+
+    #[test]
+    fn test_file_data_without_video_metadata() {
+        let part = Part::file_data("application/pdf", "https://example.com/files/abc");
+        let value = serde_json::to_value(&part).unwrap();
+        assert!(value.get("videoMetadata").is_none());
+
+        let segment: VideoSegment =
+            serde_json::from_value(serde_json::json!({ "fps": 0.5 })).unwrap();
+        assert_eq!(segment.start_offset, None);
+        assert_eq!(
+            serde_json::to_value(&segment).unwrap(),
+            serde_json::json!({ "fps": 0.5 })
+        );
+    }
+
+    #[test]
+    fn test_inline_data_debug_is_bounded() {
+        let part = Part::inline_data("image/png", vec![7u8; 1024 * 1024]);
+        let request = crate::models::Request::builder()
+            .contents(vec![crate::models::Content {
+                role: None,
+                parts: vec![Part::text("Describe this image"), part.clone()],
+            }])
+            .build();
+
+        for debug in [format!("{:?}", request), format!("{:#?}", request)] {
+            assert!(debug.len() < 2048, "debug output is {} bytes", debug.len());
+            assert!(debug.contains("image/png"));
+            assert!(debug.contains("... (1048576 bytes)"));
+        }
+
+        let small = Part::inline_data("text/plain", b"hi");
+        assert!(format!("{:?}", small).contains("data: aGk="));
+    }
+}
@@ -1,13 +1,38 @@
 //! Request models for the Gemini AI API.
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use typed_builder::TypedBuilder;
 
+use crate::file::{FileError, FileInfo};
+
 use super::{
-    model_params::GenerationConfig, system_instruction::SystemInstruction, tool::ToolConfig, Part,
-    SafetySetting, Tool,
+    model_params::GenerationConfig, system_instruction::SystemInstruction, tool::ToolConfig,
+    FunctionCallingConfig, FunctionCallingMode, FunctionDeclaration, FunctionResponse, Part,
+    SafetySetting, Schema, SchemaError, Tool, MAX_INLINE_DATA_SIZE,
 };
 
+/// The maximum number of function declarations a request may carry across all of its
+/// tools.
+pub const MAX_FUNCTION_DECLARATIONS: usize = 64;
+
+/// The maximum number of stop sequences a generation config may carry.
+pub const MAX_STOP_SEQUENCES: usize = 5;
+
+/// The range accepted for the presence and frequency penalties, exclusive of the end.
+pub const PENALTY_RANGE: std::ops::Range<f32> = -2.0..2.0;
+
+/// The maximum number of top log probabilities returned per decoding step.
+pub const MAX_LOGPROBS: i32 = 20;
+
+/// The maximum number of labels a request may carry.
+pub const MAX_LABELS: usize = 64;
+
+/// The maximum length of a label key or value, in characters.
+pub const MAX_LABEL_LENGTH: usize = 63;
+
 /// A request to the Gemini AI API.
 #[derive(Debug, Clone, Serialize, TypedBuilder)]
 #[builder(doc)]
@@ -37,14 +62,72 @@ pub struct Request {
     pub tools: Option<Vec<Tool>>,
 
     /// Optional configuration for function calling
+    ///
+    /// Besides setting it whole, the builder can set its parts with
+    /// `function_calling_mode` and `allowed_functions`.
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default, setter(strip_option, into))]
+    #[builder(
+        via_mutators,
+        mutators(
+            /// Sets the tool configuration.
+            fn tool_config(&mut self, tool_config: ToolConfig) {
+                self.tool_config = Some(tool_config);
+            }
+
+            /// Sets the function calling mode, keeping any allowed functions.
+            fn function_calling_mode(&mut self, mode: FunctionCallingMode) {
+                let config = function_calling_config(&mut self.tool_config, mode.clone());
+                config.mode = mode;
+            }
+
+            /// Restricts the functions the model may call, which requires
+            /// [`FunctionCallingMode::Any`]; the mode is set to `Any` unless
+            /// `function_calling_mode` set it already.
+            fn allowed_functions(&mut self, names: impl IntoIterator<Item = impl Into<String>>) {
+                function_calling_config(&mut self.tool_config, FunctionCallingMode::Any)
+                    .allowed_function_names = Some(names.into_iter().map(Into::into).collect());
+            }
+        )
+    )]
     pub tool_config: Option<ToolConfig>,
 
-    /// Optional cached content for the request
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Optional name of cached content to use as context, e.g. `cachedContents/abc123`,
+    /// as created with [`CacheManager`](crate::cache::CacheManager).
+    ///
+    /// The cached contents are prepended to the request, so `contents` then only
+    /// needs the new user turn. The model must match the one the cache was created
+    /// for. [`UsageMetadata::cached_content_token_count`](super::UsageMetadata::cached_content_token_count)
+    /// of the response tells how many prompt tokens were served from the cache.
+    #[serde(rename = "cachedContent", skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub cached_content: Option<String>,
+
+    /// Optional labels attached to the request for billing attribution, e.g. a tenant
+    /// id. Keys start with a lowercase letter; keys and values consist of lowercase
+    /// letters, digits, `_` and `-`, and are at most [`MAX_LABEL_LENGTH`] characters
+    /// long.
+    #[serde(skip_serializing_if = "labels_are_empty")]
+    #[builder(default, setter(strip_option, into))]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+/// Returns the function calling configuration of `tool_config`, creating it with
+/// `mode` if there is none.
+fn function_calling_config(
+    tool_config: &mut Option<ToolConfig>,
+    mode: FunctionCallingMode,
+) -> &mut FunctionCallingConfig {
+    &mut tool_config
+        .get_or_insert_with(|| {
+            ToolConfig::builder()
+                .function_calling_config(FunctionCallingConfig::builder().mode(mode).build())
+                .build()
+        })
+        .function_calling_config
+}
+
+fn labels_are_empty(labels: &Option<HashMap<String, String>>) -> bool {
+    labels.as_ref().is_none_or(HashMap::is_empty)
 }
 
 /// Role of a participant in a chat
@@ -71,19 +154,439 @@ pub struct Content {
     pub parts: Vec<Part>,
 }
 
+impl Content {
+    /// Creates a user content with a single text part.
+    pub fn user(text: impl Into<String>) -> Self {
+        Self::user_parts(vec![Part::text(text)])
+    }
+
+    /// Creates a user content from parts, e.g. text and an image.
+    pub fn user_parts(parts: Vec<Part>) -> Self {
+        Self {
+            role: Some(Role::User),
+            parts,
+        }
+    }
+
+    /// Creates a model content with a single text part, e.g. an earlier reply in a
+    /// conversation.
+    pub fn model(text: impl Into<String>) -> Self {
+        Self {
+            role: Some(Role::Model),
+            parts: vec![Part::text(text)],
+        }
+    }
+
+    /// Creates a function content answering a function call.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function that was called
+    /// * `response` - The result of the function
+    pub fn function_response(name: impl Into<String>, response: serde_json::Value) -> Self {
+        Self {
+            role: Some(Role::Function),
+            parts: vec![Part::function_response(FunctionResponse {
+                name: name.into(),
+                response,
+            })],
+        }
+    }
+
+    /// Creates a function content answering all function calls of one model turn.
+    ///
+    /// When the model calls several functions at once, all responses must be sent
+    /// back together in a single content, one part per call.
+    pub fn function_responses(responses: Vec<FunctionResponse>) -> Self {
+        Self {
+            role: Some(Role::Function),
+            parts: responses.into_iter().map(Part::function_response).collect(),
+        }
+    }
+
+    /// Creates a user content asking a question about an uploaded file.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The question or instruction about the file
+    /// * `file` - The uploaded file, which must be active
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FileError::NotActive`] if the file is not active yet.
+    pub fn user_with_file(text: impl Into<String>, file: &FileInfo) -> Result<Self, FileError> {
+        Ok(Self {
+            role: Some(Role::User),
+            parts: vec![Part::text(text), Part::from_file_info(file)?],
+        })
+    }
+}
+
 impl Request {
     /// Creates a new request with the given text prompt.
     ///
     /// # Arguments
     ///
     /// * `text` - The text prompt to generate content from
+    pub fn from_prompt(text: impl Into<String>) -> Self {
+        Self::from_contents(vec![Content::user(text)])
+    }
+
+    /// Creates a new request with the given text prompt, like
+    /// [`from_prompt`](Self::from_prompt).
     pub fn with_prompt(text: impl Into<String>) -> Self {
-        Self::builder()
-            .contents(vec![Content {
-                role: Some(Role::User),
-                parts: vec![Part::Text { text: text.into() }],
-            }])
-            .build()
+        Self::from_prompt(text)
+    }
+
+    /// Creates a new request from the contents of a conversation, without further
+    /// configuration.
+    pub fn from_contents(contents: Vec<Content>) -> Self {
+        Self::builder().contents(contents).build()
+    }
+
+    /// Checks the request for mistakes the API would reject with an opaque 400.
+    ///
+    /// [`GenerativeModel`](crate::GenerativeModel) calls this before sending a request
+    /// unless disabled with
+    /// [`with_request_validation`](crate::GenerativeModel::with_request_validation).
+    ///
+    /// # Errors
+    ///
+    /// Returns the first [`RequestValidationError`] found:
+    ///
+    /// * the request has no contents, or a content has no parts
+    /// * a content with [`Role::Function`] has a part that is not a function response
+    /// * the tools declare more than [`MAX_FUNCTION_DECLARATIONS`] functions, a
+    ///   function name is not 1 to 63 letters, digits, underscores or dashes, or
+    ///   two functions share a name
+    /// * `allowed_function_names` is set with a mode other than
+    ///   [`FunctionCallingMode::Any`], or names a function that is not declared
+    /// * the inline data adds up to more than [`MAX_INLINE_DATA_SIZE`] bytes
+    /// * `response_mime_type` is `application/json` without a `response_schema`, or
+    ///   the `response_schema` fails [`Schema::check`]
+    /// * there are more than [`MAX_STOP_SEQUENCES`] stop sequences, the candidate
+    ///   count is below 1, or a penalty is outside [`PENALTY_RANGE`]
+    /// * `logprobs` is set without `response_logprobs`, or is outside 0 to
+    ///   [`MAX_LOGPROBS`]
+    /// * there are more than [`MAX_LABELS`] labels, or a label is malformed
+    pub fn validate(&self) -> Result<(), RequestValidationError> {
+        if self.contents.is_empty() {
+            return Err(RequestValidationError::EmptyContents);
+        }
+
+        let mut inline_data_size = 0;
+        for (index, content) in self.contents.iter().enumerate() {
+            if content.parts.is_empty() {
+                return Err(RequestValidationError::EmptyParts { index });
+            }
+            if matches!(content.role, Some(Role::Function))
+                && !content
+                    .parts
+                    .iter()
+                    .all(|part| matches!(part, Part::FunctionResponse { .. }))
+            {
+                return Err(RequestValidationError::InvalidFunctionContent { index });
+            }
+            inline_data_size += content
+                .parts
+                .iter()
+                .filter_map(|part| match part {
+                    Part::InlineData { inline_data } => Some(decoded_len(&inline_data.data) as u64),
+                    _ => None,
+                })
+                .sum::<u64>();
+        }
+        if inline_data_size > MAX_INLINE_DATA_SIZE {
+            return Err(RequestValidationError::InlineDataTooLarge {
+                size: inline_data_size,
+            });
+        }
+
+        let declarations = self
+            .tools
+            .iter()
+            .flatten()
+            .flat_map(|tool| match tool {
+                Tool::FunctionDeclarationsTool(tool) => tool.function_declarations.as_slice(),
+                _ => &[],
+            })
+            .collect::<Vec<_>>();
+        validate_function_declarations(&declarations)?;
+
+        if let Some(config) = &self.tool_config {
+            let config = &config.function_calling_config;
+            if let Some(names) = &config.allowed_function_names {
+                if !matches!(config.mode, FunctionCallingMode::Any) {
+                    return Err(RequestValidationError::AllowedFunctionNamesWithoutAny);
+                }
+                if let Some(name) = names.iter().find(|name| {
+                    !declarations
+                        .iter()
+                        .any(|declaration| declaration.name.as_ref() == Some(*name))
+                }) {
+                    return Err(RequestValidationError::UnknownAllowedFunction {
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+
+        if let Some(config) = &self.generation_config {
+            if config.response_mime_type.as_deref() == Some("application/json")
+                && config.response_schema.is_none()
+            {
+                return Err(RequestValidationError::MissingResponseSchema);
+            }
+            if let Some(Err(SchemaError::Invalid(problems))) =
+                config.response_schema.as_ref().map(Schema::check)
+            {
+                return Err(RequestValidationError::InvalidResponseSchema { problems });
+            }
+            if let Some(stop_sequences) = &config.stop_sequences {
+                if stop_sequences.len() > MAX_STOP_SEQUENCES {
+                    return Err(RequestValidationError::TooManyStopSequences {
+                        count: stop_sequences.len(),
+                    });
+                }
+            }
+            if let Some(count) = config.candidate_count.filter(|count| *count < 1) {
+                return Err(RequestValidationError::InvalidCandidateCount { count });
+            }
+            for (name, penalty) in [
+                ("presence_penalty", config.presence_penalty),
+                ("frequency_penalty", config.frequency_penalty),
+            ] {
+                if let Some(value) = penalty.filter(|value| !PENALTY_RANGE.contains(value)) {
+                    return Err(RequestValidationError::PenaltyOutOfRange { name, value });
+                }
+            }
+            if let Some(count) = config.logprobs {
+                if config.response_logprobs != Some(true) {
+                    return Err(RequestValidationError::LogprobsWithoutResponseLogprobs);
+                }
+                if !(0..=MAX_LOGPROBS).contains(&count) {
+                    return Err(RequestValidationError::LogprobsOutOfRange { count });
+                }
+            }
+        }
+
+        if let Some(labels) = &self.labels {
+            if labels.len() > MAX_LABELS {
+                return Err(RequestValidationError::TooManyLabels {
+                    count: labels.len(),
+                });
+            }
+            for (key, value) in labels {
+                validate_label(key, value).map_err(|reason| {
+                    RequestValidationError::InvalidLabel {
+                        key: key.clone(),
+                        reason: reason.to_string(),
+                    }
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks a label against the rules of the API, returning why it is malformed.
+fn validate_label(key: &str, value: &str) -> Result<(), &'static str> {
+    let allowed = |c: char| c.is_lowercase() || c.is_ascii_digit() || c == '_' || c == '-';
+
+    if !key.chars().next().is_some_and(char::is_lowercase) {
+        return Err("the key must start with a lowercase letter");
+    }
+    if key.chars().count() > MAX_LABEL_LENGTH {
+        return Err("the key is too long");
+    }
+    if !key.chars().all(allowed) {
+        return Err("the key may only contain lowercase letters, digits, `_` and `-`");
+    }
+    if value.chars().count() > MAX_LABEL_LENGTH {
+        return Err("the value is too long");
+    }
+    if !value.chars().all(allowed) {
+        return Err("the value may only contain lowercase letters, digits, `_` and `-`");
+    }
+    Ok(())
+}
+
+/// Returns the number of bytes encoded by a base64 string.
+pub(super) fn decoded_len(data: &str) -> usize {
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+    (data.len() / 4 * 3 + data.len() % 4 * 3 / 4).saturating_sub(padding)
+}
+
+/// A mistake in a [`Request`], found by [`Request::validate`] before it is sent.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RequestValidationError {
+    /// The request has no contents.
+    #[error("the request has no contents")]
+    EmptyContents,
+
+    /// A content has no parts.
+    #[error("content {index} has no parts")]
+    EmptyParts {
+        /// The index of the content in the request.
+        index: usize,
+    },
+
+    /// A content with the function role has a part that is not a function response.
+    #[error("content {index} has the function role but a part that is not a function response")]
+    InvalidFunctionContent {
+        /// The index of the content in the request.
+        index: usize,
+    },
+
+    /// The tools declare more functions than the API accepts.
+    #[error(
+        "the tools declare {count} functions, at most {} are allowed",
+        MAX_FUNCTION_DECLARATIONS
+    )]
+    TooManyFunctionDeclarations {
+        /// The number of declared functions.
+        count: usize,
+    },
+
+    /// A function name does not match `[a-zA-Z0-9_-]{1,63}`.
+    #[error("invalid function name `{name}`: use 1 to 63 letters, digits, underscores or dashes")]
+    InvalidFunctionName {
+        /// The invalid name, empty if the declaration has none.
+        name: String,
+    },
+
+    /// Two function declarations share a name.
+    #[error("function `{name}` is declared more than once")]
+    DuplicateFunctionName {
+        /// The duplicated name.
+        name: String,
+    },
+
+    /// `allowed_function_names` is set but the function calling mode is not `ANY`.
+    #[error("allowed_function_names may only be set with function calling mode ANY")]
+    AllowedFunctionNamesWithoutAny,
+
+    /// `allowed_function_names` names a function that no tool declares.
+    #[error("allowed function `{name}` is not declared in the tools")]
+    UnknownAllowedFunction {
+        /// The undeclared name.
+        name: String,
+    },
+
+    /// The inline data of the request is too large to send inline.
+    #[error(
+        "the inline data adds up to {size} bytes, at most {} are allowed; upload large files with the File API",
+        MAX_INLINE_DATA_SIZE
+    )]
+    InlineDataTooLarge {
+        /// The decoded size of all inline data, in bytes.
+        size: u64,
+    },
+
+    /// JSON output was requested without a schema.
+    #[error("response_mime_type application/json requires a response_schema")]
+    MissingResponseSchema,
+
+    /// The response schema has mistakes, see [`Schema::check`].
+    #[error("invalid response schema: {}", problems.join(", "))]
+    InvalidResponseSchema {
+        /// The mistakes with their location in the schema.
+        problems: Vec<String>,
+    },
+
+    /// The generation config has more stop sequences than the API accepts.
+    #[error(
+        "the generation config has {count} stop sequences, at most {} are allowed",
+        MAX_STOP_SEQUENCES
+    )]
+    TooManyStopSequences {
+        /// The number of stop sequences.
+        count: usize,
+    },
+
+    /// The generation config asks for fewer than one candidate.
+    #[error("candidate_count is {count}, it must be at least 1")]
+    InvalidCandidateCount {
+        /// The requested number of candidates.
+        count: i32,
+    },
+
+    /// A penalty of the generation config is outside [`PENALTY_RANGE`].
+    #[error(
+        "{name} is {value}, it must be at least {} and less than {}",
+        PENALTY_RANGE.start,
+        PENALTY_RANGE.end
+    )]
+    PenaltyOutOfRange {
+        /// The name of the penalty, `presence_penalty` or `frequency_penalty`.
+        name: &'static str,
+        /// The value of the penalty.
+        value: f32,
+    },
+
+    /// `logprobs` is set but `response_logprobs` is not enabled.
+    #[error("logprobs requires response_logprobs to be true")]
+    LogprobsWithoutResponseLogprobs,
+
+    /// `logprobs` asks for more top log probabilities than the API returns.
+    #[error("logprobs is {count}, it must be between 0 and {}", MAX_LOGPROBS)]
+    LogprobsOutOfRange {
+        /// The requested number of top log probabilities.
+        count: i32,
+    },
+
+    /// The request carries more labels than the API accepts.
+    #[error("the request has {count} labels, at most {} are allowed", MAX_LABELS)]
+    TooManyLabels {
+        /// The number of labels.
+        count: usize,
+    },
+
+    /// A label is malformed.
+    #[error("invalid label `{key}`: {reason}")]
+    InvalidLabel {
+        /// The key of the label.
+        key: String,
+        /// Why the label is malformed.
+        reason: String,
+    },
+}
+
+/// Checks the function declarations of all tools of a request together.
+pub(crate) fn validate_function_declarations(
+    declarations: &[&FunctionDeclaration],
+) -> Result<(), RequestValidationError> {
+    if declarations.len() > MAX_FUNCTION_DECLARATIONS {
+        return Err(RequestValidationError::TooManyFunctionDeclarations {
+            count: declarations.len(),
+        });
+    }
+
+    let mut names = std::collections::HashSet::new();
+    for declaration in declarations {
+        let name = declaration.name.as_deref().unwrap_or_default();
+        let valid = (1..=63).contains(&name.len())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(RequestValidationError::InvalidFunctionName {
+                name: name.to_string(),
+            });
+        }
+        if !names.insert(name) {
+            return Err(RequestValidationError::DuplicateFunctionName {
+                name: name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl From<&str> for Request {
+    fn from(text: &str) -> Self {
+        Self::from_prompt(text)
     }
 }
 
@@ -93,8 +596,10 @@ pub struct EmbedContentRequest {
     /// The content to generate embeddings for
     pub content: Content,
     /// Optional task type to optimize the embedding for
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub task_type: Option<TaskType>,
     /// Optional title for the request
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
 }
 
@@ -113,9 +618,7 @@ impl EmbedContentRequest {
         Self {
             content: Content {
                 role: None,
-                parts: vec![Part::Text {
-                    text: prompt.to_string(),
-                }],
+                parts: vec![Part::text(prompt.to_string())],
             },
             task_type,
             title,
@@ -145,3 +648,934 @@ pub struct BatchEmbedContentRequest {
     /// Vector of individual embedding requests to process in batch
     pub requests: Vec<EmbedContentRequest>,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_cached_content_serialization() {
+        let request = Request::builder()
+            .contents(vec![Content {
+                role: Some(Role::User),
+                parts: vec![Part::text("What does chapter 3 say?")],
+            }])
+            .cached_content("cachedContents/abc123")
+            .build();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "contents": [
+                    { "role": "user", "parts": [{ "text": "What does chapter 3 say?" }] }
+                ],
+                "cachedContent": "cachedContents/abc123"
+            })
+        );
+    }
+
+    #[test]
+    fn test_cached_content_is_omitted_when_unset() {
+        let request = Request::builder()
+            .contents(vec![Content {
+                role: None,
+                parts: vec![Part::text("Hi")],
+            }])
+            .build();
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert!(value.get("cachedContent").is_none());
+        assert!(value.get("cached_content").is_none());
+    }
+
+    fn file_info(state: &str) -> FileInfo {
+        serde_json::from_value(json!({
+            "name": "files/abc",
+            "mimeType": "application/pdf",
+            "sizeBytes": "42",
+            "createTime": "2024-01-01T00:00:00Z",
+            "updateTime": "2024-01-01T00:00:00Z",
+            "sha256Hash": "aGFzaA==",
+            "uri": "https://generativelanguage.googleapis.com/v1beta/files/abc",
+            "state": state
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_user_with_file_serialization() {
+        let content = Content::user_with_file("Summarize this", &file_info("ACTIVE")).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            json!({
+                "role": "user",
+                "parts": [
+                    { "text": "Summarize this" },
+                    {
+                        "file_data": {
+                            "mime_type": "application/pdf",
+                            "file_uri": "https://generativelanguage.googleapis.com/v1beta/files/abc"
+                        }
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_file_part_requires_active_file() {
+        match Part::from_file_info(&file_info("PROCESSING")) {
+            Err(FileError::NotActive { name, state }) => {
+                assert_eq!(name, "files/abc");
+                assert_eq!(state, crate::file::FileState::Processing);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(Content::user_with_file("Summarize this", &file_info("FAILED")).is_err());
+    }
+
+    #[test]
+    fn test_content_constructors_match_verbose_forms() {
+        let pairs = [
+            (
+                Content::user("Hi"),
+                Content {
+                    role: Some(Role::User),
+                    parts: vec![Part::Text {
+                        text: "Hi".into(),
+                        thought: None,
+                    }],
+                },
+            ),
+            (
+                Content::model("Hello!"),
+                Content {
+                    role: Some(Role::Model),
+                    parts: vec![Part::text("Hello!")],
+                },
+            ),
+            (
+                Content::user_parts(vec![
+                    Part::text("What is this?"),
+                    Part::inline_data("image/png", b"png"),
+                ]),
+                Content {
+                    role: Some(Role::User),
+                    parts: vec![
+                        Part::text("What is this?"),
+                        Part::inline_data("image/png", b"png"),
+                    ],
+                },
+            ),
+            (
+                Content::function_response("get_weather", json!({ "temperature": 21 })),
+                Content {
+                    role: Some(Role::Function),
+                    parts: vec![Part::function_response(FunctionResponse {
+                        name: "get_weather".into(),
+                        response: json!({ "temperature": 21 }),
+                    })],
+                },
+            ),
+        ];
+        for (short, verbose) in pairs {
+            assert_eq!(
+                serde_json::to_value(&short).unwrap(),
+                serde_json::to_value(&verbose).unwrap()
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_value(Content::function_response("f", json!({}))).unwrap(),
+            json!({ "role": "function", "parts": [{ "functionResponse": { "name": "f", "response": {} } }] })
+        );
+    }
+
+    #[test]
+    fn test_request_constructors_match_verbose_forms() {
+        let verbose = serde_json::to_value(
+            Request::builder()
+                .contents(vec![Content {
+                    role: Some(Role::User),
+                    parts: vec![Part::text("Tell me a joke")],
+                }])
+                .build(),
+        )
+        .unwrap();
+
+        for request in [
+            Request::from_prompt("Tell me a joke"),
+            Request::from("Tell me a joke"),
+            Request::from_contents(vec![Content::user("Tell me a joke")]),
+        ] {
+            assert_eq!(serde_json::to_value(&request).unwrap(), verbose);
+        }
+    }
+
+    fn assert_invalid(request: Request, expected: RequestValidationError) {
+        assert_eq!(request.validate(), Err(expected));
+    }
+
+    #[test]
+    fn test_validate_accepts_plain_prompt() {
+        assert_eq!(Request::from_prompt("Hi").validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_rejects_empty_contents_and_parts() {
+        assert_invalid(
+            Request::from_contents(vec![]),
+            RequestValidationError::EmptyContents,
+        );
+        assert_invalid(
+            Request::from_contents(vec![Content::user("Hi"), Content::user_parts(vec![])]),
+            RequestValidationError::EmptyParts { index: 1 },
+        );
+    }
+
+    #[test]
+    fn test_validate_function_role_requires_function_responses() {
+        let mut content = Content::function_response("get_weather", json!({ "temp": 21 }));
+        assert_eq!(
+            Request::from_contents(vec![content.clone()]).validate(),
+            Ok(())
+        );
+
+        content.parts.push(Part::text("It is 21 degrees"));
+        assert_invalid(
+            Request::from_contents(vec![content]),
+            RequestValidationError::InvalidFunctionContent { index: 0 },
+        );
+    }
+
+    #[test]
+    fn test_validate_limits_function_declarations() {
+        let declarations = |count: usize| {
+            (0..count)
+                .map(|i| super::super::FunctionDeclaration::new().with_name(format!("f{}", i)))
+                .collect::<Vec<_>>()
+        };
+        let request = |tools: Vec<Tool>| {
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .tools(tools)
+                .build()
+        };
+
+        assert_eq!(
+            request(vec![declarations(MAX_FUNCTION_DECLARATIONS).into()]).validate(),
+            Ok(())
+        );
+        assert_invalid(
+            request(vec![declarations(40).into(), declarations(30).into()]),
+            RequestValidationError::TooManyFunctionDeclarations { count: 70 },
+        );
+        assert_invalid(
+            request(vec![declarations(MAX_FUNCTION_DECLARATIONS + 1).into()]),
+            RequestValidationError::TooManyFunctionDeclarations { count: 65 },
+        );
+    }
+
+    #[test]
+    fn test_validate_function_names() {
+        let request = |names: &[&str]| {
+            let declarations = names
+                .iter()
+                .map(|name| super::super::FunctionDeclaration::new().with_name(*name))
+                .collect::<Vec<_>>();
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .tools(vec![declarations.into(), Tool::GOOGLE_SEARCH])
+                .build()
+        };
+
+        assert_eq!(
+            request(&["get_weather", "get-time", "F1", &"a".repeat(63)]).validate(),
+            Ok(())
+        );
+        for name in [
+            "",
+            "get weather",
+            "get.time",
+            "wetter_für_heute",
+            &"a".repeat(64),
+        ] {
+            assert_invalid(
+                request(&[name]),
+                RequestValidationError::InvalidFunctionName {
+                    name: name.to_string(),
+                },
+            );
+        }
+        assert_invalid(
+            request(&["get_weather", "get_time", "get_weather"]),
+            RequestValidationError::DuplicateFunctionName {
+                name: "get_weather".to_string(),
+            },
+        );
+
+        let unnamed = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .tools(vec![vec![super::super::FunctionDeclaration::new()].into()])
+            .build();
+        assert_invalid(
+            unnamed,
+            RequestValidationError::InvalidFunctionName {
+                name: String::new(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate_allowed_function_names_require_mode_any() {
+        let request = |mode| {
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .tools(vec![vec![
+                    super::super::FunctionDeclaration::new().with_name("get_weather")
+                ]
+                .into()])
+                .tool_config(
+                    ToolConfig::builder()
+                        .function_calling_config(
+                            super::super::FunctionCallingConfig::builder()
+                                .mode(mode)
+                                .allowed_function_names(vec!["get_weather".into()])
+                                .build(),
+                        )
+                        .build(),
+                )
+                .build()
+        };
+
+        assert_eq!(request(FunctionCallingMode::Any).validate(), Ok(()));
+        assert_invalid(
+            request(FunctionCallingMode::Auto),
+            RequestValidationError::AllowedFunctionNamesWithoutAny,
+        );
+    }
+
+    fn weather_tools() -> Vec<Tool> {
+        vec![vec![
+            super::super::FunctionDeclaration::new().with_name("get_weather"),
+            super::super::FunctionDeclaration::new().with_name("get_time"),
+        ]
+        .into()]
+    }
+
+    #[test]
+    fn test_validate_allowed_functions_must_be_declared() {
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .tools(weather_tools())
+            .allowed_functions(["get_weather", "book_table"])
+            .build();
+
+        assert_invalid(
+            request,
+            RequestValidationError::UnknownAllowedFunction {
+                name: "book_table".into(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_function_calling_helpers_serialize_tool_config() {
+        let tool_config =
+            |request: Request| serde_json::to_value(request).unwrap()["tool_config"].clone();
+
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .tools(weather_tools())
+            .allowed_functions(["get_weather"])
+            .build();
+        assert_eq!(request.validate(), Ok(()));
+        assert_eq!(
+            tool_config(request),
+            json!({
+                "functionCallingConfig": {
+                    "mode": "ANY",
+                    "allowedFunctionNames": ["get_weather"]
+                }
+            })
+        );
+
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .tools(weather_tools())
+            .function_calling_mode(FunctionCallingMode::None)
+            .build();
+        assert_eq!(
+            tool_config(request),
+            json!({ "functionCallingConfig": { "mode": "NONE" } })
+        );
+
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .tools(weather_tools())
+            .allowed_functions(["get_time"])
+            .function_calling_mode(FunctionCallingMode::Auto)
+            .build();
+        assert_eq!(
+            tool_config(request.clone()),
+            json!({
+                "functionCallingConfig": {
+                    "mode": "AUTO",
+                    "allowedFunctionNames": ["get_time"]
+                }
+            })
+        );
+        assert_invalid(
+            request,
+            RequestValidationError::AllowedFunctionNamesWithoutAny,
+        );
+    }
+
+    #[test]
+    fn test_validate_caps_inline_data() {
+        let image = |data: String| Part::InlineData {
+            inline_data: super::super::InlineData {
+                mime_type: "image/png".into(),
+                data,
+            },
+        };
+        // A little more than half of the limit each, once decoded.
+        let half = "A".repeat(MAX_INLINE_DATA_SIZE as usize * 2 / 3 + 4);
+
+        assert_eq!(
+            Request::from_contents(vec![Content::user_parts(vec![image(half.clone())])]).validate(),
+            Ok(())
+        );
+        match Request::from_contents(vec![
+            Content::user_parts(vec![image(half.clone())]),
+            Content::user_parts(vec![image(half)]),
+        ])
+        .validate()
+        {
+            Err(RequestValidationError::InlineDataTooLarge { size }) => {
+                assert!(size > MAX_INLINE_DATA_SIZE)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        assert_eq!(decoded_len(""), 0);
+        assert_eq!(decoded_len("aGk="), 2);
+        assert_eq!(decoded_len("aGk"), 2);
+        assert_eq!(decoded_len("aGVsbG8="), 5);
+        assert_eq!(decoded_len("aGVsbG8h"), 6);
+    }
+
+    #[test]
+    fn test_validate_json_output_requires_schema() {
+        let request = |config: GenerationConfig| {
+            Request::builder()
+                .contents(vec![Content::user("List three colors")])
+                .generation_config(config)
+                .build()
+        };
+
+        assert_invalid(
+            request(
+                GenerationConfig::builder()
+                    .response_mime_type("application/json")
+                    .build(),
+            ),
+            RequestValidationError::MissingResponseSchema,
+        );
+        assert_eq!(
+            request(
+                GenerationConfig::builder()
+                    .response_mime_type("application/json")
+                    .response_schema(
+                        super::super::Schema::builder()
+                            .r#type(super::super::SchemaType::Object)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .validate(),
+            Ok(())
+        );
+    }
+
+    fn golden(request: &Request, expected: serde_json::Value) {
+        assert_eq!(serde_json::to_value(request).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_golden_text_only() {
+        golden(
+            &Request::from_prompt("Hello"),
+            json!({ "contents": [{ "role": "user", "parts": [{ "text": "Hello" }] }] }),
+        );
+    }
+
+    #[test]
+    fn test_golden_multimodal() {
+        let request = Request::from_contents(vec![Content::user_parts(vec![
+            Part::text("Compare these"),
+            Part::InlineData {
+                inline_data: super::super::InlineData {
+                    mime_type: "image/png".into(),
+                    data: "aGk=".into(),
+                },
+            },
+            Part::FileData {
+                file_data: super::super::FileData {
+                    mime_type: "video/mp4".into(),
+                    file_uri: "https://example.com/files/abc".into(),
+                },
+                video_metadata: None,
+            },
+        ])]);
+
+        golden(
+            &request,
+            json!({
+                "contents": [{
+                    "role": "user",
+                    "parts": [
+                        { "text": "Compare these" },
+                        { "inline_data": { "mime_type": "image/png", "data": "aGk=" } },
+                        {
+                            "file_data": {
+                                "mime_type": "video/mp4",
+                                "file_uri": "https://example.com/files/abc"
+                            }
+                        }
+                    ]
+                }]
+            }),
+        );
+    }
+
+    #[test]
+    fn test_golden_tools_and_tool_config() {
+        let get_weather = super::super::FunctionDeclaration::new()
+            .with_name("get_weather")
+            .with_description("Gets the weather")
+            .with_parameters(&["city, string, The city"])
+            .unwrap();
+        let request = Request::builder()
+            .contents(vec![Content::user("Weather in Paris?")])
+            .tools(vec![
+                vec![get_weather].into(),
+                Tool::GOOGLE_SEARCH,
+                Tool::CODE_EXECUTION,
+            ])
+            .tool_config(
+                ToolConfig::builder()
+                    .function_calling_config(
+                        super::super::FunctionCallingConfig::builder()
+                            .mode(FunctionCallingMode::Any)
+                            .allowed_function_names(vec!["get_weather".into()])
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+
+        golden(
+            &request,
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Weather in Paris?" }] }],
+                "tools": [
+                    {
+                        "function_declarations": [{
+                            "name": "get_weather",
+                            "description": "Gets the weather",
+                            "parameters": {
+                                "type": "object",
+                                "properties": {
+                                    "city": { "type": "string", "description": "The city" }
+                                },
+                                "required": ["city"]
+                            }
+                        }]
+                    },
+                    { "google_search": {} },
+                    { "code_execution": {} }
+                ],
+                "tool_config": {
+                    "functionCallingConfig": {
+                        "mode": "ANY",
+                        "allowedFunctionNames": ["get_weather"]
+                    }
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_function_responses_share_one_content() {
+        let response = |name: &str| FunctionResponse {
+            name: name.to_string(),
+            response: json!({ "ok": true }),
+        };
+        let content = Content::function_responses(vec![response("a"), response("b")]);
+
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            json!({
+                "role": "function",
+                "parts": [
+                    { "functionResponse": { "name": "a", "response": { "ok": true } } },
+                    { "functionResponse": { "name": "b", "response": { "ok": true } } }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_golden_combined_tools() {
+        let declaration = |name: &str| super::super::FunctionDeclaration::new().with_name(name);
+        let request = Request::builder()
+            .contents(vec![Content::user("Find and bookmark a Rust tutorial")])
+            .tools(
+                super::super::Tools::new()
+                    .google_search()
+                    .functions(vec![declaration("bookmark")])
+                    .code_execution()
+                    .functions(vec![declaration("share")]),
+            )
+            .build();
+
+        golden(
+            &request,
+            json!({
+                "contents": [{
+                    "role": "user",
+                    "parts": [{ "text": "Find and bookmark a Rust tutorial" }]
+                }],
+                "tools": [
+                    { "google_search": {} },
+                    {
+                        "function_declarations": [
+                            { "name": "bookmark" },
+                            { "name": "share" }
+                        ]
+                    },
+                    { "code_execution": {} }
+                ]
+            }),
+        );
+        assert_eq!(
+            serde_json::to_string(&request.tools).unwrap(),
+            r#"[{"google_search":{}},{"function_declarations":[{"name":"bookmark"},{"name":"share"}]},{"code_execution":{}}]"#
+        );
+    }
+
+    #[test]
+    fn test_golden_safety_settings_and_system_instruction() {
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .system_instruction(SystemInstruction::from("Be brief"))
+            .safety_settings(vec![SafetySetting::from((
+                super::super::HarmCategory::HarmCategoryHarassment,
+                super::super::SafetyThreshold::BlockOnlyHigh,
+            ))])
+            .build();
+
+        golden(
+            &request,
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }],
+                "system_instruction": { "role": "system", "parts": [{ "text": "Be brief" }] },
+                "safety_settings": [{
+                    "category": "HARM_CATEGORY_HARASSMENT",
+                    "threshold": "BLOCK_ONLY_HIGH"
+                }]
+            }),
+        );
+    }
+
+    #[test]
+    fn test_golden_generation_config_with_schema() {
+        use super::super::{Schema, SchemaType};
+
+        let schema = Schema::builder()
+            .r#type(SchemaType::Array)
+            .items(Box::new(
+                Schema::builder()
+                    .r#type(SchemaType::String)
+                    .enum_values(vec!["red".to_string(), "blue".to_string()])
+                    .build(),
+            ))
+            .build();
+        let request = Request::builder()
+            .contents(vec![Content::user("List colors")])
+            .generation_config(
+                GenerationConfig::builder()
+                    .temperature(0.5)
+                    .max_output_tokens(100)
+                    .response_mime_type("application/json")
+                    .response_schema(schema)
+                    .build(),
+            )
+            .build();
+
+        golden(
+            &request,
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "List colors" }] }],
+                "generation_config": {
+                    "temperature": 0.5,
+                    "max_output_tokens": 100,
+                    "response_mime_type": "application/json",
+                    "response_schema": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["red", "blue"] }
+                    }
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_golden_generation_config_sampling_options() {
+        let request = Request::builder()
+            .contents(vec![Content::user("Write a list")])
+            .generation_config(
+                GenerationConfig::builder()
+                    .candidate_count(2)
+                    .stop_sequences(vec!["END".to_string(), "\n\n".to_string()])
+                    .presence_penalty(0.5)
+                    .frequency_penalty(-1.5)
+                    .build(),
+            )
+            .build();
+
+        golden(
+            &request,
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Write a list" }] }],
+                "generation_config": {
+                    "candidate_count": 2,
+                    "stop_sequences": ["END", "\n\n"],
+                    "presence_penalty": 0.5,
+                    "frequency_penalty": -1.5
+                }
+            }),
+        );
+        assert_eq!(request.validate(), Ok(()));
+
+        golden(
+            &Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .generation_config(GenerationConfig::builder().build())
+                .build(),
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }],
+                "generation_config": {}
+            }),
+        );
+    }
+
+    #[test]
+    fn test_validate_sampling_options() {
+        let request = |config: GenerationConfig| {
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .generation_config(config)
+                .build()
+        };
+        let stop_sequences =
+            |count: usize| (0..count).map(|i| format!("STOP{}", i)).collect::<Vec<_>>();
+
+        assert_eq!(
+            request(
+                GenerationConfig::builder()
+                    .stop_sequences(stop_sequences(MAX_STOP_SEQUENCES))
+                    .candidate_count(1)
+                    .presence_penalty(-2.0)
+                    .frequency_penalty(1.99)
+                    .build()
+            )
+            .validate(),
+            Ok(())
+        );
+        assert_invalid(
+            request(
+                GenerationConfig::builder()
+                    .stop_sequences(stop_sequences(6))
+                    .build(),
+            ),
+            RequestValidationError::TooManyStopSequences { count: 6 },
+        );
+        assert_invalid(
+            request(GenerationConfig::builder().candidate_count(0).build()),
+            RequestValidationError::InvalidCandidateCount { count: 0 },
+        );
+        assert_invalid(
+            request(GenerationConfig::builder().presence_penalty(2.0).build()),
+            RequestValidationError::PenaltyOutOfRange {
+                name: "presence_penalty",
+                value: 2.0,
+            },
+        );
+        assert_invalid(
+            request(GenerationConfig::builder().frequency_penalty(-2.5).build()),
+            RequestValidationError::PenaltyOutOfRange {
+                name: "frequency_penalty",
+                value: -2.5,
+            },
+        );
+    }
+
+    #[test]
+    fn test_logprobs_options() {
+        let request = |config: GenerationConfig| {
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .generation_config(config)
+                .build()
+        };
+
+        let logprobs = request(
+            GenerationConfig::builder()
+                .response_logprobs(true)
+                .logprobs(MAX_LOGPROBS)
+                .build(),
+        );
+        assert_eq!(logprobs.validate(), Ok(()));
+        golden(
+            &logprobs,
+            json!({
+                "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }],
+                "generation_config": { "response_logprobs": true, "logprobs": 20 }
+            }),
+        );
+
+        assert_invalid(
+            request(GenerationConfig::builder().logprobs(3).build()),
+            RequestValidationError::LogprobsWithoutResponseLogprobs,
+        );
+        assert_invalid(
+            request(
+                GenerationConfig::builder()
+                    .response_logprobs(true)
+                    .logprobs(21)
+                    .build(),
+            ),
+            RequestValidationError::LogprobsOutOfRange { count: 21 },
+        );
+    }
+
+    #[test]
+    fn test_embed_request_omits_unset_options() {
+        let request = EmbedContentRequest::new("Hello", None, None);
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({ "content": { "parts": [{ "text": "Hello" }] } })
+        );
+    }
+
+    #[test]
+    fn test_labels_serialization() {
+        let request = Request::builder()
+            .contents(vec![Content::user("Hi")])
+            .labels(HashMap::from([(
+                "tenant".to_string(),
+                "acme-42".to_string(),
+            )]))
+            .build();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["labels"],
+            json!({ "tenant": "acme-42" })
+        );
+
+        let mut request = Request::from_prompt("Hi");
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("labels")
+            .is_none());
+        request.labels = Some(HashMap::new());
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("labels")
+            .is_none());
+    }
+
+    #[test]
+    fn test_validate_labels() {
+        let request = |key: &str, value: &str| {
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .labels(HashMap::from([(key.to_string(), value.to_string())]))
+                .build()
+        };
+
+        assert_eq!(request("tenant_id", "acme-42").validate(), Ok(()));
+        assert_eq!(request("env", "").validate(), Ok(()));
+        for (key, value) in [
+            ("Tenant", "acme"),
+            ("1tenant", "acme"),
+            ("", "acme"),
+            ("tenant", "Acme"),
+            ("tenant.id", "acme"),
+            ("tenant", &"a".repeat(MAX_LABEL_LENGTH + 1)),
+            (&"a".repeat(MAX_LABEL_LENGTH + 1), "acme"),
+        ] {
+            assert!(
+                matches!(
+                    request(key, value).validate(),
+                    Err(RequestValidationError::InvalidLabel { .. })
+                ),
+                "{}={} should be rejected",
+                key,
+                value
+            );
+        }
+
+        let labels = (0..=MAX_LABELS)
+            .map(|i| (format!("k{}", i), String::new()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            Request::builder()
+                .contents(vec![Content::user("Hi")])
+                .labels(labels)
+                .build()
+                .validate(),
+            Err(RequestValidationError::TooManyLabels {
+                count: MAX_LABELS + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_checks_response_schema() {
+        let mut schema = Schema::builder()
+            .r#type(super::super::SchemaType::Object)
+            .build()
+            .ordered_properties(&[(
+                "name",
+                Schema::builder()
+                    .r#type(super::super::SchemaType::String)
+                    .build(),
+            )]);
+        schema.property_ordering = Some(vec!["title".into()]);
+        let request = Request::builder()
+            .contents(vec![Content::user("Name a color")])
+            .generation_config(
+                GenerationConfig::builder()
+                    .response_mime_type("application/json")
+                    .response_schema(schema)
+                    .build(),
+            )
+            .build();
+
+        assert_invalid(
+            request,
+            RequestValidationError::InvalidResponseSchema {
+                problems: vec!["`property_ordering` names unknown property `title` at #".into()],
+            },
+        );
+    }
+}
@@ -1,9 +1,14 @@
 //! Response models for the Gemini AI API.
 
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use serde::{Deserialize, Serialize};
 
+use crate::error::GoogleGenerativeAIError;
+
 use super::{
-    code_execution::{CodeExecutionResult, ExecutableCode},
+    code_execution::{CodeExecution, CodeExecutionResult, ExecutableCode},
     grounding_metadata::GroundingMetadata,
     Content, FunctionCall, HarmCategory, ModelInfo, Part,
 };
@@ -18,23 +23,134 @@ pub struct Response {
     pub usage_metadata: Option<UsageMetadata>,
     /// The version of the model used.
     pub model_version: Option<String>,
+    /// A redacted label of the API key that served this response, for logging.
+    #[serde(skip)]
+    pub served_by: Option<String>,
+    /// The raw JSON body of the response, kept when [`ModelParams::keep_raw`](super::ModelParams::keep_raw) is set.
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
 }
 
 impl Response {
-    /// Gets the text content from the first candidate's first part.
+    /// Gets the text content of the first candidate, joining all of its text parts.
+    ///
+    /// The model often splits a reply into several text parts, e.g. around executed
+    /// code, so all of them are concatenated in order; other parts and thoughts are
+    /// skipped. Earlier versions returned only the first part.
     pub fn text(&self) -> String {
+        self.text_parts().concat()
+    }
+
+    /// Gets the thoughts of the first candidate, joined in order.
+    ///
+    /// Thinking models only return their thoughts when
+    /// [`ThinkingConfig::include_thoughts`](super::ThinkingConfig::include_thoughts) is set.
+    pub fn thoughts(&self) -> String {
         self.candidates
             .as_ref()
             .and_then(|candidates| candidates.first())
-            .and_then(|candidate| candidate.content.as_ref())
-            .and_then(|content| content.parts.first())
-            .and_then(|part| match part {
-                Part::Text { text } => Some(text.clone()),
-                _ => None,
-            })
+            .map(Candidate::thoughts)
+            .unwrap_or_default()
+    }
+
+    /// Gets the text parts of the first candidate in order, without thoughts.
+    pub fn text_parts(&self) -> Vec<&str> {
+        self.candidates
+            .as_ref()
+            .and_then(|candidates| candidates.first())
+            .map(Candidate::text_parts)
             .unwrap_or_default()
     }
 
+    /// Gets the text content of the candidate at `index`, or `None` if there is no
+    /// such candidate.
+    pub fn candidate_text(&self, index: usize) -> Option<String> {
+        self.candidates
+            .as_ref()
+            .and_then(|candidates| candidates.get(index))
+            .map(Candidate::text)
+    }
+
+    /// Gets the text content of every candidate, in order.
+    pub fn all_text(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .flatten()
+            .map(Candidate::text)
+            .collect()
+    }
+
+    /// Appends a streamed chunk to this response.
+    ///
+    /// Candidates are matched by their `index` (or position, if the API omitted it)
+    /// and the chunk's parts are appended in order, with consecutive text parts
+    /// joined into one. Per-candidate fields such as `finish_reason` as well as
+    /// `usage_metadata` and `model_version` are replaced by the chunk's values when
+    /// present. The raw JSON is not merged and is cleared.
+    pub fn merge(&mut self, chunk: Response) {
+        if let Some(chunk_candidates) = chunk.candidates {
+            let candidates = self.candidates.get_or_insert_with(Vec::new);
+            for (position, candidate) in chunk_candidates.into_iter().enumerate() {
+                let index = candidate.index.unwrap_or(position as i32);
+                let existing = candidates
+                    .iter_mut()
+                    .enumerate()
+                    .find(|(position, existing)| {
+                        existing.index.unwrap_or(*position as i32) == index
+                    });
+                match existing {
+                    Some((_, existing)) => existing.merge(candidate),
+                    None => candidates.push(candidate),
+                }
+            }
+        }
+
+        if chunk.usage_metadata.is_some() {
+            self.usage_metadata = chunk.usage_metadata;
+        }
+        if chunk.model_version.is_some() {
+            self.model_version = chunk.model_version;
+        }
+        if chunk.served_by.is_some() {
+            self.served_by = chunk.served_by;
+        }
+        self.raw = None;
+    }
+
+    /// Returns `true` if any candidate contains a function call.
+    pub fn has_function_call(&self) -> bool {
+        self.candidates.iter().flatten().any(|candidate| {
+            candidate.content.as_ref().is_some_and(|content| {
+                content
+                    .parts
+                    .iter()
+                    .any(|part| matches!(part, Part::FunctionCall { .. }))
+            })
+        })
+    }
+
+    /// Returns `true` if any candidate is grounded, i.e. carries grounding metadata
+    /// such as the search queries or sources of Google search grounding.
+    pub fn is_grounded(&self) -> bool {
+        self.candidates.iter().flatten().any(|candidate| {
+            candidate
+                .grounding_metadata
+                .as_ref()
+                .is_some_and(|metadata| *metadata != GroundingMetadata::default())
+        })
+    }
+
+    /// Reports which tools the model used for this response.
+    ///
+    /// Useful when a request offers both Google search and functions, see
+    /// [`Tools`](super::Tools).
+    pub fn tool_usage(&self) -> ToolUsage {
+        ToolUsage {
+            grounding: self.is_grounded(),
+            function_call: self.has_function_call(),
+        }
+    }
+
     /// Returns a vector of function calls from all candidates in the response.
     ///
     /// This method collects all function calls from the response candidates and returns them
@@ -78,6 +194,131 @@ impl Response {
             .unwrap_or_default()
     }
 
+    /// Returns the images of all candidates with their MIME type, decoded from the
+    /// inline data parts.
+    ///
+    /// The model replies with images when [`Modality::Image`](super::Modality::Image)
+    /// is among the `response_modalities` of the generation config. Parts whose data
+    /// is not valid base64 are skipped.
+    pub fn inline_images(&self) -> Vec<(&str, Vec<u8>)> {
+        self.candidates
+            .iter()
+            .flatten()
+            .filter_map(|candidate| candidate.content.as_ref())
+            .flat_map(|content| &content.parts)
+            .filter_map(|part| match part {
+                Part::InlineData { inline_data } if inline_data.mime_type.starts_with("image/") => {
+                    let data = base64_engine.decode(&inline_data.data).ok()?;
+                    Some((inline_data.mime_type.as_str(), data))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Writes the [`inline_images`](Self::inline_images) to `dir` as `image-1.png`,
+    /// `image-2.jpg` and so on, creating the directory if needed, and returns their
+    /// paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or a file cannot be written.
+    pub async fn save_images_to(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, GoogleGenerativeAIError> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        let mut paths = Vec::new();
+        for (index, (mime_type, data)) in self.inline_images().into_iter().enumerate() {
+            let extension = match mime_type {
+                "image/jpeg" => "jpg",
+                "image/svg+xml" => "svg",
+                other => other.strip_prefix("image/").unwrap_or("bin"),
+            };
+            let path = dir.join(format!("image-{}.{}", index + 1, extension));
+            tokio::fs::write(&path, data).await?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Returns the first audio part of the response with its MIME type, decoded from
+    /// the inline data.
+    ///
+    /// Text-to-speech models reply with raw 16-bit PCM, e.g. `audio/L16;codec=pcm;rate=24000`,
+    /// when [`Modality::Audio`](super::Modality::Audio) is among the
+    /// `response_modalities` of the generation config.
+    pub fn audio(&self) -> Option<(&str, Vec<u8>)> {
+        self.candidates
+            .iter()
+            .flatten()
+            .filter_map(|candidate| candidate.content.as_ref())
+            .flat_map(|content| &content.parts)
+            .find_map(|part| match part {
+                Part::InlineData { inline_data } if inline_data.mime_type.starts_with("audio/") => {
+                    let data = base64_engine.decode(&inline_data.data).ok()?;
+                    Some((inline_data.mime_type.as_str(), data))
+                }
+                _ => None,
+            })
+    }
+
+    /// Writes the [`audio`](Self::audio) of the response to `path`.
+    ///
+    /// Raw PCM (`audio/L16` or `audio/pcm`) is wrapped in a WAV header, using the
+    /// `rate` of the MIME type (24 kHz if missing), so the file plays as is; other
+    /// formats are written unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the response has no audio or the file cannot be written.
+    pub async fn save_audio(&self, path: impl AsRef<Path>) -> Result<(), GoogleGenerativeAIError> {
+        let (mime_type, data) = self
+            .audio()
+            .ok_or_else(|| GoogleGenerativeAIError::new("The response contains no audio"))?;
+        let data = match pcm_sample_rate(mime_type) {
+            Some(sample_rate) => wav(&data, sample_rate),
+            None => data,
+        };
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    /// Pairs the code the model ran with its result, in order.
+    ///
+    /// Each result is matched with the code part preceding it; code without a
+    /// result, e.g. in an incomplete stream chunk, has `result` set to `None`.
+    pub fn code_execution_summary(&self) -> Vec<CodeExecution> {
+        let mut executions: Vec<CodeExecution> = Vec::new();
+        let parts = self
+            .candidates
+            .iter()
+            .flatten()
+            .filter_map(|candidate| candidate.content.as_ref())
+            .flat_map(|content| &content.parts);
+        for part in parts {
+            match part {
+                Part::ExecutableCode { executable_code } => executions.push(CodeExecution {
+                    code: executable_code.clone(),
+                    result: None,
+                }),
+                Part::CodeExecutionResult {
+                    code_execution_result,
+                } => {
+                    if let Some(execution) = executions
+                        .last_mut()
+                        .filter(|execution| execution.result.is_none())
+                    {
+                        execution.result = Some(code_execution_result.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        executions
+    }
+
     /// Gets all code execution results from the response.
     pub fn code_execution_results(&self) -> Vec<CodeExecutionResult> {
         self.candidates
@@ -100,10 +341,60 @@ impl Response {
     }
 }
 
+/// Returns the sample rate of raw 16-bit PCM audio, or `None` for other formats.
+fn pcm_sample_rate(mime_type: &str) -> Option<u32> {
+    let mut params = mime_type.split(';').map(str::trim);
+    let essence = params.next()?.to_ascii_lowercase();
+    if essence != "audio/l16" && essence != "audio/pcm" {
+        return None;
+    }
+    let rate = params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("rate"))
+        .and_then(|(_, value)| value.parse().ok());
+    Some(rate.unwrap_or(24_000))
+}
+
+/// Wraps mono 16-bit little-endian PCM samples in a WAV container.
+fn wav(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * u32::from(block_align);
+    let data_len = pcm.len() as u32;
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+/// Which tools the model used for a response, see [`Response::tool_usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolUsage {
+    /// Whether the response is grounded in Google search results.
+    pub grounding: bool,
+    /// Whether the response contains a function call.
+    pub function_call: bool,
+}
+
 /// A candidate response from the model.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    /// The index of the candidate in the list of candidates.
+    pub index: Option<i32>,
     /// The content of the candidate response.
     pub content: Option<Content>,
     /// The reason why the generation finished.
@@ -122,6 +413,84 @@ pub struct Candidate {
     pub grounding_metadata: Option<GroundingMetadata>,
 }
 
+impl Candidate {
+    /// Gets the text content of the candidate, joining all of its text parts.
+    pub fn text(&self) -> String {
+        self.text_parts().concat()
+    }
+
+    /// Gets the text parts of the candidate in order, without thoughts.
+    pub fn text_parts(&self) -> Vec<&str> {
+        self.texts(false)
+    }
+
+    /// Gets the thoughts of the candidate, joined in order.
+    pub fn thoughts(&self) -> String {
+        self.texts(true).concat()
+    }
+
+    /// Gets the text parts that are or are not thoughts.
+    fn texts(&self, thoughts: bool) -> Vec<&str> {
+        self.content
+            .iter()
+            .flat_map(|content| &content.parts)
+            .filter(|part| part.is_thought() == thoughts)
+            .filter_map(|part| match part {
+                Part::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Appends a later chunk of the same candidate.
+    fn merge(&mut self, chunk: Candidate) {
+        if let Some(chunk_content) = chunk.content {
+            match &mut self.content {
+                Some(content) => {
+                    if content.role.is_none() {
+                        content.role = chunk_content.role;
+                    }
+                    for part in chunk_content.parts {
+                        match (content.parts.last_mut(), part) {
+                            (
+                                Some(Part::Text { text, thought }),
+                                Part::Text {
+                                    text: more,
+                                    thought: more_thought,
+                                },
+                            ) if *thought == more_thought => text.push_str(&more),
+                            (_, part) => content.parts.push(part),
+                        }
+                    }
+                }
+                None => self.content = Some(chunk_content),
+            }
+        }
+
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason;
+        }
+        if chunk.finish_message.is_some() {
+            self.finish_message = chunk.finish_message;
+        }
+        if chunk.safety_ratings.is_some() {
+            self.safety_ratings = chunk.safety_ratings;
+        }
+        if chunk.citation_metadata.is_some() {
+            self.citation_metadata = chunk.citation_metadata;
+        }
+        if chunk.avg_logprobs.is_some() {
+            self.avg_logprobs = chunk.avg_logprobs;
+        }
+        if chunk.logprobs_result.is_some() {
+            self.logprobs_result = chunk.logprobs_result;
+        }
+        if chunk.grounding_metadata.is_some() {
+            self.grounding_metadata = chunk.grounding_metadata;
+        }
+    }
+}
+
 /// Safety rating for a specific harm category.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -158,31 +527,60 @@ pub struct Citation {
     pub license: String,
 }
 
-/// Log probabilities for the response tokens and top tokens
+/// Log probabilities for the response tokens and top tokens.
+///
+/// Requested with `response_logprobs` and `logprobs` on the
+/// [`GenerationConfig`](super::GenerationConfig).
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LogprobsResult {
-    /// Length = total number of decoding steps.
+    /// Length = total number of decoding steps. Empty unless `logprobs` was set.
+    #[serde(default)]
     pub top_candidates: Vec<TopCandidates>,
     /// Length = total number of decoding steps.
     /// The chosen candidates may or may not be in topCandidates.
+    #[serde(default)]
     pub chosen_candidates: Vec<LogprobsCandidate>,
 }
 
+impl LogprobsResult {
+    /// Pairs the chosen token of every decoding step with the top tokens of that
+    /// step, which are empty unless `logprobs` was set.
+    pub fn steps(&self) -> impl Iterator<Item = (&LogprobsCandidate, &[LogprobsCandidate])> {
+        self.chosen_candidates
+            .iter()
+            .enumerate()
+            .map(|(step, chosen)| {
+                let top = self
+                    .top_candidates
+                    .get(step)
+                    .map_or(&[][..], |top| top.candidates.as_slice());
+                (chosen, top)
+            })
+    }
+}
+
 /// Candidates with top log probabilities at each decoding step
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TopCandidates {
     /// Sorted by log probability in descending order.
+    #[serde(default)]
     pub candidates: Vec<LogprobsCandidate>,
 }
 
 /// Candidate with a log probability
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LogprobsCandidate {
     /// The candidate's token string value.
+    #[serde(default)]
     pub token: String,
     /// The candidate's token id value.
+    #[serde(default)]
     pub token_id: i32,
     /// The candidate's log probability.
+    #[serde(default)]
     pub log_probability: f64,
 }
 
@@ -201,7 +599,7 @@ pub enum SafetyProbability {
 }
 
 /// Reason why the generation finished.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum FinishReason {
     #[serde(rename = "FINISH_REASON_UNSPECIFIED")]
@@ -235,6 +633,8 @@ pub enum FinishReason {
 pub struct UsageMetadata {
     /// Number of tokens in the prompt.
     pub prompt_token_count: i32,
+    /// Number of tokens of the prompt that were served from cached content.
+    pub cached_content_token_count: Option<i32>,
     /// Number of tokens in the generated candidates.
     pub candidates_token_count: Option<i32>,
     /// Total number of tokens used.
@@ -279,3 +679,431 @@ pub struct BatchEmbedContentResponse {
     /// Vector of embeddings generated for each request
     pub embeddings: Vec<Embedding>,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::CodeExecutionOutcome;
+
+    fn response(value: serde_json::Value) -> Response {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_text_joins_all_text_parts() {
+        let response = response(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        { "text": "Let me compute that. " },
+                        { "executableCode": { "language": "PYTHON", "code": "print(6 * 7)" } },
+                        { "codeExecutionResult": { "outcome": "OUTCOME_OK", "output": "42" } },
+                        { "text": "The answer is 42." }
+                    ]
+                }
+            }]
+        }));
+
+        assert_eq!(
+            response.text_parts(),
+            ["Let me compute that. ", "The answer is 42."]
+        );
+        assert_eq!(response.text(), "Let me compute that. The answer is 42.");
+    }
+
+    #[test]
+    fn test_text_of_every_candidate() {
+        let response = response(json!({
+            "candidates": [
+                { "content": { "parts": [{ "text": "Hello" }, { "text": " there" }] }, "index": 0 },
+                { "content": { "parts": [{ "text": "Hi" }] }, "index": 1 },
+                { "finishReason": "SAFETY", "index": 2 }
+            ]
+        }));
+
+        assert_eq!(response.candidate_text(1).as_deref(), Some("Hi"));
+        assert_eq!(response.candidate_text(2).as_deref(), Some(""));
+        assert_eq!(response.candidate_text(3), None);
+        assert_eq!(response.all_text(), ["Hello there", "Hi", ""]);
+
+        let empty: Response = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(empty.text(), "");
+        assert!(empty.all_text().is_empty());
+    }
+
+    #[test]
+    fn test_thinking_model_response() {
+        // Captured from gemini-2.5-flash with `includeThoughts` enabled.
+        let response = response(json!({
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        {
+                            "text": "**Calculating the sum**\n\nThe user wants 2 + 2, which is 4.\n",
+                            "thought": true
+                        },
+                        { "text": "2 + 2 = 4" }
+                    ],
+                    "role": "model"
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 8,
+                "candidatesTokenCount": 7,
+                "totalTokenCount": 62,
+                "thoughtsTokenCount": 47
+            },
+            "modelVersion": "gemini-2.5-flash"
+        }));
+
+        assert_eq!(response.text(), "2 + 2 = 4");
+        assert_eq!(
+            response.thoughts(),
+            "**Calculating the sum**\n\nThe user wants 2 + 2, which is 4.\n"
+        );
+        let parts = &response.candidates.as_ref().unwrap()[0]
+            .content
+            .as_ref()
+            .unwrap()
+            .parts;
+        assert!(parts[0].is_thought());
+        assert!(!parts[1].is_thought());
+    }
+
+    #[test]
+    fn test_merge_keeps_thoughts_apart() {
+        let mut merged = response(json!({
+            "candidates": [{ "content": { "parts": [{ "text": "Thinking", "thought": true }] } }]
+        }));
+        merged.merge(response(json!({
+            "candidates": [{ "content": { "parts": [{ "text": "...", "thought": true }] } }]
+        })));
+        merged.merge(response(json!({
+            "candidates": [{ "content": { "parts": [{ "text": "Answer" }] } }]
+        })));
+
+        assert_eq!(merged.thoughts(), "Thinking...");
+        assert_eq!(merged.text(), "Answer");
+        assert_eq!(
+            merged.candidates.unwrap()[0]
+                .content
+                .as_ref()
+                .unwrap()
+                .parts
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_tool_usage() {
+        let grounded = response(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "Rust 1.83" }] },
+                "groundingMetadata": { "webSearchQueries": ["latest rust version"] }
+            }]
+        }));
+        assert_eq!(
+            grounded.tool_usage(),
+            ToolUsage {
+                grounding: true,
+                function_call: false
+            }
+        );
+
+        let function_call = response(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{ "functionCall": { "name": "bookmark", "args": {} } }]
+                },
+                "groundingMetadata": {}
+            }]
+        }));
+        assert_eq!(
+            function_call.tool_usage(),
+            ToolUsage {
+                grounding: false,
+                function_call: true
+            }
+        );
+        assert_eq!(response(json!({})).tool_usage(), ToolUsage::default());
+    }
+
+    #[test]
+    fn test_code_execution_summary() {
+        // Captured from a code execution reply of gemini-2.0-flash.
+        let response = response(json!({
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        { "text": "I will calculate the sum of the first 10 primes.\n\n" },
+                        {
+                            "executableCode": {
+                                "language": "PYTHON",
+                                "code": "primes = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]\nprint(sum(primes))\n"
+                            }
+                        },
+                        { "codeExecutionResult": { "outcome": "OUTCOME_OK", "output": "129\n" } },
+                        {
+                            "executableCode": {
+                                "language": "PYTHON",
+                                "code": "print(primes[10])\n"
+                            }
+                        },
+                        {
+                            "codeExecutionResult": {
+                                "outcome": "OUTCOME_ERROR",
+                                "output": "Traceback (most recent call last):\n  File \"<string>\", line 1, in <module>\nIndexError: list index out of range\n"
+                            }
+                        },
+                        { "executableCode": { "language": "PYTHON", "code": "pass\n" } },
+                        { "codeExecutionResult": { "outcome": "OUTCOME_OK" } },
+                        { "text": "The sum of the first 10 primes is 129." }
+                    ],
+                    "role": "model"
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 19,
+                "candidatesTokenCount": 151,
+                "totalTokenCount": 170
+            },
+            "modelVersion": "gemini-2.0-flash"
+        }));
+
+        let summary = response.code_execution_summary();
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0].code.language, "PYTHON");
+        assert_eq!(summary[0].outcome(), Some(CodeExecutionOutcome::Ok));
+        assert_eq!(summary[0].result.as_ref().unwrap().output, "129\n");
+
+        assert_eq!(summary[1].code.code, "print(primes[10])\n");
+        let error = summary[1]
+            .result
+            .clone()
+            .unwrap()
+            .into_result()
+            .unwrap_err();
+        assert_eq!(error.outcome, CodeExecutionOutcome::Error);
+        assert!(error
+            .to_string()
+            .ends_with("IndexError: list index out of range"));
+
+        assert!(summary[2].result.as_ref().unwrap().is_success());
+        assert_eq!(summary[2].result.as_ref().unwrap().output, "");
+    }
+
+    #[test]
+    fn test_code_execution_summary_without_result() {
+        let response = response(json!({
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        { "codeExecutionResult": { "outcome": "OUTCOME_OK", "output": "1" } },
+                        { "executableCode": { "language": "PYTHON", "code": "print(1)" } }
+                    ]
+                }
+            }]
+        }));
+
+        let summary = response.code_execution_summary();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].outcome(), None);
+    }
+
+    #[test]
+    fn test_logprobs_result() {
+        // Captured from gemini-1.5-flash with responseLogprobs and logprobs: 2.
+        let response = response(json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": "Yes" }], "role": "model" },
+                "finishReason": "STOP",
+                "avgLogprobs": -0.0013589813699945807,
+                "logprobsResult": {
+                    "topCandidates": [
+                        {
+                            "candidates": [
+                                { "token": "Yes", "tokenId": 8438, "logProbability": -0.0013589814 },
+                                { "token": "No", "tokenId": 1294, "logProbability": -6.6063957 }
+                            ]
+                        },
+                        {
+                            "candidates": [
+                                { "token": "\n", "logProbability": 0 }
+                            ]
+                        }
+                    ],
+                    "chosenCandidates": [
+                        { "token": "Yes", "tokenId": 8438, "logProbability": -0.0013589814 },
+                        { "token": "\n", "logProbability": 0 }
+                    ]
+                }
+            }],
+            "modelVersion": "gemini-1.5-flash-002"
+        }));
+
+        let candidate = &response.candidates.as_ref().unwrap()[0];
+        assert_eq!(candidate.avg_logprobs, Some(-0.0013589813699945807));
+        let logprobs = candidate.logprobs_result.as_ref().unwrap();
+        let steps = logprobs.steps().collect::<Vec<_>>();
+        assert_eq!(steps.len(), 2);
+
+        let (chosen, top) = steps[0];
+        assert_eq!(chosen.token, "Yes");
+        assert_eq!(chosen.token_id, 8438);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[1].token, "No");
+        assert_eq!(top[1].log_probability, -6.6063957);
+
+        let (chosen, top) = steps[1];
+        assert_eq!(chosen.token_id, 0);
+        assert_eq!(chosen.log_probability, 0.0);
+        assert_eq!(top.len(), 1);
+    }
+
+    #[test]
+    fn test_logprobs_result_without_top_candidates() {
+        let result: LogprobsResult = serde_json::from_value(json!({
+            "chosenCandidates": [{ "token": "Hi", "tokenId": 1, "logProbability": -0.5 }]
+        }))
+        .unwrap();
+
+        let steps = result.steps().collect::<Vec<_>>();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].1.is_empty());
+    }
+
+    /// A 1x1 transparent PNG.
+    const PIXEL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+
+    fn image_response() -> Response {
+        response(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        { "text": "Here is a tiny image:" },
+                        { "inlineData": { "mimeType": "image/png", "data": PIXEL_PNG } },
+                        { "inlineData": { "mimeType": "audio/wav", "data": "UklGRg==" } },
+                        { "inlineData": { "mimeType": "image/jpeg", "data": "/9j/4AAQ" } },
+                        { "inlineData": { "mimeType": "image/png", "data": "not base64!" } }
+                    ]
+                },
+                "finishReason": "STOP"
+            }],
+            "modelVersion": "gemini-2.0-flash-exp"
+        }))
+    }
+
+    #[test]
+    fn test_inline_images() {
+        let response = image_response();
+        assert_eq!(response.text(), "Here is a tiny image:");
+
+        let images = response.inline_images();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].0, "image/png");
+        assert!(images[0].1.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(images[0].1.len(), 68);
+        assert_eq!(
+            images[1],
+            ("image/jpeg", vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_images_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let images_dir = dir.path().join("images");
+
+        let paths = image_response().save_images_to(&images_dir).await.unwrap();
+
+        assert_eq!(
+            paths,
+            [
+                images_dir.join("image-1.png"),
+                images_dir.join("image-2.jpg")
+            ]
+        );
+        let png = std::fs::read(&paths[0]).unwrap();
+        assert_eq!(png, base64_engine.decode(PIXEL_PNG).unwrap());
+    }
+
+    fn audio_response(mime_type: &str) -> Response {
+        response(json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{
+                        "inlineData": {
+                            "mimeType": mime_type,
+                            "data": base64_engine.encode([0x01, 0x00, 0xff, 0x7f])
+                        }
+                    }]
+                },
+                "finishReason": "STOP"
+            }]
+        }))
+    }
+
+    #[test]
+    fn test_audio() {
+        let response = audio_response("audio/L16;codec=pcm;rate=24000");
+        assert_eq!(
+            response.audio(),
+            Some((
+                "audio/L16;codec=pcm;rate=24000",
+                vec![0x01, 0x00, 0xff, 0x7f]
+            ))
+        );
+        assert!(response.inline_images().is_empty());
+        assert_eq!(image_response().audio().unwrap().0, "audio/wav");
+    }
+
+    #[test]
+    fn test_pcm_sample_rate() {
+        assert_eq!(
+            pcm_sample_rate("audio/L16;codec=pcm;rate=16000"),
+            Some(16_000)
+        );
+        assert_eq!(pcm_sample_rate("audio/pcm"), Some(24_000));
+        assert_eq!(pcm_sample_rate("audio/wav"), None);
+        assert_eq!(pcm_sample_rate("audio/mpeg; rate=44100"), None);
+    }
+
+    #[tokio::test]
+    async fn test_save_audio_wraps_pcm_in_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+
+        audio_response("audio/L16;codec=pcm;rate=24000")
+            .save_audio(&path)
+            .await
+            .unwrap();
+
+        let wav = std::fs::read(&path).unwrap();
+        assert_eq!(wav.len(), 48);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 40);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 1);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 24_000);
+        assert_eq!(u32::from_le_bytes(wav[28..32].try_into().unwrap()), 48_000);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[44..], [0x01, 0x00, 0xff, 0x7f]);
+
+        let mp3 = dir.path().join("speech.mp3");
+        audio_response("audio/mpeg").save_audio(&mp3).await.unwrap();
+        assert_eq!(std::fs::read(&mp3).unwrap(), [0x01, 0x00, 0xff, 0x7f]);
+
+        assert!(response(json!({})).save_audio(&mp3).await.is_err());
+    }
+}
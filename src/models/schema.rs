@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
 use typed_builder::TypedBuilder;
 
+use super::SchemaError;
+
 /// The type of a property in a schema.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -24,6 +30,7 @@ pub enum SchemaType {
 /// This struct represents the JSON Schema format used to define parameters for function declarations.
 /// It supports various types, formats, descriptions, and nested schemas for complex types.
 #[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder)]
+#[serde(rename_all = "camelCase")]
 #[builder(doc)]
 pub struct Schema {
     /// Optional. The type of the property.
@@ -66,8 +73,710 @@ pub struct Schema {
     #[builder(default, setter(strip_option, into))]
     pub required: Option<Vec<String>>,
 
+    /// Optional. The minimum value of a number or integer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub minimum: Option<f64>,
+
+    /// Optional. The maximum value of a number or integer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub maximum: Option<f64>,
+
+    /// Optional. The minimum number of items of an array.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub min_items: Option<i64>,
+
+    /// Optional. The maximum number of items of an array.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub max_items: Option<i64>,
+
+    /// Optional. A regular expression a string must match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub pattern: Option<String>,
+
     /// Optional. The example of the property.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option, into))]
     pub example: Option<serde_json::Value>, // Use serde_json::Value for unknown types
+
+    /// Optional. The order in which the properties appear in the generated JSON.
+    /// Every name must be a key of [`properties`](Self::properties).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub property_ordering: Option<Vec<String>>,
+
+    /// Optional. The value must match at least one of these schemas, e.g. either a
+    /// success or an error object. A schema with `any_of` needs no type of its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option, into))]
+    pub any_of: Option<Vec<Schema>>,
+}
+
+impl Schema {
+    /// Sets the properties and keeps their order as the
+    /// [`property_ordering`](Self::property_ordering), which a `HashMap` alone loses.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{Schema, SchemaType};
+    ///
+    /// let string = Schema::builder().r#type(SchemaType::String).build();
+    /// let schema = Schema::builder()
+    ///     .r#type(SchemaType::Object)
+    ///     .build()
+    ///     .ordered_properties(&[("title", string.clone()), ("summary", string)]);
+    ///
+    /// assert_eq!(schema.property_ordering.unwrap(), ["title", "summary"]);
+    /// ```
+    pub fn ordered_properties(mut self, properties: &[(&str, Schema)]) -> Self {
+        self.properties = Some(
+            properties
+                .iter()
+                .map(|(name, schema)| (name.to_string(), schema.clone()))
+                .collect::<HashMap<_, _>>(),
+        );
+        self.property_ordering = Some(
+            properties
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        );
+        self
+    }
+
+    /// Checks the schema and its nested schemas for mistakes the API rejects: a
+    /// missing type (unless `any_of` is set), or a name in `property_ordering` that is
+    /// not a property.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SchemaError::Invalid`] listing every mistake with its location, e.g.
+    /// ``"`property_ordering` names unknown property `title` at #"``.
+    pub fn check(&self) -> Result<(), SchemaError> {
+        let mut problems = Vec::new();
+        self.collect_problems("#", &mut problems);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaError::Invalid(problems))
+        }
+    }
+
+    fn collect_problems(&self, path: &str, problems: &mut Vec<String>) {
+        if self.r#type.is_none() && self.any_of.is_none() {
+            problems.push(format!("missing `type` at {}", path));
+        }
+        for name in self.property_ordering.iter().flatten() {
+            if !self
+                .properties
+                .as_ref()
+                .is_some_and(|properties| properties.contains_key(name))
+            {
+                problems.push(format!(
+                    "`property_ordering` names unknown property `{}` at {}",
+                    name, path
+                ));
+            }
+        }
+        for (name, property) in self.properties.iter().flatten() {
+            property.collect_problems(&format!("{}/properties/{}", path, name), problems);
+        }
+        if let Some(items) = &self.items {
+            items.collect_problems(&format!("{}/items", path), problems);
+        }
+        for (index, variant) in self.any_of.iter().flatten().enumerate() {
+            variant.collect_problems(&format!("{}/anyOf/{}", path, index), problems);
+        }
+    }
+
+    /// Validates a value, e.g. a structured reply of the model, against the schema.
+    ///
+    /// Checks types (`null` is accepted for nullable schemas), required properties,
+    /// enum membership, `any_of` variants, array items and nested objects.
+    ///
+    /// # Errors
+    ///
+    /// Returns every [`SchemaViolation`] found.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::{Schema, SchemaType};
+    /// use serde_json::json;
+    ///
+    /// let schema = Schema::builder()
+    ///     .r#type(SchemaType::String)
+    ///     .enum_values(vec!["red".to_string(), "blue".to_string()])
+    ///     .build();
+    ///
+    /// assert!(schema.validate(&json!("red")).is_ok());
+    /// assert!(schema.validate(&json!("green")).is_err());
+    /// ```
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<SchemaViolation>> {
+        self.validate_with(value, false)
+    }
+
+    /// Validates a value like [`validate`](Self::validate), also reporting properties
+    /// of objects that the schema does not declare if `reject_unknown` is set.
+    pub(crate) fn validate_with(
+        &self,
+        value: &Value,
+        reject_unknown: bool,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+        self.collect_violations(value, "#", reject_unknown, &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    fn collect_violations(
+        &self,
+        value: &Value,
+        path: &str,
+        reject_unknown: bool,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        if value.is_null() && self.nullable == Some(true) {
+            return;
+        }
+
+        if let Some(variants) = &self.any_of {
+            if !variants
+                .iter()
+                .any(|variant| variant.validate_with(value, reject_unknown).is_ok())
+            {
+                violations.push(SchemaViolation::NoMatchingVariant {
+                    path: path.to_string(),
+                });
+            }
+        }
+
+        if let Some(expected) = &self.r#type {
+            let matches = match expected {
+                SchemaType::String => value.is_string(),
+                SchemaType::Number => value.is_number(),
+                SchemaType::Integer => {
+                    value.is_i64()
+                        || value.is_u64()
+                        || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+                }
+                SchemaType::Boolean => value.is_boolean(),
+                SchemaType::Array => value.is_array(),
+                SchemaType::Object => value.is_object(),
+            };
+            if !matches {
+                violations.push(SchemaViolation::TypeMismatch {
+                    path: path.to_string(),
+                    expected: expected.clone(),
+                    found: json_type_name(value).to_string(),
+                });
+                return;
+            }
+        }
+
+        // An empty enum does not constrain the value.
+        if let (Some(allowed), Some(text)) = (&self.enum_values, value.as_str()) {
+            if !allowed.is_empty() && !allowed.iter().any(|allowed| allowed == text) {
+                violations.push(SchemaViolation::NotInEnum {
+                    path: path.to_string(),
+                    value: text.to_string(),
+                    allowed: allowed.clone(),
+                });
+            }
+        }
+
+        if let Some(object) = value.as_object() {
+            for name in self.required.iter().flatten() {
+                if !object.contains_key(name) {
+                    violations.push(SchemaViolation::MissingProperty {
+                        path: path.to_string(),
+                        name: name.clone(),
+                    });
+                }
+            }
+            for (name, property) in self.properties.iter().flatten() {
+                if let Some(value) = object.get(name) {
+                    let path = format!("{}/{}", path, name);
+                    property.collect_violations(value, &path, reject_unknown, violations);
+                }
+            }
+            if let (true, Some(properties)) = (reject_unknown, &self.properties) {
+                for name in object.keys() {
+                    if !properties.contains_key(name) {
+                        violations.push(SchemaViolation::UnknownProperty {
+                            path: path.to_string(),
+                            name: name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if let (Some(items), Some(values)) = (&self.items, value.as_array()) {
+            for (index, value) in values.iter().enumerate() {
+                let path = format!("{}/{}", path, index);
+                items.collect_violations(value, &path, reject_unknown, violations);
+            }
+        }
+    }
+}
+
+/// A way in which a value does not conform to a [`Schema`], found by
+/// [`Schema::validate`].
+///
+/// The `path` of a violation points into the value, e.g. `#/items/0/name`.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SchemaViolation {
+    /// The value has the wrong type.
+    #[error("{path}: expected {expected:?}, found {found}")]
+    TypeMismatch {
+        /// The location of the value.
+        path: String,
+        /// The type required by the schema.
+        expected: SchemaType,
+        /// The JSON type of the value, e.g. `string` or `null`.
+        found: String,
+    },
+
+    /// An object lacks a required property.
+    #[error("{path}: missing required property `{name}`")]
+    MissingProperty {
+        /// The location of the object.
+        path: String,
+        /// The name of the missing property.
+        name: String,
+    },
+
+    /// An object has a property the schema does not declare. Only reported when
+    /// validating function call arguments, see
+    /// [`FunctionDeclaration::validate_call`](super::FunctionDeclaration::validate_call).
+    #[error("{path}: unknown property `{name}`")]
+    UnknownProperty {
+        /// The location of the object.
+        path: String,
+        /// The name of the unknown property.
+        name: String,
+    },
+
+    /// A string is not one of the enum values.
+    #[error("{path}: `{value}` is not one of {allowed:?}")]
+    NotInEnum {
+        /// The location of the string.
+        path: String,
+        /// The string.
+        value: String,
+        /// The enum values of the schema.
+        allowed: Vec<String>,
+    },
+
+    /// The value matches none of the `any_of` schemas.
+    #[error("{path}: matches none of the `any_of` schemas")]
+    NoMatchingVariant {
+        /// The location of the value.
+        path: String,
+    },
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn serialize(schema: Schema) -> serde_json::Value {
+        serde_json::to_value(schema).unwrap()
+    }
+
+    #[test]
+    fn test_unset_fields_are_omitted() {
+        assert_eq!(
+            serialize(Schema::builder().r#type(SchemaType::String).build()),
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn test_nullable_and_format_serialization() {
+        assert_eq!(
+            serialize(
+                Schema::builder()
+                    .r#type(SchemaType::String)
+                    .format("date-time")
+                    .nullable(true)
+                    .build()
+            ),
+            json!({ "type": "string", "format": "date-time", "nullable": true })
+        );
+    }
+
+    #[test]
+    fn test_minimum_and_maximum_serialization() {
+        assert_eq!(
+            serialize(
+                Schema::builder()
+                    .r#type(SchemaType::Number)
+                    .minimum(0.5)
+                    .maximum(10)
+                    .build()
+            ),
+            json!({ "type": "number", "minimum": 0.5, "maximum": 10.0 })
+        );
+    }
+
+    #[test]
+    fn test_min_items_and_max_items_serialization() {
+        assert_eq!(
+            serialize(
+                Schema::builder()
+                    .r#type(SchemaType::Array)
+                    .items(Box::new(
+                        Schema::builder().r#type(SchemaType::String).build()
+                    ))
+                    .min_items(1)
+                    .max_items(3)
+                    .build()
+            ),
+            json!({
+                "type": "array",
+                "items": { "type": "string" },
+                "minItems": 1,
+                "maxItems": 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_pattern_serialization() {
+        assert_eq!(
+            serialize(
+                Schema::builder()
+                    .r#type(SchemaType::String)
+                    .pattern("^[A-Z]{3}$")
+                    .build()
+            ),
+            json!({ "type": "string", "pattern": "^[A-Z]{3}$" })
+        );
+    }
+
+    #[test]
+    fn test_constraints_deserialize() {
+        let schema: Schema = serde_json::from_value(json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 1, "maximum": 5 },
+            "minItems": 2,
+            "maxItems": 4
+        }))
+        .unwrap();
+
+        assert_eq!(schema.min_items, Some(2));
+        assert_eq!(schema.max_items, Some(4));
+        let items = schema.items.unwrap();
+        assert_eq!(items.minimum, Some(1.0));
+        assert_eq!(items.maximum, Some(5.0));
+    }
+
+    fn string() -> Schema {
+        Schema::builder().r#type(SchemaType::String).build()
+    }
+
+    #[test]
+    fn test_ordered_properties_serialization() {
+        let schema = Schema::builder()
+            .r#type(SchemaType::Object)
+            .build()
+            .ordered_properties(&[
+                ("title", string()),
+                ("body", string()),
+                ("author", string()),
+            ]);
+
+        let value = serialize(schema);
+        assert_eq!(
+            value["propertyOrdering"],
+            json!(["title", "body", "author"])
+        );
+        assert_eq!(
+            value["properties"],
+            json!({
+                "title": { "type": "string" },
+                "body": { "type": "string" },
+                "author": { "type": "string" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_rejects_unknown_ordered_property() {
+        let mut schema = Schema::builder()
+            .r#type(SchemaType::Object)
+            .build()
+            .ordered_properties(&[("title", string())]);
+        assert!(schema.check().is_ok());
+
+        schema.property_ordering = Some(vec!["title".into(), "subtitle".into()]);
+        match schema.check() {
+            Err(SchemaError::Invalid(problems)) => assert_eq!(
+                problems,
+                ["`property_ordering` names unknown property `subtitle` at #"]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_requires_nested_types() {
+        let schema = Schema::builder()
+            .r#type(SchemaType::Array)
+            .items(Box::new(
+                Schema::builder()
+                    .r#type(SchemaType::Object)
+                    .properties(HashMap::from([(
+                        "name".to_string(),
+                        Schema::builder().build(),
+                    )]))
+                    .build(),
+            ))
+            .build();
+
+        match schema.check() {
+            Err(SchemaError::Invalid(problems)) => {
+                assert_eq!(problems, ["missing `type` at #/items/properties/name"])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    fn object(properties: &[(&str, Schema)]) -> Schema {
+        Schema::builder()
+            .r#type(SchemaType::Object)
+            .build()
+            .ordered_properties(properties)
+    }
+
+    #[test]
+    fn test_any_of_serialization() {
+        let schema = Schema::builder()
+            .any_of(vec![
+                object(&[("success", object(&[("id", string())]))]),
+                object(&[("error", object(&[("message", string())]))]),
+            ])
+            .build();
+
+        assert_eq!(
+            serialize(schema.clone()),
+            json!({
+                "anyOf": [
+                    {
+                        "type": "object",
+                        "properties": {
+                            "success": {
+                                "type": "object",
+                                "properties": { "id": { "type": "string" } },
+                                "propertyOrdering": ["id"]
+                            }
+                        },
+                        "propertyOrdering": ["success"]
+                    },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "error": {
+                                "type": "object",
+                                "properties": { "message": { "type": "string" } },
+                                "propertyOrdering": ["message"]
+                            }
+                        },
+                        "propertyOrdering": ["error"]
+                    }
+                ]
+            })
+        );
+        assert!(schema.check().is_ok());
+    }
+
+    #[test]
+    fn test_check_any_of_variants() {
+        let schema = Schema::builder()
+            .any_of(vec![string(), Schema::builder().build()])
+            .build();
+
+        match schema.check() {
+            Err(SchemaError::Invalid(problems)) => {
+                assert_eq!(problems, ["missing `type` at #/anyOf/1"])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_any_of_in_function_parameters() {
+        let parameters: Schema = serde_json::from_value(json!({
+            "type": "object",
+            "properties": {
+                "target": { "anyOf": [{ "type": "string" }, { "type": "integer" }] }
+            }
+        }))
+        .unwrap();
+
+        let target = &parameters.properties.as_ref().unwrap()["target"];
+        assert_eq!(target.r#type, None);
+        assert_eq!(target.any_of.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            serde_json::to_value(&parameters).unwrap()["properties"]["target"],
+            json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] })
+        );
+    }
+
+    fn person() -> Schema {
+        Schema::builder()
+            .r#type(SchemaType::Object)
+            .properties(HashMap::from([
+                ("name".to_string(), string()),
+                (
+                    "age".to_string(),
+                    Schema::builder().r#type(SchemaType::Integer).build(),
+                ),
+                (
+                    "role".to_string(),
+                    Schema::builder()
+                        .r#type(SchemaType::String)
+                        .enum_values(vec!["admin".to_string(), "user".to_string()])
+                        .build(),
+                ),
+                (
+                    "manager".to_string(),
+                    Schema::builder()
+                        .r#type(SchemaType::Object)
+                        .nullable(true)
+                        .properties(HashMap::from([("name".to_string(), string())]))
+                        .required(vec!["name".to_string()])
+                        .build(),
+                ),
+            ]))
+            .required(vec!["name".to_string(), "role".to_string()])
+            .build()
+    }
+
+    #[test]
+    fn test_validate_accepts_conforming_value() {
+        let schema = Schema::builder()
+            .r#type(SchemaType::Array)
+            .items(Box::new(person()))
+            .build();
+
+        let value = json!([
+            { "name": "Ada", "age": 36, "role": "admin", "manager": null },
+            { "name": "Bob", "role": "user", "manager": { "name": "Ada" } }
+        ]);
+        assert_eq!(schema.validate(&value), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_type_mismatch() {
+        assert_eq!(
+            person().validate(&json!({ "name": "Ada", "role": "user", "age": 36.5 })),
+            Err(vec![SchemaViolation::TypeMismatch {
+                path: "#/age".into(),
+                expected: SchemaType::Integer,
+                found: "number".into(),
+            }])
+        );
+        assert_eq!(
+            string().validate(&json!(null)),
+            Err(vec![SchemaViolation::TypeMismatch {
+                path: "#".into(),
+                expected: SchemaType::String,
+                found: "null".into(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_property() {
+        assert_eq!(
+            person().validate(&json!({ "role": "user", "manager": {} })),
+            Err(vec![
+                SchemaViolation::MissingProperty {
+                    path: "#".into(),
+                    name: "name".into(),
+                },
+                SchemaViolation::MissingProperty {
+                    path: "#/manager".into(),
+                    name: "name".into(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_enum_membership() {
+        assert_eq!(
+            person().validate(&json!({ "name": "Ada", "role": "owner" })),
+            Err(vec![SchemaViolation::NotInEnum {
+                path: "#/role".into(),
+                value: "owner".into(),
+                allowed: vec!["admin".into(), "user".into()],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_array_items() {
+        let schema = Schema::builder()
+            .r#type(SchemaType::Array)
+            .items(Box::new(person()))
+            .build();
+
+        let violations = schema
+            .validate(&json!([
+                { "name": "Ada", "role": "admin" },
+                { "name": 7, "role": "user" }
+            ]))
+            .unwrap_err();
+        assert_eq!(
+            violations,
+            [SchemaViolation::TypeMismatch {
+                path: "#/1/name".into(),
+                expected: SchemaType::String,
+                found: "number".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_any_of() {
+        let schema = Schema::builder()
+            .any_of(vec![
+                string(),
+                Schema::builder().r#type(SchemaType::Boolean).build(),
+            ])
+            .build();
+
+        assert_eq!(schema.validate(&json!(true)), Ok(()));
+        assert_eq!(
+            schema.validate(&json!(1)),
+            Err(vec![SchemaViolation::NoMatchingVariant {
+                path: "#".into()
+            }])
+        );
+    }
 }
@@ -0,0 +1,237 @@
+//! The [`schema!`](crate::schema!) macro.
+
+/// Builds a [`Schema`](crate::models::Schema) from a terse, JSON-like syntax.
+///
+/// The macro expands to the same builder calls you would write by hand.
+///
+/// | Syntax | Schema |
+/// | --- | --- |
+/// | `string`, `integer`, `number`, `boolean` | A schema of that type |
+/// | `string("Description")` (any of the above) | The same, with a description |
+/// | `enum_["A", "B"]` | A string limited to the listed values |
+/// | `[item]` | An array of `item` |
+/// | `{ "name": item, ... }` | An object; properties keep their order |
+/// | `"name": optional(item)` | An object property that is not required |
+///
+/// Object properties are required unless marked with `optional(...)`.
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::schema;
+///
+/// let recipe = schema!({
+///     "recipe_name": string("Name of the recipe"),
+///     "difficulty": enum_["Easy", "Medium", "Hard"],
+///     "ingredients": [string],
+///     "nutrition": optional({ "calories": integer }),
+/// });
+///
+/// assert_eq!(recipe.required.unwrap(), ["recipe_name", "difficulty", "ingredients"]);
+/// ```
+#[macro_export]
+macro_rules! schema {
+    (string) => { $crate::schema!(@scalar String) };
+    (string($description:expr)) => { $crate::schema!(@scalar String, $description) };
+    (integer) => { $crate::schema!(@scalar Integer) };
+    (integer($description:expr)) => { $crate::schema!(@scalar Integer, $description) };
+    (number) => { $crate::schema!(@scalar Number) };
+    (number($description:expr)) => { $crate::schema!(@scalar Number, $description) };
+    (boolean) => { $crate::schema!(@scalar Boolean) };
+    (boolean($description:expr)) => { $crate::schema!(@scalar Boolean, $description) };
+    (enum_[$($value:expr),+ $(,)?]) => {
+        $crate::models::Schema::builder()
+            .r#type($crate::models::SchemaType::String)
+            .enum_values(::std::vec![$(::std::string::String::from($value)),+])
+            .build()
+    };
+    ([$($item:tt)+]) => {
+        $crate::models::Schema::builder()
+            .r#type($crate::models::SchemaType::Array)
+            .items(::std::boxed::Box::new($crate::schema!($($item)+)))
+            .build()
+    };
+    ({ $($properties:tt)* }) => { $crate::schema!(@object [] $($properties)*) };
+    (optional($($item:tt)+)) => {
+        ::std::compile_error!("`optional(...)` can only mark an object property")
+    };
+
+    (@scalar $type:ident) => {
+        $crate::models::Schema::builder()
+            .r#type($crate::models::SchemaType::$type)
+            .build()
+    };
+    (@scalar $type:ident, $description:expr) => {
+        $crate::models::Schema::builder()
+            .r#type($crate::models::SchemaType::$type)
+            .description($description)
+            .build()
+    };
+
+    // Objects are munched one property at a time into `[(name, schema, required)...]`.
+    (@object [$($property:tt)*]) => {{
+        let properties: ::std::vec::Vec<(&str, $crate::models::Schema, bool)> =
+            ::std::vec![$($property),*];
+        let required: ::std::vec::Vec<::std::string::String> = properties
+            .iter()
+            .filter(|(_, _, required)| *required)
+            .map(|(name, _, _)| name.to_string())
+            .collect();
+        let properties: ::std::vec::Vec<(&str, $crate::models::Schema)> = properties
+            .into_iter()
+            .map(|(name, schema, _)| (name, schema))
+            .collect();
+        let schema = $crate::models::Schema::builder()
+            .r#type($crate::models::SchemaType::Object)
+            .build()
+            .ordered_properties(&properties);
+        if required.is_empty() {
+            schema
+        } else {
+            $crate::models::Schema {
+                required: ::std::option::Option::Some(required),
+                ..schema
+            }
+        }
+    }};
+    (@object [$($property:tt)*] $name:literal : optional($($item:tt)+) $(, $($rest:tt)*)?) => {
+        $crate::schema!(@object [$($property)* ($name, $crate::schema!($($item)+), false)] $($($rest)*)?)
+    };
+    (@object [$($property:tt)*] $name:literal : $kind:ident($($args:tt)*) $(, $($rest:tt)*)?) => {
+        $crate::schema!(@object [$($property)* ($name, $crate::schema!($kind($($args)*)), true)] $($($rest)*)?)
+    };
+    (@object [$($property:tt)*] $name:literal : $kind:ident[$($args:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::schema!(@object [$($property)* ($name, $crate::schema!($kind[$($args)*]), true)] $($($rest)*)?)
+    };
+    (@object [$($property:tt)*] $name:literal : $item:tt $(, $($rest:tt)*)?) => {
+        $crate::schema!(@object [$($property)* ($name, $crate::schema!($item), true)] $($($rest)*)?)
+    };
+    (@object [$($property:tt)*] $($invalid:tt)+) => {
+        ::std::compile_error!(::std::concat!(
+            "expected `\"name\": schema` in object, found `",
+            ::std::stringify!($($invalid)+),
+            "`"
+        ))
+    };
+
+    ($($invalid:tt)*) => {
+        ::std::compile_error!(::std::concat!(
+            "unsupported schema syntax `",
+            ::std::stringify!($($invalid)*),
+            "`; expected string, integer, number, boolean, enum_[...], [...] or {...}"
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::models::{Schema, SchemaType};
+
+    #[test]
+    fn test_scalars() {
+        let string = schema!(string);
+        assert_eq!(string.r#type, Some(SchemaType::String));
+        assert_eq!(string.description, None);
+
+        let count = schema!(integer("How many"));
+        assert_eq!(count.r#type, Some(SchemaType::Integer));
+        assert_eq!(count.description.as_deref(), Some("How many"));
+
+        assert_eq!(schema!(number).r#type, Some(SchemaType::Number));
+        assert_eq!(schema!(boolean("Flag")).r#type, Some(SchemaType::Boolean));
+    }
+
+    #[test]
+    fn test_enum_and_array() {
+        let difficulty = schema!(enum_["Easy", "Hard",]);
+        assert_eq!(difficulty.r#type, Some(SchemaType::String));
+        assert_eq!(difficulty.enum_values.unwrap(), ["Easy", "Hard"]);
+
+        let tags = schema!([[string("Tag")]]);
+        assert_eq!(
+            serde_json::to_value(&tags).unwrap(),
+            json!({
+                "type": "array",
+                "items": {
+                    "type": "array",
+                    "items": { "type": "string", "description": "Tag" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_object_matches_builder() {
+        let schema = schema!({
+            "recipe_name": string("Name"),
+            "difficulty": enum_["Easy", "Medium", "Hard"],
+            "ingredients": [string],
+            "nutrition": optional({ "calories": integer }),
+        });
+
+        let string = || Schema::builder().r#type(SchemaType::String).build();
+        let expected = Schema::builder()
+            .r#type(SchemaType::Object)
+            .required(vec![
+                "recipe_name".to_string(),
+                "difficulty".to_string(),
+                "ingredients".to_string(),
+            ])
+            .build()
+            .ordered_properties(&[
+                (
+                    "recipe_name",
+                    Schema::builder()
+                        .r#type(SchemaType::String)
+                        .description("Name")
+                        .build(),
+                ),
+                (
+                    "difficulty",
+                    Schema::builder()
+                        .r#type(SchemaType::String)
+                        .enum_values(vec!["Easy".into(), "Medium".into(), "Hard".into()])
+                        .build(),
+                ),
+                (
+                    "ingredients",
+                    Schema::builder()
+                        .r#type(SchemaType::Array)
+                        .items(Box::new(string()))
+                        .build(),
+                ),
+                (
+                    "nutrition",
+                    Schema::builder()
+                        .r#type(SchemaType::Object)
+                        .properties(HashMap::from([(
+                            "calories".to_string(),
+                            Schema::builder().r#type(SchemaType::Integer).build(),
+                        )]))
+                        .property_ordering(vec!["calories".to_string()])
+                        .required(vec!["calories".to_string()])
+                        .build(),
+                ),
+            ]);
+
+        assert_eq!(
+            serde_json::to_value(&schema).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_object_without_required_properties() {
+        let schema = schema!({ "note": optional(string) });
+        assert_eq!(schema.required, None);
+        assert_eq!(schema.property_ordering.unwrap(), ["note"]);
+
+        let empty = schema!({});
+        assert_eq!(empty.r#type, Some(SchemaType::Object));
+        assert!(empty.properties.unwrap().is_empty());
+    }
+}
@@ -1,17 +1,42 @@
 use std::{
+    collections::VecDeque,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use futures::Stream;
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use crate::error::GoogleGenerativeAIError;
 
-use super::Response;
+use super::{CodeExecutionResult, ExecutableCode, FunctionCall, Part, Response, UsageMetadata};
+
+/// An incremental event of a streamed response, as returned by
+/// [`ResponseStream::next_event`].
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// A piece of generated text.
+    TextDelta(String),
+    /// A piece of the thoughts of a thinking model.
+    Thought(String),
+    /// A function call the model wants the client to execute.
+    FunctionCall(FunctionCall),
+    /// Code the model generated for code execution.
+    CodeExecution(ExecutableCode),
+    /// The result of executing generated code.
+    CodeExecutionResult(CodeExecutionResult),
+    /// The stream has finished, with the final token usage if the API reported it.
+    Done(Option<UsageMetadata>),
+}
 
 /// A custom stream for generating response
 pub struct ResponseStream {
     receiver: tokio::sync::mpsc::Receiver<Result<Response, GoogleGenerativeAIError>>,
+    usage_metadata: Option<UsageMetadata>,
+    model_version: Option<String>,
+    pending_events: VecDeque<StreamEvent>,
+    done: bool,
+    producer: Option<tokio::task::AbortHandle>,
 }
 
 impl ResponseStream {
@@ -19,19 +44,440 @@ impl ResponseStream {
     pub fn new(
         receiver: tokio::sync::mpsc::Receiver<Result<Response, GoogleGenerativeAIError>>,
     ) -> Self {
-        Self { receiver }
+        Self {
+            receiver,
+            usage_metadata: None,
+            model_version: None,
+            pending_events: VecDeque::new(),
+            done: false,
+            producer: None,
+        }
+    }
+
+    /// Ties the task feeding the channel to this stream, so that dropping or
+    /// aborting the stream stops the task and the HTTP request it reads from.
+    pub(crate) fn with_producer(mut self, producer: tokio::task::AbortHandle) -> Self {
+        self.producer = Some(producer);
+        self
+    }
+
+    /// Stops the stream and cancels the underlying HTTP request.
+    ///
+    /// Chunks that were already received can still be read; afterwards the stream
+    /// ends. Dropping the stream has the same effect.
+    pub fn abort(&mut self) {
+        if let Some(producer) = self.producer.take() {
+            producer.abort();
+        }
+        self.receiver.close();
+    }
+
+    /// Writes the text of the first candidate to `writer` as it arrives and returns
+    /// the merged response once the stream is complete.
+    ///
+    /// The writer is flushed after every chunk.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the stream or the writer; the remaining chunks are
+    /// not read.
+    pub async fn pipe_text_to<W>(self, writer: W) -> Result<Response, GoogleGenerativeAIError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.pipe_text_to_with(writer, |_| {}).await
+    }
+
+    /// Like [`pipe_text_to`](Self::pipe_text_to), calling `on_write` with every piece
+    /// of text after it was written, e.g. to update a progress display.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the stream or the writer; the remaining chunks are
+    /// not read.
+    pub async fn pipe_text_to_with<W, F>(
+        mut self,
+        mut writer: W,
+        mut on_write: F,
+    ) -> Result<Response, GoogleGenerativeAIError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(&str),
+    {
+        let mut merged: Option<Response> = None;
+        while let Some(chunk) = self.next().await {
+            let chunk = chunk?;
+            let text = chunk.text();
+            if !text.is_empty() {
+                writer.write_all(text.as_bytes()).await?;
+                writer.flush().await?;
+                on_write(&text);
+            }
+
+            match &mut merged {
+                Some(merged) => merged.merge(chunk),
+                None => merged = Some(chunk),
+            }
+        }
+        merged.ok_or_else(|| GoogleGenerativeAIError::new("The stream ended without a response"))
+    }
+
+    /// Turns the stream into a stream of text deltas.
+    ///
+    /// Each chunk yields the concatenated text parts of its first candidate; chunks
+    /// without text are skipped and errors are passed through.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), gemini_ai_rust::error::GoogleGenerativeAIError> {
+    /// use futures::StreamExt;
+    /// use gemini_ai_rust::{models::ModelParams, GenerativeModel};
+    ///
+    /// let model = GenerativeModel::new("api-key", ModelParams::default());
+    /// let mut deltas = model.stream_send_message("Tell me a story").await?.text_deltas();
+    /// while let Some(delta) = deltas.next().await {
+    ///     print!("{}", delta?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn text_deltas(
+        self,
+    ) -> impl Stream<Item = Result<String, GoogleGenerativeAIError>> + Unpin {
+        self.filter_map(|chunk| {
+            futures::future::ready(match chunk {
+                Ok(response) => {
+                    let text = response.text();
+                    (!text.is_empty()).then_some(Ok(text))
+                }
+                Err(e) => Some(Err(e)),
+            })
+        })
+    }
+
+    /// Returns the next event of the stream.
+    ///
+    /// The parts of the first candidate are yielded one by one as they arrive, so a
+    /// caller can print text deltas and branch into tool execution when a function
+    /// call appears. After the last chunk a single [`StreamEvent::Done`] is returned,
+    /// followed by `None`.
+    ///
+    /// This consumes the same chunks as the [`Stream`] implementation, so the two
+    /// should not be mixed on one stream.
+    pub async fn next_event(&mut self) -> Option<Result<StreamEvent, GoogleGenerativeAIError>> {
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.next().await {
+                Some(Ok(response)) => self.pending_events.extend(events_of(response)),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    return Some(Ok(StreamEvent::Done(self.usage_metadata.clone())));
+                }
+            }
+        }
+    }
+
+    /// Returns the most recent usage metadata seen on the stream.
+    ///
+    /// The API reports the final token counts on the last chunk, so this is complete
+    /// once the stream has been drained.
+    pub fn usage_metadata(&self) -> Option<&UsageMetadata> {
+        self.usage_metadata.as_ref()
+    }
+
+    /// Returns the model version reported by the stream's chunks.
+    pub fn model_version(&self) -> Option<&str> {
+        self.model_version.as_deref()
+    }
+
+    /// Drains the stream and concatenates the text of the first candidate.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error yielded by the stream.
+    pub async fn collect_text(self) -> Result<String, GoogleGenerativeAIError> {
+        let response = self.collect_full().await?;
+        Ok(response.text())
+    }
+
+    /// Drains the stream and merges all chunks into a single [`Response`].
+    ///
+    /// See [`Response::merge`] for how the chunks are combined.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error yielded by the stream, or an error if the stream
+    /// ended without any chunk.
+    pub async fn collect_full(mut self) -> Result<Response, GoogleGenerativeAIError> {
+        let mut merged: Option<Response> = None;
+        while let Some(chunk) = self.next().await {
+            let chunk = chunk?;
+            match &mut merged {
+                Some(merged) => merged.merge(chunk),
+                None => merged = Some(chunk),
+            }
+        }
+        merged.ok_or_else(|| GoogleGenerativeAIError::new("The stream ended without a response"))
     }
 }
 
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        self.abort();
+    }
+}
+
+/// Splits the first candidate of a chunk into events.
+fn events_of(response: Response) -> Vec<StreamEvent> {
+    let parts = response
+        .candidates
+        .and_then(|candidates| candidates.into_iter().next())
+        .and_then(|candidate| candidate.content)
+        .map(|content| content.parts)
+        .unwrap_or_default();
+
+    parts
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Text {
+                text,
+                thought: Some(true),
+            } if !text.is_empty() => Some(StreamEvent::Thought(text)),
+            Part::Text { text, .. } if !text.is_empty() => Some(StreamEvent::TextDelta(text)),
+            Part::FunctionCall { function_call } => Some(StreamEvent::FunctionCall(function_call)),
+            Part::ExecutableCode { executable_code } => {
+                Some(StreamEvent::CodeExecution(executable_code))
+            }
+            Part::CodeExecutionResult {
+                code_execution_result,
+            } => Some(StreamEvent::CodeExecutionResult(code_execution_result)),
+            _ => None,
+        })
+        .collect()
+}
+
 impl Stream for ResponseStream {
     type Item = Result<Response, GoogleGenerativeAIError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
         match this.receiver.poll_recv(cx) {
-            Poll::Ready(Some(item)) => Poll::Ready(Some(item)),
+            Poll::Ready(Some(item)) => {
+                if let Ok(response) = &item {
+                    if let Some(usage_metadata) = &response.usage_metadata {
+                        this.usage_metadata = Some(usage_metadata.clone());
+                    }
+                    if let Some(model_version) = &response.model_version {
+                        this.model_version = Some(model_version.clone());
+                    }
+                }
+                Poll::Ready(Some(item))
+            }
             Poll::Ready(None) => Poll::Ready(None),
             Poll::Pending => Poll::Pending,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::FinishReason;
+
+    fn stream_of(chunks: Vec<serde_json::Value>) -> ResponseStream {
+        let (tx, rx) = tokio::sync::mpsc::channel(chunks.len().max(1));
+        for chunk in chunks {
+            tx.try_send(Ok(serde_json::from_value(chunk).unwrap()))
+                .unwrap();
+        }
+        ResponseStream::new(rx)
+    }
+
+    fn three_chunks() -> Vec<serde_json::Value> {
+        vec![
+            json!({
+                "candidates": [
+                    { "index": 0, "content": { "role": "model", "parts": [{ "text": "Hel" }] } },
+                    { "index": 1, "content": { "role": "model", "parts": [{ "text": "Bon" }] } }
+                ],
+                "modelVersion": "gemini-1.5-flash"
+            }),
+            json!({
+                "candidates": [
+                    { "index": 0, "content": { "parts": [{ "text": "lo" }, { "functionCall": { "name": "lookup", "args": {} } }] } },
+                    { "index": 1, "content": { "parts": [{ "text": "jour" }] } }
+                ],
+                "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5 }
+            }),
+            json!({
+                "candidates": [
+                    { "index": 0, "content": { "parts": [{ "text": "!" }] }, "finishReason": "STOP" },
+                    { "index": 1, "finishReason": "MAX_TOKENS" }
+                ],
+                "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 4, "totalTokenCount": 7 }
+            }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_collect_full_merges_chunks() {
+        let response = stream_of(three_chunks()).collect_full().await.unwrap();
+        let candidates = response.candidates.unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        let parts = &candidates[0].content.as_ref().unwrap().parts;
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0], Part::Text { text, .. } if text == "Hello"));
+        assert!(
+            matches!(&parts[1], Part::FunctionCall { function_call } if function_call.name == "lookup")
+        );
+        assert!(matches!(&parts[2], Part::Text { text, .. } if text == "!"));
+        assert!(matches!(
+            candidates[0].finish_reason,
+            Some(FinishReason::Stop)
+        ));
+
+        assert_eq!(candidates[1].content.as_ref().unwrap().parts.len(), 1);
+        assert!(matches!(
+            candidates[1].finish_reason,
+            Some(FinishReason::MaxTokens)
+        ));
+
+        assert_eq!(response.usage_metadata.unwrap().total_token_count, 7);
+        assert_eq!(response.model_version.as_deref(), Some("gemini-1.5-flash"));
+    }
+
+    #[tokio::test]
+    async fn test_collect_text() {
+        let text = stream_of(three_chunks()).collect_text().await.unwrap();
+        assert_eq!(text, "Hello!");
+    }
+
+    #[tokio::test]
+    async fn test_usage_metadata_of_last_chunk_is_retained() {
+        let mut stream = stream_of(three_chunks());
+        assert!(stream.usage_metadata().is_none());
+
+        while stream.next().await.is_some() {}
+
+        let usage = stream.usage_metadata().unwrap();
+        assert_eq!(usage.candidates_token_count, Some(4));
+        assert_eq!(usage.total_token_count, 7);
+        assert_eq!(stream.model_version(), Some("gemini-1.5-flash"));
+    }
+
+    #[tokio::test]
+    async fn test_next_event_mixes_text_and_function_call() {
+        let mut stream = stream_of(three_chunks());
+        let mut events = Vec::new();
+        while let Some(event) = stream.next_event().await {
+            events.push(event.unwrap());
+        }
+
+        assert_eq!(events.len(), 5);
+        assert!(matches!(&events[0], StreamEvent::TextDelta(text) if text == "Hel"));
+        assert!(matches!(&events[1], StreamEvent::TextDelta(text) if text == "lo"));
+        assert!(matches!(&events[2], StreamEvent::FunctionCall(call) if call.name == "lookup"));
+        assert!(matches!(&events[3], StreamEvent::TextDelta(text) if text == "!"));
+        assert!(
+            matches!(&events[4], StreamEvent::Done(Some(usage)) if usage.total_token_count == 7)
+        );
+        assert!(stream.next_event().await.is_none());
+    }
+
+    #[test]
+    fn test_has_function_call() {
+        let chunks = three_chunks();
+        let first: Response = serde_json::from_value(chunks[0].clone()).unwrap();
+        let second: Response = serde_json::from_value(chunks[1].clone()).unwrap();
+        assert!(!first.has_function_call());
+        assert!(second.has_function_call());
+    }
+
+    #[tokio::test]
+    async fn test_pipe_text_to_writer() {
+        let mut output = Vec::new();
+        let mut writes = Vec::new();
+        let response = stream_of(three_chunks())
+            .pipe_text_to_with(&mut output, |text| writes.push(text.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "Hello!");
+        assert_eq!(writes, ["Hel", "lo", "!"]);
+        assert_eq!(response.usage_metadata.unwrap().total_token_count, 7);
+    }
+
+    #[tokio::test]
+    async fn test_pipe_text_to_returns_writer_error() {
+        struct FailingWriter;
+
+        impl AsyncWrite for FailingWriter {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let error = stream_of(three_chunks())
+            .pipe_text_to(FailingWriter)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, GoogleGenerativeAIError::Io(_)));
+    }
+
+    #[tokio::test]
+    async fn test_text_deltas() {
+        let mut chunks = three_chunks();
+        chunks.insert(
+            1,
+            json!({ "candidates": [{ "index": 0, "content": { "parts": [] } }] }),
+        );
+        chunks.push(json!({
+            "candidates": [{ "content": { "parts": [{ "text": "a" }, { "text": "b" }] } }]
+        }));
+
+        let deltas: Vec<String> = stream_of(chunks)
+            .text_deltas()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(deltas, ["Hel", "lo", "!", "ab"]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_returns_first_error() {
+        let (tx, rx) = tokio::sync::mpsc::channel(2);
+        tx.try_send(Ok(serde_json::from_value(three_chunks().remove(0)).unwrap()))
+            .unwrap();
+        tx.try_send(Err(GoogleGenerativeAIError::new("boom")))
+            .unwrap();
+        drop(tx);
+
+        assert!(ResponseStream::new(rx).collect_text().await.is_err());
+    }
+}
@@ -14,9 +14,13 @@ impl From<&str> for SystemInstruction {
     fn from(prompt: &str) -> Self {
         SystemInstruction::Content(Content {
             role: Some(Role::System),
-            parts: vec![Part::Text {
-                text: prompt.into(),
-            }],
+            parts: vec![Part::text(prompt)],
         })
     }
 }
+
+impl From<Content> for SystemInstruction {
+    fn from(content: Content) -> Self {
+        SystemInstruction::Content(content)
+    }
+}
@@ -5,6 +5,7 @@ use super::{
     code_execution::{CodeExecutionConfig, CodeExecutionTool},
     function::{FunctionCallingConfig, FunctionDeclaration, FunctionDeclarationTool},
     google_search::{GoogleSearch, GoogleSearchTool},
+    request::{validate_function_declarations, RequestValidationError},
 };
 
 /// Configuration for tool behavior in the model.
@@ -62,8 +63,154 @@ impl From<Vec<FunctionDeclaration>> for Tool {
     }
 }
 
+/// Builds the tools of a request, so Google search grounding, code execution and
+/// function declarations can be combined in one call.
+///
+/// All function declarations end up in a single tool, as the API expects.
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::models::{Content, FunctionDeclaration, Request, Tools};
+///
+/// let bookmark = FunctionDeclaration::new().with_name("bookmark");
+/// let request = Request::builder()
+///     .contents(vec![Content::user("Find and bookmark a Rust tutorial")])
+///     .tools(Tools::new().google_search().functions(vec![bookmark]))
+///     .build();
+///
+/// assert_eq!(request.tools.unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Tools {
+    tools: Vec<Tool>,
+}
+
+impl Tools {
+    /// Creates an empty set of tools.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds Google search grounding.
+    pub fn google_search(self) -> Self {
+        self.tool(Tool::GOOGLE_SEARCH)
+    }
+
+    /// Adds code execution.
+    pub fn code_execution(self) -> Self {
+        self.tool(Tool::CODE_EXECUTION)
+    }
+
+    /// Adds function declarations, to the function declarations tool if there
+    /// already is one.
+    pub fn functions(
+        mut self,
+        function_declarations: impl IntoIterator<Item = FunctionDeclaration>,
+    ) -> Self {
+        let existing = self.tools.iter_mut().find_map(|tool| match tool {
+            Tool::FunctionDeclarationsTool(tool) => Some(&mut tool.function_declarations),
+            _ => None,
+        });
+        match existing {
+            Some(existing) => existing.extend(function_declarations),
+            None => self.tools.push(Tool::function_declarations(
+                function_declarations.into_iter().collect(),
+            )),
+        }
+        self
+    }
+
+    /// Adds any other tool.
+    pub fn tool(mut self, tool: Tool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Returns the tools after checking their function declarations like
+    /// [`Request::validate`](super::Request::validate) does.
+    ///
+    /// Converting with `into()` skips the check.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are more than
+    /// [`MAX_FUNCTION_DECLARATIONS`](super::MAX_FUNCTION_DECLARATIONS) declarations,
+    /// or a function name is invalid or duplicated.
+    pub fn build(self) -> Result<Vec<Tool>, RequestValidationError> {
+        let declarations = self
+            .tools
+            .iter()
+            .flat_map(|tool| match tool {
+                Tool::FunctionDeclarationsTool(tool) => tool.function_declarations.as_slice(),
+                _ => &[],
+            })
+            .collect::<Vec<_>>();
+        validate_function_declarations(&declarations)?;
+        Ok(self.tools)
+    }
+}
+
+impl From<Tools> for Vec<Tool> {
+    fn from(tools: Tools) -> Self {
+        tools.tools
+    }
+}
+
 impl From<CodeExecutionTool> for Tool {
     fn from(tool: CodeExecutionTool) -> Self {
         Self::CodeExecutionTool(tool)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MAX_FUNCTION_DECLARATIONS;
+
+    fn declarations(count: usize) -> Vec<FunctionDeclaration> {
+        (0..count)
+            .map(|i| FunctionDeclaration::new().with_name(format!("f{}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_counts_declarations_across_calls() {
+        let tools = Tools::new()
+            .google_search()
+            .functions(declarations(MAX_FUNCTION_DECLARATIONS))
+            .build()
+            .unwrap();
+        assert_eq!(tools.len(), 2);
+
+        let error = Tools::new()
+            .functions(declarations(MAX_FUNCTION_DECLARATIONS))
+            .functions(vec![FunctionDeclaration::new().with_name("extra")])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            RequestValidationError::TooManyFunctionDeclarations { count: 65 }
+        );
+        assert_eq!(
+            error.to_string(),
+            "the tools declare 65 functions, at most 64 are allowed"
+        );
+    }
+
+    #[test]
+    fn test_build_rejects_duplicate_names() {
+        let error = Tools::new()
+            .functions(declarations(2))
+            .code_execution()
+            .functions(declarations(1))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            RequestValidationError::DuplicateFunctionName {
+                name: "f0".to_string()
+            }
+        );
+    }
+}
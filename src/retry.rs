@@ -0,0 +1,148 @@
+//! Retry policy for transient API failures.
+
+use std::time::Duration;
+
+use typed_builder::TypedBuilder;
+
+use crate::error::ApiError;
+
+/// Controls how failed requests are retried.
+///
+/// Only retryable failures (429 and 5xx, see [`ApiError::is_retryable`]) are retried.
+/// When the server says how long to wait, through the `Retry-After` header (in seconds
+/// or as an HTTP date) or a `google.rpc.RetryInfo` detail, that delay is used instead
+/// of the computed backoff, capped by `max_server_delay`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use gemini_ai_rust::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::builder()
+///     .max_retries(5)
+///     .initial_backoff(Duration::from_secs(1))
+///     .build();
+/// ```
+#[derive(Debug, Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    #[builder(default = 3)]
+    pub max_retries: u32,
+
+    /// Delay before the first retry.
+    #[builder(default = Duration::from_millis(500))]
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the computed backoff.
+    #[builder(default = Duration::from_secs(30))]
+    pub max_backoff: Duration,
+
+    /// Factor applied to the backoff after each retry.
+    #[builder(default = 2.0)]
+    pub multiplier: f64,
+
+    /// Upper bound of a delay requested by the server.
+    #[builder(default = Duration::from_secs(60))]
+    pub max_server_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the computed exponential backoff before retry number `attempt` (starting at 0).
+    ///
+    /// A backoff too large to represent is capped at `max_backoff` like any other.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(32) as i32);
+        Duration::try_from_secs_f64(self.initial_backoff.as_secs_f64() * factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    /// Returns how long to wait before retrying after `error`, preferring the delay
+    /// requested by the server.
+    pub fn delay_for(&self, attempt: u32, error: &ApiError) -> Duration {
+        match error.retry_after {
+            Some(delay) => delay.min(self.max_server_delay),
+            None => self.backoff(attempt),
+        }
+    }
+
+    /// Returns `true` if another attempt should be made after `error`.
+    pub fn should_retry(&self, attempt: u32, error: &ApiError) -> bool {
+        attempt < self.max_retries && error.is_retryable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+
+    fn error_with_delay(retry_after: Option<Duration>) -> ApiError {
+        let mut error = ApiError::from_parts(429, &HeaderMap::new(), "");
+        error.retry_after = retry_after;
+        error
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(5))
+            .build();
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_backoff_does_not_overflow() {
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_secs(u64::MAX / 2))
+            .max_backoff(Duration::from_secs(5))
+            .multiplier(1e300)
+            .build();
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(5));
+        assert_eq!(policy.backoff(32), Duration::from_secs(5));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+
+        let policy = RetryPolicy::builder().multiplier(f64::INFINITY).build();
+        assert_eq!(policy.backoff(1), policy.max_backoff);
+    }
+
+    #[test]
+    fn test_server_delay_is_preferred_and_capped() {
+        let policy = RetryPolicy::builder()
+            .max_server_delay(Duration::from_secs(10))
+            .build();
+
+        let delay = policy.delay_for(0, &error_with_delay(Some(Duration::from_secs(3))));
+        assert_eq!(delay, Duration::from_secs(3));
+
+        let delay = policy.delay_for(0, &error_with_delay(Some(Duration::from_secs(120))));
+        assert_eq!(delay, Duration::from_secs(10));
+
+        let delay = policy.delay_for(0, &error_with_delay(None));
+        assert_eq!(delay, policy.initial_backoff);
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::builder().max_retries(1).build();
+        let error = error_with_delay(None);
+
+        assert!(policy.should_retry(0, &error));
+        assert!(!policy.should_retry(1, &error));
+        assert!(!policy.should_retry(0, &ApiError::from_parts(400, &HeaderMap::new(), "")));
+    }
+}
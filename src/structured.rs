@@ -0,0 +1,175 @@
+//! Lenient parsing of JSON written by a model.
+//!
+//! Even in JSON mode, models sometimes wrap the reply in Markdown code fences,
+//! introduce it with a line of prose or leave trailing commas behind.
+//! [`parse_model_json`] recovers the JSON from such replies.
+
+use serde_json::Value;
+
+/// A structured reply together with how it was parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structured<T> {
+    /// The parsed reply.
+    pub value: T,
+    /// Whether the reply was not valid JSON as is and had to be repaired by
+    /// [`parse_model_json`].
+    pub repaired: bool,
+    /// The raw text of the reply.
+    pub raw: String,
+}
+
+/// Parses JSON written by a model.
+///
+/// The text is first parsed strictly. If that fails, the JSON is cut out of any
+/// code fence and surrounding prose, trailing commas are removed and parsing is
+/// retried.
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::structured::parse_model_json;
+///
+/// let value = parse_model_json("Here is the JSON:\n```json\n{\"a\": [1, 2,],}\n```").unwrap();
+/// assert_eq!(value, serde_json::json!({ "a": [1, 2] }));
+/// ```
+///
+/// # Errors
+///
+/// Returns the error of the strict parse if the text cannot be repaired.
+pub fn parse_model_json(text: &str) -> Result<Value, serde_json::Error> {
+    parse_model_json_detailed(text).map(|(value, _)| value)
+}
+
+/// Like [`parse_model_json`], but also returns whether the text had to be repaired.
+pub(crate) fn parse_model_json_detailed(text: &str) -> Result<(Value, bool), serde_json::Error> {
+    let strict_error = match serde_json::from_str(text) {
+        Ok(value) => return Ok((value, false)),
+        Err(e) => e,
+    };
+    let repaired = remove_trailing_commas(strip_prose(strip_fence(text)));
+    serde_json::from_str(&repaired)
+        .map(|value| (value, true))
+        .map_err(|_| strict_error)
+}
+
+/// Returns the contents of the first code fence in `text`, or `text` if there is none.
+fn strip_fence(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text;
+    };
+    let after = &text[start + 3..];
+    // Skip the language tag on the opening line.
+    let body = after.find('\n').map_or("", |newline| &after[newline + 1..]);
+    body.find("```").map_or(body, |end| &body[..end])
+}
+
+/// Returns the span from the first `{` or `[` to the last matching closing bracket.
+fn strip_prose(text: &str) -> &str {
+    let Some(start) = text.find(['{', '[']) else {
+        return text;
+    };
+    let close = if text[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    match text.rfind(close) {
+        Some(end) if end > start => &text[start..=end],
+        _ => &text[start..],
+    }
+}
+
+/// Removes commas that directly precede a closing bracket, outside of strings.
+fn remove_trailing_commas(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = text[index + 1..].trim_start().chars().next();
+            if matches!(next, Some('}' | ']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_recovers_malformed_json() {
+        let cases = [
+            ("```json\n{\"a\": 1}\n```", json!({ "a": 1 })),
+            ("```\n[1, 2]\n```", json!([1, 2])),
+            ("```JSON\n{\"a\": 1}", json!({ "a": 1 })),
+            ("Here is the JSON:\n{\"a\": 1}", json!({ "a": 1 })),
+            (
+                "{\"a\": 1}\nLet me know if you need more.",
+                json!({ "a": 1 }),
+            ),
+            ("Sure! [\"x\", \"y\"] Hope this helps.", json!(["x", "y"])),
+            ("{\"a\": 1,}", json!({ "a": 1 })),
+            ("[1, 2, 3 , ]", json!([1, 2, 3])),
+            ("{\"a\": {\"b\": [1,],},\n}", json!({ "a": { "b": [1] } })),
+            (
+                "Here you go:\n```json\n{\"items\": [\"a\", \"b\",],}\n```\nDone.",
+                json!({ "items": ["a", "b"] }),
+            ),
+            (
+                "{\"text\": \"a,}\", \"list\": [\"],\",],}",
+                json!({ "text": "a,}", "list": ["],"] }),
+            ),
+            (
+                "{\"quote\": \"say \\\"hi,\\\"\",}",
+                json!({ "quote": "say \"hi,\"" }),
+            ),
+            ("\r\n  {\"a\": true,\r\n}\r\n", json!({ "a": true })),
+        ];
+        for (text, expected) in cases {
+            let (value, repaired) = parse_model_json_detailed(text).unwrap();
+            assert_eq!(value, expected, "{text:?}");
+            assert!(repaired, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_valid_json_is_not_repaired() {
+        let (value, repaired) = parse_model_json_detailed(" {\"a\": [1, 2]} ").unwrap();
+        assert_eq!(value, json!({ "a": [1, 2] }));
+        assert!(!repaired);
+    }
+
+    #[test]
+    fn test_broken_json_still_fails() {
+        let cases = [
+            "",
+            "No JSON here.",
+            "{\"a\": ",
+            "{'a': 1}",
+            "```json\n```",
+            "{\"a\": 1 \"b\": 2}",
+            "[1, 2",
+            "{\"a\": undefined}",
+        ];
+        for text in cases {
+            let strict = serde_json::from_str::<Value>(text).unwrap_err();
+            let error = parse_model_json(text).unwrap_err();
+            assert_eq!(error.to_string(), strict.to_string(), "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Minimal scripted HTTP server used by the unit tests.
+//!
+//! Each connection serves exactly one request and is then closed, which keeps the
+//! implementation small while still exercising the real `reqwest` client.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request received by the [`MockServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    /// The HTTP method.
+    pub method: String,
+    /// The request target, including the query string.
+    pub target: String,
+    /// The request headers with lowercase names.
+    pub headers: Vec<(String, String)>,
+    /// The decoded request body.
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Returns the path without the query string.
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    /// Returns the value of a query parameter.
+    pub fn query(&self, key: &str) -> Option<String> {
+        let query = self.target.split_once('?')?.1;
+        query.split('&').find_map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (k == key).then(|| v.replace("%2C", ",").replace("%3A", ":"))
+        })
+    }
+
+    /// Returns the value of a header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses the body as JSON.
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// The body of a [`MockResponse`].
+#[derive(Debug, Clone)]
+pub(crate) enum MockBody {
+    /// A body sent at once with a `Content-Length` header.
+    Full(Vec<u8>),
+    /// A chunked body; each chunk is written after its delay. When `hang` is set
+    /// the connection stays open after the last chunk.
+    Chunks {
+        chunks: Vec<(Duration, Vec<u8>)>,
+        hang: bool,
+    },
+}
+
+/// A scripted response returned by the [`MockServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: MockBody,
+}
+
+impl MockResponse {
+    /// Creates a JSON response.
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type".into(), "application/json".into())],
+            body: MockBody::Full(body.to_string().into_bytes()),
+        }
+    }
+
+    /// Creates a chunked response that writes each chunk after its delay.
+    pub fn chunked(chunks: Vec<(Duration, Vec<u8>)>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("content-type".into(), "text/event-stream".into())],
+            body: MockBody::Chunks {
+                chunks,
+                hang: false,
+            },
+        }
+    }
+
+    /// Keeps the connection open after the last chunk instead of ending the body.
+    pub fn hang(mut self) -> Self {
+        if let MockBody::Chunks { hang, .. } = &mut self.body {
+            *hang = true;
+        }
+        self
+    }
+
+    /// Adds a response header.
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_ascii_lowercase(), value.into()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync;
+
+/// A local HTTP server answering requests with a handler closure.
+pub(crate) struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    disconnects: Arc<AtomicUsize>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts a server on an ephemeral port.
+    pub async fn start(
+        handler: impl Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let disconnects = Arc::new(AtomicUsize::new(0));
+
+        let task = {
+            let requests = requests.clone();
+            let disconnects = disconnects.clone();
+            tokio::spawn(async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    let handler = handler.clone();
+                    let requests = requests.clone();
+                    let disconnects = disconnects.clone();
+                    tokio::spawn(async move {
+                        if serve(socket, handler, requests).await.is_err() {
+                            disconnects.fetch_add(1, Ordering::SeqCst);
+                        }
+                    });
+                }
+            })
+        };
+
+        Self {
+            url,
+            requests,
+            disconnects,
+            task,
+        }
+    }
+
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:1234`.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Returns all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns how many connections were closed by the client while a response
+    /// was still being written.
+    pub fn disconnects(&self) -> usize {
+        self.disconnects.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(
+    mut socket: TcpStream,
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) -> std::io::Result<()> {
+    let request = match read_request(&mut socket).await? {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    requests.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+
+    let mut head = format!("HTTP/1.1 {} Mock\r\nconnection: close\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+
+    match response.body {
+        MockBody::Full(body) => {
+            head.push_str(&format!("content-length: {}\r\n\r\n", body.len()));
+            socket.write_all(head.as_bytes()).await?;
+            socket.write_all(&body).await?;
+        }
+        MockBody::Chunks { chunks, hang } => {
+            head.push_str("transfer-encoding: chunked\r\n\r\n");
+            socket.write_all(head.as_bytes()).await?;
+            socket.flush().await?;
+            for (delay, chunk) in chunks {
+                tokio::time::sleep(delay).await;
+                socket
+                    .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                    .await?;
+                socket.write_all(&chunk).await?;
+                socket.write_all(b"\r\n").await?;
+                socket.flush().await?;
+            }
+            if hang {
+                // Wait until the client goes away.
+                let mut buf = [0u8; 64];
+                while socket.read(&mut buf).await? > 0 {}
+                return Err(std::io::ErrorKind::ConnectionAborted.into());
+            }
+            socket.write_all(b"0\r\n\r\n").await?;
+        }
+    }
+    socket.flush().await?;
+    Ok(())
+}
+
+async fn read_request(socket: &mut TcpStream) -> std::io::Result<Option<RecordedRequest>> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 8192];
+    let header_end = loop {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        data.extend_from_slice(&buf[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let mut rest = data[header_end + 4..].to_vec();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let body = if let Some(len) = header("content-length") {
+        let len: usize = len.parse().unwrap_or(0);
+        while rest.len() < len {
+            let n = socket.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            rest.extend_from_slice(&buf[..n]);
+        }
+        rest.truncate(len);
+        rest
+    } else if header("transfer-encoding").is_some_and(|v| v.contains("chunked")) {
+        while !rest.windows(5).any(|w| w == b"0\r\n\r\n") {
+            let n = socket.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            rest.extend_from_slice(&buf[..n]);
+        }
+        decode_chunked(&rest)
+    } else {
+        Vec::new()
+    };
+
+    Ok(Some(RecordedRequest {
+        method,
+        target,
+        headers,
+        body,
+    }))
+}
+
+fn decode_chunked(mut data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    while let Some(pos) = data.windows(2).position(|w| w == b"\r\n") {
+        let size =
+            usize::from_str_radix(String::from_utf8_lossy(&data[..pos]).trim(), 16).unwrap_or(0);
+        if size == 0 {
+            break;
+        }
+        let start = pos + 2;
+        body.extend_from_slice(&data[start..start + size]);
+        data = &data[start + size + 2..];
+    }
+    body
+}
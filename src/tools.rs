@@ -0,0 +1,559 @@
+//! Rust functions exposed to the model as tools.
+
+use std::fmt;
+use std::future::Future;
+
+use futures::future::{join_all, BoxFuture};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::models::{FunctionCall, FunctionDeclaration, FunctionResponse, Response, Tool};
+
+/// The default number of tool rounds [`GenerativeModel::generate_with_tools`]
+/// runs before it gives up.
+///
+/// [`GenerativeModel::generate_with_tools`]: crate::GenerativeModel::generate_with_tools
+pub const DEFAULT_MAX_TOOL_ROUNDS: usize = 8;
+
+type Handler = Box<dyn Fn(Value) -> BoxFuture<'static, Result<Value, String>> + Send + Sync>;
+
+/// Async Rust functions the model can call, by name.
+///
+/// Used by [`GenerativeModel::generate_with_tools`] to answer the function calls of
+/// the model until it replies with text.
+///
+/// # Example
+///
+/// ```
+/// use gemini_ai_rust::models::FunctionDeclaration;
+/// use gemini_ai_rust::tools::ToolRegistry;
+/// use serde_json::{json, Value};
+///
+/// let registry = ToolRegistry::new().with_function(
+///     FunctionDeclaration::new()
+///         .with_name("get_time")
+///         .with_description("Gets the current time"),
+///     |_args: Value| async { Ok::<_, String>(json!({ "time": "12:00" })) },
+/// );
+/// assert_eq!(registry.declarations().len(), 1);
+/// ```
+///
+/// [`GenerativeModel::generate_with_tools`]: crate::GenerativeModel::generate_with_tools
+pub struct ToolRegistry {
+    functions: Vec<(FunctionDeclaration, Handler)>,
+    max_rounds: usize,
+    validate_calls: bool,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            max_rounds: DEFAULT_MAX_TOOL_ROUNDS,
+            validate_calls: false,
+        }
+    }
+
+    /// Registers a function under the name of its declaration.
+    ///
+    /// The handler receives the arguments of the call. Its result is sent back to the
+    /// model, wrapped as `{"result": value}` unless it is a JSON object; an error is
+    /// sent as `{"error": message}`.
+    pub fn with_function<F, Fut, E>(mut self, declaration: FunctionDeclaration, handler: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, E>> + Send + 'static,
+        E: fmt::Display,
+    {
+        let handler: Handler = Box::new(move |args| {
+            let future = handler(args);
+            Box::pin(async move { future.await.map_err(|e| e.to_string()) })
+        });
+        self.functions.push((declaration, handler));
+        self
+    }
+
+    /// Registers a [`TypedFunction`].
+    #[cfg(feature = "schemars")]
+    pub fn with_typed_function<P, R, F>(self, function: TypedFunction<P, F>) -> Self
+    where
+        P: schemars::JsonSchema + serde::de::DeserializeOwned + 'static,
+        R: serde::Serialize,
+        F: Fn(P) -> R + Send + Sync + 'static,
+    {
+        let declaration = function.declaration().clone();
+        let name = function.name().to_string();
+        self.with_function(declaration, move |args| {
+            let call = FunctionCall {
+                name: name.clone(),
+                args,
+            };
+            let result = function.call(&call).map(|response| response.response);
+            async move { result }
+        })
+    }
+
+    /// Sets how many rounds of function calls are answered before the model's
+    /// latest response is returned as is. Defaults to [`DEFAULT_MAX_TOOL_ROUNDS`].
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Returns how many rounds of function calls are answered.
+    pub fn max_rounds(&self) -> usize {
+        self.max_rounds
+    }
+
+    /// Sets whether calls are checked against the declared parameters before the
+    /// handler runs. Disabled by default.
+    ///
+    /// A call with invalid arguments is not passed to the handler but answered with
+    /// `{"error": "Invalid arguments", "violations": [...]}`, listing each
+    /// [`SchemaViolation`](crate::models::SchemaViolation), so the model can correct
+    /// the call. See [`FunctionDeclaration::validate_call`].
+    pub fn with_call_validation(mut self, validate_calls: bool) -> Self {
+        self.validate_calls = validate_calls;
+        self
+    }
+
+    /// Returns the declarations of the registered functions.
+    pub fn declarations(&self) -> Vec<FunctionDeclaration> {
+        self.functions
+            .iter()
+            .map(|(declaration, _)| declaration.clone())
+            .collect()
+    }
+
+    /// Returns a tool declaring the registered functions.
+    pub fn tool(&self) -> Tool {
+        self.declarations().into()
+    }
+
+    /// Calls the function named by `call` and returns the response for the model.
+    ///
+    /// A call to an unknown function, or a failing one, is answered with
+    /// `{"error": message}` so the model can recover.
+    pub async fn call(&self, call: &FunctionCall) -> FunctionResponse {
+        let function = self
+            .functions
+            .iter()
+            .find(|(declaration, _)| declaration.name.as_deref() == Some(call.name.as_str()));
+        let result = match function {
+            Some((declaration, handler)) => {
+                match self.validate_calls.then(|| declaration.validate_call(call)) {
+                    Some(Err(violations)) => {
+                        let violations: Vec<String> =
+                            violations.iter().map(ToString::to_string).collect();
+                        return FunctionResponse {
+                            name: call.name.clone(),
+                            response: json!({
+                                "error": "Invalid arguments",
+                                "violations": violations,
+                            }),
+                        };
+                    }
+                    _ => handler(call.args.clone()).await,
+                }
+            }
+            None => Err(format!("Unknown function `{}`", call.name)),
+        };
+
+        FunctionResponse {
+            name: call.name.clone(),
+            response: match result {
+                Ok(value) => response_object(value),
+                Err(message) => json!({ "error": message }),
+            },
+        }
+    }
+
+    /// Calls the functions named by `calls` concurrently and returns their responses
+    /// in the same order.
+    pub async fn call_all(&self, calls: &[FunctionCall]) -> Vec<FunctionResponse> {
+        join_all(calls.iter().map(|call| self.call(call))).await
+    }
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("functions", &self.declarations())
+            .field("max_rounds", &self.max_rounds)
+            .field("validate_calls", &self.validate_calls)
+            .finish()
+    }
+}
+
+/// One round of function calls answered by [`GenerativeModel::generate_with_tools`].
+///
+/// [`GenerativeModel::generate_with_tools`]: crate::GenerativeModel::generate_with_tools
+#[derive(Debug, Clone)]
+pub struct ToolRound {
+    /// The function calls of the model.
+    pub calls: Vec<FunctionCall>,
+    /// The responses sent back, in the order of the calls.
+    pub responses: Vec<FunctionResponse>,
+}
+
+/// The result of [`GenerativeModel::generate_with_tools`].
+///
+/// [`GenerativeModel::generate_with_tools`]: crate::GenerativeModel::generate_with_tools
+#[derive(Debug, Clone)]
+pub struct ToolRun {
+    /// The last response of the model: its text reply, or its unanswered function
+    /// calls if the round limit was reached.
+    pub response: Response,
+    /// The rounds of function calls answered before the last response.
+    pub rounds: Vec<ToolRound>,
+}
+
+/// Wraps a function result that is not a JSON object, which the API requires for
+/// function responses, as `{"result": value}`.
+fn response_object(value: Value) -> Value {
+    match value {
+        Value::Object(_) => value,
+        value => json!({ "result": value }),
+    }
+}
+
+/// An error dispatching a [`FunctionCall`](crate::models::FunctionCall) to a Rust
+/// function.
+#[derive(Debug, Error)]
+pub enum ToolError {
+    /// The call names a different function.
+    #[error("Call to `{found}` was dispatched to `{expected}`")]
+    WrongFunction {
+        /// The name of the function the call was dispatched to.
+        expected: String,
+        /// The name of the function the model called.
+        found: String,
+    },
+
+    /// The arguments of the call do not match the parameters of the function.
+    #[error("Invalid arguments for `{name}`: {source}; arguments: {args}")]
+    InvalidArguments {
+        /// The name of the function.
+        name: String,
+        /// The arguments the model sent.
+        args: Value,
+        /// The deserialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The result of the function could not be serialized.
+    #[error("Failed to serialize the result of `{name}`: {source}")]
+    InvalidResult {
+        /// The name of the function.
+        name: String,
+        /// The serialization error.
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+#[cfg(feature = "schemars")]
+pub use typed::TypedFunction;
+
+#[cfg(feature = "schemars")]
+mod typed {
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    use schemars::JsonSchema;
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::{response_object, ToolError};
+    use crate::models::{FunctionCall, FunctionDeclaration, FunctionResponse, Schema};
+
+    /// A Rust function the model can call, with its declaration derived from the
+    /// parameter type.
+    ///
+    /// The parameters are a struct deriving [`JsonSchema`] and
+    /// [`Deserialize`](serde::Deserialize); their schema comes from
+    /// [`Schema::for_type`], and the doc comment of the struct becomes the
+    /// description of the function.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gemini_ai_rust::models::FunctionCall;
+    /// use gemini_ai_rust::tools::TypedFunction;
+    ///
+    /// /// Gets the current weather in a city.
+    /// #[derive(serde::Deserialize, schemars::JsonSchema)]
+    /// struct WeatherParams {
+    ///     /// The city name
+    ///     city: String,
+    /// }
+    ///
+    /// let get_weather = TypedFunction::new("get_weather", |params: WeatherParams| {
+    ///     format!("Sunny in {}", params.city)
+    /// });
+    /// assert_eq!(
+    ///     get_weather.declaration().description.as_deref(),
+    ///     Some("Gets the current weather in a city.")
+    /// );
+    ///
+    /// let call = FunctionCall {
+    ///     name: "get_weather".to_string(),
+    ///     args: serde_json::json!({ "city": "Paris" }),
+    /// };
+    /// let response = get_weather.call(&call).unwrap();
+    /// assert_eq!(response.response["result"], "Sunny in Paris");
+    /// ```
+    pub struct TypedFunction<P, F> {
+        declaration: FunctionDeclaration,
+        function: F,
+        params: PhantomData<fn(P)>,
+    }
+
+    impl<P, R, F> TypedFunction<P, F>
+    where
+        P: JsonSchema + DeserializeOwned,
+        R: Serialize,
+        F: Fn(P) -> R,
+    {
+        /// Creates a tool named `name` that calls `function`.
+        pub fn new(name: impl Into<String>, function: F) -> Self {
+            let mut parameters = Schema::for_type::<P>();
+            let description = parameters.description.take();
+            let mut declaration = FunctionDeclaration::new().with_name(name);
+            declaration.description = description;
+            declaration.parameters = Some(parameters);
+
+            Self {
+                declaration,
+                function,
+                params: PhantomData,
+            }
+        }
+
+        /// Sets the description of the function, replacing the doc comment of the
+        /// parameter type.
+        pub fn with_description(mut self, description: impl Into<String>) -> Self {
+            self.declaration.description = Some(description.into());
+            self
+        }
+
+        /// Returns the declaration to send to the model.
+        pub fn declaration(&self) -> &FunctionDeclaration {
+            &self.declaration
+        }
+
+        /// Returns the name of the function.
+        pub fn name(&self) -> &str {
+            self.declaration.name.as_deref().unwrap_or_default()
+        }
+
+        /// Deserializes the arguments of `call`, calls the function and returns its
+        /// result as the response to send back to the model.
+        ///
+        /// A result that does not serialize to a JSON object is sent as
+        /// `{"result": value}`.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `call` names another function, its arguments do not
+        /// match the parameter type, or the result cannot be serialized.
+        pub fn call(&self, call: &FunctionCall) -> Result<FunctionResponse, ToolError> {
+            if call.name != self.name() {
+                return Err(ToolError::WrongFunction {
+                    expected: self.name().to_string(),
+                    found: call.name.clone(),
+                });
+            }
+            let params = call
+                .args_as()
+                .map_err(|error| ToolError::InvalidArguments {
+                    name: error.name,
+                    args: error.args,
+                    source: error.source,
+                })?;
+            let result = serde_json::to_value((self.function)(params)).map_err(|source| {
+                ToolError::InvalidResult {
+                    name: call.name.clone(),
+                    source,
+                }
+            })?;
+
+            Ok(FunctionResponse {
+                name: call.name.clone(),
+                response: response_object(result),
+            })
+        }
+    }
+
+    impl<P, F> fmt::Debug for TypedFunction<P, F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("TypedFunction")
+                .field("declaration", &self.declaration)
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use serde::Deserialize;
+        use serde_json::json;
+
+        use super::*;
+        use crate::models::SchemaType;
+
+        /// Converts an amount between currencies.
+        #[derive(Debug, Deserialize, JsonSchema)]
+        struct ConvertParams {
+            /// The amount to convert
+            amount: f64,
+            /// The currency code to convert from
+            from: String,
+            /// The currency code to convert to
+            to: Option<String>,
+        }
+
+        #[derive(Serialize)]
+        struct Converted {
+            amount: f64,
+            currency: String,
+        }
+
+        fn convert() -> TypedFunction<ConvertParams, impl Fn(ConvertParams) -> Converted> {
+            TypedFunction::new("convert_currency", |params: ConvertParams| Converted {
+                amount: params.amount * 2.0,
+                currency: params.to.unwrap_or(params.from),
+            })
+        }
+
+        #[test]
+        fn test_declaration_matches_hand_written() {
+            let number = |description: &str| {
+                Schema::builder()
+                    .r#type(SchemaType::Number)
+                    .format("double")
+                    .description(description)
+                    .build()
+            };
+            let string = |description: &str| {
+                Schema::builder()
+                    .r#type(SchemaType::String)
+                    .description(description)
+                    .build()
+            };
+            let expected = FunctionDeclaration::builder()
+                .name("convert_currency")
+                .description("Converts an amount between currencies.")
+                .parameters(
+                    Schema::builder()
+                        .r#type(SchemaType::Object)
+                        .properties([
+                            ("amount".to_string(), number("The amount to convert")),
+                            (
+                                "from".to_string(),
+                                string("The currency code to convert from"),
+                            ),
+                            (
+                                "to".to_string(),
+                                Schema {
+                                    nullable: Some(true),
+                                    ..string("The currency code to convert to")
+                                },
+                            ),
+                        ])
+                        .required(vec!["amount".to_string(), "from".to_string()])
+                        .build(),
+                )
+                .build();
+
+            assert_eq!(
+                serde_json::to_value(convert().declaration()).unwrap(),
+                serde_json::to_value(&expected).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_call_round_trips_args() {
+            let call = FunctionCall {
+                name: "convert_currency".to_string(),
+                args: json!({ "amount": 21.5, "from": "EUR", "to": "GBP" }),
+            };
+
+            let response = convert().call(&call).unwrap();
+            assert_eq!(response.name, "convert_currency");
+            assert_eq!(
+                response.response,
+                json!({ "amount": 43.0, "currency": "GBP" })
+            );
+
+            let scalar = TypedFunction::new("double", |params: ConvertParams| params.amount * 2.0);
+            let call = FunctionCall {
+                name: "double".to_string(),
+                args: json!({ "amount": 2, "from": "EUR" }),
+            };
+            assert_eq!(
+                scalar.call(&call).unwrap().response,
+                json!({ "result": 4.0 })
+            );
+        }
+
+        #[test]
+        fn test_call_rejects_bad_calls() {
+            let wrong = FunctionCall {
+                name: "get_weather".to_string(),
+                args: json!({}),
+            };
+            assert!(matches!(
+                convert().call(&wrong),
+                Err(ToolError::WrongFunction { found, .. }) if found == "get_weather"
+            ));
+
+            let invalid = FunctionCall {
+                name: "convert_currency".to_string(),
+                args: json!({ "amount": "ten" }),
+            };
+            match convert().call(&invalid) {
+                Err(ToolError::InvalidArguments { name, args, .. }) => {
+                    assert_eq!(name, "convert_currency");
+                    assert_eq!(args, json!({ "amount": "ten" }));
+                }
+                other => panic!("unexpected result: {other:?}"),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_registry_calls_typed_function() {
+            let registry = super::super::ToolRegistry::new().with_typed_function(convert());
+            assert_eq!(
+                registry.declarations()[0].name.as_deref(),
+                Some("convert_currency")
+            );
+
+            let call = FunctionCall {
+                name: "convert_currency".to_string(),
+                args: json!({ "amount": 1, "from": "EUR" }),
+            };
+            let response = registry.call(&call).await;
+            assert_eq!(
+                response.response,
+                json!({ "amount": 2.0, "currency": "EUR" })
+            );
+
+            let invalid = FunctionCall {
+                name: "convert_currency".to_string(),
+                args: json!({}),
+            };
+            let response = registry.call(&invalid).await;
+            assert!(response.response["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid arguments for `convert_currency`"));
+        }
+    }
+}
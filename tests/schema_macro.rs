@@ -0,0 +1,11 @@
+//! Compile tests for the `schema!` macro.
+
+#[test]
+fn schema_macro() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/schema_valid.rs");
+    t.compile_fail("tests/ui/schema_optional_outside_object.rs");
+    t.compile_fail("tests/ui/schema_unknown_type.rs");
+    t.compile_fail("tests/ui/schema_missing_colon.rs");
+    t.compile_fail("tests/ui/schema_empty_enum.rs");
+}
@@ -0,0 +1,5 @@
+use gemini_ai_rust::schema;
+
+fn main() {
+    let _ = schema!(optional(string));
+}
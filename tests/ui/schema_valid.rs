@@ -0,0 +1,22 @@
+use gemini_ai_rust::models::{Schema, SchemaType};
+use gemini_ai_rust::schema;
+
+fn main() {
+    let recipes: Schema = schema!([{
+        "recipe_name": string("Name of the cookie recipe"),
+        "difficulty": enum_["Easy", "Medium", "Hard"],
+        "ingredients": [string("An ingredient")],
+        "servings": optional(integer),
+        "nutrition": {
+            "calories": number("Calories per serving"),
+            "vegan": optional(boolean),
+        },
+    }]);
+
+    let item = recipes.items.unwrap();
+    assert_eq!(item.r#type, Some(SchemaType::Object));
+    assert_eq!(
+        item.required.unwrap(),
+        ["recipe_name", "difficulty", "ingredients", "nutrition"]
+    );
+}